@@ -0,0 +1,96 @@
+//! Benchmarks of the parser and of the tree rendering on a large outline, also checked against
+//! fixed budgets so that `cargo bench` fails when either regresses.
+//!
+//! Run with `cargo bench --bench outline`.
+
+use std::time::{Duration, Instant};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use djvu_nav::{
+    djvu::parser::parse_djvu_nav,
+    tree_widget::{flatten, TreeIdentifierSet, TreeItem, TreeState},
+    BookmarkLink, Nav, NavNode,
+};
+use ratatui::{backend::TestBackend, style::Style, Terminal};
+
+/// Number of nodes of the benchmarked outline.
+const NODE_COUNT: usize = 50_000;
+const PARSE_BUDGET: Duration = Duration::from_millis(50);
+const FLATTEN_BUDGET: Duration = Duration::from_millis(2);
+const RENDER_BUDGET: Duration = Duration::from_millis(25);
+const RUNS: usize = 25;
+
+/// Return an outline of `NODE_COUNT` nodes: parts holding chapters holding sections, then
+/// appendices.
+fn large_nav() -> Nav {
+    let node = |string: String, page, children| NavNode { string, link: BookmarkLink::PageNumber(page), children };
+    let sections = |part: usize, chapter: usize| (0..8)
+        .map(|section| node(format!("Section {}.{}.{}", part, chapter, section), 1, vec![]))
+        .collect();
+    let chapters = |part: usize| (0..9)
+        .map(|chapter| node(format!("Chapter {}.{} \"quoted\"", part, chapter), 1, sections(part, chapter)))
+        .collect();
+    // A part is 82 nodes with its chapters and sections, and appendices make up the rest.
+    let mut nodes: Vec<_> = (0..NODE_COUNT / 82).map(|part| node(format!("Part {}", part), 1, chapters(part))).collect();
+    nodes.extend((0..NODE_COUNT % 82).map(|appendix| node(format!("Appendix {}", appendix), 1, vec![])));
+    let nav = Nav { nodes };
+    assert_eq!(nav.all_nodes().len(), NODE_COUNT);
+    nav
+}
+
+fn tree_items(nodes: &[NavNode]) -> Vec<TreeItem<'_>> {
+    nodes.iter()
+        .map(|node| TreeItem::new(node.string.as_str(), tree_items(&node.children)))
+        .collect()
+}
+
+/// Return the shortest time taken by `f` over `RUNS` runs, which is the least affected by the
+/// other processes of the machine.
+fn fastest<T>(mut f: impl FnMut() -> T) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+/// Benchmark `f` as `name`, then fail if its fastest run is over `budget`. Criterion reports the
+/// changes since the last run, but only the budget stops a slow change from going unnoticed.
+fn bench_within<T>(c: &mut Criterion, name: &str, budget: Duration, mut f: impl FnMut() -> T) {
+    c.bench_function(name, |b| b.iter(&mut f));
+    let time = fastest(f);
+    assert!(time <= budget, "{} took {:?}, over its budget of {:?}", name, time, budget);
+}
+
+fn outline(c: &mut Criterion) {
+    let nav = large_nav();
+    let djvu = nav.to_djvu();
+    let mut state = TreeState::default();
+    for (id, node) in nav.all_nodes() {
+        if !node.children.is_empty() {
+            state.open(&id);
+        }
+    }
+    state.select(vec![NODE_COUNT / 82 / 2, 4, 4]);
+    let opened: TreeIdentifierSet = state.get_all_opened().into_iter().collect();
+    let items = tree_items(&nav.nodes);
+    let mut terminal = Terminal::new(TestBackend::new(120, 50)).unwrap();
+
+    bench_within(c, "parse", PARSE_BUDGET, || parse_djvu_nav(&djvu).unwrap().1);
+    bench_within(c, "flatten", FLATTEN_BUDGET, || flatten(&opened, &items).len());
+    bench_within(c, "render", RENDER_BUDGET, || {
+        terminal.draw(|f| nav.ui(f, f.size(), &mut state, |_| Style::default())).unwrap();
+    });
+}
+
+criterion_group! {
+    name = benches;
+    // The outline is large, so fewer samples than the default keep the run short.
+    config = Criterion::default().sample_size(20);
+    targets = outline
+}
+criterion_main!(benches);
@@ -0,0 +1,158 @@
+//! Names given to pages of a document, such as `frontispiece` or `plate-XII`, to type in place of
+//! their numbers. DjVu links only point to pages, so the names are kept in a file next to the
+//! document, `book.anchors.toml` for `book.djvu`, and shown next to the pages they name.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    djvu::{write_through_copy, PageInfo},
+    history::format_identifier,
+    link::{page_number, LinkResolvers, LinkTarget},
+    nav::{BookmarkLink, Nav},
+    page_expr::PageExpr,
+    report::{Report, ReportRow},
+};
+
+/// Extension replacing that of the document for the file of its anchors.
+const ANCHORS_EXTENSION: &str = "anchors.toml";
+
+#[derive(Debug)]
+pub enum AnchorError {
+    IOError(PathBuf, io::Error),
+    ParsingError(PathBuf, toml::de::Error),
+    FormatError(toml::ser::Error),
+}
+
+impl Display for AnchorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(path, e) => write!(f, "cannot access {}: {}", path.display(), e),
+            Self::ParsingError(path, e) => write!(f, "invalid anchors in {}: {}", path.display(), e),
+            Self::FormatError(e) => write!(f, "cannot write the anchors: {}", e),
+        }
+    }
+}
+
+impl Error for AnchorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IOError(_, e) => Some(e),
+            Self::ParsingError(_, e) => Some(e),
+            Self::FormatError(e) => Some(e),
+        }
+    }
+}
+
+/// The names of the pages of a document, written `[pages]` then one `name = page` line each.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Anchors {
+    pub pages: BTreeMap<String, u32>,
+}
+
+/// Return the path of the file holding the anchors of the document `filename`.
+pub fn anchors_path(filename: &str) -> PathBuf {
+    Path::new(filename).with_extension(ANCHORS_EXTENSION)
+}
+
+/// Whether `name` can name a page: a single word that is not a page, such as `12` or `last+1`,
+/// nor a link to one of `pages` for `resolvers`, such as the component `p0012.djvu`.
+pub fn is_valid_name(name: &str, resolvers: &LinkResolvers, pages: Option<&[PageInfo]>) -> bool {
+    !name.is_empty()
+        && !name.chars().any(char::is_whitespace)
+        && PageExpr::parse(name).is_none()
+        && !matches!(resolvers.resolve(&BookmarkLink::from_string(name), pages), LinkTarget::Page(_))
+}
+
+impl Anchors {
+    /// Read the anchors from `path`, there being none if the file does not exist.
+    pub fn load(path: &Path) -> Result<Self, AnchorError> {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| AnchorError::ParsingError(path.to_path_buf(), e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(AnchorError::IOError(path.to_path_buf(), e)),
+        }
+    }
+
+    /// Write the anchors to `path` through a copy renamed over it, or remove the file if there are
+    /// none left.
+    pub fn save(&self, path: &Path) -> Result<(), AnchorError> {
+        if self.pages.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(AnchorError::IOError(path.to_path_buf(), e)),
+                _ => Ok(()),
+            };
+        }
+        let content = toml::to_string(self).map_err(AnchorError::FormatError)?;
+        write_through_copy(path, |copy| fs::write(copy, content), |e| e)
+            .map_err(|e| AnchorError::IOError(path.to_path_buf(), e))
+    }
+
+    /// Return the page named `name`, if any.
+    pub fn page(&self, name: &str) -> Option<u32> {
+        self.pages.get(name).copied()
+    }
+
+    /// Return the names of page `page`, in alphabetical order.
+    pub fn names(&self, page: u32) -> Vec<&str> {
+        self.pages.iter()
+            .filter(|(_, other)| **other == page)
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Return the names of page `page` to show next to it, such as ` (frontispiece)`, or nothing
+    /// if it has none.
+    pub fn describe(&self, page: u32) -> String {
+        match self.names(page).as_slice() {
+            [] => String::new(),
+            names => format!(" ({})", names.join(", ")),
+        }
+    }
+}
+
+/// List the anchors of a document with pages `pages`, each followed by the entries of `nav`
+/// pointing to the page it names.
+pub fn anchors_report(anchors: &Anchors, nav: &Nav, pages: Option<&[PageInfo]>) -> Report {
+    let nodes = nav.all_nodes();
+    let mut rows = Vec::new();
+    for (name, page) in &anchors.pages {
+        rows.push(ReportRow::header(format!("{}: page {}", name, page)));
+        for (id, node) in nodes.iter().filter(|(_, node)| page_number(&node.link, pages) == Some(*page)) {
+            rows.push(ReportRow::node(format!("  {} \"{}\"", format_identifier(id), node.string), id.clone()));
+        }
+    }
+    Report {
+        title: String::from("Anchors"),
+        rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchors_name_pages() {
+        let anchors: Anchors = toml::from_str("[pages]\nfrontispiece = 4\n\"plate-XII\" = 212\nportrait = 4").unwrap();
+        assert_eq!(anchors.page("plate-XII"), Some(212));
+        assert_eq!(anchors.describe(4), " (frontispiece, portrait)");
+        assert_eq!(anchors.describe(5), "");
+        assert_eq!(toml::from_str::<Anchors>(&toml::to_string(&anchors).unwrap()).unwrap(), anchors);
+
+        let resolvers = LinkResolvers::default();
+        let pages = [PageInfo { number: 12, id: String::from("p0012.djvu"), title: None }];
+        let valid = |name| is_valid_name(name, &resolvers, Some(&pages));
+        assert!(valid("plate-XII"));
+        assert!(!valid("next-1") && !valid("12") && !valid("two words") && !valid("p0012.djvu"));
+        assert_eq!(anchors_path("scans/book.djvu"), Path::new("scans/book.anchors.toml"));
+    }
+}
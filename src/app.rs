@@ -1,170 +1,2939 @@
 use std::{
+    borrow::Cow,
+    cell::OnceCell,
+    collections::HashMap,
     fs::File, 
-    io::{BufWriter, Write, BufReader, BufRead, self, Stdout}, 
-    process::Command, 
-    time::{Duration, Instant}, path::PathBuf
+    io::{BufWriter, Write, BufReader, BufRead, self}, 
+    process::{Command, Stdio},
+    thread,
+    ops::RangeInclusive,
+    panic,
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, Ordering}, OnceLock},
+    thread::ThreadId,
+    time::{Duration, Instant},
 };
 
 use crossterm::{
-    event::{self, Event, KeyEvent, KeyCode, EnableMouseCapture}, 
+    cursor::Show,
+    event::{
+        self, Event, KeyEvent, KeyCode, KeyModifiers, EnableMouseCapture, DisableMouseCapture, MouseButton,
+        MouseEvent, MouseEventKind,
+    },
     terminal::{enable_raw_mode, EnterAlternateScreen, disable_raw_mode, LeaveAlternateScreen}, 
     execute
 };
 
-use ratatui::{backend::CrosstermBackend, Terminal};
+use chrono::NaiveDate;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    widgets::{Block, ListState, Paragraph},
+    Frame, Terminal,
+};
 
 use crate::{
-    nav::{Nav, BookmarkLink}, 
-    tree_widget::{TreeState, TreeView}, 
-    djvu::{NavReadingError, get_nav_from_djvu, embed_nav_in_djvu_file}
+    nav::{Nav, NavNode, BookmarkLink}, 
+    tree_widget::{TreeHit, TreeState, TreeView, TreeIdentifier, TreeIdentifierVec}, 
+    djvu::{
+        NavReadingError, PageInfo, TempFileError,
+        get_nav_from_djvu, get_pages_from_djvu, get_page_text, get_page_text_with, get_document_text, embed_nav_in_djvu_file,
+        check_djvused, get_page_count, get_metadata, set_metadata, set_page_titles, remote, FileStamp, CancellableTools, SystemTools,
+        ssh::{self, SshTarget},
+    },
+    history::{self, Extras, History, HistoryEntry, format_identifier, DEFAULT_HISTORY_LIMIT},
+    config::{Config, ConfigError, Feedback, SearchExpansion},
+    lint::{Finding, lint, findings_for},
+    detail,
+    fix::{title_from_page, FixContext, QuickFix, ALL_FIXES},
+    popup,
+    link::{self, page_id_candidates, page_number, LinkResolvers, LinkTarget, PageLinkKind},
+    heading::{self, default_heading_patterns},
+    input::LineInput,
+    command::{self, parse_command},
+    generate::{self, Frequency, Spacing},
+    index_import,
+    dedup,
+    hyphenation,
+    offset,
+    plan,
+    text_outline,
+    ocr,
+    scandata,
+    series::{self, Numbering},
+    columns::{self, Badges, Column, ColumnKind, Row},
+    targets,
+    check,
+    tempfile::TempFile,
+    page_expr::{parse_link, PageContext, PageExpr},
+    anchors::{self, Anchors},
+    metadata::{self, Metadata},
+    page_titles,
+    strict,
+    autosave::{self, Autosave},
+    session::{self, Session},
+    report::Report,
+    status_bar::StatusBar,
+    presentation,
+    preview::Previews,
+    viewer,
+    verify::{title_in_text, Verdict, Verification},
+    tasks::Tasks,
+    keymap::{Action, Key, KeyMap},
+    recording::Screen,
+    validate,
+    diagnostics::{self, Diagnostics},
+    merge::Merge,
+    import::{self, ImportFormat},
 };
 
-const APP_NAME: &str = "nav_edit";
-const TEMP_FILE_NAME: &str = "tempfile";
+/// Minutes without input after which caches are released, unless configured.
+const DEFAULT_IDLE_MINUTES: u64 = 10;
+/// How long the screen stays reversed when flashed.
+const FLASH_DURATION: Duration = Duration::from_millis(100);
+/// Time between two autosaves of the outline, at least.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(10);
+/// Entries scrolled by a turn of the mouse wheel.
+const WHEEL_LINES: usize = 3;
+/// Pages whose text layer is kept once read, the ones shown last, to go back and forth cheaply.
+const PAGE_TEXTS_KEPT: usize = 16;
+
+/// What is kept about an entry besides the outline. It is not written to the file, and is
+/// dropped once the entry at its identifier has another title.
+#[derive(Debug, Default)]
+struct Annotation {
+    /// Title of the entry when it was annotated.
+    title: String,
+    note: Option<String>,
+    pinned: bool,
+}
+
+pub struct App {
+    terminal: Terminal<CrosstermBackend<Screen>>,
+    filename: String,
+    nav: Nav,
+    /// Pages of the document, if they could be listed.
+    pages: Option<Vec<PageInfo>>,
+    /// Number of pages of the document, if it could be found.
+    page_count: Option<u32>,
+    tree_state: TreeState,
+    config: Config,
+    findings: Vec<Finding>,
+    history: History,
+    history_list: ListState,
+    quick_fixes: Vec<QuickFix>,
+    quick_fix_menu: ListState,
+    /// Change to many nodes waiting for confirmation, and the description of what it does.
+    bulk_change: Option<BulkChange>,
+    bulk_preview: Vec<String>,
+    bulk_list: ListState,
+    command_line: LineInput,
+    /// Subtrees copied or cut, siblings in order, to be pasted.
+    clipboard: Vec<NavNode>,
+    /// Subtrees cut during the session, the last ones first, and where the first of them was.
+    trash: Vec<(TreeIdentifierVec, Vec<NavNode>)>,
+    trash_list: ListState,
+    /// Imported entries being reviewed before they are merged into the outline.
+    merge: Option<Merge>,
+    merge_list: ListState,
+    /// Choices of the confirmation popups.
+    confirm_menu: ListState,
+    /// Entries deeper than this level are read-only.
+    locked_depth: Option<usize>,
+    /// The outline as last read from or written to the file.
+    saved_nav: Nav,
+    /// The state of the file when it was last read or written, if it could be read.
+    file_stamp: Option<FileStamp>,
+    /// The outline as read from the file when it was opened.
+    loaded_nav: Nav,
+    /// Notes and pins of the entries, by identifier.
+    annotations: HashMap<TreeIdentifierVec, Annotation>,
+    keymap: KeyMap,
+    /// How the links are interpreted, with the rewrites from the configuration.
+    link_resolvers: LinkResolvers,
+    /// Previous key pressed while navigating, for actions bound to two keys.
+    last_key: Option<Key>,
+    /// Title and link of the entry being edited, and the index of the field being typed in.
+    entry_form: [LineInput; 2],
+    entry_form_field: usize,
+    /// What was typed in the page field of the entry form before completing it with names of
+    /// pages, and the position of the name shown among the candidates.
+    page_completion: Option<(String, usize)>,
+    /// Metadata of the document, read the first time they are shown.
+    metadata: Option<Metadata>,
+    /// Metadata as last read from or written to the file.
+    saved_metadata: Option<Metadata>,
+    metadata_list: ListState,
+    /// Key and value being edited, and the key they replace, if any.
+    metadata_form: [LineInput; 2],
+    metadata_form_field: usize,
+    edited_key: Option<String>,
+    /// Titles of the pages as last read from or written to the file.
+    saved_page_titles: Vec<Option<String>>,
+    page_list: ListState,
+    /// Title of the selected page being edited.
+    page_title_input: LineInput,
+    /// Whether the page column shows the titles of the pages that have one, in place of their
+    /// numbers.
+    page_labels: bool,
+    /// Names of pages, to type in place of them.
+    anchors: Anchors,
+    /// File the names of pages are kept in, if the document is on this machine.
+    anchors_path: Option<PathBuf>,
+    /// Nodes matching the last search, and the position of the selected one among them.
+    search_matches: Vec<TreeIdentifierVec>,
+    search_position: usize,
+    /// Nodes opened to show the current search match, to close when going to the next one.
+    temporarily_opened: Vec<TreeIdentifierVec>,
+    report: Report,
+    report_list: ListState,
+    /// Verification of the titles against the text layer, running or finished.
+    verification: Option<Verification>,
+    /// Whether the outline is shown for reviewing with others, without the editing panes.
+    presenting: bool,
+    /// Whether writing to the file is disabled.
+    read_only: bool,
+    /// Whether the outline is written in strict mode.
+    strict: bool,
+    /// Columns of the rows of the tree.
+    columns: Vec<Column>,
+    /// Text of the pages read from the OCR file `ocr_source`, or why it could not be read. Only
+    /// read through `ocr_text()`, as it is released while idle.
+    ocr_text: OnceCell<Result<HashMap<u32, String>, String>>,
+    /// The OCR file and scan data file whose text is used instead of the text layer.
+    ocr_source: Option<(String, Option<String>)>,
+    /// When the last key was pressed.
+    last_input: Instant,
+    /// Whether the caches were released since the last key was pressed.
+    suspended: bool,
+    /// The file on another machine `filename` is a copy of, to copy it back to when writing.
+    ssh_target: Option<SshTarget>,
+    /// Whether the page of the selected entry is shown in place of its details.
+    show_preview: bool,
+    previews: Previews,
+    /// Text of the pages of the entries selected, read in the background to check their titles in
+    /// the details pane.
+    page_texts: Tasks<u32, Result<String, String>>,
+    /// Text of the pages of the index imported with `:index`, read in the background.
+    index_texts: Tasks<RangeInclusive<u32>, Result<String, String>>,
+    /// The pages of the index being read, and the file to write its entries to if not the outline.
+    index_request: Option<(RangeInclusive<u32>, Option<String>)>,
+    /// Absolute path of the file, by which its autosave is found, if it is autosaved: files opened
+    /// read-only and files on other machines are not.
+    autosave_filename: Option<PathBuf>,
+    /// Absolute path of the file, by which where its tree was left is saved, if it is a local file.
+    session_filename: Option<PathBuf>,
+    /// The outline when it was last autosaved, to autosave it only when it changed.
+    autosaved_nav: Nav,
+    /// The titles of the pages when they were last autosaved.
+    autosaved_page_titles: Vec<Option<String>>,
+    last_autosave: Instant,
+    /// The autosave of an earlier session, waiting for the user to restore or discard it.
+    recovered: Option<Autosave>,
+    /// Until when the screen is shown reversed, to flag an action that cannot be done.
+    flash_until: Option<Instant>,
+    status_message: Option<String>,
+    pub state: AppState,
+}
+
+#[derive(Debug)]
+pub enum AppLifetimeError {
+    NavReadingError(NavReadingError),
+    ExternalProgramError(io::Error),
+    TerminalIOError(io::Error),
+    TempFileError(TempFileError),
+    ConfigError(ConfigError),
+    RecordingError(io::Error),
+}
+
+impl std::fmt::Display for AppLifetimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NavReadingError(e) => write!(f, "{}", e),
+            Self::ExternalProgramError(e) => write!(f, "cannot run the editor: {}", e),
+            Self::TerminalIOError(e) => write!(f, "terminal error: {}", e),
+            Self::TempFileError(e) => write!(f, "{}", e),
+            Self::ConfigError(e) => write!(f, "{}", e),
+            Self::RecordingError(e) => write!(f, "cannot record the session: {}", e),
+        }
+    }
+}
+
+impl AppLifetimeError {
+    /// Whether the error is worth a bug report, rather than something the user can fix.
+    pub fn is_unexpected(&self) -> bool {
+        match self {
+            Self::NavReadingError(e) => e.is_unexpected(),
+            Self::TerminalIOError(_) => true,
+            Self::ExternalProgramError(_) | Self::TempFileError(_) | Self::ConfigError(_) | Self::RecordingError(_) => false,
+        }
+    }
+}
+
+impl std::error::Error for AppLifetimeError {}
+
+/// A change to many nodes at once, waiting for the user's confirmation.
+enum BulkChange {
+    Fix(QuickFix, Vec<TreeIdentifierVec>),
+    /// New titles for some nodes, and what they are.
+    Titles(&'static str, Vec<(TreeIdentifierVec, String)>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AppState {
+    Quitting,
+    Navigating,
+    BrowsingHistory,
+    ChoosingQuickFix,
+    ChoosingBulkFix,
+    PreviewingBulkChange,
+    EnteringCommand,
+    EnteringSearch,
+    EditingEntry,
+    ConfirmingDeletion,
+    /// Asking whether to remove the whole outline.
+    ConfirmingClear,
+    ConfirmingQuit,
+    /// Asking whether to restore the autosave of an earlier session.
+    ConfirmingRestore,
+    /// Reviewing imported entries one by one.
+    Merging,
+    BrowsingMetadata,
+    /// Editing a key of the metadata and its value.
+    EditingMetadata,
+    BrowsingPageTitles,
+    EditingPageTitle,
+    BrowsingReport,
+    BrowsingTrash,
+    RunningOtherCommand,
+}
+
+/// Whether the terminal is in raw mode on the alternate screen, and has to be restored.
+static TERMINAL_PREPARED: AtomicBool = AtomicBool::new(false);
+/// The thread drawing the interface, whose panics end the application.
+static UI_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+/// Restore the terminal before the panic message is printed, since it would be lost with the
+/// alternate screen, and the shell would be left in raw mode. Panics of other threads leave the
+/// interface running.
+fn install_panic_hook() {
+    if UI_THREAD.set(thread::current().id()).is_err() {
+        return;
+    }
+    // Asked for now, since the hook does not run programs.
+    thread::spawn(diagnostics::djvused_version);
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let ui_thread = UI_THREAD.get() == Some(&thread::current().id());
+        if ui_thread {
+            reset_terminal();
+        }
+        default_hook(info);
+        if ui_thread {
+            diagnostics::report(&Diagnostics::of_panic(info.to_string()));
+        }
+    }));
+}
+
+/// Give the terminal back to the shell without a `Terminal`, for when there is none at hand.
+fn reset_terminal() {
+    if TERMINAL_PREPARED.swap(false, Ordering::SeqCst) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, Show);
+    }
+}
+
+fn prepare_terminal(mut screen: Screen) -> Result<Terminal<CrosstermBackend<Screen>>, io::Error> {
+    install_panic_hook();
+    enable_raw_mode()?;
+    TERMINAL_PREPARED.store(true, Ordering::SeqCst);
+    let terminal = execute!(screen, EnterAlternateScreen, EnableMouseCapture)
+        .and_then(|_| Terminal::new(CrosstermBackend::new(screen)));
+    if terminal.is_err() {
+        reset_terminal();
+    }
+    terminal
+}
+
+/// Give the terminal back to the shell, unless it was already.
+fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Screen>>) -> Result<(), io::Error> {
+    if !TERMINAL_PREPARED.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
+    terminal.show_cursor()
+}
+
+/// Show `error`, which prevented the application from starting, until a key is pressed, and give
+/// the terminal back.
+fn show_startup_error(
+    mut terminal: Terminal<CrosstermBackend<Screen>>,
+    error: &AppLifetimeError,
+) -> Result<(), io::Error> {
+    let shown = terminal
+        .draw(|f| {
+            let text = format!("{}\n\nPress any key to quit.", error);
+            popup::message(f, f.size(), "Cannot open the file", &text);
+        })
+        .and_then(|_| loop {
+            if let Event::Key(_) = event::read()? {
+                return Ok(());
+            }
+        });
+    restore_terminal(&mut terminal)?;
+    shown
+}
+
+impl App {
+    /// Open the file `filename`, which may be the URL of a remote file if `read_only` is set, or
+    /// a file on another machine written `[user@]host:path`. The session is recorded as a cast
+    /// written to `record`, if set.
+    pub fn new(filename: &str, read_only: bool, strict: bool, record: Option<&Path>) -> Result<Self, AppLifetimeError> {
+        let config = Config::load(filename)
+            .map_err(AppLifetimeError::ConfigError)?;
+
+        // Files on other machines are edited in a local copy, fetched before the interface is
+        // shown in case scp has to ask for a password.
+        let ssh_target = SshTarget::parse(filename);
+        let local_copy = match &ssh_target {
+            Some(target) => Some(Self::fetch(target).map_err(AppLifetimeError::NavReadingError)?),
+            None => None,
+        };
+        let filename = local_copy.as_deref().unwrap_or(filename);
+
+        let screen = match record {
+            Some(path) => Screen::recorded(path).map_err(AppLifetimeError::RecordingError)?,
+            None => Screen::new(),
+        };
+        let terminal = prepare_terminal(screen)
+            .map_err(|e| AppLifetimeError::TerminalIOError(e))?;
+
+        let remote = remote::is_url(filename);
+        let nav = if remote {
+            remote::read_outline(&SystemTools, filename).map_err(NavReadingError::NativeReadingError)
+        } else {
+            check_djvused().and_then(|_| get_nav_from_djvu(filename))
+        };
+        let nav = match nav {
+            Ok(nav) => nav,
+            Err(e) => {
+                let error = AppLifetimeError::NavReadingError(e);
+                show_startup_error(terminal, &error).map_err(AppLifetimeError::TerminalIOError)?;
+                return Err(error);
+            },
+        };
+
+        // djvused cannot read remote files.
+        let pages = if remote { None } else { get_pages_from_djvu(filename).ok() };
+        let page_count = match &pages {
+            Some(pages) => Some(pages.len() as u32),
+            None if remote => None,
+            None => get_page_count(filename).ok(),
+        };
+
+        let mut tree_state = TreeState::default();
+
+        if !nav.nodes.is_empty() {
+            tree_state.select_first();
+        }
+        for id in nav.down_to_depth(config.navigation.open_depth.unwrap_or(0)) {
+            tree_state.open(&id);
+        }
+        // Local files go back to where their tree was left when last closed.
+        let session_filename = (!remote && ssh_target.is_none()).then(|| autosave::absolute_path(filename));
+        if let Some(session) = session_filename.as_deref().and_then(|filename| session::recall(filename).ok().flatten()) {
+            let session = session.valid_in(&nav);
+            tree_state.close_all();
+            for id in &session.opened {
+                tree_state.open(id);
+            }
+            if !session.selected.is_empty() {
+                tree_state.select(session.selected);
+            }
+        }
+
+        // An autosave newer than the file holds the changes of a session that did not end.
+        let autosave_filename = (!read_only && ssh_target.is_none()).then(|| autosave::absolute_path(filename));
+        let recovered = autosave_filename.as_deref()
+            .and_then(|filename| autosave::recover(filename).ok().flatten())
+            .filter(|autosave| autosave.nav != nav);
+        let mut confirm_menu = ListState::default();
+        let state = if recovered.is_some() {
+            confirm_menu.select(Some(0));
+            AppState::ConfirmingRestore
+        } else {
+            AppState::Navigating
+        };
+        let url_rewrites = config.links.url_rewrites.clone().unwrap_or_default();
+        let link_resolvers = LinkResolvers::with_rewrites(&url_rewrites);
+        let findings = lint(&nav, &config.policy, pages.as_deref(), &link_resolvers);
+        let keymap = KeyMap::new(&config.keys);
+        let columns = configured_columns(&config);
+        let history = History::with_limit(config.history_limit.unwrap_or(DEFAULT_HISTORY_LIMIT));
+        let strict = strict || config.strict.unwrap_or(false);
+        let saved_page_titles: Vec<_> = pages.iter().flatten().map(|page| page.title.clone()).collect();
+        let page_labels = config.page_labels.unwrap_or(false);
+        // The anchors of files on other machines would be lost with their local copy.
+        let anchors_path = (!remote && ssh_target.is_none()).then(|| anchors::anchors_path(filename));
+        let (anchors, status_message) = match anchors_path.as_deref().map(Anchors::load).transpose() {
+            Ok(anchors) => (anchors.unwrap_or_default(), None),
+            Err(e) => (Anchors::default(), Some(format!("Anchors not read: {}", e))),
+        };
+
+        Ok(Self {
+            terminal,
+            filename: String::from(filename),
+            saved_nav: nav.clone(),
+            file_stamp: FileStamp::of(filename).ok(),
+            loaded_nav: nav.clone(),
+            autosaved_nav: nav.clone(),
+            autosaved_page_titles: saved_page_titles.clone(),
+            annotations: HashMap::new(),
+            nav,
+            pages,
+            page_count,
+            tree_state,
+            config,
+            keymap,
+            link_resolvers,
+            findings,
+            history,
+            history_list: ListState::default(),
+            quick_fixes: Vec::new(),
+            quick_fix_menu: ListState::default(),
+            bulk_change: None,
+            bulk_preview: Vec::new(),
+            bulk_list: ListState::default(),
+            command_line: LineInput::default(),
+            clipboard: Vec::new(),
+            trash: Vec::new(),
+            trash_list: ListState::default(),
+            merge: None,
+            merge_list: ListState::default(),
+            confirm_menu,
+            last_key: None,
+            locked_depth: None,
+            entry_form: Default::default(),
+            entry_form_field: 0,
+            page_completion: None,
+            metadata: None,
+            saved_metadata: None,
+            metadata_list: ListState::default(),
+            metadata_form: Default::default(),
+            metadata_form_field: 0,
+            edited_key: None,
+            saved_page_titles,
+            page_list: ListState::default(),
+            page_title_input: LineInput::default(),
+            page_labels,
+            anchors,
+            anchors_path,
+            search_matches: Vec::new(),
+            search_position: 0,
+            temporarily_opened: Vec::new(),
+            report: Report::default(),
+            report_list: ListState::default(),
+            verification: None,
+            presenting: false,
+            read_only,
+            strict,
+            ocr_text: OnceCell::new(),
+            ocr_source: None,
+            last_input: Instant::now(),
+            suspended: false,
+            columns,
+            ssh_target,
+            show_preview: false,
+            previews: Previews::new(filename),
+            page_texts: Tasks::with_limit(PAGE_TEXTS_KEPT),
+            index_texts: Tasks::new(),
+            index_request: None,
+            autosave_filename,
+            session_filename,
+            last_autosave: Instant::now(),
+            recovered,
+            flash_until: None,
+            status_message,
+            state,
+        })
+    }
+
+    pub fn handle_input(
+        &mut self, 
+        key: KeyEvent, 
+    ) -> Result<(), AppLifetimeError> {
+        self.status_message = None;
+        match self.state {
+            AppState::Navigating => self.handle_navigation_input(key),
+            AppState::BrowsingHistory => {
+                self.handle_history_input(key);
+                Ok(())
+            },
+            AppState::ChoosingQuickFix | AppState::ChoosingBulkFix => {
+                self.handle_quick_fix_input(key);
+                Ok(())
+            },
+            AppState::PreviewingBulkChange => {
+                self.handle_bulk_change_input(key);
+                Ok(())
+            },
+            AppState::EnteringCommand => self.handle_command_input(key),
+            AppState::EnteringSearch => {
+                self.handle_search_input(key);
+                Ok(())
+            },
+            AppState::EditingEntry => {
+                self.handle_entry_form_input(key);
+                Ok(())
+            },
+            AppState::ConfirmingDeletion => {
+                self.handle_deletion_input(key);
+                Ok(())
+            },
+            AppState::ConfirmingClear => {
+                self.handle_clear_input(key);
+                Ok(())
+            },
+            AppState::ConfirmingQuit => self.handle_quit_input(key),
+            AppState::ConfirmingRestore => {
+                self.handle_restore_input(key);
+                Ok(())
+            },
+            AppState::Merging => {
+                self.handle_merge_input(key);
+                Ok(())
+            },
+            AppState::BrowsingReport => {
+                self.handle_report_input(key);
+                Ok(())
+            },
+            AppState::BrowsingTrash => {
+                self.handle_trash_input(key);
+                Ok(())
+            },
+            AppState::BrowsingMetadata => {
+                self.handle_metadata_input(key);
+                Ok(())
+            },
+            AppState::EditingMetadata => {
+                self.handle_metadata_form_input(key);
+                Ok(())
+            },
+            AppState::BrowsingPageTitles => {
+                self.handle_page_titles_input(key);
+                Ok(())
+            },
+            AppState::EditingPageTitle => {
+                self.handle_page_title_input(key);
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Select the entry clicked, open or close it when its triangle is, and scroll the outline with
+    /// the wheel. The mouse is only used on the outline.
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        if self.state != AppState::Navigating || self.presenting {
+            return;
+        }
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.last_key = None;
+                match self.tree_state.hit(mouse.column, mouse.row) {
+                    Some(TreeHit::Toggle(id)) => {
+                        self.status_message = None;
+                        self.tree_state.toggle(&id);
+                        // The selection does not stay hidden in a closed entry.
+                        if self.tree_state.selected().starts_with(&id) && !self.tree_state.is_open(&id) {
+                            self.tree_state.select(id);
+                        }
+                    },
+                    Some(TreeHit::Item(id)) => {
+                        self.status_message = None;
+                        self.tree_state.clear_range();
+                        self.tree_state.select(id);
+                        self.expand_on_select();
+                    },
+                    None => (),
+                }
+            },
+            MouseEventKind::ScrollDown => self.tree_state.scroll_down(&self.nav, WHEEL_LINES),
+            MouseEventKind::ScrollUp => self.tree_state.scroll_up(WHEEL_LINES),
+            _ => (),
+        }
+    }
+
+    fn handle_navigation_input(&mut self, key: KeyEvent) -> Result<(), AppLifetimeError> {
+        if self.presenting && key.code == KeyCode::Esc {
+            self.presenting = false;
+            return Ok(());
+        }
+        if self.tree_state.range().is_some() && key.code == KeyCode::Esc {
+            self.tree_state.clear_range();
+            return Ok(());
+        }
+        let key = Key::from(key);
+        let previous = self.last_key.replace(key);
+        let action = match previous.and_then(|previous| self.keymap.get(&[previous, key])) {
+            Some(action) => {
+                self.last_key = None;
+                action
+            },
+            None => match self.keymap.get(&[key]) {
+                Some(action) => action,
+                None => return Ok(()),
+            },
+        };
+        if self.presenting && !action.is_available_when_presenting() {
+            return Ok(());
+        }
+        if self.tree_state.range().is_some() && self.handle_range_action(action) {
+            return Ok(());
+        }
+        match action {
+            Action::Quit => self.quit(),
+            Action::WriteQuit | Action::Write => match self.write() {
+                Ok(true) if action == Action::WriteQuit => self.state = AppState::Quitting,
+                Ok(true) => (),
+                // The reason was given in the status line.
+                Ok(false) => self.signal(action),
+                Err(e) => {
+                    self.status_message = Some(format!("Cannot write {}: {}", self.display_name(), e));
+                    self.signal(action);
+                },
+            },
+            Action::MoveLeft | Action::MoveDown | Action::MoveUp | Action::MoveRight => {
+                let before = self.visible_selection();
+                match action {
+                    Action::MoveLeft => self.move_left(),
+                    Action::MoveDown => self.move_down(),
+                    Action::MoveUp => self.move_up(),
+                    _ => self.move_right(),
+                }
+                if self.visible_selection() == before {
+                    let reason = match action {
+                        Action::MoveLeft => "Already at the top level",
+                        Action::MoveDown => "Already at the last entry",
+                        Action::MoveUp => "Already at the first entry",
+                        _ if before.1 => "Already open",
+                        _ => "This entry has no children",
+                    };
+                    self.reject(action, reason);
+                }
+            },
+            Action::FirstEntry | Action::LastEntry | Action::HalfPageDown | Action::HalfPageUp
+                | Action::PageDown | Action::PageUp => {
+                let before = self.visible_selection();
+                self.jump(action);
+                if self.visible_selection() == before {
+                    let reason = match action {
+                        Action::FirstEntry | Action::HalfPageUp | Action::PageUp => "Already at the first entry",
+                        _ => "Already at the last entry",
+                    };
+                    self.reject(action, reason);
+                }
+            },
+            Action::EditEntry => self.open_entry_form(),
+            Action::EditInEditor => self.edit_currently_selected()?,
+            Action::EditPlan => self.edit_plan()?,
+            Action::ToggleLock => {
+                let depth = self.tree_state.selected().len();
+                self.lock_below(if self.locked_depth.is_some() { None } else { Some(depth) });
+            },
+            Action::TogglePresentation => self.presenting = !self.presenting,
+            Action::ShowMetadata => self.open_metadata(),
+            Action::ShowPageTitles => self.open_page_titles(),
+            Action::OpenInViewer => self.open_in_viewer()?,
+            Action::TogglePreview => self.show_preview = !self.show_preview,
+            Action::VisualMode => self.tree_state.start_range(),
+            Action::Focus => {
+                let selected = self.tree_state.selected().to_owned();
+                self.tree_state.focus(&selected);
+            },
+            Action::AddEntry => self.add_new_entry_below(),
+            Action::Cut => self.ask_cut_selected(),
+            Action::Yank => self.yank_selected(),
+            Action::PasteBelow => self.paste(false),
+            Action::PasteAbove => self.paste(true),
+            Action::MoveEntryUp | Action::MoveEntryDown | Action::Demote | Action::Promote => {
+                let moved = match action {
+                    Action::MoveEntryUp => self.move_selected("move entry up", Nav::move_up),
+                    Action::MoveEntryDown => self.move_selected("move entry down", Nav::move_down),
+                    Action::Demote => self.move_selected("demote entry", Nav::indent),
+                    _ => self.move_selected("promote entry", Nav::outdent),
+                };
+                if !moved {
+                    self.reject(action, move_rejection(action));
+                }
+            },
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::History => self.open_history(),
+            Action::QuickFix => self.open_quick_fix_menu(),
+            Action::BulkFix => self.open_bulk_fix_menu(),
+            Action::ProposeTitles => self.propose_titles(),
+            Action::CommandLine => {
+                self.command_line = LineInput::default();
+                self.state = AppState::EnteringCommand;
+            },
+            Action::Search => {
+                self.command_line = LineInput::default();
+                self.state = AppState::EnteringSearch;
+            },
+            Action::NextMatch => self.next_match(true),
+            Action::PreviousMatch => self.next_match(false),
+            Action::Unbound => (),
+        }
+        Ok(())
+    }
+
+    fn handle_history_input(&mut self, key: KeyEvent) {
+        let num_entries = self.history.entries().len();
+        let selected = self.history_list.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.state = AppState::Navigating,
+            KeyCode::Char('j') if selected + 1 < num_entries => {
+                self.history_list.select(Some(selected + 1));
+            },
+            KeyCode::Char('k') if selected > 0 => self.history_list.select(Some(selected - 1)),
+            KeyCode::Enter if num_entries > 0 => {
+                self.jump_to_step(num_entries - 1 - selected);
+                self.state = AppState::Navigating;
+            },
+            _ => (),
+        }
+    }
+
+    fn handle_quick_fix_input(&mut self, key: KeyEvent) {
+        let selected = self.quick_fix_menu.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.state = AppState::Navigating,
+            KeyCode::Char('j') if selected + 1 < self.quick_fixes.len() => {
+                self.quick_fix_menu.select(Some(selected + 1));
+            },
+            KeyCode::Char('k') if selected > 0 => self.quick_fix_menu.select(Some(selected - 1)),
+            KeyCode::Enter if self.state == AppState::ChoosingBulkFix => {
+                self.preview_bulk_fix(self.quick_fixes[selected]);
+            },
+            KeyCode::Enter => {
+                self.apply_quick_fix(self.quick_fixes[selected]);
+                self.state = AppState::Navigating;
+            },
+            _ => (),
+        }
+    }
+
+    fn handle_command_input(&mut self, key: KeyEvent) -> Result<(), AppLifetimeError> {
+        match key.code {
+            KeyCode::Esc => self.state = AppState::Navigating,
+            KeyCode::Enter => {
+                self.state = AppState::Navigating;
+                diagnostics::log(format!(":{}", self.command_line.text()));
+                match parse_command(self.command_line.text()) {
+                    Ok(command) => self.run_command(command)?,
+                    Err(e) => self.status_message = Some(e.to_string()),
+                }
+                self.tree_state.clear_range();
+            },
+            _ => {
+                self.command_line.handle_key(key);
+            },
+        }
+        Ok(())
+    }
+
+    /// Make the entries deeper than `depth` read-only, or all of them editable if `depth` is
+    /// `None`.
+    fn lock_below(&mut self, depth: Option<usize>) {
+        self.locked_depth = depth;
+        self.status_message = Some(match depth {
+            Some(depth) => format!("Entries deeper than level {} are locked", depth),
+            None => String::from("All entries are editable"),
+        });
+    }
+
+    fn is_locked(&self, id: TreeIdentifier) -> bool {
+        self.locked_depth.is_some_and(|depth| id.len() > depth)
+    }
+
+    /// Return whether the nodes at level `depth` can be edited, telling the user why if not.
+    fn check_editable(&mut self, depth: usize) -> bool {
+        if self.locked_depth.is_some_and(|locked| depth > locked) {
+            let depth = self.locked_depth.unwrap_or_default();
+            self.status_message = Some(format!("Locked: entries deeper than level {} cannot be edited", depth));
+            return false;
+        }
+        true
+    }
+
+    /// Return whether the whole outline can be rewritten at once, which a lock forbids, telling
+    /// the user why if not.
+    fn check_unlocked(&mut self) -> bool {
+        if let Some(depth) = self.locked_depth {
+            self.status_message = Some(format!("Locked: entries deeper than level {} cannot be edited, :unlock first", depth));
+            return false;
+        }
+        true
+    }
+
+    /// Keep the links of `changed`, a copy of the outline with only links changed, on the entries
+    /// that are not locked. Return the number of entries whose link changed.
+    fn keep_unlocked_links(&mut self, label: &str, changed: Nav) -> usize {
+        let links: Vec<_> = changed.all_nodes().into_iter()
+            .filter(|(id, node)| !self.is_locked(id) && self.nav.get(id).is_some_and(|old| old.link != node.link))
+            .map(|(id, node)| (id, node.link.clone()))
+            .collect();
+        self.transaction(label, |app| {
+            for (id, link) in &links {
+                app.nav[id].link = link.clone();
+            }
+        });
+        links.len()
+    }
+
+    /// Return the pages a page typed for the node `id` can refer to.
+    fn page_context(&self, id: TreeIdentifier) -> PageContext {
+        let pages = self.pages.as_deref();
+        let entries: Vec<_> = self.nav.all_nodes().into_iter()
+            .map(|(other, node)| (other.len(), page_number(&node.link, pages)))
+            .collect();
+        let index = self.nav.all_nodes().iter().position(|(other, _)| other == id);
+        PageContext::around(&entries, index.unwrap_or(entries.len()), self.page_count)
+    }
+
+    /// Return the pages a page typed for the imported entry `index` can refer to.
+    fn merge_page_context(&self, index: usize) -> PageContext {
+        let pages = self.pages.as_deref();
+        let entries: Vec<_> = self.merge.iter().flat_map(|merge| &merge.rows)
+            .map(|row| (row.depth, page_number(&row.node.link, pages)))
+            .collect();
+        PageContext::around(&entries, index, self.page_count)
+    }
+
+    fn open_entry_form(&mut self) {
+        if !self.check_editable(self.tree_state.selected().len()) {
+            return;
+        }
+        let Some(node) = self.nav.get(self.tree_state.selected()) else {
+            return;
+        };
+        self.entry_form = [LineInput::new(&node.string), LineInput::new(&node.link.to_string())];
+        self.entry_form_field = 0;
+        self.state = AppState::EditingEntry;
+    }
+
+    /// Edit the selected node or, while merging, the selected imported entry.
+    fn handle_entry_form_input(&mut self, key: KeyEvent) {
+        let previous_state = match self.merge {
+            Some(_) => AppState::Merging,
+            None => AppState::Navigating,
+        };
+        let completing = self.entry_form_field == 1
+            && key.modifiers.contains(KeyModifiers::CONTROL)
+            && matches!(key.code, KeyCode::Char('n' | 'p'));
+        if !completing {
+            self.page_completion = None;
+        }
+        match key.code {
+            KeyCode::Esc => self.state = previous_state,
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down => {
+                self.entry_form_field = 1 - self.entry_form_field;
+            },
+            KeyCode::Char(c) if completing => self.complete_page_id(c == 'n'),
+            KeyCode::Enter if previous_state == AppState::Merging => {
+                let selected = self.merge_list.selected().unwrap_or(0);
+                let [title, link] = &self.entry_form;
+                let link = match self.typed_link(link.text(), &self.entry_form_context()) {
+                    Ok(link) => link,
+                    Err(e) => {
+                        self.status_message = Some(e);
+                        return;
+                    },
+                };
+                self.state = previous_state;
+                if let Some(row) = self.merge.as_mut().and_then(|merge| merge.rows.get_mut(selected)) {
+                    row.node.string = String::from(title.text());
+                    row.node.link = link;
+                    row.accepted = true;
+                }
+            },
+            KeyCode::Enter => {
+                let selected = self.tree_state.selected().to_owned();
+                let [title, link] = &self.entry_form;
+                let link = match self.typed_link(link.text(), &self.entry_form_context()) {
+                    Ok(link) => link,
+                    Err(e) => {
+                        self.status_message = Some(e);
+                        return;
+                    },
+                };
+                self.state = AppState::Navigating;
+                let title = String::from(title.text());
+                self.transaction("edit entry", |app| {
+                    app.nav[&selected].string = title;
+                    app.nav[&selected].link = link;
+                });
+            },
+            _ => {
+                let changed = self.entry_form[self.entry_form_field].handle_key(key);
+                // Tell what the page being typed points to, to catch mistakes before saving.
+                if changed && self.entry_form_field == 1 {
+                    let context = self.entry_form_context();
+                    self.status_message = Some(match parse_link(self.entry_form[1].text(), &context, &self.anchors) {
+                        Ok(link) => {
+                            let pages = self.pages.as_deref();
+                            let names = page_number(&link, pages).map(|page| self.anchors.describe(page)).unwrap_or_default();
+                            format!("{}{}", self.link_resolvers.resolve(&link, pages), names)
+                        },
+                        Err(e) => e.to_string(),
+                    });
+                }
+            },
+        }
+    }
+
+    /// Return the pages a page typed in the entry form can refer to.
+    fn entry_form_context(&self) -> PageContext {
+        match self.merge {
+            Some(_) => self.merge_page_context(self.merge_list.selected().unwrap_or(0)),
+            None => self.page_context(self.tree_state.selected()),
+        }
+    }
+
+    /// Return the link typed as `input` for an entry with the surroundings `context`, or why it
+    /// cannot be used: page expressions are evaluated, and pages named have to exist.
+    fn typed_link(&self, input: &str, context: &PageContext) -> Result<BookmarkLink, String> {
+        let link = parse_link(input, context, &self.anchors).map_err(|e| format!("Cannot use the page: {}", e))?;
+        if let LinkTarget::UnknownComponent(id) = self.link_resolvers.resolve(&link, self.pages.as_deref()) {
+            return Err(format!("No page is named {} (Ctrl-n and Ctrl-p complete the names of pages)", id));
+        }
+        Ok(link)
+    }
+
+    /// Replace the page of the entry form with the next name of a page completing what was typed,
+    /// or the previous one if `forward` is not set.
+    fn complete_page_id(&mut self, forward: bool) {
+        let Some(pages) = self.pages.as_deref() else {
+            self.status_message = Some(String::from("The pages of the document are not known"));
+            return;
+        };
+        let (typed, index) = self.page_completion.take()
+            .unwrap_or_else(|| (String::from(self.entry_form[1].text()), usize::MAX));
+        let candidates = page_id_candidates(&typed, pages);
+        if candidates.is_empty() {
+            self.status_message = Some(format!("No page is named like {}", typed));
+            return;
+        }
+        let count = candidates.len();
+        let index = match (index, forward) {
+            (usize::MAX, true) => 0,
+            (usize::MAX, false) => count - 1,
+            (index, true) => (index + 1) % count,
+            (index, false) => (index + count - 1) % count,
+        };
+        let page = candidates[index];
+        self.entry_form[1] = LineInput::new(&page.id);
+        self.status_message = Some(format!("{} ({} of {})", LinkTarget::Page(page), index + 1, count));
+        self.page_completion = Some((typed, index));
+    }
+
+    fn handle_search_input(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.state = AppState::Navigating,
+            KeyCode::Enter => {
+                self.state = AppState::Navigating;
+                let query = String::from(self.command_line.text());
+                self.search(&query);
+            },
+            _ => {
+                self.command_line.handle_key(key);
+            },
+        }
+    }
+
+    /// Select the first node after the selected one whose title contains `query`.
+    fn search(&mut self, query: &str) {
+        self.search_matches = self.nav.find(query);
+        if self.search_matches.is_empty() {
+            self.status_message = Some(format!("No match for \"{}\"", query));
+            return;
+        }
+        let selected = self.tree_state.selected();
+        let position = self.search_matches.iter()
+            .position(|id| id.as_slice() > selected)
+            .unwrap_or(0);
+        self.select_match(position);
+    }
+
+    /// Select the next match of the last search, or the previous one if `forward` is false,
+    /// wrapping around the outline.
+    fn next_match(&mut self, forward: bool) {
+        let count = self.search_matches.len();
+        if count == 0 {
+            self.status_message = Some(String::from("No search"));
+            return;
+        }
+        let position = if forward {
+            (self.search_position + 1) % count
+        } else {
+            (self.search_position + count - 1) % count
+        };
+        self.select_match(position);
+    }
+
+    fn select_match(&mut self, position: usize) {
+        self.search_position = position;
+        let id = self.search_matches[position].clone();
+        if self.nav.get(&id).is_none() {
+            self.status_message = Some(String::from("The outline changed since the search"));
+            return;
+        }
+        for opened in self.temporarily_opened.drain(..) {
+            self.tree_state.close(&opened);
+        }
+        let opened = self.reveal(&id);
+        if self.config.navigation.search_expansion == Some(SearchExpansion::Temporary) {
+            self.temporarily_opened = opened;
+        }
+        self.status_message = Some(format!("Match {} of {}", position + 1, self.search_matches.len()));
+    }
+
+    fn run_command(&mut self, command: command::Command) -> Result<(), AppLifetimeError> {
+        match command {
+            command::Command::Skeleton(spacing) => self.generate_skeleton(spacing),
+            command::Command::Generate => self.propose_outline(),
+            command::Command::Import(path) => self.import_file(&path),
+            command::Command::Issues(start, frequency, pages_per_issue) => {
+                self.generate_issues(start, frequency, pages_per_issue);
+            },
+            command::Command::IssuesCsv(path) => self.generate_issues_from_csv(&path),
+            command::Command::Index(pages, output) => self.import_index(pages, output),
+            command::Command::Offset(delta, from) => {
+                match from.evaluate(&self.page_context(self.tree_state.selected())) {
+                    Ok(from) => self.shift_pages(&[Vec::new()], delta, from),
+                    Err(e) => self.status_message = Some(format!("Cannot use the page: {}", e)),
+                }
+            },
+            command::Command::OffsetSubtree(delta) => {
+                if let Some((father, range)) = self.selected_siblings() {
+                    let subtrees: Vec<_> = range.map(|i| [&father[..], &[i]].concat()).collect();
+                    self.shift_pages(&subtrees, delta, 0);
+                }
+            },
+            command::Command::ConvertLinks(kind, subtree) => {
+                let within = match subtree {
+                    true => self.selected_siblings()
+                        .map(|(father, range)| range.map(|i| [&father[..], &[i]].concat()).collect())
+                        .unwrap_or_default(),
+                    false => vec![Vec::new()],
+                };
+                self.convert_links(&within, kind);
+            },
+            command::Command::Interpolate => self.interpolate_pages(),
+            command::Command::SuggestOffset => self.suggest_offset(),
+            command::Command::Write => {
+                if let Err(e) = self.write() {
+                    self.status_message = Some(format!("Cannot write {}: {}", self.display_name(), e));
+                }
+            },
+            command::Command::ForceWrite => {
+                if let Err(e) = self.force_write() {
+                    self.status_message = Some(format!("Cannot write {}: {}", self.display_name(), e));
+                }
+            },
+            command::Command::Quit => self.quit(),
+            command::Command::ForceQuit => self.state = AppState::Quitting,
+            command::Command::WriteQuit => match self.write() {
+                Ok(true) => self.state = AppState::Quitting,
+                Ok(false) => (),
+                Err(e) => self.status_message = Some(format!("Cannot write {}: {}", self.display_name(), e)),
+            },
+            command::Command::Titles => self.open_report(dedup::title_groups(&self.nav)),
+            command::Command::Dehyphenate => self.propose_dehyphenation(),
+            command::Command::Lock(depth) => self.lock_below(depth),
+            command::Command::Targets => {
+                self.open_report(targets::target_groups(&self.nav, self.pages.as_deref()));
+            },
+            command::Command::Trash => self.open_trash(),
+            command::Command::Clear => self.ask_clear(),
+            command::Command::Changes => self.open_report(history::changes_report(&self.loaded_nav, &self.nav)),
+            command::Command::Verify => {
+                // Replacing a running verification cancels it.
+                self.verification = Some(Verification::start(&self.filename, &self.nav, self.pages.as_deref()));
+            },
+            command::Command::Check => match self.page_count {
+                Some(page_count) => self.open_report(check::out_of_range_report(&self.nav, page_count)),
+                None => self.status_message = Some(String::from("The number of pages is unknown")),
+            },
+            command::Command::Lint => {
+                let findings = validate::validate(&self.nav, self.pages.as_deref(), self.config.policy.max_gap);
+                if findings.is_empty() {
+                    self.status_message = Some(String::from("The outline follows the pages of the document"));
+                } else {
+                    self.open_report(validate::validation_report(&self.nav, &findings));
+                }
+            },
+            command::Command::Text => self.edit_text()?,
+            command::Command::Ocr(ocr, scandata) => self.load_ocr(&ocr, scandata.as_deref()),
+            command::Command::Series(model, numbering) => self.propose_from_volume(&model, numbering),
+            command::Command::Columns(columns) if columns.is_empty() => self.columns = configured_columns(&self.config),
+            command::Command::Columns(columns) => self.columns = columns,
+            command::Command::Note(note) => self.annotate_note(note),
+            command::Command::Pin => self.toggle_pin(),
+            command::Command::Anchor(name, page) => self.name_page(name, page),
+            command::Command::Unanchor(name) => {
+                if self.anchors.pages.remove(&name).is_some() {
+                    self.save_anchors(format!("{} no longer names a page", name));
+                } else {
+                    self.status_message = Some(format!("No page is named {}", name));
+                }
+            },
+            command::Command::Anchors => {
+                self.open_report(anchors::anchors_report(&self.anchors, &self.nav, self.pages.as_deref()));
+            },
+            command::Command::Goto(page) => self.go_to_page(&page),
+            command::Command::PageLabels => {
+                self.page_labels = !self.page_labels;
+                self.status_message = Some(String::from(match self.page_labels {
+                    true => "The page column shows the titles of the pages",
+                    false => "The page column shows the numbers of the pages",
+                }));
+            },
+        }
+        Ok(())
+    }
+
+    /// Return the annotation of the entry `id`, unless the entry changed title since.
+    fn annotation(&self, id: TreeIdentifier) -> Option<&Annotation> {
+        let annotation = self.annotations.get(id)?;
+        (Some(annotation.title.as_str()) == self.nav.get(id).map(|node| node.string.as_str())).then_some(annotation)
+    }
+
+    /// Change the annotation of the selected entry with `f`, starting afresh if it is stale, and
+    /// drop it if nothing is left in it.
+    fn update_annotation(&mut self, f: impl FnOnce(&mut Annotation)) {
+        let id = self.tree_state.selected().to_owned();
+        let Some(node) = self.nav.get(&id) else {
+            return;
+        };
+        let title = node.string.clone();
+        let annotation = self.annotations.entry(id.clone()).or_default();
+        if annotation.title != title {
+            *annotation = Annotation { title, ..Annotation::default() };
+        }
+        f(annotation);
+        if annotation.note.is_none() && !annotation.pinned {
+            self.annotations.remove(&id);
+        }
+    }
+
+    fn annotate_note(&mut self, note: Option<String>) {
+        match note {
+            Some(note) if note.is_empty() => {
+                let note = self.annotation(self.tree_state.selected()).and_then(|annotation| annotation.note.clone());
+                self.status_message = Some(note.unwrap_or_else(|| String::from("No note on this entry")));
+            },
+            note => self.update_annotation(|annotation| annotation.note = note),
+        }
+    }
+
+    fn toggle_pin(&mut self) {
+        self.update_annotation(|annotation| annotation.pinned = !annotation.pinned);
+    }
+
+    /// Return the badges of the entries having any, by identifier.
+    fn badges(&self) -> HashMap<TreeIdentifierVec, Badges> {
+        let mut badges: HashMap<TreeIdentifierVec, Badges> = HashMap::new();
+        for finding in &self.findings {
+            badges.entry(finding.id.clone()).or_default().warning = true;
+        }
+        for id in self.annotations.keys() {
+            if let Some(annotation) = self.annotation(id) {
+                let entry = badges.entry(id.clone()).or_default();
+                entry.note = annotation.note.is_some();
+                entry.pinned = annotation.pinned;
+            }
+        }
+        // An empty identifier stands for the top level, which has no row.
+        for id in self.loaded_nav.changed_nodes(&self.nav).into_iter().filter(|id| !id.is_empty()) {
+            badges.entry(id).or_default().modified = true;
+        }
+        badges
+    }
+
+    /// Collect the results of the verification running in the background, and tell when it ends.
+    fn poll_verification(&mut self) {
+        let Some(verification) = &mut self.verification else {
+            return;
+        };
+        if verification.poll() {
+            self.status_message = Some(format!(
+                "Verification done: {} titles not found on their page",
+                verification.missing(),
+            ));
+        }
+    }
+
+    /// Start the queries about the document the panes need for the selected entry, and cancel those
+    /// for the entries selected before, so that moving quickly through the outline does not queue
+    /// them up.
+    fn poll_page_queries(&mut self) {
+        let page = self.nav.get(self.tree_state.selected())
+            .and_then(|node| page_number(&node.link, self.pages.as_deref()));
+        self.previews.keep(page);
+        self.page_texts.keep(|other| Some(*other) == page);
+        self.page_texts.poll();
+        // The text of OCR files is at hand, and `:verify` checks the titles by itself.
+        let details_shown = !self.presenting && !self.show_preview;
+        if let Some(page) = page.filter(|_| details_shown && self.ocr_source.is_none() && self.verification.is_none()) {
+            let filename = self.filename.clone();
+            self.page_texts.request(page, move |cancel| {
+                get_page_text_with(&CancellableTools::new(cancel), &filename, page).map_err(|e| e.to_string())
+            });
+        }
+    }
+
+    /// Shift the pages of the subtrees `within`, as `offset::shift_pages` does, except for the
+    /// locked entries.
+    fn shift_pages(&mut self, within: &[TreeIdentifierVec], delta: i32, from: u32) {
+        let mut shifted = self.nav.clone();
+        for subtree in within {
+            offset::shift_pages(&mut shifted, subtree, delta, from);
+        }
+        let changed = self.keep_unlocked_links("offset pages", shifted);
+        self.status_message = Some(format!("Shifted {} entries by {} pages", changed, delta));
+    }
+
+    /// Make the links of the subtrees `within` point to their pages in the way `kind`, except for
+    /// the locked entries.
+    fn convert_links(&mut self, within: &[TreeIdentifierVec], kind: PageLinkKind) {
+        let Some(pages) = self.pages.as_deref() else {
+            self.status_message = Some(String::from("The pages of the document are not known"));
+            return;
+        };
+        let mut converted = self.nav.clone();
+        for subtree in within {
+            link::convert_links(&mut converted, subtree, kind, pages);
+        }
+        let changed = self.keep_unlocked_links("convert links", converted);
+        let way = match kind {
+            PageLinkKind::Number => "page numbers",
+            PageLinkKind::Id => "page names",
+        };
+        self.status_message = Some(format!("Changed {} links to {}", changed, way));
+    }
+
+    /// Give the siblings of the selected node without a page a first guess of their page, from the
+    /// pages around them.
+    fn interpolate_pages(&mut self) {
+        let selected = self.tree_state.selected().to_owned();
+        if !self.check_editable(selected.len()) {
+            return;
+        }
+        let parent = selected.split_last().map_or(&[][..], |(_, parent)| parent);
+        let changed = self.transaction("interpolate pages", |app| {
+            offset::interpolate_pages(&mut app.nav, parent, app.pages.as_deref(), app.page_count)
+        });
+        self.status_message = Some(match changed {
+            0 => String::from("No sibling without a page between known pages"),
+            changed => format!("Guessed the pages of {} entries, to refine", changed),
+        });
+    }
+
+    /// Point at the first entry breaking the page order of the outline, whose page is likely where
+    /// an offset should start.
+    fn suggest_offset(&mut self) {
+        let breaks = offset::order_breaks(&self.nav, self.pages.as_deref());
+        let Some(first) = breaks.first() else {
+            // Page titles imported from scan data give the offset of an outline typed from the
+            // printed page numbers.
+            let titles: Vec<_> = self.pages.iter().flatten().map(|page| page.title.clone()).collect();
+            self.status_message = Some(match offset::label_offset(&titles) {
+                Some(delta) if delta != 0 => format!(
+                    "The outline is in page order; for printed page numbers, the page titles suggest :offset {}",
+                    delta,
+                ),
+                _ => String::from("The outline is in page order"),
+            });
+            return;
+        };
+        self.reveal(&first.id);
+        self.status_message = Some(format!(
+            "{}: page {} after page {} ({} breaks in page order), try :offset <pages> {}",
+            format_identifier(&first.id), first.page, first.previous_page, breaks.len(), first.page,
+        ));
+    }
+
+    fn open_report(&mut self, report: Report) {
+        if report.rows.is_empty() {
+            self.status_message = Some(format!("{}: nothing to report", report.title));
+            return;
+        }
+        self.report = report;
+        self.report_list.select(Some(0));
+        self.state = AppState::BrowsingReport;
+    }
+
+    fn handle_report_input(&mut self, key: KeyEvent) {
+        let selected = self.report_list.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.state = AppState::Navigating,
+            KeyCode::Char('j') if selected + 1 < self.report.rows.len() => {
+                self.report_list.select(Some(selected + 1));
+            },
+            KeyCode::Char('k') if selected > 0 => self.report_list.select(Some(selected - 1)),
+            KeyCode::Enter => {
+                if let Some(target) = self.report.rows[selected].target.clone() {
+                    self.reveal(&target);
+                    self.state = AppState::Navigating;
+                }
+            },
+            _ => (),
+        }
+    }
+
+    /// Select the node `id`, opening its ancestors so that it is visible. Return the nodes that
+    /// were opened.
+    fn reveal(&mut self, id: TreeIdentifier) -> Vec<TreeIdentifierVec> {
+        if self.nav.get(id).is_none() {
+            return Vec::new();
+        }
+        let opened = (1..id.len())
+            .map(|depth| &id[..depth])
+            .filter(|ancestor| self.tree_state.open(ancestor))
+            .map(|ancestor| ancestor.to_owned())
+            .collect();
+        self.tree_state.select(id);
+        opened
+    }
+
+    /// Replace the outline with one proposed from the outline of `model`, another volume of the
+    /// series, whose pages are to be confirmed.
+    fn propose_from_volume(&mut self, model: &str, numbering: Numbering) {
+        let model_nav = match get_nav_from_djvu(model) {
+            Ok(nav) if nav.nodes.is_empty() => {
+                self.status_message = Some(format!("{} has no outline", model));
+                return;
+            },
+            Ok(nav) => nav,
+            Err(e) => {
+                self.status_message = Some(format!("Cannot read the outline of {}: {}", model, e));
+                return;
+            },
+        };
+        let (Ok(model_page_count), Some(page_count)) = (get_page_count(model), self.page_count) else {
+            self.status_message = Some(String::from("The number of pages is unknown"));
+            return;
+        };
+        let nav = series::propose_volume(&model_nav, model_page_count, page_count, numbering);
+        self.review_import("series", nav.nodes, true);
+        self.status_message = Some(String::from(
+            "Proposed the outline of the other volume: the pages are estimates, :verify them once merged",
+        ));
+    }
+
+    /// Read the outline file `path` and review its entries.
+    fn import_file(&mut self, path: &str) {
+        let Some(format) = ImportFormat::from_path(path) else {
+            self.status_message = Some(format!("Cannot guess the format of {}: use .json, .yaml or .txt", path));
+            return;
+        };
+        let nav = std::fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path, e))
+            .and_then(|input| import::import(&input, format).map_err(|e| e.to_string()));
+        match nav {
+            Ok(nav) => self.review_import("import", nav.nodes, false),
+            Err(e) => self.status_message = Some(format!("Outline not imported: {}", e)),
+        }
+    }
+
+    /// Show the entries `nodes` imported from `source` one by one, to accept, reject or edit each
+    /// of them before they are added to the outline, or replace it if `replace`.
+    fn review_import(&mut self, source: &str, nodes: Vec<NavNode>, replace: bool) {
+        if nodes.is_empty() {
+            self.status_message = Some(format!("Nothing to import from {}", source));
+            return;
+        }
+        self.merge = Some(Merge::new(source, nodes, &self.nav, replace));
+        self.merge_list.select(Some(0));
+        self.state = AppState::Merging;
+    }
+
+    fn handle_merge_input(&mut self, key: KeyEvent) {
+        let Some(merge) = &mut self.merge else {
+            self.state = AppState::Navigating;
+            return;
+        };
+        let selected = self.merge_list.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.merge = None;
+                self.state = AppState::Navigating;
+                self.status_message = Some(String::from("Nothing imported"));
+            },
+            KeyCode::Char('j') | KeyCode::Down if selected + 1 < merge.rows.len() => {
+                self.merge_list.select(Some(selected + 1));
+            },
+            KeyCode::Char('k') | KeyCode::Up if selected > 0 => self.merge_list.select(Some(selected - 1)),
+            KeyCode::Char(' ') => {
+                merge.rows[selected].accepted = !merge.rows[selected].accepted;
+                if selected + 1 < merge.rows.len() {
+                    self.merge_list.select(Some(selected + 1));
+                }
+            },
+            KeyCode::Char('a') => {
+                let accepted = merge.accepted() < merge.rows.len();
+                for row in &mut merge.rows {
+                    row.accepted = accepted;
+                }
+            },
+            KeyCode::Char('r') => merge.replace = !merge.replace,
+            KeyCode::Char('e') => {
+                let node = &merge.rows[selected].node;
+                self.entry_form = [LineInput::new(&node.string), LineInput::new(&node.link.to_string())];
+                self.entry_form_field = 0;
+                self.state = AppState::EditingEntry;
+            },
+            KeyCode::Enter => self.apply_merge(),
+            _ => (),
+        }
+    }
+
+    /// Add the accepted imported entries to the outline, or replace it with them.
+    fn apply_merge(&mut self) {
+        let Some(merge) = self.merge.take() else {
+            return;
+        };
+        let mut merged = self.nav.clone();
+        merge.apply(&mut merged);
+        let deepest = merged.all_nodes().iter().map(|(id, _)| id.len()).max().unwrap_or(1);
+        if !self.check_editable(deepest) {
+            self.merge = Some(merge);
+            return;
+        }
+        self.state = AppState::Navigating;
+        if merge.replace {
+            self.trash_nodes(vec![0], self.nav.nodes.clone());
+        }
+        self.transaction(&format!("merge {}", merge.source), |app| app.nav = merged);
+        self.ensure_valid_selection();
+        self.status_message = Some(match merge.replace {
+            true => format!("Replaced the outline with {} entries", merge.accepted()),
+            false => format!("Added {} entries", merge.accepted()),
+        });
+    }
+
+    /// Read the text of the pages from the hOCR or ALTO file `path`, whose images are mapped to
+    /// pages with the scan data file `scandata` if given, to use in place of the text layer.
+    fn load_ocr(&mut self, path: &str, scandata: Option<&str>) {
+        self.status_message = Some(match read_ocr_text(path, scandata) {
+            Ok(texts) => {
+                let message = format!("Read the text of {} pages from {}", texts.len(), path);
+                self.ocr_text = OnceCell::from(Ok(texts));
+                self.ocr_source = Some((String::from(path), scandata.map(String::from)));
+                message
+            },
+            Err(e) => format!("OCR text not loaded: {}", e),
+        });
+    }
+
+    /// Return the text of the pages read from the OCR file loaded with `:ocr`, reading it again
+    /// if it was released while idle, or `None` if there is no such file.
+    fn ocr_text(&self) -> Option<Result<&HashMap<u32, String>, &str>> {
+        let (path, scandata) = self.ocr_source.as_ref()?;
+        let texts = self.ocr_text.get_or_init(|| read_ocr_text(path, scandata.as_deref()));
+        Some(texts.as_ref().map_err(String::as_str))
+    }
+
+    /// Read the OCR text again if it was released while idle. Return `false`, saying why, if it
+    /// cannot be read anymore.
+    fn reload_ocr_text(&mut self) -> bool {
+        let Some(Err(e)) = self.ocr_text() else {
+            return true;
+        };
+        self.status_message = Some(format!("OCR text not read again: {}", e));
+        // The next command tries again, while drawing does not.
+        self.ocr_text.take();
+        false
+    }
+
+    /// Release what can be read or rendered again when needed, once there was no input for the
+    /// configured time, so that a session left open does not hold on to memory.
+    fn suspend_if_idle(&mut self) {
+        let minutes = self.config.idle_minutes.unwrap_or(DEFAULT_IDLE_MINUTES);
+        if self.suspended || minutes == 0 || self.last_input.elapsed() < Duration::from_secs(minutes * 60) {
+            return;
+        }
+        self.previews.release();
+        self.page_texts.clear();
+        self.ocr_text.take();
+        self.suspended = true;
+    }
+
+    /// Return the text of page `page`, from the OCR file loaded with `:ocr` if any, or else from
+    /// the text layer.
+    fn page_text(&self, page: u32) -> Result<String, NavReadingError> {
+        match self.ocr_text() {
+            Some(Ok(texts)) => Ok(texts.get(&page).cloned().unwrap_or_default()),
+            Some(Err(e)) => Err(NavReadingError::IOError(io::Error::other(e))),
+            None => match self.page_texts.get(&page) {
+                Some(Ok(text)) => Ok(text.clone()),
+                _ => get_page_text(&self.filename, page),
+            },
+        }
+    }
+
+    /// Return the text of every page, from the OCR file loaded with `:ocr` if any, or else from
+    /// the text layer.
+    fn document_text(&self) -> Result<Vec<String>, NavReadingError> {
+        match (self.ocr_text(), self.page_count) {
+            (Some(Ok(texts)), Some(page_count)) => Ok((1..=page_count)
+                .map(|page| texts.get(&page).cloned().unwrap_or_default())
+                .collect()),
+            (Some(Err(e)), _) => Err(NavReadingError::IOError(io::Error::other(e))),
+            _ => get_document_text(&self.filename),
+        }
+    }
+
+    /// Look for headings in the text of the pages, and review them to add them to the outline.
+    fn propose_outline(&mut self) {
+        if !self.reload_ocr_text() {
+            return;
+        }
+        let texts = match self.document_text() {
+            Ok(texts) => texts,
+            Err(e) => {
+                self.status_message = Some(format!("Cannot read the text of the document: {}", e));
+                return;
+            },
+        };
+        let patterns = self.config.generate.headings.clone().unwrap_or_else(default_heading_patterns);
+        let nodes = match heading::outline_from_text(&texts, &patterns) {
+            Ok(nodes) => nodes,
+            Err(e) => {
+                self.status_message = Some(e.to_string());
+                return;
+            },
+        };
+        if nodes.is_empty() {
+            self.status_message = Some(String::from("No heading found in the text of the pages"));
+            return;
+        }
+        self.review_import("generate", nodes, false);
+    }
+
+    /// Read the back-of-book index on `pages` and turn it into a tree of bookmarks, appended to
+    /// the outline or written to the file `output` in the `djvused` outline format. The text layer
+    /// is read in the background, and the entries added once `poll_index_import` gets it.
+    fn import_index(&mut self, pages: RangeInclusive<u32>, output: Option<String>) {
+        if !self.reload_ocr_text() {
+            return;
+        }
+        if self.ocr_source.is_some() {
+            let texts = pages.clone()
+                .map(|page| self.page_text(page).map_err(|e| page_text_error(page, e)))
+                .collect::<Result<Vec<_>, _>>();
+            self.import_index_text(pages, output, texts.map(|texts| texts.join("\n")));
+            return;
+        }
+        let filename = self.filename.clone();
+        let range = pages.clone();
+        // A new index replaces the one being read.
+        self.index_texts.clear();
+        self.index_texts.request(pages.clone(), move |cancel| {
+            let tools = CancellableTools::new(cancel);
+            let texts = range
+                .map(|page| get_page_text_with(&tools, &filename, page).map_err(|e| page_text_error(page, e)))
+                .collect::<Result<Vec<_>, _>>()?;
+            // A page may end without a line break, which would join its last line to the next page.
+            Ok(texts.join("\n"))
+        });
+        self.status_message = Some(format!("Reading the index on pages {} to {}", pages.start(), pages.end()));
+        self.index_request = Some((pages, output));
+    }
+
+    /// Add the entries of the index being read with `:index` once its text is read.
+    fn poll_index_import(&mut self) {
+        let Some((pages, _)) = &self.index_request else {
+            return;
+        };
+        self.index_texts.poll();
+        let Some(text) = self.index_texts.get(pages).cloned() else {
+            return;
+        };
+        if let Some((pages, output)) = self.index_request.take() {
+            self.index_texts.clear();
+            self.import_index_text(pages, output, text);
+        }
+    }
+
+    /// Turn `text`, the text of the index on `pages`, into bookmarks as `import_index` does.
+    fn import_index_text(&mut self, pages: RangeInclusive<u32>, output: Option<String>, text: Result<String, String>) {
+        let text = match text {
+            Ok(text) => text,
+            Err(e) => {
+                self.status_message = Some(e);
+                return;
+            },
+        };
+        let expand = self.config.import.expand_multiple_targets.unwrap_or(false);
+        let groups = index_import::index_to_nodes(index_import::parse_index(&text), expand);
+        if groups.is_empty() {
+            self.status_message = Some(String::from("No index entry found"));
+            return;
+        }
+
+        match output {
+            Some(path) => {
+                let count = groups.iter().map(|group| group.children.len()).sum::<usize>();
+                self.status_message = Some(match std::fs::write(&path, Nav { nodes: groups }.to_djvu()) {
+                    Ok(()) => format!("Wrote {} index entries to {}", count, path),
+                    Err(e) => format!("Cannot write {}: {}", path, e),
+                });
+            },
+            None => {
+                let index = NavNode {
+                    string: String::from("Index"),
+                    link: BookmarkLink::PageNumber(*pages.start()),
+                    children: groups,
+                };
+                self.append_generated("import index", vec![index]);
+            },
+        }
+    }
+
+    /// Append one bookmark per issue of a periodical to the outline.
+    fn generate_issues(&mut self, start: NaiveDate, frequency: Frequency, pages_per_issue: u32) {
+        let Some(pages) = &self.pages else {
+            self.status_message = Some(String::from("The pages of the document are unknown"));
+            return;
+        };
+        let nodes = generate::periodical_issues(pages.len(), start, frequency, pages_per_issue);
+        self.append_generated("generate issues", nodes);
+    }
+
+    fn generate_issues_from_csv(&mut self, path: &str) {
+        let nodes = match std::fs::read_to_string(path) {
+            Ok(content) => generate::issues_from_csv(&content).map_err(|e| e.to_string()),
+            Err(e) => Err(format!("cannot read {}: {}", path, e)),
+        };
+        match nodes {
+            Ok(nodes) => self.append_generated("import issues", nodes),
+            Err(message) => self.status_message = Some(message),
+        }
+    }
+
+    fn append_generated(&mut self, label: &str, nodes: Vec<NavNode>) {
+        let count = nodes.len();
+        self.transaction(label, |app| app.nav.nodes.extend(nodes));
+        self.ensure_valid_selection();
+        self.status_message = Some(format!("Added {} entries", count));
+    }
+
+    /// Append bookmarks spread evenly across the document to the outline.
+    fn generate_skeleton(&mut self, spacing: Spacing) {
+        let Some(pages) = &self.pages else {
+            self.status_message = Some(String::from("The pages of the document are unknown"));
+            return;
+        };
+        let nodes = generate::evenly_spaced(pages, spacing);
+        self.append_generated("generate skeleton", nodes);
+    }
+
+    fn handle_bulk_change_input(&mut self, key: KeyEvent) {
+        let selected = self.bulk_list.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                self.bulk_change = None;
+                self.state = AppState::Navigating;
+            },
+            KeyCode::Char('j') if selected + 1 < self.bulk_preview.len() => {
+                self.bulk_list.select(Some(selected + 1));
+            },
+            KeyCode::Char('k') if selected > 0 => self.bulk_list.select(Some(selected - 1)),
+            KeyCode::Enter => {
+                self.apply_bulk_change();
+                self.state = AppState::Navigating;
+            },
+            _ => (),
+        }
+    }
+
+    /// Ask for confirmation of `change`, described line by line by `preview`.
+    fn preview_bulk_change(&mut self, change: BulkChange, preview: Vec<String>) {
+        self.bulk_change = Some(change);
+        self.bulk_preview = preview;
+        self.bulk_list.select(Some(0));
+        self.state = AppState::PreviewingBulkChange;
+    }
+
+    fn apply_bulk_change(&mut self) {
+        let Some(change) = self.bulk_change.take() else {
+            return;
+        };
+        let (label, changed) = match change {
+            BulkChange::Fix(fix, targets) => {
+                let mut nav = self.nav.clone();
+                let page_text = |page| self.page_text(page).ok();
+                let changed = fix.apply_all(&mut nav, &targets, &self.fix_context(&page_text));
+                self.transaction(&format!("{} (all)", fix.label()), |app| app.nav = nav);
+                (fix.label(), changed)
+            },
+            BulkChange::Titles(label, titles) => {
+                self.transaction(&label.to_lowercase(), |app| {
+                    for (id, title) in &titles {
+                        app.nav[id].string = title.clone();
+                    }
+                });
+                (label, titles.len())
+            },
+        };
+        self.ensure_valid_selection();
+        self.status_message = Some(format!("Applied: {} to {} entries", label, changed));
+    }
+
+    /// Look for a heading in the text of the target page of every entry without a title, and
+    /// propose to use it as the title.
+    fn propose_titles(&mut self) {
+        if !self.reload_ocr_text() {
+            return;
+        }
+        let page_text = |page| self.page_text(page).ok();
+        let context = self.fix_context(&page_text);
+        let titles: Vec<_> = self.nav.all_nodes().into_iter()
+            .filter(|(id, node)| node.string.trim().is_empty() && !self.is_locked(id))
+            .filter_map(|(id, node)| title_from_page(node, &context).map(|title| (id, title)))
+            .collect();
+        if titles.is_empty() {
+            self.status_message = Some(String::from("No title found for the entries without one"));
+            return;
+        }
+        let preview = titles.iter()
+            .map(|(id, title)| format!("{}: \"{}\"", format_identifier(id), title))
+            .collect();
+        self.preview_bulk_change(BulkChange::Titles("Title from page text", titles), preview);
+    }
+
+    /// Propose to join back the words broken by hyphenation in the titles of the whole outline.
+    fn propose_dehyphenation(&mut self) {
+        let titles: Vec<_> = hyphenation::broken_titles(&self.nav).into_iter()
+            .filter(|(id, _)| !self.is_locked(id))
+            .collect();
+        if titles.is_empty() {
+            self.status_message = Some(String::from("No word broken by hyphenation"));
+            return;
+        }
+        let preview = titles.iter()
+            .map(|(id, title)| format!("{}: \"{}\" -> \"{}\"", format_identifier(id), self.nav[id].string, title))
+            .collect();
+        self.preview_bulk_change(BulkChange::Titles("Join hyphenated words", titles), preview);
+    }
+
+    /// Offer the fixes applicable to the selected node, if the linter reported anything about it.
+    fn open_quick_fix_menu(&mut self) {
+        if !self.check_editable(self.tree_state.selected().len()) {
+            return;
+        }
+        if !self.reload_ocr_text() {
+            return;
+        }
+        let selected = self.tree_state.selected();
+        if findings_for(&self.findings, selected).next().is_none() {
+            self.status_message = Some(String::from("Nothing to fix here"));
+            return;
+        }
+        let page_text = |page| self.page_text(page).ok();
+        self.quick_fixes = QuickFix::applicable(&self.nav, selected, &self.fix_context(&page_text));
+        if self.quick_fixes.is_empty() {
+            self.status_message = Some(String::from("No quick fix available"));
+            return;
+        }
+        self.quick_fix_menu.select(Some(0));
+        self.state = AppState::ChoosingQuickFix;
+    }
+
+    fn apply_quick_fix(&mut self, fix: QuickFix) {
+        // The fix works on a copy, as filling a title reads the page text from `self`.
+        let mut nav = self.nav.clone();
+        let page_text = |page| self.page_text(page).ok();
+        fix.apply(&mut nav, self.tree_state.selected(), &self.fix_context(&page_text));
+        self.transaction(fix.label(), |app| app.nav = nav);
+        self.ensure_valid_selection();
+        self.status_message = Some(format!("Applied: {}", fix.label()));
+    }
+
+    /// Offer the fixes applicable to at least one node with lint findings, to apply them to all
+    /// such nodes at once.
+    fn open_bulk_fix_menu(&mut self) {
+        if !self.reload_ocr_text() {
+            return;
+        }
+        let page_text = |page| self.page_text(page).ok();
+        let context = self.fix_context(&page_text);
+        self.quick_fixes = ALL_FIXES.into_iter()
+            .filter(|fix| !fix.targets(&self.nav, &self.findings, &context).is_empty())
+            .collect();
+        if self.quick_fixes.is_empty() {
+            self.status_message = Some(String::from("No quick fix available"));
+            return;
+        }
+        self.quick_fix_menu.select(Some(0));
+        self.state = AppState::ChoosingBulkFix;
+    }
+
+    fn preview_bulk_fix(&mut self, fix: QuickFix) {
+        let page_text = |page| self.page_text(page).ok();
+        let context = self.fix_context(&page_text);
+        let targets: Vec<_> = fix.targets(&self.nav, &self.findings, &context).into_iter()
+            .filter(|id| !self.is_locked(id))
+            .collect();
+        let preview = targets.iter()
+            .filter_map(|id| fix.preview(&self.nav, id, &context))
+            .collect();
+        self.preview_bulk_change(BulkChange::Fix(fix, targets), preview);
+    }
+
+    /// Return what the quick fixes need to know about the document, reading the text of the pages
+    /// with `page_text`.
+    fn fix_context<'a>(&'a self, page_text: &'a dyn Fn(u32) -> Option<String>) -> FixContext<'a> {
+        FixContext {
+            pages: self.pages.as_deref(),
+            policy: &self.config.policy,
+            resolvers: &self.link_resolvers,
+            page_text,
+        }
+    }
+
+    /// Select the first node if the selected one does not exist anymore.
+    fn ensure_valid_selection(&mut self) {
+        if self.nav.get(self.tree_state.selected()).is_none() {
+            self.tree_state.select(Vec::new());
+            if !self.nav.nodes.is_empty() {
+                self.tree_state.select_first();
+            }
+        }
+    }
+
+    fn open_history(&mut self) {
+        if self.history.entries().is_empty() {
+            self.status_message = Some(String::from("No changes yet"));
+            return;
+        }
+        self.history_list.select(Some(0));
+        self.state = AppState::BrowsingHistory;
+    }
+
+    /// Bring the outline back to its state right after step `index` of the history. The jump is
+    /// itself recorded, so no step is lost and it can be undone.
+    fn jump_to_step(&mut self, index: usize) {
+        let target = self.history.state_after(index, &self.nav);
+        let label = format!("jump to step {}", index + 1);
+        self.transaction(&label, |app| app.nav = target);
+        self.ensure_valid_selection();
+        self.status_message = Some(format!("Jumped to step {}", index + 1));
+    }
+
+    fn edit_currently_selected(&mut self) -> Result<(), AppLifetimeError> {
+        if self.tree_state.selected().is_empty() {
+            return Ok(());
+        }
+        let currently_selected_id = self.tree_state.selected().to_owned();
+        if !self.check_editable(currently_selected_id.len()) {
+            return Ok(());
+        }
+        let current_node = &self.nav[&currently_selected_id];
+        let content = format!("{}\n{}", current_node.string, current_node.link);
+
+        let Some(lines) = self.edit_in_editor(&content)? else {
+            return Ok(());
+        };
+        if lines.len() < 2 {
+            self.status_message = Some(String::from("Expected the title and the page on two lines"));
+            return Ok(());
+        }
+        let link = match self.typed_link(&lines[1], &self.page_context(&currently_selected_id)) {
+            Ok(link) => link,
+            Err(e) => {
+                self.status_message = Some(e);
+                return Ok(());
+            },
+        };
+
+        self.transaction("edit entry", |app| {
+            app.nav[&currently_selected_id].string = lines[0].clone();
+            app.nav[&currently_selected_id].link = link;
+        });
+
+        Ok(())
+    }
+
+    /// Write a plan of the whole outline, let the user edit it, and apply it.
+    fn edit_plan(&mut self) -> Result<(), AppLifetimeError> {
+        if !self.check_unlocked() {
+            return Ok(());
+        }
+        let Some(lines) = self.edit_in_editor(&plan::make_plan(&self.nav))? else {
+            return Ok(());
+        };
+        match plan::apply_plan(&lines.join("\n"), &self.nav) {
+            Ok(nav) => {
+                self.transaction("edit plan", |app| app.nav = nav);
+                self.ensure_valid_selection();
+                self.status_message = Some(String::from("Applied the plan"));
+            },
+            Err(e) => self.status_message = Some(format!("Plan not applied: {}", e)),
+        }
+        Ok(())
+    }
+
+    /// Write the whole outline as indented text, let the user edit it, and read it back.
+    fn edit_text(&mut self) -> Result<(), AppLifetimeError> {
+        if !self.check_unlocked() {
+            return Ok(());
+        }
+        let Some(lines) = self.edit_in_editor(&text_outline::to_text(&self.nav))? else {
+            return Ok(());
+        };
+        match text_outline::from_text(&lines.join("\n")) {
+            Ok(nav) if nav == self.nav => (),
+            Ok(nav) => {
+                self.transaction("edit text", |app| app.nav = nav);
+                self.ensure_valid_selection();
+                self.status_message = Some(String::from("Replaced the outline with the edited text"));
+            },
+            Err(e) => self.status_message = Some(format!("Outline not changed: {}", e)),
+        }
+        Ok(())
+    }
+
+    /// Open `content` in the editor, and return the lines of the text once the editor is closed,
+    /// or `None` if no editor could be launched.
+    fn edit_in_editor(&mut self, content: &str) -> Result<Option<Vec<String>>, AppLifetimeError> {
+        // Create temp file with data in it
+        let temp_file = TempFile::new(".txt").map_err(AppLifetimeError::TempFileError)?;
+        let temp_filename = temp_file.path();
+        {
+            let f = File::create(temp_filename)
+                .map_err(|e| AppLifetimeError::TempFileError(TempFileError::SystemIOError(e)))?;
+            let mut writer = BufWriter::new(f);
+
+            writer.write_all(content.as_bytes())
+                .map_err(|e| AppLifetimeError::TempFileError(TempFileError::SystemIOError(e)))?;
+        }
+
+        // Edit file with the first editor that can be launched
+        self.suspend_terminal()?;
+        let editors = self.config.editors();
+        let mut launch_error = None;
+        for editor in &editors {
+            let mut words = editor.split_whitespace();
+            let Some(program) = words.next() else {
+                continue;
+            };
+            match Command::new(program).args(words).arg(temp_filename).spawn() {
+                Ok(mut command) => {
+                    command.wait().map_err(AppLifetimeError::ExternalProgramError)?;
+                    launch_error = None;
+                    break;
+                },
+                Err(e) => launch_error = Some(e),
+            }
+        }
+        self.resume_terminal()?;
+
+        if let Some(e) = launch_error {
+            self.status_message = Some(format!("Cannot launch an editor (tried {}): {}", editors.join(", "), e));
+            return Ok(None);
+        }
+
+        let tempfile = File::open(temp_filename)
+            .map_err(|e| AppLifetimeError::TempFileError(TempFileError::SystemIOError(e)))?;
+        let reader = BufReader::new(tempfile);
+        let lines: Vec<String> = reader.lines()
+            .map(|result| result.unwrap())
+            .collect();
+        Ok(Some(lines))
+    }
+
+    /// Open the document at the page the selected entry points to, with the first viewer that can
+    /// be launched.
+    fn open_in_viewer(&mut self) -> Result<(), AppLifetimeError> {
+        let Some(node) = self.nav.get(self.tree_state.selected()) else {
+            return Ok(());
+        };
+        let Some(page) = page_number(&node.link, self.pages.as_deref()) else {
+            self.status_message = Some(String::from("The entry does not point to a page of the document"));
+            return Ok(());
+        };
+
+        let in_terminal = self.config.viewer_in_terminal.unwrap_or(false);
+        if in_terminal {
+            self.suspend_terminal()?;
+        }
+        let viewers = self.config.viewers();
+        let mut launched = false;
+        let mut launch_error = None;
+        for viewer in &viewers {
+            let Some((program, args)) = viewer::command_line(viewer, &self.filename, page) else {
+                continue;
+            };
+            let mut command = Command::new(program);
+            command.args(args);
+            if !in_terminal {
+                // Graphical viewers keep running alongside, and must not write over the interface.
+                command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null());
+            }
+            match command.spawn() {
+                Ok(mut child) => {
+                    if in_terminal {
+                        child.wait().map_err(AppLifetimeError::ExternalProgramError)?;
+                    } else {
+                        thread::spawn(move || child.wait());
+                    }
+                    launched = true;
+                    break;
+                },
+                Err(e) => launch_error = Some(e),
+            }
+        }
+        if in_terminal {
+            self.resume_terminal()?;
+        }
+
+        self.status_message = Some(match launch_error {
+            _ if launched => format!("Opened page {} in the viewer", page),
+            Some(e) => format!("Cannot launch a viewer (tried {}): {}", viewers.join(", "), e),
+            None => String::from("No viewer is configured"),
+        });
+        Ok(())
+    }
+
+    /// Give the terminal to another program until `resume_terminal` is called.
+    fn suspend_terminal(&mut self) -> Result<(), AppLifetimeError> {
+        self.state = AppState::RunningOtherCommand;
+        restore_terminal(&mut self.terminal).map_err(AppLifetimeError::TerminalIOError)
+    }
+
+    fn resume_terminal(&mut self) -> Result<(), AppLifetimeError> {
+        self.state = AppState::Navigating;
+        enable_raw_mode().map_err(AppLifetimeError::TerminalIOError)?;
+        TERMINAL_PREPARED.store(true, Ordering::SeqCst);
+        execute!(self.terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)
+            .map_err(AppLifetimeError::TerminalIOError)?;
+        self.terminal.clear().map_err(AppLifetimeError::TerminalIOError)
+    }
+
+    /// Run `f` as a single undoable step labelled `label`. Transactions started from within `f`
+    /// are merged into this one.
+    fn transaction<F, R>(&mut self, label: &str, f: F) -> R
+    where
+        F: FnOnce(&mut Self) -> R
+    {
+        diagnostics::log(label);
+        self.history.begin(label, &self.nav, &self.extras(), self.tree_state.selected());
+        let res = f(self);
+        self.history.commit(&self.nav, &self.extras());
+        self.refresh_findings();
+        res
+    }
+
+    fn refresh_findings(&mut self) {
+        self.findings = lint(&self.nav, &self.config.policy, self.pages.as_deref(), &self.link_resolvers);
+    }
+
+    fn status_bar(&self) -> StatusBar {
+        let selected = self.tree_state.selected();
+        StatusBar {
+            filename: self.display_name(),
+            dirty: self.is_dirty(),
+            read_only: self.read_only,
+            breadcrumb: self.nav.breadcrumb(selected).into_iter().map(String::from).collect(),
+            target: self.nav.get(selected)
+                .map(|node| self.link_resolvers.resolve(&node.link, self.pages.as_deref()).to_string()),
+            task: self.verification.as_ref()
+                .filter(|verification| !verification.is_finished())
+                .map(|verification| {
+                    let (done, total) = verification.progress();
+                    format!("verifying {}/{}", done, total)
+                }),
+        }
+    }
+
+    /// Text of the status line: the last message if there is one, otherwise the warnings about
+    /// the selected node.
+    fn status_line(&self) -> String {
+        if self.state == AppState::EnteringCommand {
+            return format!(":{}", self.command_line.text());
+        }
+        if self.state == AppState::EnteringSearch {
+            return format!("/{}", self.command_line.text());
+        }
+        if let Some(message) = &self.status_message {
+            return message.clone();
+        }
+        if let Some((_, range)) = self.tree_state.range() {
+            return format!("-- VISUAL -- {} entries (d, y, J, K, >, <, :offset <pages> subtree; Esc to leave)", range.count());
+        }
+        match findings_for(&self.findings, self.tree_state.selected()).next() {
+            Some(finding) => format!("Warning: {} ({} in outline)", finding.message, self.findings.len()),
+            None => String::new(),
+        }
+    }
+
+    /// Return what is wrong with the entry `id`: the findings of the policy, a page past the end
+    /// of the document, and a title not found on its page by `:verify`.
+    fn problems(&self, id: TreeIdentifier) -> Vec<String> {
+        let Some(node) = self.nav.get(id) else {
+            return Vec::new();
+        };
+        let mut problems: Vec<String> = findings_for(&self.findings, id)
+            .map(|finding| finding.message.clone())
+            .collect();
+        if let Some(page_count) = self.page_count.filter(|page_count| check::is_out_of_range(node, *page_count)) {
+            problems.push(format!("the document has {} pages", page_count));
+        }
+        let verdict = self.verification.as_ref()
+            .and_then(|verification| verification.verdict(id, node, self.pages.as_deref()));
+        let text = page_number(&node.link, self.pages.as_deref()).and_then(|page| match self.ocr_text() {
+            Some(Ok(texts)) => texts.get(&page).map(String::as_str),
+            Some(Err(_)) => None,
+            None => self.page_texts.get(&page).and_then(|text| text.as_deref().ok()),
+        });
+        // Pages without text, such as those of documents without a text layer, tell nothing.
+        let missing = text.is_some_and(|text| !text.trim().is_empty() && !title_in_text(&node.string, text));
+        if verdict == Some(Verdict::Missing) || (verdict.is_none() && missing) {
+            problems.push(String::from("the title was not found on its page"));
+        }
+        problems
+    }
+
+    fn undo(&mut self) {
+        match self.history.undo(&self.nav, &self.extras(), self.tree_state.selected()) {
+            Some(entry) => {
+                self.restore(&entry);
+                self.status_message = Some(format!("Undid: {}", entry.label));
+            },
+            None => self.status_message = Some(String::from("Already at oldest change")),
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.history.redo(&self.nav, &self.extras(), self.tree_state.selected()) {
+            Some(entry) => {
+                self.restore(&entry);
+                self.status_message = Some(format!("Redid: {}", entry.label));
+            },
+            None => self.status_message = Some(String::from("Already at newest change")),
+        }
+    }
+
+    /// Return what the history keeps besides the outline.
+    fn extras(&self) -> Extras {
+        Extras { metadata: self.metadata.clone(), page_titles: self.page_titles() }
+    }
+
+    /// Return the titles of the pages in order, none if the pages could not be listed.
+    fn page_titles(&self) -> Vec<Option<String>> {
+        self.pages.iter().flatten().map(|page| page.title.clone()).collect()
+    }
+
+    /// Give the pages the titles `titles`, unless they are not those of as many pages.
+    fn apply_page_titles(&mut self, titles: &[Option<String>]) {
+        if let Some(pages) = self.pages.as_mut().filter(|pages| pages.len() == titles.len()) {
+            for (page, title) in pages.iter_mut().zip(titles) {
+                page.title.clone_from(title);
+            }
+        }
+    }
+
+    /// Bring the outline and the selection back to the state saved in `entry`.
+    fn restore(&mut self, entry: &HistoryEntry) {
+        self.nav = entry.nav();
+        // Metadata read after the step was recorded are kept, as they are not read again.
+        if let Some(metadata) = &entry.extras().metadata {
+            self.metadata = Some(metadata.clone());
+        }
+        self.apply_page_titles(&entry.extras().page_titles);
+        self.tree_state.select(entry.selected());
+        self.refresh_findings();
+    }
+
+    /// Cut the selected node, after confirmation if it has descendants.
+    fn ask_cut_selected(&mut self) {
+        let Some((father, _, nodes)) = self.selected_nodes() else {
+            return;
+        };
+        let has_children = nodes.iter().any(|node| !node.children.is_empty());
+        let single = nodes.len() == 1;
+        // The children are checked too, since they are deleted along with their parent.
+        if !self.check_editable(father.len() + 1 + usize::from(has_children)) {
+            return;
+        }
+        if !has_children && single {
+            self.cut_selected();
+        } else {
+            self.confirm_menu.select(Some(1));
+            self.state = AppState::ConfirmingDeletion;
+        }
+    }
+
+    /// Return the question asked before deleting the selected entries along with their descendants.
+    fn deletion_title(&self) -> Option<String> {
+        let (_, _, nodes) = self.selected_nodes()?;
+        Some(match nodes {
+            [node] => format!("Delete \"{}\" and its {} descendants?", node.string, node_count(node) - 1),
+            _ => format!(
+                "Delete these {} entries and their {} descendants?",
+                nodes.len(), nodes_count(nodes) - nodes.len(),
+            ),
+        })
+    }
+
+    fn handle_deletion_input(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Char('k') => {
+                let selected = self.confirm_menu.selected().unwrap_or(1);
+                self.confirm_menu.select(Some(1 - selected));
+            },
+            KeyCode::Char('y') => {
+                self.cut_selected();
+                self.state = AppState::Navigating;
+            },
+            KeyCode::Enter if self.confirm_menu.selected() == Some(0) => {
+                self.cut_selected();
+                self.state = AppState::Navigating;
+            },
+            KeyCode::Enter | KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => {
+                self.state = AppState::Navigating;
+            },
+            _ => (),
+        }
+    }
+
+    /// Remove the whole outline, after confirmation.
+    fn ask_clear(&mut self) {
+        if self.nav.nodes.is_empty() {
+            self.status_message = Some(String::from("The outline is already empty"));
+            return;
+        }
+        let deepest = self.nav.all_nodes().iter().map(|(id, _)| id.len()).max().unwrap_or(1);
+        if !self.check_editable(deepest) {
+            return;
+        }
+        self.confirm_menu.select(Some(1));
+        self.state = AppState::ConfirmingClear;
+    }
+
+    fn handle_clear_input(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Char('k') => {
+                let selected = self.confirm_menu.selected().unwrap_or(1);
+                self.confirm_menu.select(Some(1 - selected));
+            },
+            KeyCode::Char('y') => {
+                self.clear();
+                self.state = AppState::Navigating;
+            },
+            KeyCode::Enter if self.confirm_menu.selected() == Some(0) => {
+                self.clear();
+                self.state = AppState::Navigating;
+            },
+            KeyCode::Enter | KeyCode::Char('n') | KeyCode::Char('q') | KeyCode::Esc => {
+                self.state = AppState::Navigating;
+            },
+            _ => (),
+        }
+    }
+
+    fn clear(&mut self) {
+        let count = self.nav.all_nodes().len();
+        self.trash_nodes(vec![0], self.nav.nodes.clone());
+        self.transaction("remove outline", |app| app.nav.nodes.clear());
+        self.ensure_valid_selection();
+        self.status_message = Some(format!(
+            "Removed the {} entries: :w writes the document without an outline (u to undo)",
+            count,
+        ));
+    }
+
+    /// Delete the selected node and its descendants, keeping them in the clipboard.
+    fn cut_selected(&mut self) {
+        let Some((father, range, nodes)) = self.selected_nodes() else {
+            return;
+        };
+        let nodes = nodes.to_vec();
+        let count = nodes_count(&nodes);
+        self.trash_nodes([&father[..], &[*range.start()]].concat(), nodes.clone());
+        self.clipboard = nodes;
+        // A range of one entry is cut as a single entry, and must not stay started either.
+        self.tree_state.clear_range();
+
+        if range.start() == range.end() {
+            self.transaction("cut entry", |app| app.delete_entry_and_fix_selection());
+        } else {
+            self.transaction("cut entries", |app| app.remove_range_and_fix_selection(&father, range));
+        }
+        if count > 1 {
+            self.status_message = Some(format!("Cut {} entries, p to paste them back", count));
+        }
+    }
+
+    /// Keep the removed siblings `nodes`, the first of which was at `id`, in the trash.
+    fn trash_nodes(&mut self, id: TreeIdentifierVec, nodes: Vec<NavNode>) {
+        if !nodes.is_empty() {
+            self.trash.insert(0, (id, nodes));
+        }
+    }
+
+    fn handle_restore_input(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Char('k') => {
+                let selected = self.confirm_menu.selected().unwrap_or(1);
+                self.confirm_menu.select(Some(1 - selected));
+            },
+            KeyCode::Enter if self.confirm_menu.selected() == Some(0) => {
+                self.restore_autosave();
+                self.state = AppState::Navigating;
+            },
+            KeyCode::Enter => {
+                self.recovered = None;
+                self.discard_autosave();
+                self.state = AppState::Navigating;
+            },
+            _ => (),
+        }
+    }
+
+    /// Replace the outline by the autosave of the earlier session, as an edit that can be undone.
+    fn restore_autosave(&mut self) {
+        let Some(autosave) = self.recovered.take() else {
+            return;
+        };
+        self.transaction("restore autosave", |app| {
+            app.nav = autosave.nav;
+            app.apply_page_titles(&autosave.page_titles);
+        });
+        for id in &autosave.opened {
+            self.tree_state.open(id);
+        }
+        self.tree_state.select(autosave.selected);
+        self.ensure_valid_selection();
+        self.status_message = Some(String::from("Restored the unsaved changes of the last session, u to undo"));
+    }
+
+    /// Save the outline to its autosave if it changed since the last autosave, or remove the
+    /// autosave once there is nothing unsaved.
+    fn autosave(&mut self) {
+        let Some(filename) = &self.autosave_filename else {
+            return;
+        };
+        let page_titles = self.page_titles();
+        let unchanged = self.nav == self.autosaved_nav && page_titles == self.autosaved_page_titles;
+        if self.last_autosave.elapsed() < AUTOSAVE_INTERVAL || unchanged || self.recovered.is_some() {
+            return;
+        }
+        self.last_autosave = Instant::now();
+        let result = if self.is_dirty() {
+            Autosave {
+                filename: filename.clone(),
+                nav: self.nav.clone(),
+                selected: self.tree_state.selected().to_vec(),
+                opened: self.tree_state.get_all_opened(),
+                page_titles: if self.page_titles_changed() { page_titles.clone() } else { Vec::new() },
+            }.save()
+        } else {
+            autosave::remove(filename)
+        };
+        match result {
+            Ok(()) => {
+                self.autosaved_nav = self.nav.clone();
+                self.autosaved_page_titles = page_titles;
+            },
+            Err(e) => self.status_message = Some(format!("Autosave failed: {}", e)),
+        }
+    }
 
-const EDITOR: &str = "nvim";
+    /// Save what is selected and opened in the tree, to go back there when the file is opened again.
+    fn save_session(&self) {
+        if let Some(filename) = &self.session_filename {
+            let _ = session::save(Session {
+                filename: filename.clone(),
+                selected: self.tree_state.selected().to_vec(),
+                opened: self.tree_state.get_all_opened(),
+            });
+        }
+    }
 
-pub fn get_temp_file_name() -> Result<PathBuf, TempFileError> {
-    let xdg_dirs = xdg::BaseDirectories::with_prefix(format!("{}", APP_NAME))
-        .map_err(|e| TempFileError::XDGSpecificError(e))?;
-    // this does not create the cache file, but it creates the directories necessary to create it.
-    xdg_dirs.place_cache_file(TEMP_FILE_NAME)
-        .map_err(|e| TempFileError::SystemIOError(e))
-}
+    /// Remove the autosave, once the outline is written or its changes given up.
+    fn discard_autosave(&mut self) {
+        if let Some(filename) = &self.autosave_filename {
+            let _ = autosave::remove(filename);
+        }
+        self.autosaved_nav = self.saved_nav.clone();
+        self.autosaved_page_titles = self.saved_page_titles.clone();
+    }
 
-pub struct App {
-    terminal: Terminal<CrosstermBackend<Stdout>>,
-    filename: String,
-    nav: Nav,
-    tree_state: TreeState,
-    pub state: AppState,
-}
+    fn open_trash(&mut self) {
+        if self.trash.is_empty() {
+            self.status_message = Some(String::from("The trash is empty"));
+            return;
+        }
+        self.trash_list.select(Some(0));
+        self.state = AppState::BrowsingTrash;
+    }
 
-#[derive(Debug)]
-pub enum TempFileError {
-    SystemIOError(io::Error),
-    XDGSpecificError(xdg::BaseDirectoriesError),
-}
+    fn handle_trash_input(&mut self, key: KeyEvent) {
+        let selected = self.trash_list.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.state = AppState::Navigating,
+            KeyCode::Char('j') if selected + 1 < self.trash.len() => self.trash_list.select(Some(selected + 1)),
+            KeyCode::Char('k') if selected > 0 => self.trash_list.select(Some(selected - 1)),
+            KeyCode::Enter => {
+                self.restore_from_trash(selected);
+                self.state = AppState::Navigating;
+            },
+            KeyCode::Char('y') => {
+                let (_, nodes) = &self.trash[selected];
+                self.clipboard = nodes.clone();
+                self.status_message = Some(format!("Copied {} entries, p to paste them", nodes_count(nodes)));
+                self.state = AppState::Navigating;
+            },
+            _ => (),
+        }
+    }
 
-#[derive(Debug)]
-pub enum AppLifetimeError {
-    NavReadingError(NavReadingError),
-    ExternalProgramError(io::Error),
-    TerminalIOError(io::Error),
-    TempFileError(TempFileError),
-}
+    /// Show the metadata of the document, reading them the first time.
+    fn open_metadata(&mut self) {
+        if self.metadata.is_none() {
+            if remote::is_url(&self.filename) {
+                self.status_message = Some(String::from("The metadata of remote documents cannot be read"));
+                return;
+            }
+            match get_metadata(&self.filename) {
+                Ok(metadata) => {
+                    self.saved_metadata = Some(metadata.clone());
+                    self.metadata = Some(metadata);
+                },
+                Err(e) => {
+                    self.status_message = Some(format!("Cannot read the metadata: {}", e));
+                    return;
+                },
+            }
+        }
+        self.metadata_list.select(Some(self.metadata_list.selected().unwrap_or(0)));
+        self.state = AppState::BrowsingMetadata;
+    }
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum AppState {
-    Quitting,
-    Navigating,
-    RunningOtherCommand,
-}
+    fn handle_metadata_input(&mut self, key: KeyEvent) {
+        let Some(metadata) = &mut self.metadata else {
+            self.state = AppState::Navigating;
+            return;
+        };
+        let keys: Vec<String> = metadata.keys().into_iter().map(String::from).collect();
+        let selected = self.metadata_list.selected().unwrap_or(0).min(keys.len() - 1);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc | KeyCode::F(4) => self.state = AppState::Navigating,
+            KeyCode::Char('j') | KeyCode::Down if selected + 1 < keys.len() => self.metadata_list.select(Some(selected + 1)),
+            KeyCode::Char('k') | KeyCode::Up if selected > 0 => self.metadata_list.select(Some(selected - 1)),
+            KeyCode::Enter | KeyCode::Char('i') => {
+                let value = metadata.get(&keys[selected]).unwrap_or_default();
+                self.metadata_form = [LineInput::new(&keys[selected]), LineInput::new(value)];
+                self.metadata_form_field = 1;
+                self.edited_key = Some(keys[selected].clone());
+                self.state = AppState::EditingMetadata;
+            },
+            KeyCode::Char('a') => {
+                self.metadata_form = Default::default();
+                self.metadata_form_field = 0;
+                self.edited_key = None;
+                self.state = AppState::EditingMetadata;
+            },
+            KeyCode::Char('d') => self.transaction("delete metadata", |app| {
+                if let Some(metadata) = &mut app.metadata {
+                    metadata.set(&keys[selected], "");
+                }
+            }),
+            KeyCode::Char('w') => {
+                if let Err(e) = self.write() {
+                    self.status_message = Some(format!("Cannot write {}: {}", self.display_name(), e));
+                }
+            },
+            _ => (),
+        }
+    }
 
-fn prepare_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>, io::Error> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let terminal = Terminal::new(backend)?;
-    Ok(terminal)
-}
+    fn handle_metadata_form_input(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.state = AppState::BrowsingMetadata,
+            KeyCode::Tab | KeyCode::BackTab | KeyCode::Up | KeyCode::Down => {
+                self.metadata_form_field = 1 - self.metadata_form_field;
+            },
+            KeyCode::Enter => {
+                let [key, value] = &self.metadata_form;
+                let (key, value) = (key.text().trim(), value.text());
+                if !metadata::is_valid_key(key) {
+                    self.status_message = Some(String::from("Keys are single words, such as title or ISBN"));
+                    return;
+                }
+                let (key, value) = (String::from(key), String::from(value));
+                let position = self.transaction("edit metadata", |app| {
+                    let metadata = app.metadata.as_mut()?;
+                    if let Some(edited) = app.edited_key.as_deref().filter(|edited| *edited != key) {
+                        metadata.set(edited, "");
+                    }
+                    metadata.set(&key, &value);
+                    metadata.keys().iter().position(|other| *other == key)
+                });
+                if let Some(position) = position {
+                    self.metadata_list.select(Some(position));
+                }
+                self.state = AppState::BrowsingMetadata;
+            },
+            _ => {
+                self.metadata_form[self.metadata_form_field].handle_key(key);
+            },
+        }
+    }
 
-impl App {
-    pub fn new(filename: &str) -> Result<Self, AppLifetimeError> {
-        let terminal = prepare_terminal()
-            .map_err(|e| AppLifetimeError::TerminalIOError(e))?;
+    /// Name the page `page`, evaluated around the selected entry, or the page of the selected entry
+    /// if not given, `name`.
+    fn name_page(&mut self, name: String, page: Option<PageExpr>) {
+        if self.anchors_path.is_none() {
+            self.status_message = Some(String::from("Only the pages of documents on this machine can be named"));
+            return;
+        }
+        if !anchors::is_valid_name(&name, &self.link_resolvers, self.pages.as_deref()) {
+            self.status_message = Some(format!("{} cannot name a page, since it is a page itself", name));
+            return;
+        }
+        let selected = self.tree_state.selected();
+        let page = match page {
+            Some(page) => page.evaluate(&self.page_context(selected)).map_err(|e| e.to_string()),
+            None => self.nav.get(selected)
+                .and_then(|node| page_number(&node.link, self.pages.as_deref()))
+                .ok_or_else(|| String::from("the selected entry does not point to a page")),
+        };
+        match page {
+            Ok(page) => {
+                self.anchors.pages.insert(name.clone(), page);
+                self.save_anchors(format!("{} names page {}", name, page));
+            },
+            Err(e) => self.status_message = Some(format!("Cannot name the page: {}", e)),
+        }
+    }
 
-        let nav = get_nav_from_djvu(filename)
-            .map_err(|e| AppLifetimeError::NavReadingError(e))?;
+    /// Select the entry covering `page`, a page such as `153` or `next+4` or the name of a page,
+    /// opening its ancestors.
+    fn go_to_page(&mut self, page: &str) {
+        let target = match (self.anchors.page(page), PageExpr::parse(page)) {
+            (Some(target), _) => Ok(target),
+            (None, Some(expr)) => expr.evaluate(&self.page_context(self.tree_state.selected())).map_err(|e| e.to_string()),
+            (None, None) => Err(format!("{} is not a page or the name of one", page)),
+        };
+        let target = match target {
+            Ok(target) => target,
+            Err(e) => {
+                self.status_message = Some(format!("Cannot go to the page: {}", e));
+                return;
+            },
+        };
+        match targets::entry_at_page(&self.nav, self.pages.as_deref(), target) {
+            Some(id) => {
+                self.tree_state.clear_range();
+                self.reveal(&id);
+                self.status_message = Some(format!("Page {} is in \"{}\"", target, self.nav[&id].string));
+            },
+            None => self.status_message = Some(format!("No entry points to page {} or before", target)),
+        }
+    }
 
-        let mut tree_state = TreeState::default();
+    /// Write the names of pages to their file, telling `done` if they were written.
+    fn save_anchors(&mut self, done: String) {
+        let Some(path) = &self.anchors_path else {
+            return;
+        };
+        self.status_message = Some(match self.anchors.save(path) {
+            Ok(()) => done,
+            Err(e) => format!("Anchors not written: {}", e),
+        });
+    }
 
-        if !nav.nodes.is_empty() {
-            tree_state.select_first();
+    /// Show the pages of the document with their titles, to edit them.
+    fn open_page_titles(&mut self) {
+        if self.pages.as_ref().is_none_or(Vec::is_empty) {
+            self.status_message = Some(String::from("The pages of the document could not be listed"));
+            return;
         }
-        
-        let state = AppState::Navigating;
-
-        Ok(Self {
-            terminal,
-            filename: String::from(filename),
-            nav,
-            tree_state,
-            state,
-        })
+        self.page_list.select(Some(self.page_list.selected().unwrap_or(0)));
+        self.state = AppState::BrowsingPageTitles;
     }
 
-    pub fn handle_input(
-        &mut self, 
-        key: KeyEvent, 
-    ) -> Result<(), AppLifetimeError> {
+    fn handle_page_titles_input(&mut self, key: KeyEvent) {
+        let Some(pages) = &self.pages else {
+            self.state = AppState::Navigating;
+            return;
+        };
+        let count = pages.len();
+        let selected = self.page_list.selected().unwrap_or(0).min(count - 1);
         match key.code {
-            KeyCode::Char('q') => self.state = AppState::Quitting,
-            KeyCode::Char('h') => self.move_left(),
-            KeyCode::Char('j') => self.move_down(),
-            KeyCode::Char('k') => self.move_up(),
-            KeyCode::Char('l') => self.move_right(),
-            KeyCode::Char('i') => self.edit_currently_selected()?,
-            KeyCode::Char('w') => self.write().map_err(|e| AppLifetimeError::NavReadingError(e))?,
-            KeyCode::Char('o') => self.add_new_entry_below(),
-            KeyCode::Char('d') => self.delete_currently_selected(),
+            KeyCode::Char('q') | KeyCode::Esc | KeyCode::F(6) => self.state = AppState::Navigating,
+            KeyCode::Char('j') | KeyCode::Down if selected + 1 < count => self.page_list.select(Some(selected + 1)),
+            KeyCode::Char('k') | KeyCode::Up if selected > 0 => self.page_list.select(Some(selected - 1)),
+            KeyCode::Char('g') | KeyCode::Home => self.page_list.select(Some(0)),
+            KeyCode::Char('G') | KeyCode::End => self.page_list.select(Some(count - 1)),
+            KeyCode::Enter | KeyCode::Char('i') => {
+                self.page_title_input = LineInput::new(pages[selected].title.as_deref().unwrap_or_default());
+                self.state = AppState::EditingPageTitle;
+            },
+            KeyCode::Char('n') if selected + 1 < count => {
+                match pages[selected].title.as_deref().and_then(page_titles::next_label) {
+                    Some(label) => {
+                        self.set_page_title("label next page", selected + 1, Some(label));
+                        self.page_list.select(Some(selected + 1));
+                    },
+                    None => self.status_message = Some(String::from("The title of the page is not a number, such as 12 or iii")),
+                }
+            },
+            KeyCode::Char('d') => self.set_page_title("delete page title", selected, None),
+            KeyCode::Char('w') => {
+                if let Err(e) = self.write() {
+                    self.status_message = Some(format!("Cannot write {}: {}", self.display_name(), e));
+                }
+            },
             _ => (),
         }
-        Ok(())
     }
 
-    fn edit_currently_selected(&mut self) -> Result<(), AppLifetimeError> {
-        if self.tree_state.selected().is_empty() {
-            return Ok(());
+    fn handle_page_title_input(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.state = AppState::BrowsingPageTitles,
+            KeyCode::Enter => {
+                let title = self.page_title_input.text().trim();
+                if title.chars().any(char::is_control) {
+                    self.status_message = Some(String::from("Page titles cannot have control characters"));
+                    return;
+                }
+                let title = (!title.is_empty()).then(|| String::from(title));
+                self.set_page_title("edit page title", self.page_list.selected().unwrap_or(0), title);
+                self.state = AppState::BrowsingPageTitles;
+            },
+            _ => {
+                self.page_title_input.handle_key(key);
+            },
         }
-        // Create temp file with data in it
-        let temp_filename = get_temp_file_name().map_err(|e| AppLifetimeError::TempFileError(e))?;
-        let currently_selected_id = &self.tree_state.selected();
-        {
-            let f = File::create(&temp_filename)
-                .map_err(|e| AppLifetimeError::TempFileError(TempFileError::SystemIOError(e)))?;
-            let mut writer = BufWriter::new(f);
+    }
 
-            let current_node = &self.nav[currently_selected_id];
+    /// Give the page at `index` in the list of pages the title `title`, as a step of the history.
+    fn set_page_title(&mut self, label: &str, index: usize, title: Option<String>) {
+        self.transaction(label, |app| {
+            if let Some(page) = app.pages.as_mut().and_then(|pages| pages.get_mut(index)) {
+                page.title = title;
+            }
+        });
+    }
 
-            writer.write_fmt(format_args!("{}\n{}", current_node.string, current_node.link))
-                .map_err(|e| AppLifetimeError::TempFileError(TempFileError::SystemIOError(e)))?;
+    /// Put the subtree `index` of the trash back where it was removed from, if that place still exists.
+    fn restore_from_trash(&mut self, index: usize) {
+        let id = self.trash[index].0.clone();
+        let Some((&last, father)) = id.split_last() else {
+            return;
+        };
+        let siblings = if father.is_empty() {
+            Some(self.nav.nodes.len())
+        } else {
+            self.nav.get(father).map(|father| father.children.len())
+        };
+        if siblings.is_none_or(|siblings| last > siblings) {
+            self.status_message = Some(String::from("Its place is gone from the outline: y to copy it and paste it elsewhere"));
+            return;
         }
+        if !self.check_editable(id.len()) {
+            return;
+        }
+        let (_, nodes) = self.trash.remove(index);
+        let restored = self.transaction("restore entry", |app| app.nav.insert_range(father, last, nodes));
+        self.reveal(&[father, &[*restored.start()]].concat());
+    }
 
-        // Edit file with EDITOR
-        self.state = AppState::RunningOtherCommand;
-        let mut command = Command::new(EDITOR)
-            .arg(&temp_filename)
-            .spawn()
-            .map_err(|e| AppLifetimeError::ExternalProgramError(e))?;
+    fn yank_selected(&mut self) {
+        let Some((_, _, nodes)) = self.selected_nodes() else {
+            return;
+        };
+        let count = nodes_count(nodes);
+        self.clipboard = nodes.to_vec();
+        self.tree_state.clear_range();
+        self.status_message = Some(format!("Copied {} entries", count));
+    }
 
-        command.wait().map_err(|e| AppLifetimeError::ExternalProgramError(e))?;
-        self.state = AppState::Navigating;
+    /// Insert a copy of the clipboard right after the selected node or, if `as_child` is set, as
+    /// its first child.
+    fn paste(&mut self, as_child: bool) {
+        if self.clipboard.is_empty() {
+            self.status_message = Some(String::from("Nothing to paste"));
+            return;
+        }
+        let nodes = self.clipboard.clone();
+        let selected = self.tree_state.selected().to_owned();
+        let depth = if as_child || selected.is_empty() { selected.len() + 1 } else { selected.len() };
+        if !self.check_editable(depth) {
+            return;
+        }
+        let (father, at) = match selected.split_last() {
+            Some((&last, father)) if !as_child => (father.to_owned(), last + 1),
+            _ => (selected.clone(), 0),
+        };
+        let pasted = self.transaction("paste entry", |app| app.nav.insert_range(&father, at, nodes));
+        self.reveal(&[&father[..], &[*pasted.start()]].concat());
+    }
 
-        enable_raw_mode().map_err(|e| AppLifetimeError::TerminalIOError(e))?;
-        execute!(self.terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)
-            .map_err(|e| AppLifetimeError::TerminalIOError(e))?;
-        self.terminal.clear().map_err(|e| AppLifetimeError::TerminalIOError(e))?;
+    /// Move the selected node with `f`, which returns its new identifier if it could be moved, and
+    /// keep it selected. Return `false` if it cannot be moved that way.
+    fn move_selected<F>(&mut self, label: &str, f: F) -> bool
+    where
+        F: Fn(&mut Nav, TreeIdentifier) -> Option<TreeIdentifierVec>
+    {
+        let selected = self.tree_state.selected().to_owned();
+        let Some(new_id) = f(&mut self.nav.clone(), &selected) else {
+            return selected.is_empty();
+        };
+        if !self.check_editable(selected.len()) || !self.check_editable(new_id.len()) {
+            return true;
+        }
+        if let Some(new_id) = self.transaction(label, |app| f(&mut app.nav, &selected)) {
+            self.reveal(&new_id);
+        }
+        true
+    }
 
-        let tempfile = File::open(&temp_filename)
-            .map_err(|e| AppLifetimeError::TempFileError(TempFileError::SystemIOError(e)))?;
-        let reader = BufReader::new(tempfile);
-        let lines: Vec<String> = reader.lines()
-            .map(|result| result.unwrap())
-            .collect();
+    /// Return the selected node, and whether its children are shown, to tell whether moving around
+    /// the tree changed anything.
+    fn visible_selection(&self) -> (TreeIdentifierVec, bool) {
+        let selected = self.tree_state.selected();
+        let open = self.tree_state.is_open(selected) && self.nav.num_children(selected) > 0;
+        (selected.to_owned(), open)
+    }
 
-        self.nav[currently_selected_id].string = lines[0].clone();
-        self.nav[currently_selected_id].link = BookmarkLink::from_string(&lines[1]);
+    /// Tell the user that `action` cannot be done because of `reason`, as configured for it.
+    fn reject(&mut self, action: Action, reason: &str) {
+        match self.config.feedback.get(action) {
+            Feedback::Message => self.status_message = Some(String::from(reason)),
+            _ => self.signal(action),
+        }
+    }
 
-        Ok(())
+    /// Ring the bell or flash the screen if configured for `action`, for actions that failed.
+    fn signal(&mut self, action: Action) {
+        match self.config.feedback.get(action) {
+            Feedback::Bell => {
+                let backend = self.terminal.backend_mut();
+                let _ = backend.write_all(b"\x07").and_then(|_| backend.flush());
+            },
+            Feedback::Flash => self.flash_until = Some(Instant::now() + FLASH_DURATION),
+            Feedback::Message | Feedback::None => (),
+        }
     }
 
-    fn delete_currently_selected(&mut self) {
-        if self.tree_state.selected().is_empty() {
+    /// Handle `action` in visual mode, where it applies to the selected range of siblings. Return
+    /// whether it was handled: other actions act on the range the same way as on a single entry,
+    /// or leave visual mode.
+    fn handle_range_action(&mut self, action: Action) -> bool {
+        match action {
+            Action::VisualMode => self.tree_state.clear_range(),
+            Action::MoveDown => self.extend_range(true),
+            Action::MoveUp => self.extend_range(false),
+            // The range stays among the siblings it was started at.
+            Action::MoveLeft | Action::MoveRight => (),
+            Action::MoveEntryUp | Action::MoveEntryDown | Action::Demote | Action::Promote => {
+                let moved = match action {
+                    Action::MoveEntryUp => self.move_range("move entries up", Nav::move_range_up),
+                    Action::MoveEntryDown => self.move_range("move entries down", Nav::move_range_down),
+                    Action::Demote => self.move_range("demote entries", Nav::indent_range),
+                    _ => self.move_range("promote entries", Nav::outdent_range),
+                };
+                if !moved {
+                    self.reject(action, move_rejection(action));
+                }
+            },
+            Action::Cut | Action::Yank | Action::CommandLine => return false,
+            _ => {
+                self.tree_state.clear_range();
+                return false;
+            },
+        }
+        true
+    }
+
+    /// Move the end of the range to the next sibling, or the previous one.
+    fn extend_range(&mut self, down: bool) {
+        let selected = self.tree_state.selected().to_owned();
+        let Some((&last, father)) = selected.split_last() else {
             return;
+        };
+        let next = if down { last + 1 } else { last.wrapping_sub(1) };
+        if next < self.nav.num_children(father) {
+            self.tree_state.select([father, &[next]].concat());
+        }
+    }
+
+    /// Return the father and the positions of the entries to act on: the range selected in visual
+    /// mode, or the selected entry alone.
+    fn selected_siblings(&self) -> Option<(TreeIdentifierVec, RangeInclusive<usize>)> {
+        if let Some((father, range)) = self.tree_state.range() {
+            return Some((father.to_owned(), range));
+        }
+        let (&last, father) = self.tree_state.selected().split_last()?;
+        Some((father.to_owned(), last..=last))
+    }
+
+    /// Return the entries to act on, as `selected_siblings` does, along with the entries.
+    fn selected_nodes(&self) -> Option<(TreeIdentifierVec, RangeInclusive<usize>, &[NavNode])> {
+        let (father, range) = self.selected_siblings()?;
+        let siblings = if father.is_empty() { &self.nav.nodes } else { &self.nav.get(&father)?.children };
+        let nodes = siblings.get(range.clone())?;
+        Some((father, range, nodes))
+    }
+
+    /// Move the selected range of siblings with `f`, which returns their new father and positions
+    /// if they could be moved, and keep them selected. Return `false` if they cannot be moved
+    /// that way.
+    fn move_range<F>(&mut self, label: &str, f: F) -> bool
+    where
+        F: Fn(&mut Nav, TreeIdentifier, RangeInclusive<usize>) -> Option<(TreeIdentifierVec, RangeInclusive<usize>)>
+    {
+        let Some((father, range)) = self.selected_siblings() else {
+            return true;
+        };
+        let Some((new_father, _)) = f(&mut self.nav.clone(), &father, range.clone()) else {
+            return false;
+        };
+        if !self.check_editable(father.len() + 1) || !self.check_editable(new_father.len() + 1) {
+            return true;
+        }
+        if let Some((father, range)) = self.transaction(label, |app| f(&mut app.nav, &father, range)) {
+            self.reveal(&[&father[..], &[*range.end()]].concat());
+            self.tree_state.select_range(&father, range);
         }
+        true
+    }
+
+    /// Remove the siblings `range` of the node `father`, and select the entry taking their place,
+    /// or the one before them, or their father.
+    fn remove_range_and_fix_selection(&mut self, father: TreeIdentifier, range: RangeInclusive<usize>) {
+        let start = *range.start();
+        self.nav.remove_range(father, range);
+        if start < self.nav.num_children(father) {
+            self.tree_state.select([father, &[start]].concat());
+        } else if start > 0 {
+            self.tree_state.select([father, &[start - 1]].concat());
+        } else {
+            self.tree_state.select(father);
+        }
+    }
 
+    fn delete_entry_and_fix_selection(&mut self) {
         let selected = self.tree_state.selected().to_owned();
         let father = &selected[..selected.len() - 1];
         let last = selected[selected.len() - 1];
@@ -182,13 +2951,47 @@ impl App {
 
     pub fn move_up(&mut self) {
         self.tree_state.key_up(&self.nav);
+        self.expand_on_select();
     }
 
     pub fn move_down(&mut self) {
         self.tree_state.key_down(&self.nav);
+        self.expand_on_select();
+    }
+
+    fn expand_on_select(&mut self) {
+        if self.config.navigation.expand_on_select.unwrap_or(false) {
+            let selected = self.tree_state.selected().to_owned();
+            self.tree_state.open(&selected);
+        }
+    }
+
+    /// Select the first or the last entry, or move by a page or half a page of the tree, for
+    /// `action`.
+    fn jump(&mut self, action: Action) {
+        if self.nav.nodes.is_empty() {
+            return;
+        }
+        let page = self.tree_state.page_size().max(1);
+        match action {
+            Action::FirstEntry => self.tree_state.select_first(),
+            Action::LastEntry => self.tree_state.select_last(&self.nav),
+            Action::HalfPageDown => self.tree_state.page_down(&self.nav, (page / 2).max(1)),
+            Action::HalfPageUp => self.tree_state.page_up(&self.nav, (page / 2).max(1)),
+            Action::PageDown => self.tree_state.page_down(&self.nav, page),
+            _ => self.tree_state.page_up(&self.nav, page),
+        }
+        self.expand_on_select();
     }
 
     pub fn move_left(&mut self) {
+        self.temporarily_opened.clear();
+        let selected = self.tree_state.selected().to_owned();
+        if self.config.navigation.collapse_recursively.unwrap_or(false)
+            && self.tree_state.close_recursive(&selected)
+        {
+            return;
+        }
         let mut temp_state = self.tree_state.clone();
         temp_state.key_left();
         if !temp_state.selected().is_empty() {
@@ -197,10 +3000,24 @@ impl App {
     }
     
     pub fn move_right(&mut self) {
+        self.temporarily_opened.clear();
         self.tree_state.key_right();
     }
 
-    pub fn run(
+    /// Run the interface until the user quits, and give the terminal back before returning, so
+    /// that errors can be printed.
+    pub fn run(&mut self, tick_rate: Duration) -> Result<(), AppLifetimeError> {
+        let result = self.event_loop(tick_rate);
+        self.save_session();
+        // The autosave is only kept for sessions that did not end normally.
+        if result.is_ok() {
+            self.discard_autosave();
+        }
+        let restored = restore_terminal(&mut self.terminal).map_err(AppLifetimeError::TerminalIOError);
+        result.and(restored)
+    }
+
+    fn event_loop(
         &mut self,
         tick_rate: Duration,
     ) -> Result<(), AppLifetimeError> {
@@ -209,33 +3026,379 @@ impl App {
             if self.state == AppState::Quitting {
                 return Ok(());
             }
-            if self.state == AppState::Navigating {
+            if self.state != AppState::RunningOtherCommand {
+                self.poll_verification();
+                self.poll_page_queries();
+                self.poll_index_import();
+                let status_bar = self.status_bar();
+                let status_line = self.status_line();
+                let deletion_title = (self.state == AppState::ConfirmingDeletion)
+                    .then(|| self.deletion_title())
+                    .flatten();
+                let title = self.display_name();
+                let badges = self.badges();
+                let cursor = matches!(self.state, AppState::EnteringCommand | AppState::EnteringSearch)
+                    .then(|| 1 + self.command_line.cursor_width());
+                self.flash_until = self.flash_until.filter(|until| *until > Instant::now());
+                let flashing = self.flash_until.is_some();
+                let showing_metadata = matches!(self.state, AppState::BrowsingMetadata | AppState::EditingMetadata);
+                let showing_page_titles = matches!(self.state, AppState::BrowsingPageTitles | AppState::EditingPageTitle);
+                let problems = self.problems(self.tree_state.selected());
+                // Entries of the outline are edited in the details pane, imported ones in a popup.
+                let editing_in_pane = self.state == AppState::EditingEntry && self.merge.is_none();
                 self.terminal.draw(|f| {
-                    self.nav.ui(f, &mut self.tree_state)
+                    let area = if self.presenting {
+                        f.size()
+                    } else {
+                        ui(f, &status_bar, &status_line, cursor)
+                    };
+                    let (outline_area, detail_area) = panes(area);
+                    if self.presenting {
+                        presentation::ui(f, area, &self.nav, &self.tree_state, &title);
+                    } else if self.state == AppState::BrowsingHistory {
+                        self.history.ui(f, area, &mut self.history_list, &self.nav);
+                    } else if self.state == AppState::BrowsingReport {
+                        self.report.ui(f, area, &mut self.report_list);
+                    } else if let Some(metadata) = self.metadata.as_ref().filter(|_| showing_metadata) {
+                        metadata.ui(f, area, &mut self.metadata_list);
+                        if self.state == AppState::EditingMetadata {
+                            let [key, value] = &self.metadata_form;
+                            let fields = [("Key", key), ("Value", value)];
+                            let help = "Edit metadata (Tab to switch, Enter to save, an empty value removes the key, Esc to cancel)";
+                            popup::form(f, area, help, &fields, self.metadata_form_field);
+                        }
+                    } else if let Some(pages) = self.pages.as_deref().filter(|_| showing_page_titles) {
+                        page_titles::ui(f, area, pages, &mut self.page_list);
+                        if self.state == AppState::EditingPageTitle {
+                            let help = "Edit page title (Enter to save, an empty title removes it, Esc to cancel)";
+                            popup::form(f, area, help, &[("Title", &self.page_title_input)], 0);
+                        }
+                    } else {
+                        let locked_depth = self.locked_depth;
+                        let (nav, pages) = (&self.nav, self.pages.as_deref());
+                        let verification = self.verification.as_ref();
+                        let page_count = self.page_count;
+                        let page_labels = self.page_labels;
+                        // The badge column is left out until an entry has a badge.
+                        let columns: Vec<Column> = self.columns.iter()
+                            .filter(|column| column.kind != ColumnKind::Badges || !badges.is_empty())
+                            .copied()
+                            .collect();
+                        let label_of = |page: Option<u32>| pages
+                            .and_then(|pages| pages.iter().find(|info| Some(info.number) == page))
+                            .and_then(|info| info.title.as_deref());
+                        // When the document has page titles, the rows end with the page and its
+                        // label, unless a column shows them already.
+                        let page_references = pages.is_some_and(|pages| pages.iter().any(|info| info.title.is_some()))
+                            && !columns.iter().any(|column| matches!(column.kind, ColumnKind::Page | ColumnKind::Label));
+                        let row_text = |id: TreeIdentifier, node: &NavNode| -> String {
+                            let page = page_number(&node.link, pages);
+                            let label = label_of(page);
+                            let target = match (page, label) {
+                                (Some(_), Some(label)) if page_labels => String::from(label),
+                                (Some(page), _) => page.to_string(),
+                                (None, _) => node.link.to_string(),
+                            };
+                            columns::row_text(&columns, Row {
+                                title: &node.string,
+                                target: Some(target),
+                                label,
+                                verdict: verification.and_then(|verification| verification.verdict(id, node, pages)),
+                                badges: badges.get(id).copied().unwrap_or_default(),
+                            })
+                        };
+                        // The default layout needs no copy of the titles.
+                        let default_layout = matches!(columns.as_slice(), [Column { kind: ColumnKind::Title, width: None }]);
+                        nav.ui_with_text(f, outline_area, &mut self.tree_state, |id, node| {
+                            if default_layout {
+                                Cow::Borrowed(node.string.as_str())
+                            } else {
+                                Cow::Owned(row_text(id, node))
+                            }
+                        }, |_, node| {
+                            let page = page_number(&node.link, pages).filter(|_| page_references)?;
+                            Some(columns::page_reference(page, label_of(Some(page))))
+                        }, |id| {
+                            let verdict = verification
+                                .and_then(|verification| verification.verdict(id, &nav[id], pages));
+                            let out_of_range = page_count
+                                .is_some_and(|page_count| check::is_out_of_range(&nav[id], page_count));
+                            let style = if locked_depth.is_some_and(|depth| id.len() > depth) {
+                                Style::default().fg(Color::DarkGray)
+                            } else if out_of_range || verdict == Some(Verdict::Missing) {
+                                Style::default().fg(Color::Red)
+                            } else {
+                                Style::default()
+                            };
+                            // Entries changed since the file was opened are in italics.
+                            if badges.get(id).is_some_and(|badges| badges.modified) {
+                                style.add_modifier(Modifier::ITALIC)
+                            } else {
+                                style
+                            }
+                        });
+                        let selected = self.nav.get(self.tree_state.selected());
+                        match detail_area {
+                            Some(detail_area) if editing_in_pane => {
+                                let [title, link] = &self.entry_form;
+                                let fields = [("Title", title), ("Page", link)];
+                                let help = "Edit entry (Tab to switch, Ctrl-n to complete, Enter to save, Esc to cancel)";
+                                detail::form(f, detail_area, help, &fields, self.entry_form_field);
+                            },
+                            Some(detail_area) if self.show_preview => {
+                                let page = selected.and_then(|node| page_number(&node.link, pages));
+                                self.previews.ui(f, detail_area, page);
+                            },
+                            Some(detail_area) => {
+                                detail::ui(f, detail_area, selected, pages, &self.link_resolvers, &self.anchors, &problems);
+                            },
+                            None => (),
+                        }
+                    }
+                    if self.state == AppState::BrowsingTrash {
+                        let labels: Vec<_> = self.trash.iter()
+                            .map(|(id, nodes)| {
+                                let more = match nodes.len() {
+                                    1 => String::new(),
+                                    n => format!(" and {} more", n - 1),
+                                };
+                                format!("{} \"{}\"{} ({} entries)", format_identifier(id), nodes[0].string, more, nodes_count(nodes))
+                            })
+                            .collect();
+                        let title = "Trash (Enter to restore in place, y to copy, Esc to close)";
+                        popup::menu(f, area, title, &labels, &mut self.trash_list);
+                    }
+                    if self.state == AppState::ChoosingQuickFix || self.state == AppState::ChoosingBulkFix {
+                        let labels: Vec<_> = self.quick_fixes.iter().map(|fix| fix.label()).collect();
+                        popup::menu(f, area, "Quick fixes", &labels, &mut self.quick_fix_menu);
+                    }
+                    let merging = self.state == AppState::Merging || self.state == AppState::EditingEntry;
+                    if let Some(merge) = self.merge.as_ref().filter(|_| merging) {
+                        let mode = if merge.replace { "replacing the outline" } else { "added to the outline" };
+                        let title = format!(
+                            "Import from {}: {} of {} entries accepted, {} (Space to accept, e to edit, a for all, r to switch, Enter to apply, Esc to cancel)",
+                            merge.source,
+                            merge.accepted(),
+                            merge.rows.len(),
+                            mode,
+                        );
+                        popup::menu(f, area, &title, &merge.labels(), &mut self.merge_list);
+                    }
+                    if self.state == AppState::EditingEntry && !(editing_in_pane && detail_area.is_some()) {
+                        let [title, link] = &self.entry_form;
+                        let fields = [("Title", title), ("Page", link)];
+                        let help = "Edit entry (Tab to switch, Ctrl-n to complete page names, Enter to save, Esc to cancel)";
+                        popup::form(f, area, help, &fields, self.entry_form_field);
+                    }
+                    if let Some(title) = &deletion_title {
+                        popup::menu(f, area, title, &["Delete", "Cancel"], &mut self.confirm_menu);
+                    }
+                    if self.state == AppState::ConfirmingClear {
+                        let title = format!("Remove the whole outline ({} entries)?", self.nav.all_nodes().len());
+                        popup::menu(f, area, &title, &["Remove", "Cancel"], &mut self.confirm_menu);
+                    }
+                    if self.state == AppState::ConfirmingRestore {
+                        let title = "Unsaved changes of an earlier session were autosaved";
+                        popup::menu(f, area, title, &["Restore them", "Discard them"], &mut self.confirm_menu);
+                    }
+                    if self.state == AppState::ConfirmingQuit {
+                        let choices = ["Write and quit", "Quit without saving", "Cancel"];
+                        popup::menu(f, area, "There are unsaved changes", &choices, &mut self.confirm_menu);
+                    }
+                    if self.state == AppState::PreviewingBulkChange {
+                        let title = "Apply to all? (Enter to confirm, Esc to cancel)";
+                        popup::menu(f, area, title, &self.bulk_preview, &mut self.bulk_list);
+                    }
+                    if flashing {
+                        f.render_widget(Block::default().style(Style::default().add_modifier(Modifier::REVERSED)), f.size());
+                    }
                 })
                 .map_err(|e| AppLifetimeError::TerminalIOError(e))?;
 
-                let timeout = tick_rate
+                let mut timeout = tick_rate
                     .checked_sub(last_tick.elapsed())
                     .unwrap_or_else(|| Duration::from_secs(0));
+                // The screen is drawn again when the flash ends.
+                if let Some(until) = self.flash_until {
+                    timeout = timeout.min(until.saturating_duration_since(Instant::now()));
+                }
                 if event::poll(timeout).map_err(|e| AppLifetimeError::TerminalIOError(e))? {
-                    if let Event::Key(key) = event::read().map_err(|e| AppLifetimeError::TerminalIOError(e))? {
-                        self.handle_input(key)?;
+                    match event::read().map_err(|e| AppLifetimeError::TerminalIOError(e))? {
+                        Event::Key(key) => {
+                            self.last_input = Instant::now();
+                            self.suspended = false;
+                            self.handle_input(key)?;
+                        },
+                        Event::Mouse(mouse) => {
+                            self.last_input = Instant::now();
+                            self.suspended = false;
+                            self.handle_mouse(mouse);
+                        },
+                        _ => (),
                     }
                 }
             }
             if last_tick.elapsed() >= tick_rate {
+                self.autosave();
+                self.suspend_if_idle();
                 last_tick = Instant::now();
             }
         }
     }
 
-    fn write(&self) -> Result<(), NavReadingError> {
-        embed_nav_in_djvu_file(&self.filename, &self.nav)?;
+    /// Write the outline to the file, unless some entries point past the last page or the outline
+    /// of the file changed since it was read. Return whether it was written.
+    fn write(&mut self) -> Result<bool, NavReadingError> {
+        let changed = self.file_stamp.as_ref()
+            .is_some_and(|stamp| stamp.changed(&self.filename).unwrap_or(false));
+        if changed {
+            // Other changes to the file, such as page titles, are kept by writing the outline
+            // alone, so only another outline is a conflict.
+            let theirs = get_nav_from_djvu(&self.filename)?;
+            if theirs != self.saved_nav {
+                let mut report = history::changes_report(&self.saved_nav, &theirs);
+                report.title = format!("Entries changed in {} by someone else", self.display_name());
+                self.open_report(report);
+                self.status_message = Some(format!(
+                    "Not written: the outline of {} changed since it was read (:w! to overwrite it)",
+                    self.display_name(),
+                ));
+                return Ok(false);
+            }
+        }
+        if let Some(page_count) = self.page_count {
+            let invalid = check::out_of_range(&self.nav, page_count).len();
+            if invalid > 0 {
+                self.status_message = Some(format!(
+                    "Not written: {} entries point past the {} pages (:check to list them, :w! to write anyway)",
+                    invalid, page_count,
+                ));
+                return Ok(false);
+            }
+        }
+        self.force_write()
+    }
+
+    /// Write the outline to the file, unless it was opened read-only. Return whether it was written.
+    fn force_write(&mut self) -> Result<bool, NavReadingError> {
+        if self.read_only {
+            self.status_message = Some(format!("Not written: {} is opened read-only", self.display_name()));
+            return Ok(false);
+        }
+        let unwritable = check::unwritable_titles(&self.nav).len();
+        if unwritable > 0 {
+            self.open_report(check::unwritable_report(&self.nav));
+            self.status_message = Some(format!(
+                "Not written: {} titles have control characters djvused cannot write, remove them first",
+                unwritable,
+            ));
+            return Ok(false);
+        }
+        let changed_metadata = self.metadata.clone().filter(|metadata| Some(metadata) != self.saved_metadata.as_ref());
+        let changed_page_titles = self.page_titles_changed();
+        let write_others = || {
+            if let Some(metadata) = &changed_metadata {
+                set_metadata(&self.filename, metadata)?;
+            }
+            if let Some(pages) = self.pages.as_deref().filter(|_| changed_page_titles) {
+                set_page_titles(&self.filename, &page_titles::changed_titles(pages, &self.saved_page_titles))?;
+            }
+            Ok(())
+        };
+        if self.strict {
+            let written = strict::check(&self.nav, self.pages.as_deref(), &self.config, &self.link_resolvers)
+                .and_then(|()| strict::write(&self.filename, &self.nav, write_others));
+            if let Err(e) = written {
+                self.status_message = Some(format!("Not written (strict mode): {}", e));
+                return Ok(false);
+            }
+        } else {
+            embed_nav_in_djvu_file(&self.filename, &self.nav)?;
+            write_others()?;
+        }
+        // The outline stays unsaved until the file is copied back, so that quitting still asks.
+        if let Some(target) = &self.ssh_target {
+            ssh::push(&SystemTools, Path::new(&self.filename), target)
+                .map_err(NavReadingError::TransferError)?;
+        }
+        self.saved_nav = self.nav.clone();
+        if changed_metadata.is_some() {
+            self.saved_metadata = changed_metadata;
+        }
+        self.saved_page_titles = self.pages.iter().flatten().map(|page| page.title.clone()).collect();
+        self.file_stamp = FileStamp::of(&self.filename).ok();
+        self.discard_autosave();
+        self.status_message = Some(format!("Written to {}", self.display_name()));
+        Ok(true)
+    }
+
+    /// Return the name of the file as given by the user, which is not the name of the local copy
+    /// for files on other machines.
+    fn display_name(&self) -> String {
+        match &self.ssh_target {
+            Some(target) => target.to_string(),
+            None => self.filename.clone(),
+        }
+    }
+
+    /// Copy the file `target` to the cache, and return the path of the copy.
+    fn fetch(target: &SshTarget) -> Result<String, NavReadingError> {
+        let local = target.local_copy().map_err(NavReadingError::TempFileError)?;
+        ssh::fetch(&SystemTools, target, &local).map_err(NavReadingError::TransferError)?;
+        Ok(local.display().to_string())
+    }
+
+    /// Whether the outline was changed since it was last read or written.
+    fn is_dirty(&self) -> bool {
+        self.nav != self.saved_nav || self.metadata != self.saved_metadata || self.page_titles_changed()
+    }
+
+    /// Whether titles of pages were changed since they were last read or written.
+    fn page_titles_changed(&self) -> bool {
+        self.pages.iter().flatten().map(|page| &page.title).ne(self.saved_page_titles.iter())
+    }
+
+    /// Quit, after confirmation if there are unsaved changes.
+    fn quit(&mut self) {
+        if self.is_dirty() {
+            self.confirm_menu.select(Some(2));
+            self.state = AppState::ConfirmingQuit;
+        } else {
+            self.state = AppState::Quitting;
+        }
+    }
+
+    fn handle_quit_input(&mut self, key: KeyEvent) -> Result<(), AppLifetimeError> {
+        let selected = self.confirm_menu.selected().unwrap_or(2);
+        match key.code {
+            KeyCode::Char('j') if selected < 2 => self.confirm_menu.select(Some(selected + 1)),
+            KeyCode::Char('k') if selected > 0 => self.confirm_menu.select(Some(selected - 1)),
+            KeyCode::Enter if selected == 0 => match self.write() {
+                Ok(true) => self.state = AppState::Quitting,
+                // The reason was given in the status line.
+                Ok(false) => self.state = AppState::Navigating,
+                Err(e) => {
+                    self.status_message = Some(format!("Cannot write {}: {}", self.display_name(), e));
+                    self.state = AppState::Navigating;
+                },
+            },
+            KeyCode::Enter if selected == 1 => self.state = AppState::Quitting,
+            KeyCode::Enter | KeyCode::Char('q') | KeyCode::Esc => self.state = AppState::Navigating,
+            _ => (),
+        }
         Ok(())
     }
 
     fn add_new_entry_below(&mut self) {
+        let selected = self.tree_state.selected();
+        let depth = selected.len() + usize::from(self.tree_state.is_open(selected));
+        if !self.check_editable(depth) {
+            return;
+        }
+        self.transaction("add entry", |app| app.insert_new_entry());
+    }
+
+    fn insert_new_entry(&mut self) {
         let is_selected_open = self.tree_state.is_open(self.tree_state.selected());
         if is_selected_open {
             self.nav.new_first_child(&self.tree_state.selected());
@@ -252,10 +3415,188 @@ impl App {
     // }
 }
 
+/// Return the columns of the tree set in `config`, or the default ones.
+fn configured_columns(config: &Config) -> Vec<Column> {
+    config.columns.clone().unwrap_or_else(|| columns::DEFAULT_COLUMNS.to_vec())
+}
+
+/// Tell that the text of page `page` could not be read.
+fn page_text_error(page: u32, e: NavReadingError) -> String {
+    format!("Cannot read the text of page {}: {}", page, e)
+}
+
+/// Read the text of the pages from the hOCR or ALTO file `path`, mapping its images to pages with
+/// the scan data file `scandata` if given.
+fn read_ocr_text(path: &str, scandata: Option<&str>) -> Result<HashMap<u32, String>, String> {
+    let read = |path: &str| std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e));
+    let pages = read(path).and_then(|document| ocr::parse_ocr(&document).map_err(|e| e.to_string()))?;
+    let leaves = match scandata {
+        Some(scandata) => Some(read(scandata)
+            .and_then(|xml| scandata::parse_leaves(&xml).map_err(|e| e.to_string()))?),
+        None => None,
+    };
+    Ok(ocr::text_by_page(pages, leaves.as_deref()))
+}
+
+/// Return why moving entries with `action` did nothing.
+fn move_rejection(action: Action) -> &'static str {
+    match action {
+        Action::MoveEntryUp => "Already the first of its siblings",
+        Action::MoveEntryDown => "Already the last of its siblings",
+        Action::Demote => "The first of its siblings cannot be demoted",
+        _ => "Top level entries cannot be promoted",
+    }
+}
+
+/// Return the number of nodes in the subtree rooted at `node`.
+fn node_count(node: &NavNode) -> usize {
+    1 + node.children.iter().map(node_count).sum::<usize>()
+}
+
+/// Return the number of nodes in the subtrees rooted at `nodes`.
+fn nodes_count(nodes: &[NavNode]) -> usize {
+    nodes.iter().map(node_count).sum()
+}
+
+/// Below this many columns, the detail pane is hidden and the status bar is shortened so that the
+/// outline keeps the whole width.
+const COMPACT_WIDTH: u16 = 60;
+
+/// Draw the status bar and the status line at the bottom of the frame, and return the area left
+/// for the main view.
+/// If `cursor` is given, the terminal cursor is shown at that column of the status line.
+/// On terminals less than three lines high, the status bar and then the status line are dropped,
+/// unless the status line is being typed in.
+fn ui(f: &mut Frame, status_bar: &StatusBar, status_line: &str, cursor: Option<usize>) -> Rect {
+    let area = f.size();
+    let (status_bar_height, status_line_height) = match area.height {
+        0 | 1 if cursor.is_some() => (0, area.height),
+        0 | 1 => (0, 0),
+        2 => (0, 1),
+        _ => (1, 1),
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(status_bar_height),
+            Constraint::Length(status_line_height),
+        ])
+        .split(area);
+
+    if status_bar_height > 0 {
+        status_bar.ui(f, chunks[1], area.width < COMPACT_WIDTH);
+    }
+    if status_line_height > 0 {
+        f.render_widget(Paragraph::new(status_line), chunks[2]);
+        if let Some(cursor) = cursor {
+            let x = chunks[2].x.saturating_add(cursor as u16).min(chunks[2].right().saturating_sub(1));
+            f.set_cursor(x, chunks[2].y);
+        }
+    }
+    chunks[0]
+}
+
+/// Split `area` between the outline and the detail pane, or leave it all to the outline if it is
+/// too narrow for both.
+fn panes(area: Rect) -> (Rect, Option<Rect>) {
+    if area.width < COMPACT_WIDTH {
+        return (area, None);
+    }
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(area);
+    (chunks[0], Some(chunks[1]))
+}
+
 impl Drop for App {
     fn drop(&mut self) {
-        disable_raw_mode().unwrap();
-        execute!(self.terminal.backend_mut(), LeaveAlternateScreen).unwrap();
-        self.terminal.show_cursor().unwrap();
+        // Panicking again while unwinding would abort without restoring anything.
+        let _ = restore_terminal(&mut self.terminal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, buffer::Buffer};
+
+    use super::*;
+
+    /// Draw the main screen, with a menu on top, on a terminal of `width` by `height` cells.
+    fn draw(width: u16, height: u16, cursor: Option<usize>) -> Buffer {
+        let nav = Nav {
+            nodes: vec![NavNode {
+                string: String::from("Part I"),
+                link: BookmarkLink::PageNumber(1),
+                children: vec![NavNode { string: String::from("Chapter 1"), ..NavNode::default() }],
+            }],
+        };
+        let mut tree_state = TreeState::default();
+        tree_state.open(&[0]);
+        tree_state.select(vec![0, 0]);
+        let status_bar = StatusBar {
+            filename: String::from("book.djvu"),
+            dirty: true,
+            read_only: false,
+            breadcrumb: vec![String::from("Part I"), String::from("Chapter 1")],
+            target: Some(String::from("page 1")),
+            task: None,
+        };
+        let mut menu = ListState::default();
+        menu.select(Some(0));
+
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| {
+            let area = ui(f, &status_bar, ":write", cursor);
+            let (outline_area, detail_area) = panes(area);
+            nav.ui(f, outline_area, &mut tree_state, |_| Style::default());
+            if let Some(detail_area) = detail_area {
+                detail::ui(f, detail_area, nav.get(&[0, 0]), None, &LinkResolvers::default(), &Anchors::default(), &[]);
+            }
+            popup::menu(f, area, "There are unsaved changes", &["Write and quit", "Cancel"], &mut menu);
+        }).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    fn text(buffer: &Buffer) -> String {
+        buffer.content().iter().map(|cell| cell.symbol()).collect()
+    }
+
+    #[test]
+    fn tiny_terminals_do_not_panic() {
+        for (width, height) in [(1, 1), (1, 40), (200, 1), (2, 2), (10, 3), (COMPACT_WIDTH, 4)] {
+            draw(width, height, None);
+            draw(width, height, Some(7));
+
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            terminal.draw(|f| {
+                History::default().ui(f, f.size(), &mut ListState::default(), &Nav { nodes: vec![] });
+                Report::default().ui(f, f.size(), &mut ListState::default());
+                popup::message(f, f.size(), "Cannot open the file", "djvused was not found\n\nPress any key");
+                let fields = [("Title", &LineInput::default()), ("Page", &LineInput::default())];
+                popup::form(f, f.size(), "Edit entry", &fields, 1);
+            }).unwrap();
+        }
+    }
+
+    #[test]
+    fn narrow_terminals_are_compact() {
+        let compact = text(&draw(COMPACT_WIDTH - 1, 20, None));
+        assert!(!compact.contains("Details"));
+        assert!(!compact.contains("Part I > Chapter 1"));
+        assert!(compact.contains("book.djvu [+]  Chapter 1"));
+
+        let wide = text(&draw(120, 20, None));
+        assert!(wide.contains("Details"));
+        assert!(wide.contains("Part I > Chapter 1"));
+        assert!(wide.contains("page 1"));
+    }
+
+    #[test]
+    fn status_line_is_kept_while_typing() {
+        assert!(text(&draw(40, 1, Some(6))).starts_with(":write"));
+        assert!(!text(&draw(40, 1, None)).contains(":write"));
+        assert!(text(&draw(40, 2, None)).contains(":write"));
     }
 }
@@ -0,0 +1,201 @@
+use std::{
+    fmt::Display,
+    fs,
+    io,
+    path::Path,
+    process::ExitStatus,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    djvu::ToolRunner,
+    scandata::{parse_scandata, ScandataError},
+    tempfile::TempFile,
+};
+
+/// Where the metadata API of the Internet Archive serves the description of items.
+const METADATA_URL: &str = "https://archive.org/metadata";
+const DOWNLOAD_URL: &str = "https://archive.org/download";
+/// Endpoint of the S3-like API files are uploaded with.
+const UPLOAD_URL: &str = "https://s3.us.archive.org";
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// `curl` could not be run.
+    IOError(io::Error),
+    CurlError(ExitStatus, String),
+    MetadataError(serde_json::Error),
+    /// The metadata API returned nothing for this identifier.
+    UnknownItem(String),
+    /// The item has no file in this format.
+    MissingFile(String, &'static str),
+    ScandataError(ScandataError),
+    /// Uploading needs the S3 keys of an account.
+    MissingKeys,
+    /// The name of the file of the item, such as `..`, cannot name a file of the working
+    /// directory.
+    InvalidFileName(String),
+}
+
+impl Display for ArchiveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(e) if e.kind() == io::ErrorKind::NotFound => write!(f, "curl was not found in the PATH"),
+            Self::IOError(e) => write!(f, "cannot run curl: {}", e),
+            Self::CurlError(status, stderr) => write!(f, "curl failed ({}): {}", status, stderr.trim_end()),
+            Self::MetadataError(e) => write!(f, "invalid metadata: {}", e),
+            Self::UnknownItem(identifier) => write!(f, "no item named {} on the Internet Archive", identifier),
+            Self::MissingFile(identifier, format) => write!(f, "the item {} has no {} file", identifier, format),
+            Self::ScandataError(e) => write!(f, "{}", e),
+            Self::MissingKeys => write!(
+                f,
+                "set access_key and secret_key in the [archive] section of the configuration \
+                (see https://archive.org/account/s3.php)",
+            ),
+            Self::InvalidFileName(name) => write!(f, "the item has a file named {:?}, which -o has to rename", name),
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IOError(e) => Some(e),
+            Self::MetadataError(e) => Some(e),
+            Self::ScandataError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A file of an item, as described by the metadata API.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ItemFile {
+    pub name: String,
+    #[serde(default)]
+    pub format: String,
+}
+
+/// An item of the Internet Archive, such as a scanned book, and the files it is made of.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Item {
+    #[serde(skip)]
+    pub identifier: String,
+    #[serde(default)]
+    pub files: Vec<ItemFile>,
+}
+
+impl Item {
+    /// Parse the answer of the metadata API for the item `identifier`.
+    pub fn from_metadata(identifier: &str, metadata: &[u8]) -> Result<Self, ArchiveError> {
+        let mut item: Self = serde_json::from_slice(metadata).map_err(ArchiveError::MetadataError)?;
+        // Unknown identifiers get an empty object rather than an error.
+        if item.files.is_empty() {
+            return Err(ArchiveError::UnknownItem(String::from(identifier)));
+        }
+        item.identifier = String::from(identifier);
+        Ok(item)
+    }
+
+    /// Return the name of the file of the item in `format`, as named by the Internet Archive,
+    /// e.g. `DjVu` or `Scandata`.
+    pub fn file(&self, format: &'static str) -> Result<&str, ArchiveError> {
+        self.files.iter()
+            .find(|file| file.format == format)
+            .map(|file| file.name.as_str())
+            .ok_or_else(|| ArchiveError::MissingFile(self.identifier.clone(), format))
+    }
+
+    pub fn download_url(&self, name: &str) -> String {
+        format!("{}/{}/{}", DOWNLOAD_URL, encode_path(&self.identifier), encode_path(name))
+    }
+}
+
+/// Percent-encode `path` for use in a URL, keeping its slashes.
+fn encode_path(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => String::from(b as char),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Run `curl` with `args`, failing on HTTP errors, and return what it printed.
+fn curl(tools: &impl ToolRunner, args: &[&str]) -> Result<Vec<u8>, ArchiveError> {
+    let mut all_args = vec!["--silent", "--show-error", "--fail", "--location"];
+    all_args.extend(args);
+    let output = tools.output("curl", &all_args).map_err(ArchiveError::IOError)?;
+    if !output.status.success() {
+        return Err(ArchiveError::CurlError(output.status, String::from_utf8_lossy(&output.stderr).into_owned()));
+    }
+    Ok(output.stdout)
+}
+
+/// Fetch the description of the item `identifier`.
+pub fn fetch_item(tools: &impl ToolRunner, identifier: &str) -> Result<Item, ArchiveError> {
+    let url = format!("{}/{}", METADATA_URL, encode_path(identifier));
+    Item::from_metadata(identifier, &curl(tools, &[&url])?)
+}
+
+/// Fetch the printed page number of each page of the DjVu file of the item, from its scan data.
+pub fn fetch_page_labels(tools: &impl ToolRunner, item: &Item) -> Result<Vec<Option<String>>, ArchiveError> {
+    let scandata = curl(tools, &[&item.download_url(item.file("Scandata")?)])?;
+    parse_scandata(&String::from_utf8_lossy(&scandata)).map_err(ArchiveError::ScandataError)
+}
+
+/// Return the name of the file of an item `name` without its directories, which could lead out
+/// of the working directory, to download it there.
+pub fn local_name(name: &str) -> Result<String, ArchiveError> {
+    match Path::new(name).file_name() {
+        Some(file_name) => Ok(file_name.to_string_lossy().into_owned()),
+        None => Err(ArchiveError::InvalidFileName(String::from(name))),
+    }
+}
+
+/// Download the DjVu file of the item to `output`.
+pub fn download(tools: &impl ToolRunner, item: &Item, output: &Path) -> Result<(), ArchiveError> {
+    let url = item.download_url(item.file("DjVu")?);
+    curl(tools, &["--output", &output.display().to_string(), &url]).map(|_| ())
+}
+
+/// Upload `file` in place of the DjVu file of the item, with the S3 keys `access_key` and
+/// `secret_key` of an account allowed to edit it.
+pub fn upload(
+    tools: &impl ToolRunner,
+    item: &Item,
+    file: &Path,
+    access_key: &str,
+    secret_key: &str,
+) -> Result<(), ArchiveError> {
+    let url = format!("{}/{}/{}", UPLOAD_URL, encode_path(&item.identifier), encode_path(item.file("DjVu")?));
+    // The authorization header is passed in a file only its owner can read, so that the keys do
+    // not show up in the list of processes.
+    let header_file = TempFile::private(".headers").map_err(|e| ArchiveError::IOError(io::Error::other(e)))?;
+    fs::write(header_file.path(), format!("authorization: LOW {}:{}\n", access_key, secret_key))
+        .map_err(ArchiveError::IOError)?;
+    let header = format!("@{}", header_file.path().display());
+    curl(tools, &["--header", &header, "--upload-file", &file.display().to_string(), &url]).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn djvu_file_found_in_metadata() {
+        let metadata = br#"{"files": [
+            {"name": "alice_scandata.xml", "format": "Scandata"},
+            {"name": "alice 1865.djvu", "format": "DjVu"}
+        ], "metadata": {"identifier": "alice"}}"#;
+        let item = Item::from_metadata("alice", metadata).unwrap();
+
+        assert_eq!(item.file("DjVu").unwrap(), "alice 1865.djvu");
+        assert_eq!(item.download_url("alice 1865.djvu"), "https://archive.org/download/alice/alice%201865.djvu");
+        assert!(matches!(item.file("Text PDF"), Err(ArchiveError::MissingFile(_, "Text PDF"))));
+        assert!(matches!(Item::from_metadata("nothing", b"{}"), Err(ArchiveError::UnknownItem(_))));
+        assert_eq!(local_name("../../.bashrc/alice.djvu").unwrap(), "alice.djvu");
+        assert!(matches!(local_name("scans/.."), Err(ArchiveError::InvalidFileName(_))));
+    }
+}
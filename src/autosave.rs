@@ -0,0 +1,148 @@
+//! Copies of the outline being edited, kept in the state directory while it has unsaved changes,
+//! to recover them after a crash or a lost terminal.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{nav::Nav, APP_NAME};
+
+/// Directory of the state directory holding the autosaves.
+const AUTOSAVE_DIRECTORY: &str = "autosave";
+
+#[derive(Debug)]
+pub enum AutosaveError {
+    IOError(io::Error),
+    XDGSpecificError(xdg::BaseDirectoriesError),
+    FormatError(serde_json::Error),
+}
+
+impl Display for AutosaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(e) => write!(f, "cannot write the autosave: {}", e),
+            Self::XDGSpecificError(e) => write!(f, "cannot find the state directory: {}", e),
+            Self::FormatError(e) => write!(f, "invalid autosave: {}", e),
+        }
+    }
+}
+
+impl Error for AutosaveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IOError(e) => Some(e),
+            Self::XDGSpecificError(e) => Some(e),
+            Self::FormatError(e) => Some(e),
+        }
+    }
+}
+
+/// The outline of a file as it was being edited, with what was selected and opened in the tree.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Autosave {
+    /// Absolute path of the file.
+    pub filename: PathBuf,
+    pub nav: Nav,
+    pub selected: Vec<usize>,
+    pub opened: Vec<Vec<usize>>,
+    /// The titles of all the pages in order, if some were changed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub page_titles: Vec<Option<String>>,
+}
+
+impl Autosave {
+    /// Write the autosave to `path`, through a copy renamed over it so that a crash while writing
+    /// leaves the previous one.
+    pub fn write_to(&self, path: &Path) -> Result<(), AutosaveError> {
+        let json = serde_json::to_string(self).map_err(AutosaveError::FormatError)?;
+        let copy = path.with_extension("json.tmp");
+        fs::write(&copy, json)
+            .and_then(|_| fs::rename(&copy, path))
+            .map_err(AutosaveError::IOError)
+    }
+
+    pub fn read_from(path: &Path) -> Result<Self, AutosaveError> {
+        let json = fs::read_to_string(path).map_err(AutosaveError::IOError)?;
+        serde_json::from_str(&json).map_err(AutosaveError::FormatError)
+    }
+
+    /// Write the autosave of its file to the state directory.
+    pub fn save(&self) -> Result<(), AutosaveError> {
+        self.write_to(&autosave_path(&self.filename)?)
+    }
+}
+
+/// Return the absolute path of `filename`, by which its autosave is found.
+pub fn absolute_path(filename: &str) -> PathBuf {
+    fs::canonicalize(filename).unwrap_or_else(|_| PathBuf::from(filename))
+}
+
+/// Return the path of the autosave of the file at the absolute path `filename`, named after the
+/// path with its slashes replaced, as Vim does for its swap files.
+fn autosave_path(filename: &Path) -> Result<PathBuf, AutosaveError> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_NAME)
+        .map_err(AutosaveError::XDGSpecificError)?;
+    let name = filename.to_string_lossy().replace(['/', '\\'], "%");
+    xdg_dirs.place_state_file(format!("{}/{}.json", AUTOSAVE_DIRECTORY, name))
+        .map_err(AutosaveError::IOError)
+}
+
+/// Return the autosave of the file at the absolute path `filename` if it was saved after the file
+/// was last written.
+pub fn recover(filename: &Path) -> Result<Option<Autosave>, AutosaveError> {
+    let path = autosave_path(filename)?;
+    let saved = match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+        Ok(saved) => saved,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(AutosaveError::IOError(e)),
+    };
+    let written = fs::metadata(filename).and_then(|metadata| metadata.modified()).map_err(AutosaveError::IOError)?;
+    if saved <= written {
+        return Ok(None);
+    }
+    let autosave = Autosave::read_from(&path)?;
+    Ok((autosave.filename == filename).then_some(autosave))
+}
+
+/// Remove the autosave of the file at the absolute path `filename`, if any.
+pub fn remove(filename: &Path) -> Result<(), AutosaveError> {
+    match fs::remove_file(autosave_path(filename)?) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(AutosaveError::IOError(e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{nav::{BookmarkLink, NavNode}, tempfile::TempFile};
+
+    #[test]
+    fn autosave_reads_back() {
+        let autosave = Autosave {
+            filename: PathBuf::from("/books/alice.djvu"),
+            nav: Nav {
+                nodes: vec![NavNode {
+                    string: String::from("Chapter \"One\""),
+                    link: BookmarkLink::PageNumber(7),
+                    children: vec![NavNode { string: String::from("Down the Rabbit-Hole"), ..NavNode::default() }],
+                }],
+            },
+            selected: vec![0, 0],
+            opened: vec![vec![0]],
+            page_titles: vec![None, Some(String::from("i"))],
+        };
+        let file = TempFile::new(".json").unwrap();
+        autosave.write_to(file.path()).unwrap();
+        assert_eq!(Autosave::read_from(file.path()).unwrap(), autosave);
+
+        // Autosaves written before page titles were kept have none.
+        fs::write(file.path(), r#"{"filename":"/books/alice.djvu","nav":{"nodes":[]},"selected":[],"opened":[]}"#).unwrap();
+        assert_eq!(Autosave::read_from(file.path()).unwrap().page_titles, Vec::<Option<String>>::new());
+    }
+}
@@ -0,0 +1,205 @@
+use serde::Deserialize;
+
+/// Capitalization convention of the titles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseStyle {
+    /// Only the first word is capitalized, e.g. "The history of the world".
+    Sentence,
+    /// Every word but articles, conjunctions and short prepositions is capitalized, e.g. "The
+    /// History of the World".
+    Title,
+}
+
+/// Language of the titles, which decides the words left in lower case by title case.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CasingLanguage {
+    #[default]
+    En,
+    Fr,
+    De,
+    Es,
+    It,
+    Nl,
+}
+
+impl CasingLanguage {
+    fn minor_words(self) -> &'static [&'static str] {
+        match self {
+            Self::En => &[
+                "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or",
+                "the", "to", "vs", "via",
+            ],
+            Self::Fr => &[
+                "à", "au", "aux", "d", "de", "des", "du", "en", "et", "l", "la", "le", "les", "ou",
+                "par", "pour", "sur", "un", "une",
+            ],
+            Self::De => &[
+                "als", "am", "an", "auf", "aus", "bei", "bis", "das", "dem", "den", "der", "des",
+                "die", "ein", "eine", "einer", "eines", "für", "im", "in", "mit", "nach", "oder",
+                "über", "um", "und", "unter", "vom", "von", "vor", "zu", "zum", "zur",
+            ],
+            Self::Es => &[
+                "a", "al", "con", "de", "del", "e", "el", "en", "la", "las", "lo", "los", "o", "para",
+                "por", "sin", "sobre", "u", "un", "una", "y",
+            ],
+            Self::It => &[
+                "a", "al", "all", "alla", "con", "da", "dal", "dall", "dalla", "degli", "dei", "del",
+                "dell", "della", "di", "e", "ed", "gli", "i", "il", "in", "l", "la", "le", "lo",
+                "nel", "nell", "nella", "o", "per", "su", "sul", "sull", "sulla", "tra", "un", "una",
+                "uno",
+            ],
+            Self::Nl => &[
+                "aan", "bij", "de", "een", "en", "het", "in", "met", "naar", "of", "om", "op", "over",
+                "te", "tot", "uit", "van", "voor",
+            ],
+        }
+    }
+}
+
+/// Whether `word` is capitalized the ordinary way, first letter only. Acronyms and words such as
+/// "McGill" are not, and are left alone.
+fn is_capitalized(word: &str) -> bool {
+    let mut letters = word.chars().filter(|c| c.is_alphabetic());
+    letters.next().is_some_and(char::is_uppercase) && letters.all(char::is_lowercase)
+}
+
+fn is_lowercase(word: &str) -> bool {
+    word.chars().any(char::is_alphabetic) && !word.chars().any(char::is_uppercase)
+}
+
+/// Change the case of the first letter of `word`, keeping any leading punctuation.
+fn with_first_letter(word: &str, upper: bool) -> String {
+    match word.char_indices().find(|(_, c)| c.is_alphabetic()) {
+        Some((i, c)) => {
+            let changed: String = if upper { c.to_uppercase().collect() } else { c.to_lowercase().collect() };
+            format!("{}{}{}", &word[..i], changed, &word[i + c.len_utf8()..])
+        },
+        None => String::from(word),
+    }
+}
+
+/// Change the case of the first letter of `word` to upper case if `upper`, or else to lower
+/// case, if it is in plain lower case or capitalized the ordinary way.
+fn with_case(word: &str, upper: bool) -> String {
+    if upper && is_lowercase(word) || !upper && is_capitalized(word) {
+        with_first_letter(word, upper)
+    } else {
+        String::from(word)
+    }
+}
+
+fn has_letters(word: &str) -> bool {
+    word.chars().any(char::is_alphabetic)
+}
+
+/// Return `title` written in `style`. Only words in plain lower case or capitalized the ordinary
+/// way are changed. Title case leaves the minor words of `language` and `minor_words` in lower
+/// case, unless they start the title, or a subtitle after a colon or a dash. Sentence case only
+/// puts capitalized words in lower case if the whole title is in title case, as they are proper
+/// nouns otherwise.
+pub fn recase(title: &str, style: CaseStyle, language: CasingLanguage, minor_words: &[String]) -> String {
+    let words: Vec<_> = title.split(' ').collect();
+    let last = words.iter().rposition(|word| has_letters(word));
+    let is_minor = |word: &str| {
+        let bare: String = word.chars()
+            .filter(|c| c.is_alphabetic())
+            .flat_map(char::to_lowercase)
+            .collect();
+        language.minor_words().contains(&bare.as_str()) || minor_words.iter().any(|minor| minor.to_lowercase() == bare)
+    };
+    // The words after the first one of a title in title case are capitalized, but for minor
+    // words. A single capitalized word, as in "History of France", is more likely a proper noun.
+    let following: Vec<_> = words.iter()
+        .filter(|word| has_letters(word))
+        .skip(1)
+        .map(|word| word.rsplit(['\'', '’']).next().unwrap_or(word))
+        .collect();
+    let lowers_capitals = style == CaseStyle::Title
+        || following.iter().filter(|word| is_capitalized(word)).count() >= 2
+            && !following.iter().any(|word| is_lowercase(word) && !is_minor(word));
+    let with_case = |word: &str, upper: bool| match upper || lowers_capitals {
+        true => with_case(word, upper),
+        false => String::from(word),
+    };
+    let mut starts = true;
+    let mut recased = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        if !has_letters(word) {
+            starts |= matches!(*word, "-" | "–" | "—");
+            recased.push(String::from(*word));
+            continue;
+        }
+        let major = style == CaseStyle::Title && (Some(i) == last || !is_minor(word));
+        let elision = word.find(['\'', '’']).map(|apostrophe| word.split_at(apostrophe));
+        recased.push(match elision {
+            // An elided article or preposition stays in lower case before the word it is joined
+            // to, as in "de l'Europe".
+            Some((elided, joined)) if !starts && is_minor(elided) && has_letters(joined) => {
+                let major = style == CaseStyle::Title && !is_minor(joined);
+                format!("{}{}", with_case(elided, false), with_case(joined, major))
+            },
+            _ => with_case(word, starts || major),
+        });
+        starts = word.ends_with(':');
+    }
+    recased.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_case() {
+        assert_eq!(
+            recase("the history of the world, volume 2", CaseStyle::Title, CasingLanguage::En, &[]),
+            "The History of the World, Volume 2"
+        );
+        assert_eq!(recase("A Tale Of Two Cities", CaseStyle::Title, CasingLanguage::En, &[]), "A Tale of Two Cities");
+        assert_eq!(recase("l'état de la France", CaseStyle::Title, CasingLanguage::Fr, &[]), "L'état de la France");
+    }
+
+    #[test]
+    fn sentence_case_keeps_acronyms() {
+        assert_eq!(
+            recase("1. Economic History Of The USA", CaseStyle::Sentence, CasingLanguage::En, &[]),
+            "1. Economic history of the USA"
+        );
+        assert_eq!(recase("(introduction)", CaseStyle::Sentence, CasingLanguage::En, &[]), "(Introduction)");
+        assert_eq!(recase("Paris: the city of light", CaseStyle::Sentence, CasingLanguage::En, &[]), "Paris: The city of light");
+    }
+
+    #[test]
+    fn sentence_case_keeps_proper_nouns() {
+        assert_eq!(recase("History of France", CaseStyle::Sentence, CasingLanguage::En, &[]), "History of France");
+        assert_eq!(recase("a history of France", CaseStyle::Sentence, CasingLanguage::En, &[]), "A history of France");
+        assert_eq!(
+            recase("histoire de l'Europe", CaseStyle::Sentence, CasingLanguage::Fr, &[]),
+            "Histoire de l'Europe"
+        );
+        assert_eq!(
+            recase("Histoire De La Révolution Industrielle", CaseStyle::Sentence, CasingLanguage::Fr, &[]),
+            "Histoire de la révolution industrielle"
+        );
+    }
+
+    #[test]
+    fn minor_words_of_the_language() {
+        assert_eq!(
+            recase("guerre et paix: le roman de l'europe", CaseStyle::Title, CasingLanguage::Fr, &[]),
+            "Guerre et Paix: Le Roman de l'Europe"
+        );
+        assert_eq!(
+            recase("geschichte der stadt und des landes", CaseStyle::Title, CasingLanguage::De, &[]),
+            "Geschichte der Stadt und des Landes"
+        );
+        assert_eq!(
+            recase("storia dell'arte — il rinascimento", CaseStyle::Title, CasingLanguage::It, &[]),
+            "Storia dell'Arte — Il Rinascimento"
+        );
+        let minor_words = [String::from("Per")];
+        assert_eq!(recase("Notes Per Chapter", CaseStyle::Title, CasingLanguage::En, &minor_words), "Notes per Chapter");
+    }
+}
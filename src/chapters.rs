@@ -0,0 +1,161 @@
+//! Chapters of an audio book narrating the document, placed with a file giving the time at which
+//! the reading of some pages starts, for players to jump to the entries of the outline.
+//!
+//! The timestamps file has one `<page> <time>` line per page whose time is known, such as
+//! `12 4:31.5`, times being written `[[hours:]minutes:]seconds[.fraction]`. The time of the other
+//! pages is interpolated. An `end <time>` line gives the length of the recording, and a
+//! `file <name>` line the audio file, which cue sheets refer to. Lines starting with `#` are
+//! comments.
+
+use std::fmt::Display;
+
+use crate::{
+    djvu::PageInfo,
+    link::page_number,
+    nav::Nav,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChapterError {
+    /// This line of the timestamps file cannot be read.
+    InvalidLine(usize),
+    /// The pages of the timestamps file are not in increasing order of page and time at this line.
+    OutOfOrder(usize),
+    /// This page is before the first page or after the last page with a time.
+    PageNotCovered(u32),
+    /// The format needs the length of the recording, given by an `end` line.
+    MissingEnd,
+    /// The format needs the name of the audio file, given by a `file` line.
+    MissingFile,
+}
+
+impl Display for ChapterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLine(line) => {
+                write!(f, "line {} of the timestamps is not `<page> <time>`, `end <time>` or `file <name>`", line)
+            },
+            Self::OutOfOrder(line) => write!(f, "line {} of the timestamps is before the line above it", line),
+            Self::PageNotCovered(page) => write!(f, "the timestamps do not tell when page {} is read", page),
+            Self::MissingEnd => write!(f, "the timestamps need an `end <time>` line with the length of the recording"),
+            Self::MissingFile => write!(f, "the timestamps need a `file <name>` line with the audio file"),
+        }
+    }
+}
+
+impl std::error::Error for ChapterError {}
+
+/// When the reading of pages starts in the recording, in milliseconds.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Timestamps {
+    /// Pages and their times, in increasing order of both.
+    pub pages: Vec<(u32, u64)>,
+    /// Length of the recording.
+    pub end: Option<u64>,
+    /// Name of the audio file.
+    pub file: Option<String>,
+}
+
+/// Parse a time written `[[hours:]minutes:]seconds[.fraction]` into milliseconds.
+fn parse_time(input: &str) -> Option<u64> {
+    let (whole, fraction) = input.split_once('.').unwrap_or((input, ""));
+    let mut seconds: u64 = 0;
+    for part in whole.split(':') {
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        seconds = seconds.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+    }
+    if !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let millis: u64 = format!("{:0<3}", fraction)[..3].parse().ok()?;
+    seconds.checked_mul(1000)?.checked_add(millis)
+}
+
+impl Timestamps {
+    pub fn parse(input: &str) -> Result<Self, ChapterError> {
+        let mut timestamps = Self::default();
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once(char::is_whitespace).ok_or(ChapterError::InvalidLine(i + 1))?;
+            let value = value.trim();
+            match key {
+                "end" => timestamps.end = Some(parse_time(value).ok_or(ChapterError::InvalidLine(i + 1))?),
+                "file" => timestamps.file = Some(String::from(value.trim_matches('"'))),
+                page => {
+                    let page: u32 = page.parse().map_err(|_| ChapterError::InvalidLine(i + 1))?;
+                    let time = parse_time(value).ok_or(ChapterError::InvalidLine(i + 1))?;
+                    if timestamps.pages.last().is_some_and(|(last_page, last_time)| *last_page >= page || *last_time > time) {
+                        return Err(ChapterError::OutOfOrder(i + 1));
+                    }
+                    timestamps.pages.push((page, time));
+                },
+            }
+        }
+        Ok(timestamps)
+    }
+
+    /// Return the time at which the reading of page `page` starts, interpolated between the pages
+    /// around it if it has no time of its own.
+    pub fn at(&self, page: u32) -> Result<u64, ChapterError> {
+        let after = self.pages.partition_point(|(other, _)| *other < page);
+        match (after.checked_sub(1).map(|before| self.pages[before]), self.pages.get(after)) {
+            (_, Some((next_page, next_time))) if *next_page == page => Ok(*next_time),
+            (Some((previous_page, previous_time)), Some((next_page, next_time))) => {
+                let elapsed = (next_time - previous_time) * u64::from(page - previous_page);
+                Ok(previous_time + elapsed / u64::from(next_page - previous_page))
+            },
+            _ => Err(ChapterError::PageNotCovered(page)),
+        }
+    }
+}
+
+/// A chapter of the recording: the title of an entry of the outline, and when it starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter<'a> {
+    pub title: &'a str,
+    pub start: u64,
+}
+
+/// Return the chapters of the entries of `nav` pointing to pages of the document with pages
+/// `pages`, the nested ones included, in order of their start. Entries out of page order are
+/// moved, since chapters follow each other in the recording; the others keep document order.
+pub fn chapters<'a>(nav: &'a Nav, pages: Option<&[PageInfo]>, timestamps: &Timestamps) -> Result<Vec<Chapter<'a>>, ChapterError> {
+    let mut chapters = nav.all_nodes().into_iter()
+        .filter_map(|(_, node)| Some((node.string.as_str(), page_number(&node.link, pages)?)))
+        .map(|(title, page)| Ok(Chapter { title, start: timestamps.at(page)? }))
+        .collect::<Result<Vec<_>, _>>()?;
+    chapters.sort_by_key(|chapter| chapter.start);
+    Ok(chapters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::{node, BookmarkLink, NavNode};
+
+    #[test]
+    fn chapters_from_timestamps() {
+        let timestamps = Timestamps::parse("# page time\n1 0\n11 10:00\n21 1:00:00.5\nend 1:10:00\nfile \"book.mp3\"\n").unwrap();
+        assert_eq!(timestamps.at(6), Ok(300_000));
+        assert_eq!(timestamps.at(21), Ok(3_600_500));
+        assert_eq!(timestamps.at(22), Err(ChapterError::PageNotCovered(22)));
+        assert_eq!(Timestamps::parse("3 1:00\n2 2:00"), Err(ChapterError::OutOfOrder(2)));
+        assert_eq!(Timestamps::parse("3 1:0x"), Err(ChapterError::InvalidLine(1)));
+
+        let nav = Nav {
+            nodes: vec![
+                node("Part One", 1, vec![node("Chapter 1", 6, vec![])]),
+                NavNode { link: BookmarkLink::PageLink(String::from("https://example.org/errata")), ..node("Errata", 0, vec![]) },
+                // The index of illustrations lists a plate read before the first chapter.
+                node("Plates", 21, vec![node("Frontispiece", 2, vec![])]),
+            ],
+        };
+        let titles: Vec<_> = chapters(&nav, None, &timestamps).unwrap().iter().map(|chapter| chapter.title).collect();
+        assert_eq!(titles, ["Part One", "Frontispiece", "Chapter 1", "Plates"]);
+    }
+}
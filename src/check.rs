@@ -0,0 +1,107 @@
+use crate::{
+    history::format_identifier,
+    nav::{BookmarkLink, Nav, NavNode},
+    report::{Report, ReportRow},
+    tree_widget::TreeIdentifierVec,
+};
+
+/// Whether `node` links to a page number outside of a document of `page_count` pages. Pages are
+/// numbered from 1.
+pub fn is_out_of_range(node: &NavNode, page_count: u32) -> bool {
+    matches!(node.link, BookmarkLink::PageNumber(n) if n == 0 || n > page_count)
+}
+
+/// Return the nodes of `nav` linking to a page number outside of a document of `page_count` pages,
+/// in document order.
+pub fn out_of_range(nav: &Nav, page_count: u32) -> Vec<(TreeIdentifierVec, &NavNode)> {
+    nav.all_nodes().into_iter()
+        .filter(|(_, node)| is_out_of_range(node, page_count))
+        .collect()
+}
+
+/// List the nodes of `nav` linking to a page number outside of a document of `page_count` pages.
+pub fn out_of_range_report(nav: &Nav, page_count: u32) -> Report {
+    let rows = out_of_range(nav, page_count).into_iter()
+        .map(|(id, node)| {
+            let text = format!("{} \"{}\" -> page {}", format_identifier(&id), node.string, node.link);
+            ReportRow::node(text, id)
+        })
+        .collect();
+    Report {
+        title: format!("Links past the {} pages of the document", page_count),
+        rows,
+    }
+}
+
+/// Whether `c` cannot be written in a title with `djvused` and read back: NUL ends its strings,
+/// and the other control characters but line breaks and tabs, which are escaped, come back as
+/// octal escapes.
+pub fn is_unwritable(c: char) -> bool {
+    c.is_ascii_control() && !matches!(c, '\n' | '\t' | '\r')
+}
+
+/// Return the nodes of `nav` whose title has characters that cannot be written, in document
+/// order.
+pub fn unwritable_titles(nav: &Nav) -> Vec<(TreeIdentifierVec, &NavNode)> {
+    nav.all_nodes().into_iter()
+        .filter(|(_, node)| node.string.chars().any(is_unwritable))
+        .collect()
+}
+
+/// List the nodes of `nav` whose title has characters that cannot be written, showing them as
+/// their code.
+pub fn unwritable_report(nav: &Nav) -> Report {
+    let rows = unwritable_titles(nav).into_iter()
+        .map(|(id, node)| {
+            let title: String = node.string.chars()
+                .map(|c| if is_unwritable(c) { format!("<U+{:04X}>", u32::from(c)) } else { c.to_string() })
+                .collect();
+            ReportRow::node(format!("{} \"{}\"", format_identifier(&id), title), id)
+        })
+        .collect();
+    Report {
+        title: String::from("Titles with control characters djvused cannot write"),
+        rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+
+    #[test]
+    fn links_past_the_last_page() {
+        let nav = Nav {
+            nodes: vec![
+                node("Cover", 0, vec![]),
+                node("Part I", 3, vec![
+                    node("Chapter 1", 10, vec![]),
+                    node("Chapter 2", 11, vec![]),
+                ]),
+                NavNode { link: BookmarkLink::PageLink(String::from("errata.djvu")), ..node("Errata", 0, vec![]) },
+            ],
+        };
+
+        let ids: Vec<_> = out_of_range(&nav, 10).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, [vec![0], vec![1, 1]]);
+        let report = out_of_range_report(&nav, 10);
+        assert_eq!(report.rows[1].text, "2.2 \"Chapter 2\" -> page 11");
+        assert!(out_of_range_report(&nav, 11).rows.len() == 1);
+    }
+
+    #[test]
+    fn control_characters_in_titles() {
+        let nav = Nav {
+            nodes: vec![
+                node("Line\nbreak", 1, vec![]),
+                node("Part I", 3, vec![node("Chapter\u{0}1", 4, vec![])]),
+                node("Index\u{7f}", 9, vec![]),
+            ],
+        };
+
+        let ids: Vec<_> = unwritable_titles(&nav).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, [vec![1, 0], vec![2]]);
+        assert_eq!(unwritable_report(&nav).rows[1].text, "3 \"Index<U+007F>\"");
+    }
+}
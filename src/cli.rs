@@ -0,0 +1,364 @@
+use std::{fmt::Display, fs, io, path::Path};
+
+use crate::{
+    archive::{self, ArchiveError},
+    config::{Config, ConfigError},
+    djvu::{
+        NavReadingError, SystemTools, check_djvused_with, get_nav_from_djvu, get_pages_from_djvu,
+        get_document_text, embed_nav_in_djvu_file, set_page_titles,
+    },
+    chapters::Timestamps,
+    export::{ExportError, ExportFormat, export},
+    heading::{default_heading_patterns, outline_from_text, InvalidPatternError},
+    import::{ImportError, ImportFormat, import},
+    link::LinkResolvers,
+    lint::{lint, Finding},
+    nav::Nav,
+    offset::{label_offset, shift_pages},
+    query::{QueryError, query},
+    scandata::{parse_scandata, ScandataError},
+    selftest,
+    strict::{self, StrictError},
+    text_outline::to_text,
+    validate::validate,
+};
+
+/// Errors of the non-interactive subcommands.
+#[derive(Debug)]
+pub enum CliError {
+    NavReadingError(NavReadingError),
+    ExportError(ExportError),
+    ImportError(ImportError),
+    QueryError(QueryError),
+    ArchiveError(ArchiveError),
+    ConfigError(ConfigError),
+    ScandataError(ScandataError),
+    InvalidPatternError(InvalidPatternError),
+    StrictError(StrictError),
+    InputError(String, io::Error),
+    OutputError(String, io::Error),
+    /// The format of the given file could not be guessed from its name.
+    UnknownFormatError(String),
+    /// This number of self-test steps failed.
+    SelfTestError(usize),
+    /// The scan data lists this number of pages, but the document has another.
+    PageCountMismatch(usize, usize),
+    /// No file matches this pattern.
+    NoMatch(String),
+    /// The outline breaks the policy this number of times.
+    PolicyViolations(usize),
+    /// This number of files out of the total failed in a batch.
+    BatchError(usize, usize),
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NavReadingError(e) => write!(f, "{}", e),
+            Self::ExportError(e) => write!(f, "{}", e),
+            Self::ImportError(e) => write!(f, "{}", e),
+            Self::QueryError(e) => write!(f, "{}", e),
+            Self::ArchiveError(e) => write!(f, "{}", e),
+            Self::ConfigError(e) => write!(f, "{}", e),
+            Self::ScandataError(e) => write!(f, "{}", e),
+            Self::InvalidPatternError(e) => write!(f, "{}", e),
+            Self::StrictError(e) => write!(f, "not written (strict mode): {}", e),
+            Self::InputError(path, e) => write!(f, "cannot read {}: {}", path, e),
+            Self::OutputError(path, e) => write!(f, "cannot write {}: {}", path, e),
+            Self::UnknownFormatError(path) => {
+                write!(f, "cannot guess the format of {}, use --format", path)
+            },
+            Self::SelfTestError(failed) => write!(f, "{} self-test steps failed", failed),
+            Self::PageCountMismatch(scanned, pages) => {
+                write!(f, "the scan data lists {} pages, but the document has {}", scanned, pages)
+            },
+            Self::NoMatch(pattern) => write!(f, "no file matches {}", pattern),
+            Self::PolicyViolations(count) => write!(f, "{} warnings", count),
+            Self::BatchError(failed, total) => write!(f, "{} of {} files failed", failed, total),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Write the outline of `filename` in `format` to the file `output`, or to the standard output,
+/// placing its entries in an audio recording with the file `timestamps` if given.
+pub fn export_outline(filename: &str, format: ExportFormat, output: Option<&str>, timestamps: Option<&str>) -> Result<(), CliError> {
+    let nav = get_nav_from_djvu(filename).map_err(CliError::NavReadingError)?;
+    let pages = get_pages_from_djvu(filename).ok();
+    let timestamps = match timestamps {
+        Some(path) => {
+            let content = fs::read_to_string(path).map_err(|e| CliError::InputError(String::from(path), e))?;
+            Some(Timestamps::parse(&content).map_err(|e| CliError::ExportError(ExportError::ChapterError(e)))?)
+        },
+        None => None,
+    };
+    let exported = export(&nav, format, pages.as_deref(), timestamps.as_ref()).map_err(CliError::ExportError)?;
+    match output {
+        Some(path) => fs::write(path, exported)
+            .map_err(|e| CliError::OutputError(String::from(path), e)),
+        None => {
+            print!("{}", exported);
+            Ok(())
+        },
+    }
+}
+
+/// Write the outline proposed from the headings found in the text of the pages of `filename`, as
+/// indented text, to the file `output` or to the standard output. Return the number of entries.
+pub fn generate_outline(filename: &str, output: Option<&str>) -> Result<usize, CliError> {
+    let config = Config::load(filename).map_err(CliError::ConfigError)?;
+    let texts = get_document_text(filename).map_err(CliError::NavReadingError)?;
+    let patterns = config.generate.headings.unwrap_or_else(default_heading_patterns);
+    let nav = Nav { nodes: outline_from_text(&texts, &patterns).map_err(CliError::InvalidPatternError)? };
+    let text = to_text(&nav);
+    match output {
+        Some(path) => fs::write(path, text).map_err(|e| CliError::OutputError(String::from(path), e))?,
+        None => print!("{}", text),
+    }
+    Ok(nav.all_nodes().len())
+}
+
+/// Replace the outline of `filename` with the one described in the file `outline`, in `format`
+/// or, if not given, in the format its extension suggests. With `strict`, the outline is written
+/// in strict mode.
+pub fn import_outline(filename: &str, outline: &str, format: Option<ImportFormat>, strict: bool) -> Result<(), CliError> {
+    let format = format
+        .or_else(|| ImportFormat::from_path(outline))
+        .ok_or_else(|| CliError::UnknownFormatError(String::from(outline)))?;
+    let input = fs::read_to_string(outline)
+        .map_err(|e| CliError::InputError(String::from(outline), e))?;
+    let nav = import(&input, format).map_err(CliError::ImportError)?;
+    write_outline(filename, &nav, strict)
+}
+
+/// Set the outline of `filename` to `nav`, in strict mode if `strict` is set or the configuration
+/// asks for it.
+fn write_outline(filename: &str, nav: &Nav, strict: bool) -> Result<(), CliError> {
+    let config = Config::load(filename).map_err(CliError::ConfigError)?;
+    if !strict && !config.strict.unwrap_or(false) {
+        return embed_nav_in_djvu_file(filename, nav).map_err(CliError::NavReadingError);
+    }
+    let pages = get_pages_from_djvu(filename).map_err(CliError::NavReadingError)?;
+    let resolvers = LinkResolvers::with_rewrites(&config.links.url_rewrites.clone().unwrap_or_default());
+    strict::check(nav, Some(&pages), &config, &resolvers)
+        .and_then(|()| strict::write(filename, nav, || Ok(())))
+        .map(|_| ())
+        .map_err(CliError::StrictError)
+}
+
+/// Print the output of the jq filter `expression` run on the outline of `filename`, one value per
+/// line. Strings are printed without quotes if `raw` is set.
+pub fn query_outline(filename: &str, expression: &str, raw: bool) -> Result<(), CliError> {
+    let nav = get_nav_from_djvu(filename).map_err(CliError::NavReadingError)?;
+    for value in query(&nav, expression).map_err(CliError::QueryError)? {
+        match value {
+            serde_json::Value::String(s) if raw => println!("{}", s),
+            value => println!("{}", value),
+        }
+    }
+    Ok(())
+}
+
+/// Whether the file name `name` matches `pattern`, where `*` stands for any characters and `?`
+/// for any one character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    // Position in the pattern after the last `*`, and in the name where it started matching.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            },
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            },
+            _ => match star {
+                // Let the last `*` take one more character.
+                Some((after_star, start)) => {
+                    star = Some((after_star, start + 1));
+                    p = after_star;
+                    n = start + 1;
+                },
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Return the files named by `patterns`, where a pattern with `*` or `?` in its file name is
+/// replaced by the matching files of its directory, for shells that do not expand them.
+pub fn expand_globs(patterns: &[String]) -> Result<Vec<String>, CliError> {
+    let mut filenames = Vec::new();
+    for pattern in patterns {
+        let path = Path::new(pattern);
+        let Some(name) = path.file_name().and_then(|name| name.to_str()).filter(|name| name.contains(['*', '?'])) else {
+            filenames.push(pattern.clone());
+            continue;
+        };
+        let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+        let entries = fs::read_dir(directory.unwrap_or(Path::new(".")))
+            .map_err(|e| CliError::InputError(pattern.clone(), e))?;
+        let mut matches: Vec<String> = entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|entry| !entry.starts_with('.') && glob_match(name, entry))
+            .map(|entry| match directory {
+                Some(directory) => directory.join(entry).display().to_string(),
+                None => entry,
+            })
+            .collect();
+        if matches.is_empty() {
+            return Err(CliError::NoMatch(pattern.clone()));
+        }
+        matches.sort();
+        filenames.extend(matches);
+    }
+    Ok(filenames)
+}
+
+/// Run `operation` on each of `filenames`, printing one line per file with the summary it returns
+/// or the reason it failed, and go on with the next file after a failure.
+pub fn batch(filenames: &[String], mut operation: impl FnMut(&str) -> Result<String, CliError>) -> Result<(), CliError> {
+    let mut failed = 0;
+    for filename in filenames {
+        match operation(filename) {
+            Ok(summary) => println!("{}: {}", filename, summary),
+            Err(e) => {
+                failed += 1;
+                println!("{}: {}", filename, e);
+            },
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        failed => Err(CliError::BatchError(failed, filenames.len())),
+    }
+}
+
+/// Add `delta` to the page numbers of the outline of `filename` pointing to page `from` or later,
+/// and return the number of entries changed. With `dry_run`, the file is left as it is, and with
+/// `strict`, it is written in strict mode.
+pub fn offset_outline(filename: &str, delta: i32, from: u32, dry_run: bool, strict: bool) -> Result<usize, CliError> {
+    let mut nav = get_nav_from_djvu(filename).map_err(CliError::NavReadingError)?;
+    let changed = shift_pages(&mut nav, &[], delta, from);
+    if !dry_run && changed > 0 {
+        write_outline(filename, &nav, strict)?;
+    }
+    Ok(changed)
+}
+
+/// Check the outline of `filename` against the policy of the configuration and the pages of the
+/// document, and return the number of entries.
+pub fn check_outline(filename: &str) -> Result<usize, CliError> {
+    let config = Config::load(filename).map_err(CliError::ConfigError)?;
+    let nav = get_nav_from_djvu(filename).map_err(CliError::NavReadingError)?;
+    let pages = get_pages_from_djvu(filename).map_err(CliError::NavReadingError)?;
+    let resolvers = LinkResolvers::with_rewrites(&config.links.url_rewrites.unwrap_or_default());
+    match lint(&nav, &config.policy, Some(&pages), &resolvers).len() {
+        0 => Ok(nav.all_nodes().len()),
+        count => Err(CliError::PolicyViolations(count)),
+    }
+}
+
+/// Check that the targets of the outline of `filename` follow its pages, and return the problems
+/// found along with the outline, to name the entries.
+pub fn lint_outline(filename: &str) -> Result<(Nav, Vec<Finding>), CliError> {
+    let config = Config::load(filename).map_err(CliError::ConfigError)?;
+    let nav = get_nav_from_djvu(filename).map_err(CliError::NavReadingError)?;
+    let pages = get_pages_from_djvu(filename).map_err(CliError::NavReadingError)?;
+    let findings = validate(&nav, Some(&pages), config.policy.max_gap);
+    Ok((nav, findings))
+}
+
+/// Set the titles of the pages of `filename` to the page numbers printed on them, as listed in the
+/// scan data file `scandata`. Return the number of pages labelled, and the offset between printed
+/// and physical page numbers.
+pub fn import_page_labels(filename: &str, scandata: &str) -> Result<(usize, Option<i32>), CliError> {
+    let xml = fs::read_to_string(scandata)
+        .map_err(|e| CliError::InputError(String::from(scandata), e))?;
+    let labels = parse_scandata(&xml).map_err(CliError::ScandataError)?;
+    let pages = get_pages_from_djvu(filename).map_err(CliError::NavReadingError)?;
+    if labels.len() != pages.len() {
+        return Err(CliError::PageCountMismatch(labels.len(), pages.len()));
+    }
+    set_page_titles(filename, &labels).map_err(CliError::NavReadingError)?;
+    Ok((labels.iter().flatten().count(), label_offset(&labels)))
+}
+
+/// Print the printed page number of each page of the DjVu file of the Internet Archive item
+/// `identifier`, and the offset to give to `nav_edit offset` for an outline made from them.
+pub fn archive_pages(identifier: &str) -> Result<(), CliError> {
+    let item = archive::fetch_item(&SystemTools, identifier).map_err(CliError::ArchiveError)?;
+    let labels = archive::fetch_page_labels(&SystemTools, &item).map_err(CliError::ArchiveError)?;
+    for (i, label) in labels.iter().enumerate() {
+        println!("{}\t{}", i + 1, label.as_deref().unwrap_or(""));
+    }
+    match label_offset(&labels) {
+        Some(offset) => eprintln!(
+            "Page 1 as printed is page {} of the document: use `nav_edit offset {} <files>` on an outline made of printed page numbers",
+            1 + offset, offset,
+        ),
+        None => eprintln!("No page is numbered in arabic numerals"),
+    }
+    Ok(())
+}
+
+/// Download the DjVu file of the Internet Archive item `identifier` to `output`, or to the file
+/// named after the item in the current directory. Return the path it was written to.
+pub fn archive_download(identifier: &str, output: Option<&str>) -> Result<String, CliError> {
+    let item = archive::fetch_item(&SystemTools, identifier).map_err(CliError::ArchiveError)?;
+    let output = match output {
+        Some(output) => String::from(output),
+        None => item.file("DjVu")
+            .and_then(archive::local_name)
+            .map_err(CliError::ArchiveError)?,
+    };
+    archive::download(&SystemTools, &item, Path::new(&output)).map_err(CliError::ArchiveError)?;
+    Ok(output)
+}
+
+/// Upload `filename` in place of the DjVu file of the Internet Archive item `identifier`, with the
+/// keys of the `[archive]` section of the configuration.
+pub fn archive_upload(identifier: &str, filename: &str) -> Result<(), CliError> {
+    let config = Config::load(filename).map_err(CliError::ConfigError)?;
+    let (Some(access_key), Some(secret_key)) = (&config.archive.access_key, &config.archive.secret_key) else {
+        return Err(CliError::ArchiveError(ArchiveError::MissingKeys));
+    };
+    let item = archive::fetch_item(&SystemTools, identifier).map_err(CliError::ArchiveError)?;
+    archive::upload(&SystemTools, &item, Path::new(filename), access_key, secret_key)
+        .map_err(CliError::ArchiveError)
+}
+
+/// Check that the installed djvulibre tools can read and write outlines, printing the outcome of
+/// each step.
+pub fn selftest() -> Result<(), CliError> {
+    let version = check_djvused_with(&SystemTools).map_err(CliError::NavReadingError)?;
+    println!("Using djvused from DjVuLibre {}", version);
+    let checks = selftest::run(&SystemTools);
+    for check in &checks {
+        println!("{}", check);
+    }
+    match checks.iter().filter(|check| check.result.is_err()).count() {
+        0 => Ok(()),
+        failed => Err(CliError::SelfTestError(failed)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_patterns() {
+        assert!(glob_match("*.djvu", "vol 12.djvu"));
+        assert!(glob_match("vol?.djvu", "vol3.djvu"));
+        assert!(glob_match("*a*a", "banana"));
+        assert!(!glob_match("vol?.djvu", "vol12.djvu"));
+        assert!(!glob_match("*.djvu", "book.djvu.bak"));
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt::Display;
+
+use serde::Deserialize;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::verify::Verdict;
+
+/// What a column of the tree shows about each entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    Title,
+    /// The page the entry points to, or its link if it does not point to a page.
+    Page,
+    /// The title of the target page, which viewers show as its label.
+    Label,
+    /// Whether the title was found on its page by `:verify`.
+    Verified,
+    /// Whether the entry has a note, for layouts without the badges.
+    Note,
+    /// Marks for what is known about the entry besides its title and link, see `Badges`.
+    Badges,
+}
+
+/// A column of the tree, written `<kind>` or `<kind>:<width>` such as `page:5`. Columns without a
+/// width take the width of their content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Column {
+    pub kind: ColumnKind,
+    pub width: Option<usize>,
+}
+
+/// The layout of the tree when none is configured: the badges and the title.
+pub const DEFAULT_COLUMNS: [Column; 2] = [
+    Column { kind: ColumnKind::Badges, width: None },
+    Column { kind: ColumnKind::Title, width: None },
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidColumnError(String);
+
+impl Display for InvalidColumnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid column: \"{}\" (expected title, page, label, verified, note or badges, with an optional width such as \"page:5\")",
+            self.0,
+        )
+    }
+}
+
+impl std::error::Error for InvalidColumnError {}
+
+impl TryFrom<String> for Column {
+    type Error = InvalidColumnError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let (kind, width) = match input.split_once(':') {
+            Some((kind, width)) => (kind, Some(width)),
+            None => (input.as_str(), None),
+        };
+        let kind = match kind {
+            "title" => ColumnKind::Title,
+            "page" => ColumnKind::Page,
+            "label" => ColumnKind::Label,
+            "verified" => ColumnKind::Verified,
+            "note" => ColumnKind::Note,
+            "badges" => ColumnKind::Badges,
+            _ => return Err(InvalidColumnError(input)),
+        };
+        let width = match width.map(str::parse) {
+            Some(Ok(width)) if width > 0 => Some(width),
+            Some(_) => return Err(InvalidColumnError(input)),
+            None => None,
+        };
+        Ok(Self { kind, width })
+    }
+}
+
+/// Parse a layout written as columns separated by spaces, such as `page:5 title`.
+pub fn parse_columns(input: &str) -> Result<Vec<Column>, InvalidColumnError> {
+    input.split_whitespace().map(|column| Column::try_from(String::from(column))).collect()
+}
+
+/// Marks shown in the badge column, each in its own place so that they line up from a row to
+/// the next.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Badges {
+    /// The entry breaks a rule of the policy.
+    pub warning: bool,
+    pub note: bool,
+    pub pinned: bool,
+    /// The entry changed since the file was opened.
+    pub modified: bool,
+}
+
+impl Badges {
+    pub fn text(&self) -> String {
+        [(self.warning, '!'), (self.note, '✎'), (self.pinned, '⚑'), (self.modified, '~')]
+            .into_iter()
+            .map(|(shown, mark)| if shown { mark } else { ' ' })
+            .collect()
+    }
+}
+
+/// What the columns can show about an entry.
+pub struct Row<'a> {
+    pub title: &'a str,
+    pub target: Option<String>,
+    pub label: Option<&'a str>,
+    pub verdict: Option<Verdict>,
+    pub badges: Badges,
+}
+
+/// Return `text` cut or padded with spaces to `width` columns of the terminal.
+fn fit(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return format!("{}{}", text, " ".repeat(width - text.width()));
+    }
+    let mut fitted = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let c_width = c.width().unwrap_or(0);
+        if used + c_width + 1 > width {
+            break;
+        }
+        fitted.push(c);
+        used += c_width;
+    }
+    fitted.push('…');
+    fitted.push_str(&" ".repeat(width - used - 1));
+    fitted
+}
+
+/// Return the text of the row of the tree showing `row` in `columns`, separated by spaces.
+pub fn row_text(columns: &[Column], row: Row) -> String {
+    let badges = row.badges.text();
+    let cells: Vec<String> = columns.iter()
+        .map(|column| {
+            let content = match column.kind {
+                ColumnKind::Title => row.title,
+                ColumnKind::Badges => &badges,
+                ColumnKind::Page => row.target.as_deref().unwrap_or_default(),
+                ColumnKind::Label => row.label.unwrap_or_default(),
+                ColumnKind::Verified => match row.verdict {
+                    Some(Verdict::Found) => "✓",
+                    Some(Verdict::Missing) => "✗",
+                    Some(Verdict::NoText) => "?",
+                    None => "",
+                },
+                ColumnKind::Note => if row.badges.note { "✎" } else { "" },
+            };
+            match column.width {
+                Some(width) => fit(content, width),
+                None => String::from(content),
+            }
+        })
+        .collect();
+    cells.join(" ")
+}
+
+/// Return the reference to page `page` shown at the right of the rows of the tree, such as
+/// `p. 23 (xxiii)`, with its label if it has one other than its number.
+pub fn page_reference(page: u32, label: Option<&str>) -> String {
+    match label {
+        Some(label) if label != page.to_string() => format!("p. {} ({})", page, label),
+        _ => format!("p. {}", page),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_are_fitted() {
+        let columns = parse_columns("page:4 verified:1 note:1 title:8 label badges").unwrap();
+        let row = Row {
+            title: "Chapter One",
+            target: Some(String::from("12")),
+            label: Some("iv"),
+            verdict: Some(Verdict::Found),
+            badges: Badges { warning: true, note: true, modified: true, ..Badges::default() },
+        };
+        assert_eq!(row_text(&columns, row), "12   ✓ ✎ Chapter… iv !✎ ~");
+        assert_eq!(parse_columns("title:0"), Err(InvalidColumnError(String::from("title:0"))));
+        assert!(parse_columns("notes").is_err());
+
+        assert_eq!(page_reference(23, Some("xxiii")), "p. 23 (xxiii)");
+        assert_eq!(page_reference(23, Some("23")), "p. 23");
+    }
+}
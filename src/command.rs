@@ -0,0 +1,337 @@
+use std::{fmt::Display, ops::RangeInclusive};
+
+use chrono::NaiveDate;
+
+use crate::{
+    generate::{Frequency, Spacing},
+    series::Numbering,
+    columns::{parse_columns, Column, InvalidColumnError},
+    page_expr::PageExpr,
+    link::PageLinkKind,
+};
+
+/// A command typed on the command line, after `:`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Skeleton(Spacing),
+    /// One entry per issue of a periodical: date of the first issue, frequency, and number of
+    /// pages per issue.
+    Issues(NaiveDate, Frequency, u32),
+    /// One entry per line of a CSV list of issues.
+    IssuesCsv(String),
+    /// Build a tree out of the back-of-book index found on a range of pages, and add it to the
+    /// outline or, if a file is given, save it there.
+    Index(RangeInclusive<u32>, Option<String>),
+    /// Propose an outline made of the headings found in the text of the pages, to add to the
+    /// outline.
+    Generate,
+    /// Review the entries of an outline file, in any format `import` reads, to add them to the
+    /// outline or replace it.
+    Import(String),
+    /// Add an offset to the page numbers from a given page onwards.
+    Offset(i32, PageExpr),
+    /// Add an offset to the page numbers of the selected node and its descendants.
+    OffsetSubtree(i32),
+    /// Make the links to pages of the whole outline, or of the selected subtrees if set, point to
+    /// their pages by number or by name.
+    ConvertLinks(PageLinkKind, bool),
+    /// Spread the pages of the siblings of the selected node that point to no page yet between
+    /// the known pages around them.
+    Interpolate,
+    /// Look for the page where the outline stops being in page order, to use as the start of an
+    /// offset.
+    SuggestOffset,
+    Write,
+    /// Write even if some entries point past the last page.
+    ForceWrite,
+    /// Quit, asking for confirmation if there are unsaved changes.
+    Quit,
+    /// Quit, discarding the unsaved changes.
+    ForceQuit,
+    WriteQuit,
+    /// List the titles sharing a normalized form, to spot inconsistent spellings.
+    Titles,
+    /// Join back the words of the titles broken by hyphenation, after a preview.
+    Dehyphenate,
+    /// List the pages several entries point to.
+    Targets,
+    /// List the entries changed since the file was opened.
+    Changes,
+    /// List the subtrees cut during the session, to restore them.
+    Trash,
+    /// Remove the whole outline, after confirmation.
+    Clear,
+    /// Make the entries deeper than a level read-only, or all entries editable again.
+    Lock(Option<usize>),
+    /// Look for the titles in the text of their pages, in the background.
+    Verify,
+    /// List the entries pointing past the last page.
+    Check,
+    /// List the entries out of page order, the duplicate entries and the large gaps.
+    Lint,
+    /// Edit the whole outline as indented text in the external editor.
+    Text,
+    /// Read the text of the pages from an hOCR or ALTO file instead of the text layer, mapping
+    /// its images to pages with a scan data file if given.
+    Ocr(String, Option<String>),
+    /// Replace the outline with one proposed from the outline of another volume of the series.
+    Series(String, Numbering),
+    /// Show these columns in the rows of the tree, or the configured ones if empty.
+    Columns(Vec<Column>),
+    /// Attach a note to the selected entry, remove it if `None`, or show it if empty.
+    Note(Option<String>),
+    /// Pin the selected entry, or unpin it if it is pinned.
+    Pin,
+    /// Show the pages in the page column by their titles, or by their numbers again.
+    PageLabels,
+    /// Name a page, the one of the selected entry if not given, to type the name in place of it.
+    Anchor(String, Option<PageExpr>),
+    /// Forget the name of a page.
+    Unanchor(String),
+    /// List the named pages and the entries pointing to them.
+    Anchors,
+    /// Select the entry covering a page, given by number or by name.
+    Goto(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandError {
+    UnknownCommand(String),
+    InvalidArguments(&'static str),
+    InvalidColumn(InvalidColumnError),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownCommand(name) => write!(f, "unknown command: {}", name),
+            Self::InvalidArguments(usage) => write!(f, "usage: {}", usage),
+            Self::InvalidColumn(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+const SKELETON_USAGE: &str = "skeleton every <pages> | skeleton count <entries>";
+const ISSUES_USAGE: &str = "issues <YYYY-MM-DD> <daily|weekly|monthly|...|<n>d|<n>m> <pages per issue>";
+const ISSUES_CSV_USAGE: &str = "issues-csv <file>";
+const OFFSET_USAGE: &str = "offset <pages to add> [<from page, such as 12 or next+1> | subtree] | offset suggest";
+const LINK_NUMBERS_USAGE: &str = "link-numbers [subtree]";
+const LINK_IDS_USAGE: &str = "link-ids [subtree]";
+const LOCK_USAGE: &str = "lock <depth> | unlock";
+const ANCHOR_USAGE: &str = "anchor <name> [<page, such as 12 or next-1>]";
+const UNANCHOR_USAGE: &str = "unanchor <name>";
+const GOTO_USAGE: &str = "goto <page, such as 153, last-2 or the name of a page>";
+const OCR_USAGE: &str = "ocr <hOCR or ALTO file> [<scandata.xml>]";
+const SERIES_USAGE: &str = "series <other volume> [continue]";
+const IMPORT_USAGE: &str = "import <outline file (.json, .yaml or .txt)>";
+const INDEX_USAGE: &str = "index <first page>-<last page> [<output file>]";
+
+/// Parse a page range such as `300-312`, or a single page.
+fn parse_page_range(input: &str) -> Option<RangeInclusive<u32>> {
+    let (first, last) = input.split_once('-').unwrap_or((input, input));
+    let (first, last) = (first.parse().ok()?, last.parse().ok()?);
+    (0 < first && first <= last).then_some(first..=last)
+}
+
+pub fn parse_command(input: &str) -> Result<Command, CommandError> {
+    let mut words = input.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let args: Vec<_> = words.collect();
+    match name {
+        "skeleton" => {
+            let spacing = match args.as_slice() {
+                ["every", n] => n.parse().ok().filter(|n| *n > 0).map(Spacing::Every),
+                ["count", n] => n.parse().ok().filter(|n| *n > 0).map(Spacing::Count),
+                _ => None,
+            };
+            spacing
+                .map(Command::Skeleton)
+                .ok_or(CommandError::InvalidArguments(SKELETON_USAGE))
+        },
+        "issues" => {
+            let issues = match args.as_slice() {
+                [start, frequency, pages] => NaiveDate::parse_from_str(start, "%Y-%m-%d").ok()
+                    .zip(Frequency::from_string(frequency))
+                    .zip(pages.parse().ok().filter(|n| *n > 0))
+                    .map(|((start, frequency), pages)| Command::Issues(start, frequency, pages)),
+                _ => None,
+            };
+            issues.ok_or(CommandError::InvalidArguments(ISSUES_USAGE))
+        },
+        "issues-csv" => match args.as_slice() {
+            [path] => Ok(Command::IssuesCsv(String::from(*path))),
+            _ => Err(CommandError::InvalidArguments(ISSUES_CSV_USAGE)),
+        },
+        "index" => {
+            let index = match args.as_slice() {
+                [range] => parse_page_range(range).map(|range| Command::Index(range, None)),
+                [range, file] => parse_page_range(range)
+                    .map(|range| Command::Index(range, Some(String::from(*file)))),
+                _ => None,
+            };
+            index.ok_or(CommandError::InvalidArguments(INDEX_USAGE))
+        },
+        "generate" if args.is_empty() => Ok(Command::Generate),
+        "import" => match args.as_slice() {
+            [path] => Ok(Command::Import(String::from(*path))),
+            _ => Err(CommandError::InvalidArguments(IMPORT_USAGE)),
+        },
+        "offset" => {
+            let offset = match args.as_slice() {
+                ["suggest"] => Some(Command::SuggestOffset),
+                [delta] => delta.parse().ok().map(|delta| Command::Offset(delta, PageExpr::page(0))),
+                [delta, "subtree"] => delta.parse().ok().map(Command::OffsetSubtree),
+                [delta, from] => delta.parse().ok()
+                    .zip(PageExpr::parse(from))
+                    .map(|(delta, from)| Command::Offset(delta, from)),
+                _ => None,
+            };
+            offset.ok_or(CommandError::InvalidArguments(OFFSET_USAGE))
+        },
+        "link-numbers" | "link-ids" => {
+            let (kind, usage) = match name {
+                "link-numbers" => (PageLinkKind::Number, LINK_NUMBERS_USAGE),
+                _ => (PageLinkKind::Id, LINK_IDS_USAGE),
+            };
+            match args.as_slice() {
+                [] => Ok(Command::ConvertLinks(kind, false)),
+                ["subtree"] => Ok(Command::ConvertLinks(kind, true)),
+                _ => Err(CommandError::InvalidArguments(usage)),
+            }
+        },
+        "interpolate" if args.is_empty() => Ok(Command::Interpolate),
+        "w" | "write" if args.is_empty() => Ok(Command::Write),
+        "w!" | "write!" if args.is_empty() => Ok(Command::ForceWrite),
+        "q" | "quit" if args.is_empty() => Ok(Command::Quit),
+        "q!" | "quit!" if args.is_empty() => Ok(Command::ForceQuit),
+        "wq" | "x" if args.is_empty() => Ok(Command::WriteQuit),
+        "titles" => Ok(Command::Titles),
+        "dehyphenate" if args.is_empty() => Ok(Command::Dehyphenate),
+        "targets" => Ok(Command::Targets),
+        "changes" if args.is_empty() => Ok(Command::Changes),
+        "trash" if args.is_empty() => Ok(Command::Trash),
+        "clear" if args.is_empty() => Ok(Command::Clear),
+        "lock" => match args.as_slice() {
+            [depth] => depth.parse().map(|depth| Command::Lock(Some(depth)))
+                .map_err(|_| CommandError::InvalidArguments(LOCK_USAGE)),
+            _ => Err(CommandError::InvalidArguments(LOCK_USAGE)),
+        },
+        "unlock" => Ok(Command::Lock(None)),
+        "verify" if args.is_empty() => Ok(Command::Verify),
+        "check" if args.is_empty() => Ok(Command::Check),
+        "lint" if args.is_empty() => Ok(Command::Lint),
+        "text" if args.is_empty() => Ok(Command::Text),
+        "note" => Ok(Command::Note(Some(args.join(" ")))),
+        "unnote" if args.is_empty() => Ok(Command::Note(None)),
+        "pin" if args.is_empty() => Ok(Command::Pin),
+        "page-labels" if args.is_empty() => Ok(Command::PageLabels),
+        "anchor" => match args.as_slice() {
+            [name] => Ok(Command::Anchor(String::from(*name), None)),
+            [name, page] => PageExpr::parse(page)
+                .map(|page| Command::Anchor(String::from(*name), Some(page)))
+                .ok_or(CommandError::InvalidArguments(ANCHOR_USAGE)),
+            _ => Err(CommandError::InvalidArguments(ANCHOR_USAGE)),
+        },
+        "unanchor" => match args.as_slice() {
+            [name] => Ok(Command::Unanchor(String::from(*name))),
+            _ => Err(CommandError::InvalidArguments(UNANCHOR_USAGE)),
+        },
+        "anchors" if args.is_empty() => Ok(Command::Anchors),
+        "goto" => match args.as_slice() {
+            [page] => Ok(Command::Goto(String::from(*page))),
+            _ => Err(CommandError::InvalidArguments(GOTO_USAGE)),
+        },
+        "columns" => parse_columns(&args.join(" "))
+            .map(Command::Columns)
+            .map_err(CommandError::InvalidColumn),
+        "series" => match args.as_slice() {
+            [model] => Ok(Command::Series(String::from(*model), Numbering::Restart)),
+            [model, "continue"] => Ok(Command::Series(String::from(*model), Numbering::Continue)),
+            _ => Err(CommandError::InvalidArguments(SERIES_USAGE)),
+        },
+        "ocr" => match args.as_slice() {
+            [ocr] => Ok(Command::Ocr(String::from(*ocr), None)),
+            [ocr, scandata] => Ok(Command::Ocr(String::from(*ocr), Some(String::from(*scandata)))),
+            _ => Err(CommandError::InvalidArguments(OCR_USAGE)),
+        },
+        _ => Err(CommandError::UnknownCommand(String::from(name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skeleton_command() {
+        assert_eq!(parse_command("skeleton every 25"), Ok(Command::Skeleton(Spacing::Every(25))));
+        assert_eq!(parse_command(" skeleton  count 4 "), Ok(Command::Skeleton(Spacing::Count(4))));
+        assert_eq!(
+            parse_command("skeleton every 0"),
+            Err(CommandError::InvalidArguments(SKELETON_USAGE))
+        );
+    }
+
+    #[test]
+    fn issues_command() {
+        assert_eq!(
+            parse_command("issues 1923-03-14 weekly 8"),
+            Ok(Command::Issues(NaiveDate::from_ymd_opt(1923, 3, 14).unwrap(), Frequency::Days(7), 8))
+        );
+        assert!(parse_command("issues 1923-02-30 weekly 8").is_err());
+    }
+
+    #[test]
+    fn anchor_command() {
+        assert_eq!(parse_command("anchor frontispiece"), Ok(Command::Anchor(String::from("frontispiece"), None)));
+        assert_eq!(
+            parse_command("anchor plate-XII next+1"),
+            Ok(Command::Anchor(String::from("plate-XII"), PageExpr::parse("next+1"))),
+        );
+        assert_eq!(parse_command("anchor plate XII"), Err(CommandError::InvalidArguments(ANCHOR_USAGE)));
+        assert_eq!(parse_command("goto 153"), Ok(Command::Goto(String::from("153"))));
+        assert_eq!(parse_command("goto"), Err(CommandError::InvalidArguments(GOTO_USAGE)));
+    }
+
+    #[test]
+    fn offset_command() {
+        assert_eq!(parse_command("offset +8 200"), Ok(Command::Offset(8, PageExpr::page(200))));
+        assert_eq!(parse_command("offset -2"), Ok(Command::Offset(-2, PageExpr::page(0))));
+        assert_eq!(parse_command("offset -2 next"), Ok(Command::Offset(-2, PageExpr::parse("next").unwrap())));
+        assert_eq!(parse_command("offset +12 subtree"), Ok(Command::OffsetSubtree(12)));
+        assert_eq!(parse_command("offset suggest"), Ok(Command::SuggestOffset));
+        assert!(parse_command("offset 8 page").is_err());
+    }
+
+    #[test]
+    fn link_conversion_commands() {
+        assert_eq!(parse_command("link-ids"), Ok(Command::ConvertLinks(PageLinkKind::Id, false)));
+        assert_eq!(parse_command("link-numbers subtree"), Ok(Command::ConvertLinks(PageLinkKind::Number, true)));
+        assert!(parse_command("link-ids all").is_err());
+    }
+
+    #[test]
+    fn write_and_quit_commands() {
+        assert_eq!(parse_command("q!"), Ok(Command::ForceQuit));
+        assert_eq!(parse_command("wq"), Ok(Command::WriteQuit));
+        assert_eq!(parse_command("w!"), Ok(Command::ForceWrite));
+        assert!(parse_command("w other.djvu").is_err());
+    }
+
+    #[test]
+    fn series_command() {
+        assert_eq!(parse_command("series vol1.djvu"), Ok(Command::Series(String::from("vol1.djvu"), Numbering::Restart)));
+        assert_eq!(parse_command("series vol1.djvu continue"), Ok(Command::Series(String::from("vol1.djvu"), Numbering::Continue)));
+        assert!(parse_command("series").is_err());
+    }
+
+    #[test]
+    fn note_commands() {
+        assert_eq!(parse_command("note check  the spelling"), Ok(Command::Note(Some(String::from("check the spelling")))));
+        assert_eq!(parse_command("note"), Ok(Command::Note(Some(String::new()))));
+        assert_eq!(parse_command("unnote"), Ok(Command::Note(None)));
+        assert!(parse_command("pin 3").is_err());
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    APP_NAME,
+    casing::{CaseStyle, CasingLanguage},
+    columns::Column,
+    heading::HeadingPattern,
+    keymap::Action,
+    lint::ViewerProfile,
+    viewer::FALLBACK_VIEWERS,
+};
+#[cfg(feature = "tui")]
+use crate::keymap::KeySequence;
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+/// Name of the project configuration file, looked up in the directory of the edited file.
+const PROJECT_CONFIG_FILE_NAME: &str = "nav_edit.toml";
+/// Editors tried, in order, when neither the configuration nor the environment names one.
+const FALLBACK_EDITORS: [&str; 2] = ["nano", "vi"];
+
+#[derive(Debug)]
+pub enum ConfigError {
+    IOError(PathBuf, io::Error),
+    ParsingError(PathBuf, toml::de::Error),
+    XDGSpecificError(xdg::BaseDirectoriesError),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(path, e) => write!(f, "cannot read {}: {}", path.display(), e),
+            Self::ParsingError(path, e) => write!(f, "invalid configuration in {}: {}", path.display(), e),
+            Self::XDGSpecificError(e) => write!(f, "cannot find the configuration directory: {}", e),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Rules the outline is expected to follow, because some viewers truncate or misrender outlines
+/// that break them.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    pub max_title_length: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub forbidden_characters: Option<String>,
+    /// Viewers the outline should display correctly in.
+    pub viewers: Option<Vec<ViewerProfile>>,
+    pub casing: Option<CaseStyle>,
+    /// Language of the titles, for the casing rules. English by default.
+    pub casing_language: Option<CasingLanguage>,
+    /// Words left in lower case by title case, in addition to those of the language.
+    pub casing_minor_words: Option<Vec<String>>,
+    /// Pages between an entry and the next one above which `:lint` reports a gap. By default,
+    /// gaps much longer than usual in the outline are reported.
+    pub max_gap: Option<u32>,
+}
+
+impl PolicyConfig {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            max_title_length: other.max_title_length.or(self.max_title_length),
+            max_depth: other.max_depth.or(self.max_depth),
+            forbidden_characters: other.forbidden_characters.or(self.forbidden_characters),
+            viewers: other.viewers.or(self.viewers),
+            casing: other.casing.or(self.casing),
+            casing_language: other.casing_language.or(self.casing_language),
+            casing_minor_words: other.casing_minor_words.or(self.casing_minor_words),
+            max_gap: other.max_gap.or(self.max_gap),
+        }
+    }
+}
+
+/// Settings of the importers turning external lists (indexes, tables of contents) into bookmarks.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImportConfig {
+    /// Turn an entry referring to several pages into a parent with one child per page, instead
+    /// of keeping the first page only.
+    pub expand_multiple_targets: Option<bool>,
+}
+
+impl ImportConfig {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            expand_multiple_targets: other.expand_multiple_targets.or(self.expand_multiple_targets),
+        }
+    }
+}
+
+/// Settings of `generate`, proposing an outline from the text of the pages.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerateConfig {
+    /// Patterns of the headings, e.g. `[{ pattern = "^Lecture [0-9]+", level = 1 }]`, replacing
+    /// the default ones.
+    pub headings: Option<Vec<HeadingPattern>>,
+}
+
+impl GenerateConfig {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            headings: other.headings.or(self.headings),
+        }
+    }
+}
+
+/// How links that are neither page numbers, page names nor URLs are interpreted.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkConfig {
+    /// Prefixes turning links into URLs, e.g. `"doi:" = "https://doi.org/"`.
+    pub url_rewrites: Option<BTreeMap<String, String>>,
+}
+
+impl LinkConfig {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            url_rewrites: other.url_rewrites.or(self.url_rewrites),
+        }
+    }
+}
+
+/// Account used to upload files to the Internet Archive, with the keys from
+/// <https://archive.org/account/s3.php>.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveConfig {
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+}
+
+impl ArchiveConfig {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            access_key: other.access_key.or(self.access_key),
+            secret_key: other.secret_key.or(self.secret_key),
+        }
+    }
+}
+
+/// How long the nodes opened to show a search match stay open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchExpansion {
+    /// Until the user closes them.
+    Permanent,
+    /// Until the next match is shown, unless the user opened or closed a node meanwhile.
+    Temporary,
+}
+
+/// How the user is told that an action cannot be done, such as moving past the last entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Feedback {
+    /// Say why in the status line.
+    Message,
+    /// Ring the terminal bell.
+    Bell,
+    /// Flash the screen.
+    Flash,
+    None,
+}
+
+/// Feedback given for actions that cannot be done, by action. Errors, such as a failed write, are
+/// explained in the status line whatever the feedback.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeedbackConfig {
+    /// Feedback for the actions not listed in `actions`. `message` by default.
+    pub default: Option<Feedback>,
+    /// Feedback by action, e.g. `{ move-down = "bell", write = "flash" }`.
+    pub actions: HashMap<Action, Feedback>,
+}
+
+impl FeedbackConfig {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            default: other.default.or(self.default),
+            actions: self.actions.into_iter().chain(other.actions).collect(),
+        }
+    }
+
+    /// Return the feedback for `action`.
+    pub fn get(&self, action: Action) -> Feedback {
+        self.actions.get(&action).copied().or(self.default).unwrap_or(Feedback::Message)
+    }
+}
+
+/// Behavior of the tree when moving around it.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NavigationConfig {
+    /// Open a closed node when moving onto it.
+    pub expand_on_select: Option<bool>,
+    pub search_expansion: Option<SearchExpansion>,
+    /// Close the descendants of a node along with it, so that they are closed when it is opened
+    /// again.
+    pub collapse_recursively: Option<bool>,
+    /// Open the nodes down to this depth when the file is loaded, so that with `1` the children
+    /// of the top level entries are shown. Everything is closed by default.
+    pub open_depth: Option<usize>,
+}
+
+impl NavigationConfig {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            expand_on_select: other.expand_on_select.or(self.expand_on_select),
+            search_expansion: other.search_expansion.or(self.search_expansion),
+            collapse_recursively: other.collapse_recursively.or(self.collapse_recursively),
+            open_depth: other.open_depth.or(self.open_depth),
+        }
+    }
+}
+
+/// User configuration. The global file (`$XDG_CONFIG_HOME/nav_edit/config.toml`) is read first,
+/// then the project file (`nav_edit.toml` next to the edited file), whose settings take
+/// precedence.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub policy: PolicyConfig,
+    pub import: ImportConfig,
+    pub generate: GenerateConfig,
+    pub navigation: NavigationConfig,
+    pub links: LinkConfig,
+    pub archive: ArchiveConfig,
+    pub feedback: FeedbackConfig,
+    /// Command used to edit text, possibly with arguments, e.g. `"code --wait"`.
+    pub editor: Option<String>,
+    /// Command opening the document at a page, where `{page}` and `{file}` are replaced, e.g.
+    /// `"zathura --page={page} {file}"`.
+    pub viewer: Option<String>,
+    /// Whether the viewer runs in the terminal, so that the interface has to make room for it
+    /// until it is closed.
+    pub viewer_in_terminal: Option<bool>,
+    /// Columns of the rows of the tree, in order, such as `["page:5", "title"]`. The badges and
+    /// the title by default.
+    pub columns: Option<Vec<Column>>,
+    /// Number of steps that can be undone, the oldest ones being forgotten first. 1000 by
+    /// default.
+    pub history_limit: Option<usize>,
+    /// Minutes without input after which the rendered pages and the OCR text are released, to be
+    /// read again when needed. 10 by default, and 0 never releases them.
+    pub idle_minutes: Option<u64>,
+    /// Whether to write in strict mode, as with `--strict`: only outlines that pass validation
+    /// are written, after a backup, and they are read back to compare.
+    pub strict: Option<bool>,
+    /// Whether the page column shows the titles of the pages, such as `iii`, in place of their
+    /// numbers, for the pages that have one.
+    pub page_labels: Option<bool>,
+    /// Actions bound to keys, on top of the default bindings, e.g. `"Ctrl-s" = "write"`. The
+    /// action `"none"` removes a default binding.
+    #[cfg(feature = "tui")]
+    pub keys: HashMap<KeySequence, Action>,
+    /// The keys are not read without the editor, but the configuration stays valid.
+    #[cfg(not(feature = "tui"))]
+    pub keys: HashMap<String, Action>,
+}
+
+impl Config {
+    /// Load the configuration that applies to the file with path `filename`.
+    pub fn load(filename: &str) -> Result<Self, ConfigError> {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_NAME)
+            .map_err(ConfigError::XDGSpecificError)?;
+
+        let mut config = Self::default();
+        if let Some(path) = xdg_dirs.find_config_file(CONFIG_FILE_NAME) {
+            config = config.merge(Self::from_file(&path)?);
+        }
+        let project_path = Path::new(filename)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(PROJECT_CONFIG_FILE_NAME);
+        if project_path.is_file() {
+            config = config.merge(Self::from_file(&project_path)?);
+        }
+        Ok(config)
+    }
+
+    /// Return the editor commands to try, in order: the configured one, then `$VISUAL`, then
+    /// `$EDITOR`, then common editors.
+    pub fn editors(&self) -> Vec<String> {
+        let env = |name| std::env::var(name).ok();
+        editor_candidates(self.editor.clone(), env("VISUAL"), env("EDITOR"))
+    }
+
+    /// Return the viewer commands to try, in order: the configured one, or else common viewers.
+    pub fn viewers(&self) -> Vec<String> {
+        match &self.viewer {
+            Some(viewer) => vec![viewer.clone()],
+            None => FALLBACK_VIEWERS.map(String::from).to_vec(),
+        }
+    }
+
+    fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| ConfigError::IOError(path.to_owned(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| ConfigError::ParsingError(path.to_owned(), e))
+    }
+
+    /// Return `self` with every setting present in `other` overridden.
+    fn merge(self, other: Self) -> Self {
+        Self {
+            policy: self.policy.merge(other.policy),
+            import: self.import.merge(other.import),
+            generate: self.generate.merge(other.generate),
+            navigation: self.navigation.merge(other.navigation),
+            links: self.links.merge(other.links),
+            archive: self.archive.merge(other.archive),
+            feedback: self.feedback.merge(other.feedback),
+            editor: other.editor.or(self.editor),
+            viewer: other.viewer.or(self.viewer),
+            viewer_in_terminal: other.viewer_in_terminal.or(self.viewer_in_terminal),
+            columns: other.columns.or(self.columns),
+            history_limit: other.history_limit.or(self.history_limit),
+            idle_minutes: other.idle_minutes.or(self.idle_minutes),
+            strict: other.strict.or(self.strict),
+            page_labels: other.page_labels.or(self.page_labels),
+            keys: self.keys.into_iter().chain(other.keys).collect(),
+        }
+    }
+}
+
+fn editor_candidates(configured: Option<String>, visual: Option<String>, editor: Option<String>) -> Vec<String> {
+    let mut candidates: Vec<String> = [configured, visual, editor].into_iter()
+        .flatten()
+        .filter(|command| !command.trim().is_empty())
+        .collect();
+    candidates.extend(FALLBACK_EDITORS.map(String::from));
+    let mut seen = HashSet::new();
+    candidates.retain(|command| seen.insert(command.clone()));
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_settings_take_precedence() {
+        let global: Config = toml::from_str("[policy]\nmax_title_length = 80\nmax_depth = 3").unwrap();
+        let project: Config = toml::from_str("[policy]\nmax_depth = 5").unwrap();
+
+        let config = global.merge(project);
+        assert_eq!(config.policy.max_title_length, Some(80));
+        assert_eq!(config.policy.max_depth, Some(5));
+    }
+
+    #[test]
+    fn navigation_settings() {
+        let config: Config = toml::from_str("[navigation]\nsearch_expansion = \"temporary\"").unwrap();
+        assert_eq!(config.navigation.search_expansion, Some(SearchExpansion::Temporary));
+        assert_eq!(config.navigation.expand_on_select, None);
+    }
+
+    #[test]
+    #[cfg(feature = "tui")]
+    fn project_keys_added_to_global_ones() {
+        let global: Config = toml::from_str("[keys]\n\"Ctrl-s\" = \"write\"\nx = \"cut\"").unwrap();
+        let project: Config = toml::from_str("[keys]\nx = \"none\"").unwrap();
+
+        let keys = global.merge(project).keys;
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[&KeySequence::try_from(String::from("x")).unwrap()], Action::Unbound);
+        assert!(toml::from_str::<Config>("[keys]\n\"Ctrl-Hyper-x\" = \"write\"").is_err());
+    }
+
+    #[test]
+    fn feedback_by_action() {
+        let global: Config = toml::from_str("[feedback]\ndefault = \"bell\"\nactions = { write = \"flash\" }").unwrap();
+        let project: Config = toml::from_str("[feedback]\nactions = { move-down = \"none\" }").unwrap();
+
+        let feedback = global.merge(project).feedback;
+        assert_eq!(feedback.get(Action::Write), Feedback::Flash);
+        assert_eq!(feedback.get(Action::MoveDown), Feedback::None);
+        assert_eq!(feedback.get(Action::MoveUp), Feedback::Bell);
+        assert_eq!(FeedbackConfig::default().get(Action::MoveUp), Feedback::Message);
+    }
+
+    #[test]
+    fn editor_fallback_chain() {
+        assert_eq!(
+            editor_candidates(None, Some(String::new()), Some(String::from("vim"))),
+            ["vim", "nano", "vi"]
+        );
+        assert_eq!(
+            editor_candidates(Some(String::from("code --wait")), None, Some(String::from("vi"))),
+            ["code --wait", "vi", "nano"]
+        );
+    }
+
+    #[test]
+    fn viewer_profiles_by_name() {
+        let config: Config = toml::from_str("[policy]\nviewers = [\"okular\", \"djvu.js\"]").unwrap();
+        assert_eq!(config.policy.viewers, Some(vec![ViewerProfile::Okular, ViewerProfile::DjvuJs]));
+    }
+}
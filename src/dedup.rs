@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::{
+    history::format_identifier,
+    nav::Nav,
+    report::{Report, ReportRow},
+};
+
+/// Endings removed from normalized titles, so that variants such as "Bibliography" and
+/// "Bibliographie", or "Exercise" and "Exercises", share a key. Longest first.
+const ENDINGS: [&str; 6] = ["ies", "ie", "es", "s", "y", "e"];
+
+/// Reduce `title` to a key shared by its spelling variants: lowercase letters and digits only,
+/// without diacritics, and without a plural or language-specific ending.
+pub fn normalize_title(title: &str) -> String {
+    let key: String = title.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    ENDINGS.iter()
+        .find_map(|ending| key.strip_suffix(ending).filter(|stem| stem.chars().count() >= 3))
+        .map_or(key.clone(), String::from)
+}
+
+/// Group the titles of `nav` by normalized key, keeping only the keys shared by several entries.
+/// Groups mixing several spellings come first, then the most populated ones.
+pub fn title_groups(nav: &Nav) -> Report {
+    let mut groups: HashMap<String, Vec<_>> = HashMap::new();
+    for (id, node) in nav.all_nodes() {
+        let key = normalize_title(&node.string);
+        if !key.is_empty() {
+            groups.entry(key).or_default().push((id, node));
+        }
+    }
+
+    let mut groups: Vec<_> = groups.into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(key, members)| {
+            let mut spellings: Vec<(&str, usize)> = Vec::new();
+            for (_, node) in &members {
+                match spellings.iter_mut().find(|(spelling, _)| *spelling == node.string) {
+                    Some((_, count)) => *count += 1,
+                    None => spellings.push((&node.string, 1)),
+                }
+            }
+            (key, spellings, members)
+        })
+        .collect();
+    groups.sort_by(|(key_a, spellings_a, members_a), (key_b, spellings_b, members_b)| {
+        (spellings_b.len() > 1).cmp(&(spellings_a.len() > 1))
+            .then(members_b.len().cmp(&members_a.len()))
+            .then(key_a.cmp(key_b))
+    });
+
+    let mut rows = Vec::new();
+    for (_, spellings, members) in groups {
+        let spellings: Vec<_> = spellings.iter()
+            .map(|(spelling, count)| format!("\"{}\" x{}", spelling, count))
+            .collect();
+        rows.push(ReportRow::header(format!("{} entries: {}", members.len(), spellings.join(", "))));
+        for (id, node) in members {
+            let text = format!("  {} \"{}\" -> #{}", format_identifier(&id), node.string, node.link);
+            rows.push(ReportRow::node(text, id));
+        }
+    }
+    Report {
+        title: String::from("Titles grouped by normalized form"),
+        rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+
+    #[test]
+    fn spelling_variants_share_a_key() {
+        assert_eq!(normalize_title("Bibliography"), normalize_title("Bibliographie"));
+        assert_eq!(normalize_title("Exercises."), normalize_title("exercise"));
+        assert_eq!(normalize_title("Préface"), normalize_title("PREFACE"));
+        assert_ne!(normalize_title("Chapter 1"), normalize_title("Chapter 2"));
+    }
+
+    #[test]
+    fn mixed_spellings_come_first() {
+        let nav = Nav {
+            nodes: vec![
+                node("Exercises", 0, vec![]), node("Exercises", 0, vec![]), node("Exercises", 0, vec![]),
+                node("Bibliography", 0, vec![]), node("Bibliographie", 0, vec![]),
+                node("Index", 0, vec![]),
+            ],
+        };
+
+        let headers: Vec<_> = title_groups(&nav).rows.into_iter()
+            .filter(|row| row.target.is_none())
+            .map(|row| row.text)
+            .collect();
+        assert_eq!(headers, [
+            "2 entries: \"Bibliography\" x1, \"Bibliographie\" x1",
+            "3 entries: \"Exercises\" x3",
+        ]);
+    }
+}
@@ -0,0 +1,143 @@
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    anchors::Anchors,
+    djvu::PageInfo,
+    input::LineInput,
+    link::{page_number, LinkResolvers},
+    nav::NavNode,
+};
+
+/// Render the details of `node` to the `area` of `f`: its full title, its link, what the link
+/// points to in the document with pages `pages`, according to `resolvers`, with the names the page
+/// has in `anchors`, its number of children, and the `problems` found with it.
+pub fn ui(
+    f: &mut Frame,
+    area: Rect,
+    node: Option<&NavNode>,
+    pages: Option<&[PageInfo]>,
+    resolvers: &LinkResolvers,
+    anchors: &Anchors,
+    problems: &[String],
+) {
+    let block = Block::default().borders(Borders::ALL).title("Details");
+    let Some(node) = node else {
+        f.render_widget(block, area);
+        return;
+    };
+
+    let target = resolvers.resolve(&node.link, pages);
+    let names = page_number(&node.link, pages).map(|page| anchors.describe(page)).unwrap_or_default();
+    let target_style = if target.is_valid() {
+        Style::default()
+    } else {
+        Style::default().fg(Color::Red)
+    };
+    let descendants = count_descendants(node);
+    let children = match (node.children.len(), descendants) {
+        (0, _) => String::from("Children: none"),
+        (children, descendants) if children == descendants => format!("Children: {}", children),
+        (children, descendants) => format!("Children: {} ({} entries below)", children, descendants),
+    };
+    let mut lines = vec![
+        Line::from(node.string.as_str()),
+        Line::from(""),
+        Line::from(format!("Link: #{}", node.link)),
+        Line::from(Span::styled(format!("Target: {}{}", target, names), target_style)),
+        Line::from(children),
+    ];
+    if problems.is_empty() {
+        lines.push(Line::from(Span::styled("No problems found", Style::default().fg(Color::Green))));
+    }
+    for problem in problems {
+        lines.push(Line::from(Span::styled(format!("Problem: {}", problem), Style::default().fg(Color::Yellow))));
+    }
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false });
+    f.render_widget(paragraph, area);
+}
+
+fn count_descendants(node: &NavNode) -> usize {
+    node.children.iter().map(|child| 1 + count_descendants(child)).sum()
+}
+
+/// Cut `text` into lines of at most `width` columns of the terminal, regardless of words so that
+/// the cursor can be placed, and return them along with the line and column of the cursor, which
+/// is `cursor` columns into `text`.
+fn wrap(text: &str, width: usize, cursor: usize) -> (Vec<String>, (usize, usize)) {
+    let mut lines = vec![String::new()];
+    let mut used = 0;
+    let mut before_cursor = 0;
+    let mut cursor_position = None;
+    for c in text.chars() {
+        let c_width = c.width().unwrap_or(0);
+        if used + c_width > width && used > 0 {
+            lines.push(String::new());
+            used = 0;
+        }
+        if before_cursor == cursor && cursor_position.is_none() {
+            cursor_position = Some((lines.len() - 1, used));
+        }
+        lines.last_mut().unwrap().push(c);
+        used += c_width;
+        before_cursor += c_width;
+    }
+    let cursor_position = cursor_position.unwrap_or_else(|| match used >= width {
+        true => (lines.len(), 0),
+        false => (lines.len() - 1, used),
+    });
+    (lines, cursor_position)
+}
+
+/// Render a form to the `area` of `f` in place of the details, with `title`: each input of
+/// `fields` under its label, wrapped so that it can be read whole, and the terminal cursor in the
+/// input at index `focused`.
+pub fn form(f: &mut Frame, area: Rect, title: &str, fields: &[(&str, &LineInput)], focused: usize) {
+    let block = Block::default().borders(Borders::ALL).title(title);
+    let inner = block.inner(area);
+    let width = usize::from(inner.width).max(1);
+
+    let mut lines = Vec::new();
+    let mut cursor = None;
+    for (i, (label, input)) in fields.iter().enumerate() {
+        let style = if i == focused {
+            Style::default().add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        lines.push(Line::from(Span::styled(format!("{}:", label), style)));
+        let (wrapped, (row, column)) = wrap(input.text(), width, input.cursor_width());
+        if i == focused {
+            cursor = Some((lines.len() + row, column));
+        }
+        lines.extend(wrapped.into_iter().map(Line::from));
+        lines.push(Line::from(""));
+    }
+    f.render_widget(Paragraph::new(lines).block(block), area);
+
+    if let Some((row, column)) = cursor.filter(|(row, _)| *row < usize::from(inner.height)) {
+        f.set_cursor(inner.x + column as u16, inner.y + row as u16);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_inputs_are_wrapped() {
+        let (lines, cursor) = wrap("Chapter 12: 東京東", 8, 12);
+        assert_eq!(lines, ["Chapter ", "12: 東京", "東"]);
+        assert_eq!(cursor, (1, 4));
+        assert_eq!(wrap("12345678", 8, 8), (vec![String::from("12345678")], (1, 0)));
+        assert_eq!(wrap("", 8, 0), (vec![String::new()], (0, 0)));
+    }
+}
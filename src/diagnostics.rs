@@ -0,0 +1,206 @@
+//! Bundles of what helps to understand an unexpected error: the error, the last operations, the
+//! input that could not be parsed and the versions of the programs involved. They are written to
+//! the state directory as a single file, to attach to a bug report.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::Display,
+    fs, io,
+    path::PathBuf,
+    process,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{djvu::check_djvused, APP_NAME};
+
+/// Directory of the state directory holding the bundles.
+const DIAGNOSTICS_DIRECTORY: &str = "diagnostics";
+/// Number of bundles kept, the older ones being removed when a new one is written.
+const KEPT_BUNDLES: usize = 10;
+/// Number of operations remembered for the next bundle.
+const LOGGED_OPERATIONS: usize = 50;
+/// Operations and inputs longer than this many characters are cut.
+const MAX_SNIPPET_LENGTH: usize = 2000;
+
+/// The last operations, with the time they were done at.
+static OPERATIONS: Mutex<VecDeque<(SystemTime, String)>> = Mutex::new(VecDeque::new());
+/// The last input that could not be parsed.
+static INPUT: Mutex<Option<String>> = Mutex::new(None);
+/// The version of DjVuLibre `djvused` reports, or why it could not be run, once asked.
+static DJVUSED_VERSION: OnceLock<String> = OnceLock::new();
+
+#[derive(Debug)]
+pub enum DiagnosticsError {
+    IOError(io::Error),
+    XDGSpecificError(xdg::BaseDirectoriesError),
+}
+
+impl Display for DiagnosticsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(e) => write!(f, "cannot write the diagnostics: {}", e),
+            Self::XDGSpecificError(e) => write!(f, "cannot find the state directory: {}", e),
+        }
+    }
+}
+
+impl Error for DiagnosticsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IOError(e) => Some(e),
+            Self::XDGSpecificError(e) => Some(e),
+        }
+    }
+}
+
+/// Return the first `MAX_SNIPPET_LENGTH` characters of `text`.
+fn snippet(text: &str) -> String {
+    match text.char_indices().nth(MAX_SNIPPET_LENGTH) {
+        Some((end, _)) => format!("{}[...]", &text[..end]),
+        None => String::from(text),
+    }
+}
+
+/// Remember `operation` for the bundle of an error happening later.
+pub fn log(operation: impl AsRef<str>) {
+    let mut operations = OPERATIONS.lock().unwrap_or_else(|e| e.into_inner());
+    if operations.len() == LOGGED_OPERATIONS {
+        operations.pop_front();
+    }
+    operations.push_back((SystemTime::now(), snippet(operation.as_ref())));
+}
+
+/// Return the version of DjVuLibre, running `djvused` the first time. Panics are reported
+/// without running it, so it is better asked for before, such as when the program starts.
+pub fn djvused_version() -> &'static str {
+    DJVUSED_VERSION.get_or_init(|| check_djvused().unwrap_or_else(|e| e.to_string()))
+}
+
+/// Remember `input` as the last input that could not be parsed.
+pub fn record_input(input: &str) {
+    *INPUT.lock().unwrap_or_else(|e| e.into_inner()) = Some(snippet(input));
+}
+
+/// What a bundle holds, besides the versions of the programs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// The error, then its causes.
+    pub errors: Vec<String>,
+    /// Everything known about the error, as the program sees it.
+    pub details: String,
+    /// The last operations, as seconds since the Unix epoch and description.
+    pub operations: Vec<(u64, String)>,
+    pub input: Option<String>,
+    /// The version of DjVuLibre, if known.
+    pub djvused: Option<String>,
+}
+
+impl Diagnostics {
+    /// Collect the diagnostics of `error`, with the operations and input remembered so far.
+    pub fn of(error: &(dyn Error + 'static)) -> Self {
+        let errors = std::iter::successors(Some(error), |e| (*e).source()).map(|e| e.to_string()).collect();
+        Self::new(errors, format!("{:?}", error), Some(String::from(djvused_version())))
+    }
+
+    /// Collect the diagnostics of a panic with `message`. No program is run while panicking, so
+    /// the version of DjVuLibre is only known if it was asked for before.
+    pub fn of_panic(message: String) -> Self {
+        Self::new(vec![message], String::from("panic"), DJVUSED_VERSION.get().cloned())
+    }
+
+    fn new(errors: Vec<String>, details: String, djvused: Option<String>) -> Self {
+        let operations = OPERATIONS.lock().unwrap_or_else(|e| e.into_inner()).iter()
+            .map(|(time, operation)| {
+                let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+                (seconds, operation.clone())
+            })
+            .collect();
+        let input = INPUT.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        Self { errors, details, operations, input, djvused }
+    }
+
+    /// Write the bundle as text.
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{} {} on {} {}\ndjvused: {}\ncommand line: {}\n\nerror: {}\n",
+            APP_NAME,
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            self.djvused.as_deref().unwrap_or("unknown"),
+            std::env::args().collect::<Vec<_>>().join(" "),
+            self.errors.first().map_or("", String::as_str),
+        );
+        for cause in self.errors.iter().skip(1) {
+            text.push_str(&format!("caused by: {}\n", cause));
+        }
+        text.push_str(&format!("\ndetails:\n{}\n\nlast operations:\n", self.details));
+        for (seconds, operation) in &self.operations {
+            text.push_str(&format!("{} {}\n", seconds, operation));
+        }
+        if let Some(input) = &self.input {
+            text.push_str(&format!("\ninput that could not be parsed:\n{}\n", input));
+        }
+        text
+    }
+
+    /// Write the bundle to the state directory, remove the oldest ones, and return its path.
+    pub fn write(&self) -> Result<PathBuf, DiagnosticsError> {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_NAME)
+            .map_err(DiagnosticsError::XDGSpecificError)?;
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        // Named after the time, so that sorting the names sorts the bundles by age.
+        let name = format!("{}/{:012}-{}.txt", DIAGNOSTICS_DIRECTORY, seconds, process::id());
+        let path = xdg_dirs.place_state_file(name).map_err(DiagnosticsError::IOError)?;
+        fs::write(&path, self.to_text()).map_err(DiagnosticsError::IOError)?;
+
+        if let Some(directory) = path.parent() {
+            let mut bundles: Vec<_> = fs::read_dir(directory).map_err(DiagnosticsError::IOError)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+                .collect();
+            bundles.sort();
+            for old in bundles.iter().rev().skip(KEPT_BUNDLES) {
+                let _ = fs::remove_file(old);
+            }
+        }
+        Ok(path)
+    }
+}
+
+/// Write the bundle of `diagnostics` and tell where it is on the standard error.
+pub fn report(diagnostics: &Diagnostics) {
+    match diagnostics.write() {
+        Ok(path) => eprintln!(
+            "{}: diagnostics were written to {}, please attach this file to bug reports",
+            APP_NAME,
+            path.display(),
+        ),
+        Err(e) => eprintln!("{}: {}", APP_NAME, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_text() {
+        let diagnostics = Diagnostics {
+            errors: vec![String::from("cannot read the outline"), String::from("unexpected end of file")],
+            details: String::from("NativeReadingError(UnexpectedEof)"),
+            operations: vec![(1700000000, String::from("delete entry")), (1700000005, String::from("write"))],
+            input: Some(String::from("(bookmarks (\"Chapter")),
+            djvused: Some(String::from("DjVuLibre 3.5.28")),
+        };
+        let text = diagnostics.to_text();
+        assert!(text.contains("\ndjvused: DjVuLibre 3.5.28\n"));
+        assert!(text.contains("\nerror: cannot read the outline\ncaused by: unexpected end of file\n"));
+        assert!(text.contains("\nlast operations:\n1700000000 delete entry\n1700000005 write\n"));
+        assert!(text.ends_with("\ninput that could not be parsed:\n(bookmarks (\"Chapter\n"));
+
+        assert_eq!(snippet(&"é".repeat(MAX_SNIPPET_LENGTH + 1)).chars().count(), MAX_SNIPPET_LENGTH + 5);
+    }
+}
@@ -1,67 +1,522 @@
 use std::{
-    process::Command,
-    fs::File, 
-    io::{BufWriter, Write}, path::PathBuf,
+    error::Error,
+    fmt::Display,
+    fs::{self, File},
+    hash::{DefaultHasher, Hasher},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    mem, process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::SystemTime,
 };
 
 use crate::{
+    check::unwritable_titles,
+    diagnostics,
+    history::format_identifier,
+    metadata::Metadata,
     nav::Nav,
-    app::get_temp_file_name,
+    tempfile::TempFile,
+    tree_widget::TreeIdentifierVec,
 };
 
+pub mod native;
+pub mod remote;
+pub mod ssh;
 pub mod parser;
+pub mod tool;
+
+pub use tool::{CancellableTools, SystemTools, ToolRunner};
+pub use crate::tempfile::TempFileError;
+
+/// Added to the name of a file to name the copy it is edited in before replacing it.
+const EDITED_COPY_SUFFIX: &str = ".nav_edit.tmp";
+
+/// Number of copies made so far by this process, to name the next one.
+static COPIES: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Debug)]
 pub enum NavReadingError {
-    IOError(std::io::Error),
+    IOError(io::Error),
+    /// `djvused` is not installed, or not in the `PATH`.
+    DjvusedNotFound,
     DjvusedError(std::process::ExitStatus, String),
     InvalidUtf8Error(std::string::FromUtf8Error),
     NavParsingError(String),
+    TempFileError(TempFileError),
+    /// The file was read without `djvused`, which failed.
+    NativeReadingError(native::NativeReadingError),
+    /// The file could not be copied from or to another machine.
+    TransferError(ssh::TransferError),
+    /// The edited copy of the file could not be made or put in its place.
+    ReplaceError(io::Error),
+    /// The titles of these entries have characters `djvused` cannot write.
+    UnwritableTitles(Vec<TreeIdentifierVec>),
+}
+
+impl Display for NavReadingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(e) => write!(f, "cannot run djvused: {}", e),
+            Self::DjvusedNotFound => write!(
+                f,
+                "djvused was not found in the PATH. It is part of DjVuLibre: install the \
+                 djvulibre-bin package on Debian and Ubuntu, or djvulibre on Fedora, Arch Linux \
+                 and Homebrew"
+            ),
+            Self::DjvusedError(status, stderr) => write!(f, "djvused failed ({}): {}", status, stderr.trim_end()),
+            Self::InvalidUtf8Error(e) => write!(f, "djvused output is not valid UTF-8: {}", e),
+            Self::NavParsingError(e) => write!(f, "cannot parse the outline: {}", e),
+            Self::TempFileError(e) => write!(f, "{}", e),
+            Self::NativeReadingError(e) => write!(f, "cannot read the outline: {}", e),
+            Self::TransferError(e) => write!(f, "cannot copy the file: {}", e),
+            Self::ReplaceError(e) => write!(f, "cannot replace the file: {}", e),
+            Self::UnwritableTitles(ids) => {
+                let ids: Vec<_> = ids.iter().map(|id| format_identifier(id)).collect();
+                write!(
+                    f,
+                    "the titles of entries {} have control characters djvused cannot write, such as NUL or DEL",
+                    ids.join(", "),
+                )
+            },
+        }
+    }
+}
+
+impl NavReadingError {
+    /// Whether the error is likely a bug, or a file the program should read, rather than a
+    /// problem with the installation or the permissions, or a document `djvused` refused.
+    pub fn is_unexpected(&self) -> bool {
+        matches!(
+            self,
+            Self::InvalidUtf8Error(_) | Self::NavParsingError(_) | Self::NativeReadingError(_)
+        )
+    }
+}
+
+impl Error for NavReadingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IOError(e) => Some(e),
+            Self::InvalidUtf8Error(e) => Some(e),
+            Self::TempFileError(e) => Some(e),
+            Self::NativeReadingError(e) => Some(e),
+            Self::TransferError(e) => Some(e),
+            Self::ReplaceError(e) => Some(e),
+            Self::DjvusedNotFound | Self::DjvusedError(..) | Self::NavParsingError(_) | Self::UnwritableTitles(_) => None,
+        }
+    }
+}
+
+/// Return the path of a copy of the file `path` written before it replaces the file. It is next
+/// to the file, so that it can be renamed over it, and named after the process, so that two
+/// editors writing the same file do not share it.
+fn edited_copy_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}-{}{}", process::id(), COPIES.fetch_add(1, Ordering::Relaxed), EDITED_COPY_SUFFIX));
+    path.with_file_name(name)
+}
+
+/// Write a new version of the file `path` with `write`, to a copy renamed over the file if it
+/// succeeds, so that a failure or a power loss leaves either the old file or the new one. Errors
+/// of the copy are turned into errors of `write` with `io_error`.
+pub fn write_through_copy<E>(
+    path: &Path,
+    write: impl FnOnce(&Path) -> Result<(), E>,
+    io_error: impl Fn(io::Error) -> E,
+) -> Result<(), E> {
+    let copy = edited_copy_path(path);
+    let result = write(&copy)
+        .and_then(|()| File::open(&copy).and_then(|file| file.sync_all()).map_err(&io_error))
+        .and_then(|()| fs::rename(&copy, path).map_err(&io_error));
+    if result.is_err() {
+        let _ = fs::remove_file(&copy);
+    }
+    result
+}
+
+/// Run `edit` on a copy of the file `filename`, and rename the copy over the file if it succeeds,
+/// as `write_through_copy` does.
+pub fn edit_copy(
+    filename: &str,
+    edit: impl FnOnce(&str) -> Result<(), NavReadingError>,
+) -> Result<(), NavReadingError> {
+    let write = |copy: &Path| {
+        fs::copy(filename, copy).map_err(NavReadingError::ReplaceError)?;
+        edit(&copy.to_string_lossy())
+    };
+    write_through_copy(Path::new(filename), write, NavReadingError::ReplaceError)
+}
+
+/// The state of a file when it was read, to tell whether it changed since.
+#[derive(Debug, Clone)]
+pub struct FileStamp {
+    len: u64,
+    modified: SystemTime,
+    /// Only needed once the file is touched, so computed in the background rather than when the
+    /// file is opened or written.
+    hash: Arc<Mutex<ContentHash>>,
+}
+
+/// The hash of the content of a file, `None` if it could not be read or changed while read.
+#[derive(Debug)]
+enum ContentHash {
+    Pending(JoinHandle<Option<u64>>),
+    Done(Option<u64>),
+}
+
+impl FileStamp {
+    pub fn of(filename: &str) -> io::Result<Self> {
+        let metadata = fs::metadata(filename)?;
+        let (len, modified) = (metadata.len(), metadata.modified()?);
+        let path = String::from(filename);
+        let hashing = thread::spawn(move || {
+            let hash = hash_file(&path).ok()?;
+            let metadata = fs::metadata(&path).ok()?;
+            (metadata.len() == len && metadata.modified().ok()? == modified).then_some(hash)
+        });
+        Ok(Self { len, modified, hash: Arc::new(Mutex::new(ContentHash::Pending(hashing))) })
+    }
+
+    /// Whether the file `filename` changed since `self` was taken. Files with the same size and
+    /// modification time are taken as unchanged without reading them, and files of the same size
+    /// only touched since are not changed.
+    pub fn changed(&self, filename: &str) -> io::Result<bool> {
+        let metadata = fs::metadata(filename)?;
+        if metadata.len() != self.len {
+            return Ok(true);
+        }
+        if metadata.modified()? == self.modified {
+            return Ok(false);
+        }
+        Ok(self.content_hash() != Some(hash_file(filename)?))
+    }
+
+    /// Return the hash of the content of the file when `self` was taken, waiting for it if needed.
+    fn content_hash(&self) -> Option<u64> {
+        let mut hash = self.hash.lock().unwrap_or_else(|e| e.into_inner());
+        if let ContentHash::Pending(_) = &*hash {
+            let ContentHash::Pending(hashing) = mem::replace(&mut *hash, ContentHash::Done(None)) else {
+                unreachable!();
+            };
+            *hash = ContentHash::Done(hashing.join().ok().flatten());
+        }
+        match &*hash {
+            ContentHash::Done(done) => *done,
+            ContentHash::Pending(_) => None,
+        }
+    }
+}
+
+fn hash_file(filename: &str) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(filename)?);
+    let mut hasher = DefaultHasher::new();
+    let mut buffer = [0; 1 << 16];
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => return Ok(hasher.finish()),
+            read => hasher.write(&buffer[..read]),
+        }
+    }
+}
+
+/// A page of a DjVu document, as listed by `djvused`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageInfo {
+    /// Physical page number, starting at 1.
+    pub number: u32,
+    /// Name of the component file holding the page.
+    pub id: String,
+    /// Page title, used by viewers as a page label.
+    pub title: Option<String>,
+}
+
+/// Check that `djvused` can be run, and return the version of DjVuLibre it reports.
+pub fn check_djvused() -> Result<String, NavReadingError> {
+    check_djvused_with(&SystemTools)
+}
+
+/// Same as `check_djvused`, running `djvused` with `tools`.
+pub fn check_djvused_with(tools: &impl ToolRunner) -> Result<String, NavReadingError> {
+    // Without a file, djvused prints its usage, starting with its version, and fails.
+    let output = tools.output("djvused", &["--help"]).map_err(tool_error)?;
+    let usage = String::from_utf8_lossy(&output.stderr) + String::from_utf8_lossy(&output.stdout);
+    let version = match usage.find("DjVuLibre-") {
+        Some(start) => usage[start + "DjVuLibre-".len()..].split_whitespace().next().unwrap_or_default(),
+        None => "unknown",
+    };
+    Ok(String::from(version))
+}
+
+/// Uses `djvused` to list the pages of the file with path `filename`.
+pub fn get_pages_from_djvu(filename: &str) -> Result<Vec<PageInfo>, NavReadingError> {
+    get_pages_from_djvu_with(&SystemTools, filename)
+}
+
+/// Same as `get_pages_from_djvu`, running `djvused` with `tools`.
+pub fn get_pages_from_djvu_with(tools: &impl ToolRunner, filename: &str) -> Result<Vec<PageInfo>, NavReadingError> {
+    let listing = run_djvused(tools, &[filename, "-u", "-e", "ls"])?;
+    Ok(parser::parse_page_list(&listing))
+}
+
+/// Get the number of pages of the file `filename`, reading the file directly when possible and
+/// using `djvused` otherwise.
+pub fn get_page_count(filename: &str) -> Result<u32, NavReadingError> {
+    match native::read_page_count(filename) {
+        Ok(count) => Ok(count),
+        Err(_) => get_page_count_with(&SystemTools, filename),
+    }
+}
+
+/// Same as `get_page_count`, running `djvused` with `tools`.
+pub fn get_page_count_with(tools: &impl ToolRunner, filename: &str) -> Result<u32, NavReadingError> {
+    let output = run_djvused(tools, &[filename, "-u", "-e", "n"])?;
+    let count = output.trim();
+    count.parse().map_err(|_| NavReadingError::NavParsingError(format!("invalid page count: {}", count)))
+}
+
+/// Uses `djvused` to get the text layer of page `page` (starting at 1) of the file `filename`.
+pub fn get_page_text(filename: &str, page: u32) -> Result<String, NavReadingError> {
+    get_page_text_with(&SystemTools, filename, page)
 }
 
-/// Uses `djvused` to get the outline of the file with path `filename`, and parse it into a `Nav`
-/// object.
+/// Same as `get_page_text`, running `djvused` with `tools`.
+pub fn get_page_text_with(tools: &impl ToolRunner, filename: &str, page: u32) -> Result<String, NavReadingError> {
+    run_djvused(tools, &[filename, "-u", "-e", &format!("select {}; print-pure-txt", page)])
+}
+
+/// Uses `djvused` to get the text layer of the file `filename`, one string per page, the first
+/// page first.
+pub fn get_document_text(filename: &str) -> Result<Vec<String>, NavReadingError> {
+    get_document_text_with(&SystemTools, filename)
+}
+
+/// Same as `get_document_text`, running `djvused` with `tools`.
+pub fn get_document_text_with(tools: &impl ToolRunner, filename: &str) -> Result<Vec<String>, NavReadingError> {
+    // Without a selected page, `djvused` prints the text of every page, each followed by a form
+    // feed.
+    let text = run_djvused(tools, &[filename, "-u", "-e", "print-pure-txt"])?;
+    let text = text.strip_suffix('\u{c}').unwrap_or(&text);
+    Ok(text.split('\u{c}').map(String::from).collect())
+}
+
+/// Return the `djvused` command selecting where the metadata of the document `filename` are: the
+/// page of a single page document, or else the shared annotations, created if needed.
+fn metadata_selection(tools: &impl ToolRunner, filename: &str) -> Result<&'static str, NavReadingError> {
+    Ok(match get_page_count_with(tools, filename)? {
+        1 => "select 1",
+        _ => "create-shared-ant",
+    })
+}
+
+/// Uses `djvused` to get the metadata of the document `filename`.
+pub fn get_metadata(filename: &str) -> Result<Metadata, NavReadingError> {
+    get_metadata_with(&SystemTools, filename)
+}
+
+/// Same as `get_metadata`, running `djvused` with `tools`.
+pub fn get_metadata_with(tools: &impl ToolRunner, filename: &str) -> Result<Metadata, NavReadingError> {
+    // The shared annotations created to be selected are not saved without `-s`.
+    let script = format!("{}; print-meta", metadata_selection(tools, filename)?);
+    let output = run_djvused(tools, &[filename, "-u", "-e", &script])?;
+    parser::parse_metadata(&output)
+        .map(|(_, entries)| Metadata { entries })
+        .map_err(|e| {
+            diagnostics::record_input(&output);
+            NavReadingError::NavParsingError(e.to_string())
+        })
+}
+
+/// Uses `djvused` to replace the metadata of the document `filename` with `metadata`. The file is
+/// replaced by an edited copy.
+pub fn set_metadata(filename: &str, metadata: &Metadata) -> Result<(), NavReadingError> {
+    edit_copy(filename, |copy| set_metadata_with(&SystemTools, copy, metadata))
+}
+
+/// Same as `set_metadata`, running `djvused` with `tools`.
+pub fn set_metadata_with(tools: &impl ToolRunner, filename: &str, metadata: &Metadata) -> Result<(), NavReadingError> {
+    let temp_file = TempFile::new(".meta").map_err(NavReadingError::TempFileError)?;
+    fs::write(temp_file.path(), metadata.to_djvused()).map_err(NavReadingError::IOError)?;
+    let script = format!("{}; set-meta {}", metadata_selection(tools, filename)?, temp_file.path().display());
+    run_djvused(tools, &[filename, "-e", &script, "-s"])?;
+    Ok(())
+}
+
+/// Uses `djvused` to set the title of the pages of the file `filename`, which viewers show as
+/// page labels, to `titles`, the title of the first page first. Pages with no title in `titles`
+/// keep theirs, and an empty title removes the title of its page. The file is replaced by an
+/// edited copy.
+pub fn set_page_titles(filename: &str, titles: &[Option<String>]) -> Result<(), NavReadingError> {
+    edit_copy(filename, |copy| set_page_titles_with(&SystemTools, copy, titles))
+}
+
+/// Same as `set_page_titles`, running `djvused` with `tools`.
+pub fn set_page_titles_with(tools: &impl ToolRunner, filename: &str, titles: &[Option<String>]) -> Result<(), NavReadingError> {
+    let script: String = titles.iter()
+        .enumerate()
+        .filter_map(|(i, title)| {
+            let title = title.as_deref()?.replace('\\', "\\\\").replace('"', "\\\"");
+            Some(format!("select {}; set-page-title \"{}\"\n", i + 1, title))
+        })
+        .collect();
+    if script.is_empty() {
+        return Ok(());
+    }
+    run_djvused(tools, &[filename, "-e", &script, "-s"])?;
+    Ok(())
+}
+
+/// Get the outline of the file with path `filename` as a `Nav` object, decoding its `NAVM` chunk
+/// directly, or with `djvused` if that fails and the `djvused` feature is enabled.
 pub fn get_nav_from_djvu(filename: &str) -> Result<Nav, NavReadingError> {
-    let nav_str = String::from_utf8(
-        Command::new("djvused")
-            .args([filename, "-u", "-e", "print-outline"])
-            .output()
-            .map_err(|e| NavReadingError::IOError(e))?
-            .stdout
-        ).map_err(|e| NavReadingError::InvalidUtf8Error(e))?;
+    native::read_outline(filename).or_else(|e| with_djvused(e, || get_nav_from_djvu_with(&SystemTools, filename)))
+}
 
+/// Run `fallback` with `djvused` after reading or writing the file directly failed with `error`,
+/// which is the one returned if `djvused` is missing.
+#[cfg(feature = "djvused")]
+fn with_djvused<T>(
+    error: native::NativeReadingError,
+    fallback: impl FnOnce() -> Result<T, NavReadingError>,
+) -> Result<T, NavReadingError> {
+    diagnostics::log(format!("falling back to djvused: {}", error));
+    match fallback() {
+        Err(NavReadingError::DjvusedNotFound) => Err(NavReadingError::NativeReadingError(error)),
+        result => result,
+    }
+}
+
+/// Fail with `error`, `djvused` being left out of this build.
+#[cfg(not(feature = "djvused"))]
+fn with_djvused<T>(
+    error: native::NativeReadingError,
+    _fallback: impl FnOnce() -> Result<T, NavReadingError>,
+) -> Result<T, NavReadingError> {
+    Err(NavReadingError::NativeReadingError(error))
+}
+
+/// Uses `djvused` to get the outline of the file with path `filename`.
+pub fn get_nav_from_djvu_with(tools: &impl ToolRunner, filename: &str) -> Result<Nav, NavReadingError> {
+    let nav_str = run_djvused(tools, &[filename, "-u", "-e", "print-outline"])?;
     Ok(
         parser::parse_djvu_nav(&nav_str)
-            .map_err(|e| NavReadingError::NavParsingError(e.to_string()))?.1
+            .map_err(|e| {
+                diagnostics::record_input(&nav_str);
+                NavReadingError::NavParsingError(e.to_string())
+            })?.1
     )
 }
 
 /// Write `nav` to a temp file so that it can be used by `djvused` later on.
-fn write_nav_to_temp_file(path: &PathBuf, nav: &Nav) -> Result<(), std::io::Error> {
+fn write_nav_to_temp_file(path: &Path, nav: &Nav) -> Result<(), std::io::Error> {
     let nav_s = nav.to_djvu();
 
     let temp_file = File::create(path)?;
     let mut writer = BufWriter::new(temp_file);
     write!(writer, "{}", nav_s)?;
-    Ok(())
+    writer.flush()
+}
+
+/// Fail if titles of `nav` have characters that would not read back as they were written.
+fn check_writable(nav: &Nav) -> Result<(), NavReadingError> {
+    let unwritable: Vec<_> = unwritable_titles(nav).into_iter().map(|(id, _)| id).collect();
+    match unwritable.is_empty() {
+        true => Ok(()),
+        false => Err(NavReadingError::UnwritableTitles(unwritable)),
+    }
 }
 
-/// Uses `djvused` to set the outline of the file `filename` to `nav`.
+/// Set the outline of the file `filename` to `nav`, writing its `NAVM` chunk directly, or with
+/// `djvused` if that fails and the `djvused` feature is enabled. The file is replaced by an edited
+/// copy.
 pub fn embed_nav_in_djvu_file(filename: &str, nav: &Nav) -> Result<(), NavReadingError> {
-    let temp_file_name = get_temp_file_name().unwrap();
-    write_nav_to_temp_file(&temp_file_name, nav).map_err(|e| NavReadingError::IOError(e))?;
+    check_writable(nav)?;
+    native::write_outline(filename, nav).or_else(|e| {
+        with_djvused(e, || edit_copy(filename, |copy| embed_nav_in_djvu_file_with(&SystemTools, copy, nav)))
+    })
+}
+
+/// Same as `embed_nav_in_djvu_file`, running `djvused` with `tools`. An empty `nav` removes the
+/// outline rather than writing an empty one.
+pub fn embed_nav_in_djvu_file_with(tools: &impl ToolRunner, filename: &str, nav: &Nav) -> Result<(), NavReadingError> {
+    check_writable(nav)?;
+    if nav.nodes.is_empty() {
+        run_djvused(tools, &[filename, "-e", "remove-outline", "-s"])?;
+        return Ok(());
+    }
+    let temp_file = TempFile::new(".nav").map_err(NavReadingError::TempFileError)?;
+    write_nav_to_temp_file(temp_file.path(), nav).map_err(NavReadingError::IOError)?;
 
-    let sed_command = format!("set-outline {}", temp_file_name.into_os_string().into_string().unwrap());
-    let command_result = Command::new("djvused")
-        .args([filename, "-e", &sed_command, "-s", "-v"])
-        .output()
-        .map_err(|e| NavReadingError::IOError(e))?;
+    let sed_command = format!("set-outline {}", temp_file.path().display());
+    run_djvused(tools, &[filename, "-e", &sed_command, "-s", "-v"])?;
+    Ok(())
+}
 
-    if !command_result.status.success() {
+/// Run `djvused` with `args`, and return its standard output if it succeeded.
+fn run_djvused(tools: &impl ToolRunner, args: &[&str]) -> Result<String, NavReadingError> {
+    diagnostics::log(format!("djvused {}", args.join(" ")));
+    let output = tools.output("djvused", args).map_err(tool_error)?;
+    if !output.status.success() {
         return Err(NavReadingError::DjvusedError(
-                command_result.status, 
-                String::from_utf8(command_result.stderr
-        ).unwrap()));
+            output.status,
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(NavReadingError::InvalidUtf8Error)
+}
+
+/// Turn an error running `djvused` into a `NavReadingError`, telling apart a missing program.
+fn tool_error(error: io::Error) -> NavReadingError {
+    match error.kind() {
+        io::ErrorKind::NotFound => NavReadingError::DjvusedNotFound,
+        _ => NavReadingError::IOError(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_edit_leaves_the_file() {
+        let path = std::env::temp_dir().join(format!("nav_edit_test_{}.djvu", std::process::id()));
+        let filename = path.to_string_lossy().into_owned();
+        fs::write(&path, "old").unwrap();
+
+        let mut copy_path = String::new();
+        let result = edit_copy(&filename, |copy| {
+            copy_path = String::from(copy);
+            fs::write(copy, "half").unwrap();
+            Err(NavReadingError::NavParsingError(String::from("failed")))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        assert!(!Path::new(&copy_path).exists());
+        assert_ne!(edited_copy_path(&path), edited_copy_path(&path));
+
+        edit_copy(&filename, |copy| fs::write(copy, "new").map_err(NavReadingError::IOError)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn changed_file_is_detected() {
+        let path = std::env::temp_dir().join(format!("nav_edit_stamp_{}.djvu", std::process::id()));
+        let filename = path.to_string_lossy().into_owned();
+        fs::write(&path, "outline").unwrap();
+        let stamp = FileStamp::of(&filename).unwrap();
+
+        assert!(!stamp.changed(&filename).unwrap());
+        // A file touched before it was hashed is taken as changed, not knowing better.
+        assert!(stamp.content_hash().is_some());
+        File::options().write(true).open(&path).unwrap().set_modified(SystemTime::UNIX_EPOCH).unwrap();
+        assert!(!stamp.changed(&filename).unwrap());
+        fs::write(&path, "0utline").unwrap();
+        assert!(stamp.changed(&filename).unwrap());
+        fs::remove_file(&path).unwrap();
     }
-    Ok(())
 }
@@ -0,0 +1,1114 @@
+use std::{
+    array,
+    error::Error,
+    fmt::Display,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{
+    djvu::write_through_copy,
+    nav::{BookmarkLink, Nav, NavNode},
+};
+
+/// Reasons why a file could not be read or written without `djvused`.
+#[derive(Debug)]
+pub enum NativeReadingError {
+    IOError(io::Error),
+    /// The file does not start with a DjVu header.
+    NotDjvu,
+    /// The file or the outline ends in the middle of a structure.
+    Truncated,
+    /// The decompressed outline does not follow the format of the `NAVM` chunk.
+    InvalidOutline,
+    /// The BZZ-compressed content of a chunk does not decompress.
+    CorruptCompression,
+    /// Single page documents have nowhere to put an outline without being bundled.
+    SinglePage,
+    /// The outline has more bookmarks, or longer titles, than the `NAVM` chunk can hold.
+    OutlineTooLarge,
+}
+
+impl Display for NativeReadingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(e) => write!(f, "cannot read the file: {}", e),
+            Self::NotDjvu => write!(f, "not a DjVu file"),
+            Self::Truncated => write!(f, "the file is truncated"),
+            Self::InvalidOutline => write!(f, "the outline is malformed"),
+            Self::CorruptCompression => write!(f, "the compressed data is corrupt"),
+            Self::SinglePage => write!(f, "single page documents cannot hold an outline"),
+            Self::OutlineTooLarge => write!(
+                f,
+                "the outline is too large: a DjVu file holds up to 65,535 bookmarks, with up to 255 children each"
+            ),
+        }
+    }
+}
+
+impl Error for NativeReadingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IOError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NativeReadingError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => Self::Truncated,
+            _ => Self::IOError(e),
+        }
+    }
+}
+
+/// Header of an IFF chunk: its identifier and the size of its content.
+fn read_chunk_header(reader: &mut impl Read) -> Result<([u8; 4], u32), NativeReadingError> {
+    let mut header = [0; 8];
+    reader.read_exact(&mut header)?;
+    let id = [header[0], header[1], header[2], header[3]];
+    Ok((id, u32::from_be_bytes([header[4], header[5], header[6], header[7]])))
+}
+
+/// Find the `NAVM` chunk of the document read by `reader` by walking its chunks, and return the
+/// position and size of its content, or `None` if the document has no outline.
+pub fn find_outline_chunk(reader: &mut (impl Read + Seek)) -> Result<Option<(u64, u32)>, NativeReadingError> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    let (id, size) = read_chunk_header(reader)?;
+    if magic != *b"AT&T" || id != *b"FORM" {
+        return Err(NativeReadingError::NotDjvu);
+    }
+    let mut kind = [0; 4];
+    reader.read_exact(&mut kind)?;
+    match &kind {
+        // Single page documents have no outline.
+        b"DJVU" => return Ok(None),
+        b"DJVM" => (),
+        _ => return Err(NativeReadingError::NotDjvu),
+    }
+
+    let end = reader.stream_position()? + u64::from(size) - 4;
+    while reader.stream_position()? < end {
+        let (id, size) = read_chunk_header(reader)?;
+        let start = reader.stream_position()?;
+        if id == *b"NAVM" {
+            return Ok(Some((start, size)));
+        }
+        // Chunks start at even offsets.
+        let next = start + u64::from(size) + u64::from(size % 2);
+        reader.seek(SeekFrom::Start(next))?;
+    }
+    Ok(None)
+}
+
+/// Return the number of pages of the file `filename` without `djvused`: one for a single page
+/// document, or else the number of components its directory lists as pages.
+pub fn read_page_count(filename: &str) -> Result<u32, NativeReadingError> {
+    let mut reader = BufReader::new(File::open(filename)?);
+    let mut header = [0; 16];
+    reader.read_exact(&mut header)?;
+    match (&header[..8], &header[12..]) {
+        (b"AT&TFORM", b"DJVU") => Ok(1),
+        (b"AT&TFORM", b"DJVM") => count_pages(&read_directory(&mut reader)?),
+        _ => Err(NativeReadingError::NotDjvu),
+    }
+}
+
+/// Read the content of the `DIRM` chunk, which comes first in a multi-page document, from
+/// `reader` placed right after the header of the document.
+fn read_directory(reader: &mut impl Read) -> Result<Vec<u8>, NativeReadingError> {
+    let (id, size) = read_chunk_header(reader)?;
+    if id != *b"DIRM" {
+        return Err(NativeReadingError::NotDjvu);
+    }
+    let mut directory = Vec::new();
+    reader.take(u64::from(size)).read_to_end(&mut directory)?;
+    if directory.len() < size as usize {
+        return Err(NativeReadingError::Truncated);
+    }
+    Ok(directory)
+}
+
+/// Count the pages listed in the content of a `DIRM` chunk: a version whose highest bit marks
+/// bundled documents, the number of components on two bytes, their offsets in the file on four
+/// bytes each if bundled, then BZZ-compressed the size of each component on three bytes and its
+/// flags on one byte, followed by their names.
+fn count_pages(directory: &[u8]) -> Result<u32, NativeReadingError> {
+    let [version, high, low, rest @ ..] = directory else {
+        return Err(NativeReadingError::Truncated);
+    };
+    let count = usize::from(u16::from_be_bytes([*high, *low]));
+    if count == 0 {
+        return Ok(0);
+    }
+    let offsets = match version & 0x80 {
+        0 => 0,
+        _ => 4 * count,
+    };
+    let components = bzz_decompress(rest.get(offsets..).ok_or(NativeReadingError::Truncated)?)?;
+    let flags = components.get(3 * count..4 * count).ok_or(NativeReadingError::Truncated)?;
+    // The first version marked pages by the lowest bit of the flags, the next ones by a type in
+    // the lowest six bits.
+    let is_page = |flag: &&u8| match version & 0x7f {
+        0 => **flag & 1 == 1,
+        _ => **flag & 0x3f == 1,
+    };
+    Ok(flags.iter().filter(is_page).count() as u32)
+}
+
+/// Read the outline of the file `filename` without `djvused`, decompressing its `NAVM` chunk.
+pub fn read_outline(filename: &str) -> Result<Nav, NativeReadingError> {
+    let mut reader = BufReader::new(File::open(filename)?);
+    let Some((start, size)) = find_outline_chunk(&mut reader)? else {
+        return Ok(Nav { nodes: vec![] });
+    };
+    let mut compressed = Vec::new();
+    reader.seek(SeekFrom::Start(start))?;
+    reader.take(u64::from(size)).read_to_end(&mut compressed)?;
+    if compressed.len() < size as usize {
+        return Err(NativeReadingError::Truncated);
+    }
+    decode_bookmarks(&bzz_decompress(&compressed)?)
+}
+
+/// Set the outline of the multi-page document `filename` to `nav` without `djvused`, replacing its
+/// `NAVM` chunk or adding one after the directory, in a copy renamed over the file. The offsets of
+/// the components of a bundled document are moved by as much as the chunk grew. An empty `nav`
+/// removes the chunk.
+pub fn write_outline(filename: &str, nav: &Nav) -> Result<(), NativeReadingError> {
+    let mut chunk = Vec::new();
+    if !nav.nodes.is_empty() {
+        let content = bzz_compress(&encode_bookmarks(nav)?);
+        chunk.extend(b"NAVM");
+        chunk.extend((content.len() as u32).to_be_bytes());
+        chunk.extend(content);
+        if chunk.len() % 2 == 1 {
+            chunk.push(0);
+        }
+    }
+
+    let mut reader = BufReader::new(File::open(filename)?);
+    let mut header = [0; 16];
+    reader.read_exact(&mut header)?;
+    match (&header[..8], &header[12..]) {
+        (b"AT&TFORM", b"DJVM") => (),
+        (b"AT&TFORM", b"DJVU") => return Err(NativeReadingError::SinglePage),
+        _ => return Err(NativeReadingError::NotDjvu),
+    }
+    let form_size = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+    let mut directory = read_directory(&mut reader)?;
+    let directory_end = 24 + directory.len() as u64 + directory.len() as u64 % 2;
+
+    // Where the outline is, or goes if there is none yet, and its length with its header.
+    let form_end = 12 + u64::from(form_size);
+    let (mut position, mut removed) = (directory_end, 0);
+    reader.seek(SeekFrom::Start(position))?;
+    while position < form_end {
+        let (id, size) = read_chunk_header(&mut reader)?;
+        let next = position + 8 + u64::from(size) + u64::from(size % 2);
+        if id == *b"NAVM" {
+            removed = next.min(form_end) - position;
+            break;
+        }
+        position = next;
+        reader.seek(SeekFrom::Start(position))?;
+    }
+    let (position, removed) = match removed {
+        0 => (directory_end, 0),
+        _ => (position, removed),
+    };
+    let delta = chunk.len() as i64 - removed as i64;
+    let form_size = u32::try_from(i64::from(form_size) + delta).map_err(|_| NativeReadingError::OutlineTooLarge)?;
+
+    if directory.first().is_some_and(|version| version & 0x80 != 0) {
+        let count = match directory.get(1..3) {
+            Some(count) => usize::from(u16::from_be_bytes([count[0], count[1]])),
+            None => return Err(NativeReadingError::Truncated),
+        };
+        let offsets = directory.get_mut(3..3 + 4 * count).ok_or(NativeReadingError::Truncated)?;
+        for offset in offsets.chunks_exact_mut(4) {
+            let value = u32::from_be_bytes([offset[0], offset[1], offset[2], offset[3]]);
+            if u64::from(value) >= position {
+                let moved = u32::try_from(i64::from(value) + delta).map_err(|_| NativeReadingError::OutlineTooLarge)?;
+                offset.copy_from_slice(&moved.to_be_bytes());
+            }
+        }
+    }
+
+    let permissions = fs::metadata(filename)?.permissions();
+    let write = |copy: &Path| -> Result<(), NativeReadingError> {
+        let mut writer = BufWriter::new(File::create(copy)?);
+        writer.write_all(b"AT&TFORM")?;
+        writer.write_all(&form_size.to_be_bytes())?;
+        writer.write_all(b"DJVMDIRM")?;
+        writer.write_all(&(directory.len() as u32).to_be_bytes())?;
+        writer.write_all(&directory)?;
+        if directory.len() % 2 == 1 {
+            writer.write_all(&[0])?;
+        }
+        reader.seek(SeekFrom::Start(directory_end))?;
+        io::copy(&mut reader.by_ref().take(position - directory_end), &mut writer)?;
+        writer.write_all(&chunk)?;
+        reader.seek(SeekFrom::Start(position + removed))?;
+        io::copy(&mut reader, &mut writer)?;
+        writer.flush()?;
+        fs::set_permissions(copy, permissions)?;
+        Ok(())
+    };
+    write_through_copy(Path::new(filename), write, NativeReadingError::IOError)
+}
+
+/// Decode the content of a `NAVM` chunk, once decompressed: the total number of bookmarks on two
+/// bytes, then each bookmark in depth-first order as its number of children on one byte, and its
+/// title and its link each preceded by their length on three bytes.
+pub fn decode_bookmarks(data: &[u8]) -> Result<Nav, NativeReadingError> {
+    struct Input<'a>(&'a [u8]);
+
+    impl<'a> Input<'a> {
+        fn take(&mut self, n: usize) -> Result<&'a [u8], NativeReadingError> {
+            if self.0.len() < n {
+                return Err(NativeReadingError::Truncated);
+            }
+            let (taken, rest) = self.0.split_at(n);
+            self.0 = rest;
+            Ok(taken)
+        }
+
+        fn number(&mut self, n: usize) -> Result<usize, NativeReadingError> {
+            Ok(self.take(n)?.iter().fold(0, |acc, byte| acc << 8 | usize::from(*byte)))
+        }
+
+        fn string(&mut self) -> Result<String, NativeReadingError> {
+            let length = self.number(3)?;
+            String::from_utf8(self.take(length)?.to_vec()).map_err(|_| NativeReadingError::InvalidOutline)
+        }
+    }
+
+    let mut input = Input(data);
+    let mut remaining = input.number(2)?;
+    let mut nodes = Vec::new();
+    // The bookmarks being read, outermost first, each with the number of its children left to
+    // read. They are kept here rather than on the call stack, which deep outlines would overflow.
+    let mut open: Vec<(NavNode, usize)> = Vec::new();
+    loop {
+        while let Some((_, 0)) = open.last() {
+            let Some((node, _)) = open.pop() else { break };
+            match open.last_mut() {
+                Some((parent, _)) => parent.children.push(node),
+                None => nodes.push(node),
+            }
+        }
+        if remaining == 0 {
+            break;
+        }
+        remaining -= 1;
+        let child_count = input.number(1)?;
+        let string = input.string()?;
+        let url = input.string()?;
+        let link = match url.strip_prefix('#') {
+            Some(target) => BookmarkLink::from_string(target),
+            None => BookmarkLink::PageLink(url),
+        };
+        if let Some((_, left)) = open.last_mut() {
+            *left -= 1;
+        }
+        open.push((NavNode { string, link, children: Vec::new() }, child_count));
+    }
+    // Bookmarks still open miss children the count did not leave room for.
+    if !open.is_empty() || !input.0.is_empty() {
+        return Err(NativeReadingError::InvalidOutline);
+    }
+    Ok(Nav { nodes })
+}
+
+/// Encode `nav` as the content of a `NAVM` chunk before compression, as `decode_bookmarks` reads
+/// it.
+pub fn encode_bookmarks(nav: &Nav) -> Result<Vec<u8>, NativeReadingError> {
+    let mut data = Vec::new();
+    let mut count = 0;
+    // In depth-first order, without recursion for the same reason as `decode_bookmarks`.
+    let mut pending: Vec<&NavNode> = nav.nodes.iter().rev().collect();
+    while let Some(node) = pending.pop() {
+        count += 1;
+        data.push(u8::try_from(node.children.len()).map_err(|_| NativeReadingError::OutlineTooLarge)?);
+        let url = format!("#{}", node.link);
+        for field in [node.string.as_str(), url.as_str()] {
+            let length = u32::try_from(field.len()).ok().filter(|length| *length < 1 << 24);
+            let length = length.ok_or(NativeReadingError::OutlineTooLarge)?;
+            data.extend(&length.to_be_bytes()[1..]);
+            data.extend(field.as_bytes());
+        }
+        pending.extend(node.children.iter().rev());
+    }
+    let count = u16::try_from(count).map_err(|_| NativeReadingError::OutlineTooLarge)?;
+    Ok([&count.to_be_bytes()[..], &data].concat())
+}
+
+/// States of the adaptive probabilities of the ZP coder, as DjVuLibre numbers them: the
+/// probability of the less probable bit, the interval past which a more probable bit moves to
+/// the next state, and the states after a more and a less probable bit. The more probable bit of
+/// a state is its lowest bit.
+const ZP_STATES: [(u16, u16, u8, u8); 251] = [
+    (0x8000, 0x0000, 84, 145), (0x8000, 0x0000, 3, 4), (0x8000, 0x0000, 4, 3), (0x6bbd, 0x10a5, 5, 1),
+    (0x6bbd, 0x10a5, 6, 2), (0x5d45, 0x1f28, 7, 3), (0x5d45, 0x1f28, 8, 4), (0x51b9, 0x2bd3, 9, 5),
+    (0x51b9, 0x2bd3, 10, 6), (0x4813, 0x36e3, 11, 7), (0x4813, 0x36e3, 12, 8), (0x3fd5, 0x408c, 13, 9),
+    (0x3fd5, 0x408c, 14, 10), (0x38b1, 0x48fd, 15, 11), (0x38b1, 0x48fd, 16, 12), (0x3275, 0x505d, 17, 13),
+    (0x3275, 0x505d, 18, 14), (0x2cfd, 0x56d0, 19, 15), (0x2cfd, 0x56d0, 20, 16), (0x2825, 0x5c71, 21, 17),
+    (0x2825, 0x5c71, 22, 18), (0x23ab, 0x615b, 23, 19), (0x23ab, 0x615b, 24, 20), (0x1f87, 0x65a5, 25, 21),
+    (0x1f87, 0x65a5, 26, 22), (0x1bbb, 0x6962, 27, 23), (0x1bbb, 0x6962, 28, 24), (0x1845, 0x6ca2, 29, 25),
+    (0x1845, 0x6ca2, 30, 26), (0x1523, 0x6f74, 31, 27), (0x1523, 0x6f74, 32, 28), (0x1253, 0x71e6, 33, 29),
+    (0x1253, 0x71e6, 34, 30), (0x0fcf, 0x7404, 35, 31), (0x0fcf, 0x7404, 36, 32), (0x0d95, 0x75d6, 37, 33),
+    (0x0d95, 0x75d6, 38, 34), (0x0b9d, 0x7768, 39, 35), (0x0b9d, 0x7768, 40, 36), (0x09e3, 0x78c2, 41, 37),
+    (0x09e3, 0x78c2, 42, 38), (0x0861, 0x79ea, 43, 39), (0x0861, 0x79ea, 44, 40), (0x0711, 0x7ae7, 45, 41),
+    (0x0711, 0x7ae7, 46, 42), (0x05f1, 0x7bbe, 47, 43), (0x05f1, 0x7bbe, 48, 44), (0x04f9, 0x7c75, 49, 45),
+    (0x04f9, 0x7c75, 50, 46), (0x0425, 0x7d0f, 51, 47), (0x0425, 0x7d0f, 52, 48), (0x0371, 0x7d91, 53, 49),
+    (0x0371, 0x7d91, 54, 50), (0x02d9, 0x7dfe, 55, 51), (0x02d9, 0x7dfe, 56, 52), (0x0259, 0x7e5a, 57, 53),
+    (0x0259, 0x7e5a, 58, 54), (0x01ed, 0x7ea6, 59, 55), (0x01ed, 0x7ea6, 60, 56), (0x0193, 0x7ee6, 61, 57),
+    (0x0193, 0x7ee6, 62, 58), (0x0149, 0x7f1a, 63, 59), (0x0149, 0x7f1a, 64, 60), (0x010b, 0x7f45, 65, 61),
+    (0x010b, 0x7f45, 66, 62), (0x00d5, 0x7f6b, 67, 63), (0x00d5, 0x7f6b, 68, 64), (0x00a5, 0x7f8d, 69, 65),
+    (0x00a5, 0x7f8d, 70, 66), (0x007b, 0x7faa, 71, 67), (0x007b, 0x7faa, 72, 68), (0x0057, 0x7fc3, 73, 69),
+    (0x0057, 0x7fc3, 74, 70), (0x003b, 0x7fd7, 75, 71), (0x003b, 0x7fd7, 76, 72), (0x0023, 0x7fe7, 77, 73),
+    (0x0023, 0x7fe7, 78, 74), (0x0013, 0x7ff2, 79, 75), (0x0013, 0x7ff2, 80, 76), (0x0007, 0x7ffa, 81, 77),
+    (0x0007, 0x7ffa, 82, 78), (0x0001, 0x7fff, 81, 79), (0x0001, 0x7fff, 82, 80), (0x5695, 0x0000, 9, 85),
+    (0x24ee, 0x0000, 86, 226), (0x8000, 0x0000, 5, 6), (0x0d30, 0x0000, 88, 176), (0x481a, 0x0000, 89, 143),
+    (0x0481, 0x0000, 90, 138), (0x3579, 0x0000, 91, 141), (0x017a, 0x0000, 92, 112), (0x24ef, 0x0000, 93, 135),
+    (0x007b, 0x0000, 94, 104), (0x1978, 0x0000, 95, 133), (0x0028, 0x0000, 96, 100), (0x10ca, 0x0000, 97, 129),
+    (0x000d, 0x0000, 82, 98), (0x0b5d, 0x0000, 99, 127), (0x0034, 0x0000, 76, 72), (0x078a, 0x0000, 101, 125),
+    (0x00a0, 0x0000, 70, 102), (0x050f, 0x0000, 103, 123), (0x0117, 0x0000, 66, 60), (0x0358, 0x0000, 105, 121),
+    (0x01ea, 0x0000, 106, 110), (0x0234, 0x0000, 107, 119), (0x0144, 0x0000, 66, 108), (0x0173, 0x0000, 109, 117),
+    (0x0234, 0x0000, 60, 54), (0x00f5, 0x0000, 111, 115), (0x0353, 0x0000, 56, 48), (0x00a1, 0x0000, 69, 113),
+    (0x05c5, 0x0000, 114, 134), (0x011a, 0x0000, 65, 59), (0x03cf, 0x0000, 116, 132), (0x01aa, 0x0000, 61, 55),
+    (0x0285, 0x0000, 118, 130), (0x0286, 0x0000, 57, 51), (0x01ab, 0x0000, 120, 128), (0x03d3, 0x0000, 53, 47),
+    (0x011a, 0x0000, 122, 126), (0x05c5, 0x0000, 49, 41), (0x00ba, 0x0000, 124, 62), (0x08ad, 0x0000, 43, 37),
+    (0x007a, 0x0000, 72, 66), (0x0ccc, 0x0000, 39, 31), (0x01eb, 0x0000, 60, 54), (0x1302, 0x0000, 33, 25),
+    (0x02e6, 0x0000, 56, 50), (0x1b81, 0x0000, 29, 131), (0x045e, 0x0000, 52, 46), (0x24ef, 0x0000, 23, 17),
+    (0x0690, 0x0000, 48, 40), (0x2865, 0x0000, 23, 15), (0x09de, 0x0000, 42, 136), (0x3987, 0x0000, 137, 7),
+    (0x0dc8, 0x0000, 38, 32), (0x2c99, 0x0000, 21, 139), (0x10ca, 0x0000, 140, 172), (0x3b5f, 0x0000, 15, 9),
+    (0x0b5d, 0x0000, 142, 170), (0x5695, 0x0000, 9, 85), (0x078a, 0x0000, 144, 168), (0x8000, 0x0000, 141, 248),
+    (0x050f, 0x0000, 146, 166), (0x24ee, 0x0000, 147, 247), (0x0358, 0x0000, 148, 164), (0x0d30, 0x0000, 149, 197),
+    (0x0234, 0x0000, 150, 162), (0x0481, 0x0000, 151, 95), (0x0173, 0x0000, 152, 160), (0x017a, 0x0000, 153, 173),
+    (0x00f5, 0x0000, 154, 158), (0x007b, 0x0000, 155, 165), (0x00a1, 0x0000, 70, 156), (0x0028, 0x0000, 157, 161),
+    (0x011a, 0x0000, 66, 60), (0x000d, 0x0000, 81, 159), (0x01aa, 0x0000, 62, 56), (0x0034, 0x0000, 75, 71),
+    (0x0286, 0x0000, 58, 52), (0x00a0, 0x0000, 69, 163), (0x03d3, 0x0000, 54, 48), (0x0117, 0x0000, 65, 59),
+    (0x05c5, 0x0000, 50, 42), (0x01ea, 0x0000, 167, 171), (0x08ad, 0x0000, 44, 38), (0x0144, 0x0000, 65, 169),
+    (0x0ccc, 0x0000, 40, 32), (0x0234, 0x0000, 59, 53), (0x1302, 0x0000, 34, 26), (0x0353, 0x0000, 55, 47),
+    (0x1b81, 0x0000, 30, 174), (0x05c5, 0x0000, 175, 193), (0x24ef, 0x0000, 24, 18), (0x03cf, 0x0000, 177, 191),
+    (0x2b74, 0x0000, 178, 222), (0x0285, 0x0000, 179, 189), (0x201d, 0x0000, 180, 218), (0x01ab, 0x0000, 181, 187),
+    (0x1715, 0x0000, 182, 216), (0x011a, 0x0000, 183, 185), (0x0fb7, 0x0000, 184, 214), (0x00ba, 0x0000, 69, 61),
+    (0x0a67, 0x0000, 186, 212), (0x01eb, 0x0000, 59, 53), (0x06e7, 0x0000, 188, 210), (0x02e6, 0x0000, 55, 49),
+    (0x0496, 0x0000, 190, 208), (0x045e, 0x0000, 51, 45), (0x030d, 0x0000, 192, 206), (0x0690, 0x0000, 47, 39),
+    (0x0206, 0x0000, 194, 204), (0x09de, 0x0000, 41, 195), (0x0155, 0x0000, 196, 202), (0x0dc8, 0x0000, 37, 31),
+    (0x00e1, 0x0000, 198, 200), (0x2b74, 0x0000, 199, 243), (0x0094, 0x0000, 72, 64), (0x201d, 0x0000, 201, 239),
+    (0x0188, 0x0000, 62, 56), (0x1715, 0x0000, 203, 237), (0x0252, 0x0000, 58, 52), (0x0fb7, 0x0000, 205, 235),
+    (0x0383, 0x0000, 54, 48), (0x0a67, 0x0000, 207, 233), (0x0547, 0x0000, 50, 44), (0x06e7, 0x0000, 209, 231),
+    (0x07e2, 0x0000, 46, 38), (0x0496, 0x0000, 211, 229), (0x0bc0, 0x0000, 40, 34), (0x030d, 0x0000, 213, 227),
+    (0x1178, 0x0000, 36, 28), (0x0206, 0x0000, 215, 225), (0x19da, 0x0000, 30, 22), (0x0155, 0x0000, 217, 223),
+    (0x24ef, 0x0000, 26, 16), (0x00e1, 0x0000, 219, 221), (0x320e, 0x0000, 20, 220), (0x0094, 0x0000, 71, 63),
+    (0x432a, 0x0000, 14, 8), (0x0188, 0x0000, 61, 55), (0x447d, 0x0000, 14, 224), (0x0252, 0x0000, 57, 51),
+    (0x5ece, 0x0000, 8, 2), (0x0383, 0x0000, 53, 47), (0x8000, 0x0000, 228, 87), (0x0547, 0x0000, 49, 43),
+    (0x481a, 0x0000, 230, 246), (0x07e2, 0x0000, 45, 37), (0x3579, 0x0000, 232, 244), (0x0bc0, 0x0000, 39, 33),
+    (0x24ef, 0x0000, 234, 238), (0x1178, 0x0000, 35, 27), (0x1978, 0x0000, 138, 236), (0x19da, 0x0000, 29, 21),
+    (0x2865, 0x0000, 24, 16), (0x24ef, 0x0000, 25, 15), (0x3987, 0x0000, 240, 8), (0x320e, 0x0000, 19, 241),
+    (0x2c99, 0x0000, 22, 242), (0x432a, 0x0000, 13, 7), (0x3b5f, 0x0000, 16, 10), (0x447d, 0x0000, 13, 245),
+    (0x5695, 0x0000, 10, 2), (0x5ece, 0x0000, 7, 1), (0x8000, 0x0000, 244, 83), (0x8000, 0x0000, 249, 250),
+    (0x5695, 0x0000, 10, 2), (0x481a, 0x0000, 89, 143), (0x481a, 0x0000, 230, 246),
+];
+
+/// Decoder of the ZP arithmetic coder, which BZZ compresses with.
+struct ZpDecoder<'a> {
+    input: &'a [u8],
+    a: u32,
+    code: u32,
+    /// Value of `a` under which the more probable bit comes next, to decode it quickly.
+    fence: u32,
+    buffer: u32,
+    /// Number of bits of `buffer` not yet moved into `code`.
+    bits: u32,
+    /// Number of bytes past the end of the input that may still be read as `0xff`, as DjVuLibre
+    /// does to end the input.
+    delay: u32,
+}
+
+impl<'a> ZpDecoder<'a> {
+    fn new(input: &'a [u8]) -> Result<Self, NativeReadingError> {
+        let mut decoder = Self { input, a: 0, code: 0, fence: 0, buffer: 0, bits: 0, delay: 25 };
+        for _ in 0..2 {
+            let byte = decoder.next_byte().unwrap_or(0xff);
+            decoder.code = decoder.code << 8 | u32::from(byte);
+        }
+        decoder.refill()?;
+        Ok(decoder)
+    }
+
+    fn next_byte(&mut self) -> Option<u8> {
+        let (byte, rest) = self.input.split_first()?;
+        self.input = rest;
+        Some(*byte)
+    }
+
+    /// Read more of the input once fewer than 16 bits are left in `buffer`.
+    fn refill(&mut self) -> Result<(), NativeReadingError> {
+        if self.bits < 16 {
+            while self.bits <= 24 {
+                let byte = match self.next_byte() {
+                    Some(byte) => byte,
+                    None if self.delay > 1 => {
+                        self.delay -= 1;
+                        0xff
+                    },
+                    None => return Err(NativeReadingError::Truncated),
+                };
+                self.buffer = self.buffer << 8 | u32::from(byte);
+                self.bits += 8;
+            }
+        }
+        self.fence = self.code.min(0x7fff);
+        Ok(())
+    }
+
+    /// Decode a bit whose probability is the state `context`, and update the state.
+    fn decode(&mut self, context: &mut u8) -> Result<bool, NativeReadingError> {
+        let (p, m, up, down) = ZP_STATES[usize::from(*context)];
+        let more_probable = *context & 1 == 1;
+        let z = self.a + u32::from(p);
+        if z <= self.fence {
+            self.a = z;
+            return Ok(more_probable);
+        }
+        // Keep the interval of the more probable bit the larger one.
+        let z = z.min(0x6000 + ((z + self.a) >> 2));
+        if z > self.code {
+            *context = down;
+            self.less_probable(z)?;
+            Ok(!more_probable)
+        } else {
+            if self.a >= u32::from(m) {
+                *context = up;
+            }
+            self.more_probable(z)?;
+            Ok(more_probable)
+        }
+    }
+
+    /// Decode a bit as likely to be set as not.
+    fn decode_even(&mut self) -> Result<bool, NativeReadingError> {
+        let z = 0x8000 + (self.a >> 1);
+        if z > self.code {
+            self.less_probable(z)?;
+            Ok(true)
+        } else {
+            self.more_probable(z)?;
+            Ok(false)
+        }
+    }
+
+    fn less_probable(&mut self, z: u32) -> Result<(), NativeReadingError> {
+        let z = 0x10000 - z;
+        self.a += z;
+        self.code += z;
+        let shift = (!(self.a as u16)).leading_zeros();
+        self.bits -= shift;
+        self.a = (self.a << shift) & 0xffff;
+        self.code = (self.code << shift) & 0xffff | (self.buffer >> self.bits) & ((1 << shift) - 1);
+        self.refill()
+    }
+
+    fn more_probable(&mut self, z: u32) -> Result<(), NativeReadingError> {
+        self.bits -= 1;
+        self.a = (z << 1) & 0xffff;
+        self.code = (self.code << 1) & 0xffff | (self.buffer >> self.bits) & 1;
+        self.refill()
+    }
+}
+
+/// Encoder of the ZP arithmetic coder, writing what `ZpDecoder` reads.
+struct ZpEncoder {
+    output: Vec<u8>,
+    a: u32,
+    subend: u32,
+    /// The last 24 bits coded, which a carry may still change.
+    buffer: u32,
+    /// Number of bits waiting for a carry to know their value.
+    run: u32,
+    byte: u8,
+    bits: u32,
+    /// Number of bits coded yet to be written, which the decoder starts with as `0xff`.
+    delay: u32,
+}
+
+impl ZpEncoder {
+    fn new() -> Self {
+        Self { output: Vec::new(), a: 0, subend: 0, buffer: 0xff_ffff, run: 0, byte: 0, bits: 0, delay: 25 }
+    }
+
+    fn write_bit(&mut self, bit: bool) {
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+        self.byte = self.byte << 1 | u8::from(bit);
+        self.bits += 1;
+        if self.bits == 8 {
+            self.output.push(self.byte);
+            self.bits = 0;
+            self.byte = 0;
+        }
+    }
+
+    /// Write the bit `bit`, or the bits waiting for a carry and then the bit if `bit` is set, the
+    /// lowest one of `self.buffer` having been -1, 0 or 1 before adding the bit.
+    fn emit(&mut self, bit: u32) {
+        self.buffer = (self.buffer << 1).wrapping_add(bit);
+        let out = self.buffer >> 24;
+        self.buffer &= 0xff_ffff;
+        match out {
+            0 => self.run += 1,
+            _ => {
+                self.write_bit(out == 1);
+                for _ in 0..self.run {
+                    self.write_bit(out != 1);
+                }
+                self.run = 0;
+            },
+        }
+    }
+
+    /// Encode `bit` with the probability of the state `context`, and update the state.
+    fn encode(&mut self, bit: bool, context: &mut u8) {
+        let (p, m, up, down) = ZP_STATES[usize::from(*context)];
+        let z = self.a + u32::from(p);
+        if bit != (*context & 1 == 1) {
+            *context = down;
+            self.less_probable(z.min(0x6000 + ((z + self.a) >> 2)));
+        } else if z >= 0x8000 {
+            if self.a >= u32::from(m) {
+                *context = up;
+            }
+            self.more_probable(z.min(0x6000 + ((z + self.a) >> 2)));
+        } else {
+            self.a = z;
+        }
+    }
+
+    fn encode_even(&mut self, bit: bool) {
+        let z = 0x8000 + (self.a >> 1);
+        match bit {
+            true => self.less_probable(z),
+            false => self.more_probable(z),
+        }
+    }
+
+    fn less_probable(&mut self, z: u32) {
+        let z = 0x10000 - z;
+        self.subend += z;
+        self.a += z;
+        while self.a >= 0x8000 {
+            self.shift();
+        }
+    }
+
+    fn more_probable(&mut self, z: u32) {
+        self.a = z;
+        if self.a >= 0x8000 {
+            self.shift();
+        }
+    }
+
+    fn shift(&mut self) {
+        // 1 - (subend >> 15), which is -1 on a carry.
+        self.emit(1u32.wrapping_sub(self.subend >> 15));
+        self.subend = (self.subend << 1) & 0xffff;
+        self.a = (self.a << 1) & 0xffff;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.subend = match self.subend {
+            0 => 0,
+            subend if subend > 0x8000 => 0x10000,
+            _ => 0x8000,
+        };
+        while self.buffer != 0xff_ffff || self.subend != 0 {
+            self.emit(1u32.wrapping_sub(self.subend >> 15));
+            self.subend = (self.subend << 1) & 0xffff;
+        }
+        self.write_bit(true);
+        for _ in 0..self.run {
+            self.write_bit(false);
+        }
+        while self.bits > 0 {
+            self.write_bit(true);
+        }
+        self.output
+    }
+}
+
+/// Number of ZP states BZZ codes the ranks of the characters with.
+const BZZ_CONTEXTS: usize = 300;
+/// Size of the largest block DjVuLibre decompresses.
+const BZZ_MAX_BLOCK: usize = 4096 * 1024;
+/// Size of the blocks DjVuLibre compresses outlines in.
+const BZZ_BLOCK: usize = 1024 * 1024;
+/// Number of places at the front of the list of characters whose frequency is kept.
+const FREQUENCIES: usize = 4;
+
+/// The characters of a block, the ones likely to come next first, ordered the same way when
+/// compressing and decompressing.
+struct MoveToFront {
+    characters: [u8; 256],
+    frequencies: [u32; FREQUENCIES],
+    increment: u32,
+    /// How fast the increment grows, so that the last characters seen weigh more.
+    speed: u32,
+}
+
+impl MoveToFront {
+    fn new(speed: u32) -> Self {
+        Self { characters: array::from_fn(|i| i as u8), frequencies: [0; FREQUENCIES], increment: 4, speed }
+    }
+
+    /// Move the character at `rank` to its place once seen again.
+    fn rotate(&mut self, rank: usize) {
+        let character = self.characters[rank];
+        self.increment = self.increment.wrapping_add(self.increment >> self.speed);
+        if self.increment > 0x1000_0000 {
+            self.increment >>= 24;
+            for frequency in &mut self.frequencies {
+                *frequency >>= 24;
+            }
+        }
+        let mut frequency = self.increment;
+        if rank < FREQUENCIES {
+            frequency = frequency.wrapping_add(self.frequencies[rank]);
+        }
+        let mut k = rank;
+        while k >= FREQUENCIES {
+            self.characters[k] = self.characters[k - 1];
+            k -= 1;
+        }
+        while k > 0 && frequency >= self.frequencies[k - 1] {
+            self.characters[k] = self.characters[k - 1];
+            self.frequencies[k] = self.frequencies[k - 1];
+            k -= 1;
+        }
+        self.characters[k] = character;
+        self.frequencies[k] = frequency;
+    }
+}
+
+/// Decompress `data`, compressed in the BZZ format of DjVuLibre: blocks of a Burrows-Wheeler
+/// transform, whose characters are replaced by their rank in a list of the characters ordered by
+/// how often they were seen last, coded with the ZP arithmetic coder.
+pub fn bzz_decompress(data: &[u8]) -> Result<Vec<u8>, NativeReadingError> {
+    let mut decoder = ZpDecoder::new(data)?;
+    let mut contexts = [0; BZZ_CONTEXTS];
+    let mut output = Vec::new();
+    loop {
+        let size = decode_number(|_| decoder.decode_even(), 24)?;
+        if size == 0 {
+            return Ok(output);
+        }
+        if size > BZZ_MAX_BLOCK {
+            return Err(NativeReadingError::CorruptCompression);
+        }
+        let mut speed = 0;
+        if decoder.decode_even()? {
+            speed += 1;
+            if decoder.decode_even()? {
+                speed += 1;
+            }
+        }
+        let mut characters = MoveToFront::new(speed);
+        let mut block = vec![0; size];
+        let mut marker = None;
+        let mut previous = 3;
+        for (i, character) in block.iter_mut().enumerate() {
+            match decode_rank(&mut decoder, &mut contexts, previous.min(2))? {
+                Some(rank) => {
+                    *character = characters.characters[rank];
+                    characters.rotate(rank);
+                    previous = rank;
+                },
+                None => {
+                    marker = Some(i);
+                    previous = 256;
+                },
+            }
+        }
+        output.extend(unsort_block(&block, marker.ok_or(NativeReadingError::CorruptCompression)?)?);
+    }
+}
+
+/// Compress `data` in the BZZ format of DjVuLibre, as `bzz_decompress` reads it.
+pub fn bzz_compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZpEncoder::new();
+    let mut contexts = [0; BZZ_CONTEXTS];
+    // The block holds an end marker besides the characters.
+    for block in data.chunks(BZZ_BLOCK - 1) {
+        let (block, marker) = sort_block(block);
+        encode_number(|_, bit| encoder.encode_even(bit), 24, block.len());
+        // Larger blocks are coded with frequencies adapting more slowly.
+        let speed = match block.len() {
+            size if size < 100_000 => 0,
+            size if size < 1_000_000 => 1,
+            _ => 2,
+        };
+        encoder.encode_even(speed > 0);
+        if speed > 0 {
+            encoder.encode_even(speed > 1);
+        }
+        let mut characters = MoveToFront::new(speed);
+        let mut previous = 3;
+        for (i, character) in block.iter().enumerate() {
+            let context = previous.min(2);
+            if i == marker {
+                encode_rank(&mut encoder, &mut contexts, context, None);
+                previous = 256;
+                continue;
+            }
+            let rank = characters.characters.iter().position(|c| c == character).unwrap_or_default();
+            encode_rank(&mut encoder, &mut contexts, context, Some(rank));
+            characters.rotate(rank);
+            previous = rank;
+        }
+    }
+    encode_number(|_, bit| encoder.encode_even(bit), 24, 0);
+    encoder.finish()
+}
+
+/// Decode a number of `bits` bits, the highest first, each with `decode_bit` given the bits read
+/// so far after a leading 1, which numbers the state of the bit.
+fn decode_number(
+    mut decode_bit: impl FnMut(usize) -> Result<bool, NativeReadingError>,
+    bits: u32,
+) -> Result<usize, NativeReadingError> {
+    let mut n = 1;
+    while n < 1 << bits {
+        n = n << 1 | usize::from(decode_bit(n)?);
+    }
+    Ok(n - (1 << bits))
+}
+
+/// Encode `value` on `bits` bits, as `decode_number` decodes it.
+fn encode_number(mut encode_bit: impl FnMut(usize, bool), bits: u32, value: usize) {
+    let mut n = 1;
+    for shift in (0..bits).rev() {
+        let bit = value >> shift & 1 == 1;
+        encode_bit(n, bit);
+        n = n << 1 | usize::from(bit);
+    }
+}
+
+/// Decode the rank of the next character of a block in the list of characters, or `None` for the
+/// end marker. The rank of 0 and 1 is coded with a state of its own for each `context`, the rank
+/// of the previous character up to 2, and larger ones by their highest bit and then the others
+/// with a state for each bit read so far.
+fn decode_rank(
+    decoder: &mut ZpDecoder,
+    contexts: &mut [u8; BZZ_CONTEXTS],
+    context: usize,
+) -> Result<Option<usize>, NativeReadingError> {
+    if decoder.decode(&mut contexts[context])? {
+        return Ok(Some(0));
+    }
+    if decoder.decode(&mut contexts[3 + context])? {
+        return Ok(Some(1));
+    }
+    let mut offset = 6;
+    for bits in 1..8 {
+        if decoder.decode(&mut contexts[offset])? {
+            let states = &mut contexts[offset..];
+            let rest = decode_number(|n| decoder.decode(&mut states[n]), bits)?;
+            return Ok(Some((1 << bits) + rest));
+        }
+        offset += 1 << bits;
+    }
+    Ok(None)
+}
+
+/// Encode `rank`, or the end marker for `None`, as `decode_rank` decodes it.
+fn encode_rank(encoder: &mut ZpEncoder, contexts: &mut [u8; BZZ_CONTEXTS], context: usize, rank: Option<usize>) {
+    let rank = rank.unwrap_or(256);
+    encoder.encode(rank == 0, &mut contexts[context]);
+    if rank == 0 {
+        return;
+    }
+    encoder.encode(rank == 1, &mut contexts[3 + context]);
+    if rank == 1 {
+        return;
+    }
+    let mut offset = 6;
+    for bits in 1..8 {
+        let in_range = rank < 2 << bits;
+        encoder.encode(in_range, &mut contexts[offset]);
+        if in_range {
+            let states = &mut contexts[offset..];
+            encode_number(|n, bit| encoder.encode(bit, &mut states[n]), bits, rank - (1 << bits));
+            return;
+        }
+        offset += 1 << bits;
+    }
+}
+
+/// Sort the rotations of `block` followed by an end marker, which comes before any character,
+/// and return the character before each one, with the position of the marker among them.
+fn sort_block(block: &[u8]) -> (Vec<u8>, usize) {
+    let n = block.len() + 1;
+    // The suffixes are ranked by their first character, then their first two, four... until
+    // they all differ.
+    let mut ranks: Vec<usize> = block.iter().map(|c| usize::from(*c) + 1).chain([0]).collect();
+    let mut order: Vec<usize> = (0..n).collect();
+    let mut length = 1;
+    loop {
+        let key = |i: usize| (ranks[i], ranks.get(i + length).map_or(0, |rank| rank + 1));
+        order.sort_unstable_by_key(|i| key(*i));
+        let mut next = vec![0; n];
+        for j in 1..n {
+            next[order[j]] = next[order[j - 1]] + usize::from(key(order[j - 1]) != key(order[j]));
+        }
+        ranks = next;
+        if ranks[order[n - 1]] == n - 1 {
+            break;
+        }
+        length *= 2;
+    }
+    let mut marker = 0;
+    let sorted = order.iter()
+        .enumerate()
+        .map(|(row, i)| match i.checked_sub(1) {
+            Some(before) => block[before],
+            None => {
+                marker = row;
+                0
+            },
+        })
+        .collect();
+    (sorted, marker)
+}
+
+/// Undo `sort_block`, given the characters before each rotation and the position of the marker.
+fn unsort_block(sorted: &[u8], marker: usize) -> Result<Vec<u8>, NativeReadingError> {
+    if marker < 1 || marker >= sorted.len() {
+        return Err(NativeReadingError::CorruptCompression);
+    }
+    // The rank of each character among the same ones, and where the rotations starting with each
+    // character start, after the one starting with the marker.
+    let mut counts = [0; 256];
+    let mut ranks = vec![0; sorted.len()];
+    for (i, character) in sorted.iter().enumerate() {
+        if i != marker {
+            ranks[i] = counts[usize::from(*character)];
+            counts[usize::from(*character)] += 1;
+        }
+    }
+    let mut starts = [0; 256];
+    let mut start = 1;
+    for (character, count) in counts.iter().enumerate() {
+        starts[character] = start;
+        start += count;
+    }
+
+    let mut block = vec![0; sorted.len() - 1];
+    let mut row = 0;
+    for character in block.iter_mut().rev() {
+        *character = sorted[row];
+        row = starts[usize::from(sorted[row])] + ranks[row];
+    }
+    match row == marker {
+        true => Ok(block),
+        false => Err(NativeReadingError::CorruptCompression),
+    }
+}
+
+/// The content of the `NAVM` chunk of the outline `compressed_outline` returns.
+#[cfg(test)]
+pub(crate) const COMPRESSED_OUTLINE: &[u8] = &[
+    255, 255, 195, 255, 21, 128, 33, 37, 174, 44, 10, 100, 230, 249, 143, 44, 11, 63, 64, 12, 28, 226,
+    50, 76, 46, 64, 32, 75, 116, 107, 156, 230, 134, 187, 250, 70, 110, 157, 140, 199, 180, 221, 167,
+    147, 108, 170, 98, 219, 210, 106, 109, 96, 150,
+];
+
+/// "Part I" on page 1, holding "Chapter 1" on page 2, then "Errata" linking to the component
+/// `p0009.djvu`.
+#[cfg(test)]
+pub(crate) fn compressed_outline() -> Nav {
+    use crate::nav::node;
+
+    let errata = NavNode {
+        string: String::from("Errata"),
+        link: BookmarkLink::PageLink(String::from("p0009.djvu")),
+        children: vec![],
+    };
+    Nav { nodes: vec![node("Part I", 1, vec![node("Chapter 1", 2, vec![])]), errata] }
+}
+
+/// Bundle `chunks` into a multi-page document, without the directory a real one would have.
+#[cfg(test)]
+pub(crate) fn document(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut form = Vec::from(*b"DJVM");
+    for (id, data) in chunks {
+        form.extend(*id);
+        form.extend((data.len() as u32).to_be_bytes());
+        form.extend(*data);
+        if data.len() % 2 == 1 {
+            form.push(0);
+        }
+    }
+    let mut file = Vec::from(*b"AT&TFORM");
+    file.extend((form.len() as u32).to_be_bytes());
+    file.extend(form);
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Cursor};
+
+    use super::*;
+    use crate::{nav::node, selftest::blank_page, tempfile::TempFile};
+
+    #[test]
+    fn outline_chunk_is_found() {
+        let page = blank_page(100, 100, 300);
+        let with_outline = document(&[(b"DIRM", b"odd"), (b"NAVM", b"outline"), (b"FORM", &page[8..])]);
+        let without_outline = document(&[(b"DIRM", b"odd"), (b"FORM", &page[8..])]);
+
+        let found = find_outline_chunk(&mut Cursor::new(&with_outline)).unwrap();
+        let (start, size) = found.unwrap();
+        assert_eq!(&with_outline[start as usize..][..size as usize], b"outline");
+        assert_eq!(find_outline_chunk(&mut Cursor::new(&without_outline)).unwrap(), None);
+        assert_eq!(find_outline_chunk(&mut Cursor::new(&page)).unwrap(), None);
+        assert!(matches!(
+            find_outline_chunk(&mut Cursor::new(&with_outline[..30])),
+            Err(NativeReadingError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn compressed_outline_is_read() {
+        assert_eq!(decode_bookmarks(&bzz_decompress(COMPRESSED_OUTLINE).unwrap()).unwrap(), compressed_outline());
+        let file = TempFile::new(".djvu").unwrap();
+        fs::write(file.path(), document(&[(b"DIRM", &[1, 0, 0]), (b"NAVM", COMPRESSED_OUTLINE)])).unwrap();
+        assert_eq!(read_outline(&file.path().to_string_lossy()).unwrap(), compressed_outline());
+
+        let mut corrupt = COMPRESSED_OUTLINE.to_vec();
+        corrupt[20] ^= 0x10;
+        assert!(matches!(bzz_decompress(&corrupt), Err(NativeReadingError::CorruptCompression)));
+    }
+
+    #[test]
+    fn pages_of_bundled_documents_are_counted() {
+        // The sizes, the flags and the names of a shared dictionary and two pages, compressed.
+        const COMPONENTS: &[u8] = &[
+            255, 255, 207, 191, 138, 28, 159, 114, 46, 199, 176, 40, 227, 42, 136, 123, 61, 138, 90, 64,
+            210, 223, 181, 172, 151, 95, 66, 4, 202, 119, 178, 28, 85, 98, 223, 155, 197,
+        ];
+        let mut directory = vec![0x81, 0, 3];
+        directory.extend([0; 12]);
+        directory.extend(COMPONENTS);
+
+        assert_eq!(count_pages(&directory).unwrap(), 2);
+        assert_eq!(count_pages(&[1, 0, 0]).unwrap(), 0);
+        assert!(matches!(count_pages(&directory[..2]), Err(NativeReadingError::Truncated)));
+    }
+
+    #[test]
+    fn compressed_outline_is_written() {
+        let data = encode_bookmarks(&compressed_outline()).unwrap();
+        assert_eq!(bzz_compress(&data), COMPRESSED_OUTLINE);
+        for data in [&b""[..], b"a", &[0; 1000], &Vec::from_iter(0..=255), &b"Part I#1Chapter 1#2".repeat(2000)] {
+            assert_eq!(bzz_decompress(&bzz_compress(data)).unwrap(), data);
+        }
+
+        let crowded = Nav { nodes: vec![node("Index", 1, vec![node("Entry", 1, vec![]); 256])] };
+        assert!(matches!(encode_bookmarks(&crowded), Err(NativeReadingError::OutlineTooLarge)));
+    }
+
+    /// A bundled document of two pages, with the offsets of the pages in its directory.
+    fn bundle(outline: Option<&[u8]>) -> Vec<u8> {
+        let page = blank_page(100, 100, 300);
+        let mut directory = vec![0x81, 0, 2];
+        directory.extend([0; 8]);
+        let mut chunks = vec![(b"DIRM", directory.as_slice())];
+        chunks.extend(outline.map(|outline| (b"NAVM", outline)));
+        chunks.extend([(b"FORM", &page[12..]), (b"FORM", &page[12..])]);
+        let mut file = document(&chunks);
+        let mut position = 16;
+        let mut pages = Vec::new();
+        while position < file.len() {
+            let size = u32::from_be_bytes(file[position + 4..position + 8].try_into().unwrap()) as usize;
+            if &file[position..position + 4] == b"FORM" {
+                pages.push(position as u32);
+            }
+            position += 8 + size + size % 2;
+        }
+        for (i, page) in pages.iter().enumerate() {
+            file[27 + 4 * i..31 + 4 * i].copy_from_slice(&page.to_be_bytes());
+        }
+        file
+    }
+
+    #[test]
+    fn outline_is_spliced() {
+        let file = TempFile::new(".djvu").unwrap();
+        let filename = file.path().to_string_lossy().into_owned();
+        let without_outline = bundle(None);
+        fs::write(file.path(), &without_outline).unwrap();
+
+        let longer = Nav { nodes: vec![node("Preface", 1, vec![]), node("Chapter 1", 2, vec![])] };
+        for nav in [compressed_outline(), longer] {
+            write_outline(&filename, &nav).unwrap();
+            assert_eq!(read_outline(&filename).unwrap(), nav);
+            let written = fs::read(file.path()).unwrap();
+            assert_eq!(u32::from_be_bytes(written[8..12].try_into().unwrap()) as usize, written.len() - 12);
+            for offset in written[27..35].chunks(4) {
+                let offset = u32::from_be_bytes(offset.try_into().unwrap()) as usize;
+                assert_eq!(&written[offset..offset + 12], &without_outline[36..48]);
+            }
+        }
+
+        write_outline(&filename, &Nav { nodes: vec![] }).unwrap();
+        assert_eq!(fs::read(file.path()).unwrap(), without_outline);
+        fs::write(file.path(), blank_page(100, 100, 300)).unwrap();
+        assert!(matches!(write_outline(&filename, &compressed_outline()), Err(NativeReadingError::SinglePage)));
+    }
+
+    #[test]
+    fn deep_bookmarks_are_decoded() {
+        const DEPTH: usize = 60_000;
+        let mut data = Vec::from((DEPTH as u16).to_be_bytes());
+        for depth in 0..DEPTH {
+            data.push(u8::from(depth + 1 < DEPTH));
+            data.extend([0, 0, 1, b'x', 0, 0, 2, b'#', b'1']);
+        }
+
+        let nav = decode_bookmarks(&data).unwrap();
+        assert_eq!(nav.nodes.len(), 1);
+        // A bookmark with a child past the count of bookmarks.
+        let orphan = [0, 1, 1, 0, 0, 1, b'x', 0, 0, 2, b'#', b'1'];
+        assert!(matches!(decode_bookmarks(&orphan), Err(NativeReadingError::InvalidOutline)));
+        // Dropping the nodes recursively would overflow the stack too.
+        std::mem::forget(nav);
+    }
+
+    #[test]
+    fn bookmarks_are_decoded() {
+        let mut data = vec![0, 3];
+        for (children, title, url) in [(1, "Part I", "#1"), (0, "Chapter 1", "#p0002.djvu"), (0, "Index", "#9")] {
+            data.push(children);
+            for field in [title, url] {
+                data.extend(&(field.len() as u32).to_be_bytes()[1..]);
+                data.extend(field.as_bytes());
+            }
+        }
+
+        let nav = decode_bookmarks(&data).unwrap();
+        assert_eq!(nav.nodes.len(), 2);
+        assert_eq!(nav.nodes[0].children[0].link, BookmarkLink::PageLink(String::from("p0002.djvu")));
+        assert_eq!(nav.nodes[1].link, BookmarkLink::PageNumber(9));
+        assert!(matches!(decode_bookmarks(&data[..data.len() - 1]), Err(NativeReadingError::Truncated)));
+    }
+}
@@ -1,13 +1,17 @@
 use nom::{
     IResult, Parser,
     multi::fold_many0,
-    bytes::complete::tag,
-    sequence::{delimited, preceded},
-    character::complete::{multispace0, u32},
+    bytes::complete::{tag, take_till1},
+    combinator::opt,
+    sequence::{delimited, preceded, tuple},
+    character::complete::{anychar, multispace0, space0, space1, u32},
     error::{ErrorKind, ParseError},
 };
 
-use crate::nav::{Nav, BookmarkLink, NavNode};
+use crate::{
+    nav::{Nav, BookmarkLink, NavNode},
+    djvu::PageInfo,
+};
 
 /// Parse `djvused` output into a `Nav` object.
 pub fn parse_djvu_nav(input: &str) -> IResult<&str, Nav> {
@@ -118,6 +122,49 @@ fn parse_node_interior(input: &str) -> IResult<&str, NavNode> {
     Ok((input, NavNode { string: name, link, children }))
 }
 
+/// Parse the output of the `print-meta` command of `djvused`, one `key "value"` line per entry,
+/// into the keys and values of the metadata.
+pub fn parse_metadata(input: &str) -> IResult<&str, Vec<(String, String)>> {
+    let entry = delimited(
+        multispace0,
+        tuple((take_till1(|c: char| c.is_whitespace()), preceded(space1, parse_quoted_string))),
+        multispace0,
+    );
+    let (input, entries) = fold_many0(
+        entry,
+        Vec::new,
+        |mut acc: Vec<_>, (key, value): (&str, String)| {
+            acc.push((String::from(key), value));
+            acc
+        })(input)?;
+    if !input.is_empty() {
+        return Err(nom::Err::Failure(nom::error::Error { input, code: ErrorKind::Eof }));
+    }
+    Ok((input, entries))
+}
+
+/// Parse the output of the `ls` command of `djvused` into the list of pages of the document.
+/// Lines describing components that are not pages (shared annotations, included files) are
+/// skipped.
+pub fn parse_page_list(input: &str) -> Vec<PageInfo> {
+    input.lines()
+        .filter_map(|line| parse_page_line(line).ok())
+        .map(|(_, page)| page)
+        .collect()
+}
+
+/// Parse a page line of `djvused`'s `ls` output, of the form
+/// `<number> <type> <size> <id>[ T=<quoted title>]`.
+fn parse_page_line(input: &str) -> IResult<&str, PageInfo> {
+    let (input, number) = preceded(space0, u32)(input)?;
+    let (input, _) = preceded(space1, anychar)(input)?;
+    let (input, _) = preceded(space1, u32)(input)?;
+    let (input, id) = preceded(space1, take_till1(|c: char| c.is_whitespace()))(input)?;
+    let (input, title) = opt(preceded(tuple((space1, tag("T="))), parse_quoted_string))(input)?;
+
+    Ok((input, PageInfo { number, id: String::from(id), title }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -295,4 +342,33 @@ mod tests {
             }
         )
     }
+
+    #[test]
+    fn metadata() {
+        let s = "title\t\"Les \\\"Mis\u{e9}rables\\\"\"\nyear\t\"1862\"\n";
+        assert_eq!(
+            parse_metadata(s),
+            Ok(("", vec![
+                (String::from("title"), String::from("Les \"Misérables\"")),
+                (String::from("year"), String::from("1862")),
+            ]))
+        );
+        assert_eq!(parse_metadata(""), Ok(("", vec![])));
+        assert!(parse_metadata("title \"unterminated").is_err());
+    }
+
+    #[test]
+    fn page_list() {
+        let s = r##"    1 P     9853 p0001.djvu
+    2 P    24023 p0002.djvu T="iii"
+      I      124 shared_anno.iff
+"##;
+        assert_eq!(
+            parse_page_list(s),
+            vec![
+                PageInfo { number: 1, id: "p0001.djvu".to_string(), title: None },
+                PageInfo { number: 2, id: "p0002.djvu".to_string(), title: Some("iii".to_string()) },
+            ]
+        )
+    }
 }
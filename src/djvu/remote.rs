@@ -0,0 +1,229 @@
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use crate::{
+    djvu::{native::{self, NativeReadingError}, ToolRunner},
+    tempfile::TempFile,
+    nav::Nav,
+};
+
+/// Number of bytes fetched at once. The outline of a bundled document comes right after its page
+/// directory, so a couple of requests are usually enough.
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// Whether `filename` is the URL of a remote file rather than a path.
+pub fn is_url(filename: &str) -> bool {
+    filename.starts_with("http://") || filename.starts_with("https://")
+}
+
+/// A file on an HTTP server, read with range requests made by `curl` so that only the parts that
+/// are read are downloaded.
+pub struct RemoteFile<'t, T: ToolRunner> {
+    tools: &'t T,
+    url: String,
+    position: u64,
+    /// Bytes fetched by the last request, and their position in the file.
+    block: Vec<u8>,
+    block_start: u64,
+    /// Size of the file, once a response told it.
+    length: Option<u64>,
+}
+
+impl<'t, T: ToolRunner> RemoteFile<'t, T> {
+    pub fn new(tools: &'t T, url: &str) -> Self {
+        Self { tools, url: String::from(url), position: 0, block: Vec::new(), block_start: 0, length: None }
+    }
+
+    /// Fetch the block of the file starting at `self.position`.
+    fn fetch(&mut self) -> io::Result<()> {
+        let range = format!("{}-{}", self.position, self.position + BLOCK_SIZE - 1);
+        let headers = TempFile::new(".headers").map_err(|e| io::Error::other(e.to_string()))?;
+        let headers_path = headers.path().display().to_string();
+        let args = [
+            "--silent", "--show-error", "--fail", "--location",
+            "--dump-header", &headers_path, "--range", &range, &self.url,
+        ];
+        let output = self.tools.output("curl", &args).map_err(|e| missing_tool(e, "curl"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(io::Error::other(format!("curl failed: {}", stderr.trim_end())));
+        }
+        match content_range(&fs::read_to_string(headers.path())?) {
+            Some((start, length)) => {
+                self.block_start = start;
+                self.length = length;
+            },
+            // Servers ignoring the range send the whole file.
+            None => {
+                self.block_start = 0;
+                self.length = Some(output.stdout.len() as u64);
+            },
+        }
+        self.block = output.stdout;
+        Ok(())
+    }
+}
+
+/// Return the first byte and the size of the file given by the `Content-Range` of a partial
+/// response in `headers`, as written by `curl --dump-header`. Only the last response counts, the
+/// others being redirections. Return `None` if the response holds the whole file.
+fn content_range(headers: &str) -> Option<(u64, Option<u64>)> {
+    let mut partial = false;
+    let mut range = None;
+    for line in headers.lines() {
+        if line.starts_with("HTTP/") {
+            partial = line.split_whitespace().nth(1) == Some("206");
+            range = None;
+        } else if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-range") {
+                // As in "bytes 0-65535/1048576", with "*" for an unknown size.
+                let (bytes, length) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+                let start = bytes.split_once('-')?.0.parse().ok()?;
+                range = Some((start, length.parse().ok()));
+            }
+        }
+    }
+    range.filter(|_| partial)
+}
+
+impl<'t, T: ToolRunner> Read for RemoteFile<'t, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let block_end = self.block_start + self.block.len() as u64;
+        if !(self.block_start..block_end).contains(&self.position) {
+            self.fetch()?;
+        }
+        let Some(available) = self.block.get((self.position - self.block_start) as usize..) else {
+            // Past the end of the file.
+            return Ok(0);
+        };
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<'t, T: ToolRunner> Seek for RemoteFile<'t, T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(offset) => offset,
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?,
+            // The size of the file is never needed.
+            SeekFrom::End(_) => return Err(io::Error::from(io::ErrorKind::Unsupported)),
+        };
+        Ok(self.position)
+    }
+}
+
+/// Tell apart a `program` missing from the `PATH` in `error`, which would otherwise only say that
+/// a file was not found.
+fn missing_tool(error: io::Error, program: &str) -> io::Error {
+    match error.kind() {
+        io::ErrorKind::NotFound => io::Error::other(format!("{} was not found in the PATH", program)),
+        _ => error,
+    }
+}
+
+/// Read the outline of the DjVu file at `url`, downloading only its headers and its `NAVM` chunk.
+pub fn read_outline(tools: &impl ToolRunner, url: &str) -> Result<Nav, NativeReadingError> {
+    let mut file = RemoteFile::new(tools, url);
+    let Some((start, size)) = native::find_outline_chunk(&mut file)? else {
+        return Ok(Nav { nodes: vec![] });
+    };
+    // The size comes from the file, so it is only trusted as far as the file goes.
+    if file.length.is_some_and(|length| start + u64::from(size) > length) {
+        return Err(NativeReadingError::Truncated);
+    }
+    let mut compressed = Vec::new();
+    file.seek(SeekFrom::Start(start))?;
+    file.by_ref().take(u64::from(size)).read_to_end(&mut compressed)?;
+    if compressed.len() < size as usize {
+        return Err(NativeReadingError::Truncated);
+    }
+    native::decode_bookmarks(&native::bzz_decompress(&compressed)?)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{cell::RefCell, os::unix::process::ExitStatusExt, process::{ExitStatus, Output}};
+
+    use super::*;
+    use crate::{djvu::native::{compressed_outline, COMPRESSED_OUTLINE}, selftest::blank_page};
+
+    /// Serves the ranges of `file` like `curl`.
+    struct MockServer {
+        file: Vec<u8>,
+        /// Whether to send the whole file whatever the range asked for.
+        ignores_ranges: bool,
+        requests: RefCell<Vec<String>>,
+    }
+
+    impl ToolRunner for MockServer {
+        fn output(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+            let stdout = match program {
+                "curl" => {
+                    let arg = |name| args[args.iter().position(|arg| *arg == name).unwrap() + 1];
+                    let range = arg("--range");
+                    self.requests.borrow_mut().push(String::from(range));
+                    let (first, last) = range.split_once('-').unwrap();
+                    let first = first.parse::<usize>().unwrap().min(self.file.len());
+                    let last = last.parse::<usize>().unwrap().min(self.file.len() - 1);
+                    if self.ignores_ranges {
+                        fs::write(arg("--dump-header"), "HTTP/1.1 302 Found\r\n\r\nHTTP/1.1 200 OK\r\n\r\n")?;
+                        self.file.clone()
+                    } else {
+                        let headers = format!("HTTP/2 206\r\ncontent-range: bytes {}-{}/{}\r\n\r\n", first, last, self.file.len());
+                        fs::write(arg("--dump-header"), headers)?;
+                        self.file[first..=last.max(first)].to_vec()
+                    }
+                },
+                _ => return Err(io::Error::from(io::ErrorKind::NotFound)),
+            };
+            Ok(Output { status: ExitStatus::from_raw(0), stdout, stderr: Vec::new() })
+        }
+    }
+
+    #[test]
+    fn outline_is_read_with_few_requests() {
+        let page = blank_page(100, 100, 300);
+        let file = native::document(&[
+            (b"DIRM", &[1, 0, 1]),
+            (b"NAVM", COMPRESSED_OUTLINE),
+            (b"FORM", &page[12..]),
+            (b"INCL", &[0; 200_000]),
+        ]);
+
+        let server = MockServer { file, ignores_ranges: false, requests: RefCell::new(Vec::new()) };
+        assert_eq!(read_outline(&server, "https://example.org/book.djvu").unwrap(), compressed_outline());
+        assert_eq!(*server.requests.borrow(), ["0-65535"]);
+    }
+
+    #[test]
+    fn servers_ignoring_ranges() {
+        let server = MockServer {
+            file: native::document(&[(b"DIRM", &[1, 0, 1]), (b"NAVM", COMPRESSED_OUTLINE)]),
+            ignores_ranges: true,
+            requests: RefCell::new(Vec::new()),
+        };
+        assert_eq!(read_outline(&server, "https://example.org/book.djvu").unwrap(), compressed_outline());
+
+        // The outline claims to go past the end of the file, which is not fetched again.
+        let mut file = native::document(&[(b"DIRM", &[1, 0, 1]), (b"NAVM", COMPRESSED_OUTLINE)]);
+        let size_offset = file.len() - COMPRESSED_OUTLINE.len() - 4;
+        file[size_offset..size_offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let server = MockServer { file, ignores_ranges: true, requests: RefCell::new(Vec::new()) };
+        assert!(matches!(read_outline(&server, "https://example.org/book.djvu"), Err(NativeReadingError::Truncated)));
+        assert_eq!(*server.requests.borrow(), ["0-65535"]);
+    }
+
+    #[test]
+    fn content_range_of_the_last_response() {
+        let redirected = "HTTP/1.1 301 Moved\r\nLocation: /b.djvu\r\n\r\nHTTP/1.1 206 Partial Content\r\nContent-Range: bytes 100-199/5000\r\n\r\n";
+        assert_eq!(content_range(redirected), Some((100, Some(5000))));
+        assert_eq!(content_range("HTTP/2 206\r\ncontent-range: bytes 0-9/*\r\n\r\n"), Some((0, None)));
+        assert_eq!(content_range("HTTP/2 200\r\ncontent-length: 10\r\n\r\n"), None);
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+};
+
+use crate::{
+    djvu::{TempFileError, ToolRunner},
+    APP_NAME,
+};
+
+#[derive(Debug)]
+pub enum TransferError {
+    /// `scp` or `ssh` could not be run.
+    IOError(io::Error),
+    /// The program that failed, its exit status and its error output.
+    CommandError(&'static str, ExitStatus, String),
+    TempFileError(TempFileError),
+}
+
+impl Display for TransferError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(e) => write!(f, "cannot run scp or ssh: {}", e),
+            Self::CommandError(program, status, stderr) => {
+                write!(f, "{} failed ({}): {}", program, status, stderr.trim_end())
+            },
+            Self::TempFileError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for TransferError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IOError(e) => Some(e),
+            Self::TempFileError(e) => Some(e),
+            Self::CommandError(..) => None,
+        }
+    }
+}
+
+/// A file on another machine reachable over SSH, written `[user@]host:path` as for `scp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshTarget {
+    /// The host, possibly preceded by a user name.
+    pub host: String,
+    pub path: String,
+}
+
+impl SshTarget {
+    /// Parse `input` as `[user@]host:path`. Like `scp`, a colon after a slash is part of a local
+    /// path, and so are one letter hosts, which are Windows drives.
+    pub fn parse(input: &str) -> Option<Self> {
+        if input.contains("://") {
+            return None;
+        }
+        let (host, path) = input.split_once(':')?;
+        let hostname = host.rsplit('@').next().unwrap_or_default();
+        if hostname.chars().count() < 2 || host.contains('/') || path.is_empty() {
+            return None;
+        }
+        Some(Self { host: String::from(host), path: String::from(path) })
+    }
+
+    /// Return the path where the file is copied while it is edited.
+    pub fn local_copy(&self) -> Result<PathBuf, TempFileError> {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_NAME)
+            .map_err(TempFileError::XDGSpecificError)?;
+        xdg_dirs.place_cache_file(format!("ssh/{}", self.local_name()))
+            .map_err(TempFileError::SystemIOError)
+    }
+
+    /// Return the name of the local copy: the name of the file preceded by a hash of the host and
+    /// the path, so that files of the same name in other directories or on other hosts do not
+    /// share their copy.
+    fn local_name(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        (&self.host, &self.path).hash(&mut hasher);
+        let name = Path::new(&self.path).file_name().unwrap_or_default().to_string_lossy();
+        format!("{:016x}-{}", hasher.finish(), name)
+    }
+}
+
+impl Display for SshTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.host, self.path)
+    }
+}
+
+/// Quote `s` for the shell running remote commands.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Run `program` with `args` with `tools`, and fail if it does.
+fn run(tools: &impl ToolRunner, program: &'static str, args: &[&str]) -> Result<(), TransferError> {
+    let output = tools.output(program, args).map_err(TransferError::IOError)?;
+    if !output.status.success() {
+        return Err(TransferError::CommandError(
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Copy the file `target` to `local` with `scp`.
+pub fn fetch(tools: &impl ToolRunner, target: &SshTarget, local: &Path) -> Result<(), TransferError> {
+    run(tools, "scp", &["-q", &target.to_string(), &local.display().to_string()])
+}
+
+/// Copy `local` back to `target`, first next to it and then over it, so that a transfer cut short
+/// does not damage the remote file. Runs in batch mode, since there is no terminal to ask for a
+/// password once the interface is shown: the key has to be in an agent or unencrypted.
+pub fn push(tools: &impl ToolRunner, local: &Path, target: &SshTarget) -> Result<(), TransferError> {
+    let temp_path = format!("{}.nav_edit.tmp", target.path);
+    let temp_target = SshTarget { host: target.host.clone(), path: temp_path.clone() };
+    run(tools, "scp", &["-q", "-B", &local.display().to_string(), &temp_target.to_string()])?;
+    let rename = format!("mv -- {} {}", shell_quote(&temp_path), shell_quote(&target.path));
+    run(tools, "ssh", &["-o", "BatchMode=yes", &target.host, &rename])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_are_told_apart_from_paths() {
+        assert_eq!(
+            SshTarget::parse("scan@archive:/srv/books/it's.djvu"),
+            Some(SshTarget { host: String::from("scan@archive"), path: String::from("/srv/books/it's.djvu") })
+        );
+        assert_eq!(SshTarget::parse("archive:book.djvu").unwrap().host, "archive");
+        assert_eq!(SshTarget::parse("books/a:b.djvu"), None);
+        assert_eq!(SshTarget::parse("C:\\books\\book.djvu"), None);
+        assert_eq!(SshTarget::parse("https://example.org/book.djvu"), None);
+        assert_eq!(SshTarget::parse("book.djvu"), None);
+
+        let name = |target| SshTarget::parse(target).unwrap().local_name();
+        assert!(name("archive:/a/book.djvu").ends_with("-book.djvu"));
+        assert_ne!(name("archive:/a/book.djvu"), name("archive:/b/book.djvu"));
+        assert_ne!(name("archive:/a/book.djvu"), name("mirror:/a/book.djvu"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn push_replaces_the_file_once_copied() {
+        use std::{cell::RefCell, os::unix::process::ExitStatusExt, process::Output};
+
+        struct Recorder(RefCell<Vec<String>>);
+
+        impl ToolRunner for Recorder {
+            fn output(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+                self.0.borrow_mut().push(format!("{} {}", program, args.join(" ")));
+                Ok(Output { status: ExitStatus::from_raw(0), stdout: Vec::new(), stderr: Vec::new() })
+            }
+        }
+
+        let tools = Recorder(RefCell::new(Vec::new()));
+        let target = SshTarget::parse("archive:/srv/it's.djvu").unwrap();
+        push(&tools, Path::new("/tmp/it's.djvu"), &target).unwrap();
+        assert_eq!(*tools.0.borrow(), [
+            "scp -q -B /tmp/it's.djvu archive:/srv/it's.djvu.nav_edit.tmp",
+            "ssh -o BatchMode=yes archive mv -- '/srv/it'\\''s.djvu.nav_edit.tmp' '/srv/it'\\''s.djvu'",
+        ]);
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    io::{self, Read},
+    process::{Command, Output, Stdio},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::tasks::CancelFlag;
+
+/// Runs the DjVuLibre command line tools, such as `djvused`. Implemented by `SystemTools` to run
+/// the installed programs, and by mocks in tests.
+pub trait ToolRunner {
+    /// Run `program` with `args`, wait for it to finish and return what it printed.
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<Output>;
+}
+
+/// Runs the tools found in the `PATH`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemTools;
+
+impl ToolRunner for SystemTools {
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+        Command::new(program).args(args).output()
+    }
+}
+
+/// How often `CancellableTools` checks whether the tool it runs is still wanted.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Runs the tools found in the `PATH` for a background job, killing them as soon as the job is
+/// cancelled rather than letting them finish for nothing.
+#[derive(Debug, Clone)]
+pub struct CancellableTools {
+    cancel: CancelFlag,
+}
+
+impl CancellableTools {
+    pub fn new(cancel: &CancelFlag) -> Self {
+        Self { cancel: cancel.clone() }
+    }
+
+    fn cancelled() -> io::Error {
+        io::Error::new(io::ErrorKind::Interrupted, "cancelled")
+    }
+}
+
+/// Read all of `pipe` in another thread, so that the tool writing to it never waits for room.
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut data = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut data);
+        }
+        data
+    })
+}
+
+impl ToolRunner for CancellableTools {
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+        if self.cancel.is_cancelled() {
+            return Err(Self::cancelled());
+        }
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdout = read_in_background(child.stdout.take());
+        let stderr = read_in_background(child.stderr.take());
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if self.cancel.is_cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Self::cancelled());
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        Ok(Output {
+            status,
+            stdout: stdout.join().unwrap_or_default(),
+            stderr: stderr.join().unwrap_or_default(),
+        })
+    }
+}
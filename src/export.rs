@@ -0,0 +1,473 @@
+use std::fmt::Display;
+
+use crate::{
+    chapters::{chapters, Chapter, ChapterError, Timestamps},
+    djvu::PageInfo,
+    history::format_identifier,
+    link::{page_number, LinkResolver, LinkTarget, UrlResolver},
+    nav::{Nav, NavNode},
+    text_outline::to_text,
+};
+
+/// Formats an outline can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// `{"nodes": [{"title": ..., "page": ..., "children": [...]}, ...]}`, where a link to a page
+    /// by name is stored under `link` instead of `page`.
+    Json,
+    /// One line per node, in document order, with its position in the tree and the number of
+    /// pages until the next node: `id,depth,title,page,span,children`.
+    Csv,
+    /// `pdfmark` operators, to add the outline to a PDF with Ghostscript:
+    /// `gs -o out.pdf -sDEVICE=pdfwrite in.pdf outline.ps`.
+    Pdfmark,
+    /// A bookmarks block in the format of `pdftk dump_data_utf8`, to add the outline to a PDF
+    /// with `pdftk in.pdf update_info_utf8 outline.txt output out.pdf`.
+    Pdftk,
+    /// One `<indent><title><tab><page>` line per node, to be edited by hand.
+    Text,
+    /// A nested list of links such as `- [Title](#page=3)`, to publish the table of contents.
+    Markdown,
+    /// The chapters of an audio book narrating the document, as an FFmpeg metadata file.
+    FfMetadata,
+    /// The chapters of an audio book narrating the document, as a cue sheet.
+    Cue,
+}
+
+pub const FORMAT_NAMES: [&str; 8] = ["json", "csv", "pdfmark", "pdftk", "txt", "markdown", "ffmetadata", "cue"];
+
+impl ExportFormat {
+    pub fn from_string(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "pdfmark" => Some(Self::Pdfmark),
+            "pdftk" => Some(Self::Pdftk),
+            "txt" => Some(Self::Text),
+            "markdown" => Some(Self::Markdown),
+            "ffmetadata" => Some(Self::FfMetadata),
+            "cue" => Some(Self::Cue),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    SerializationError(serde_json::Error),
+    /// The format places the entries in an audio recording, and no timestamps were given.
+    MissingTimestamps,
+    ChapterError(ChapterError),
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SerializationError(e) => write!(f, "cannot serialize the outline: {}", e),
+            Self::MissingTimestamps => write!(f, "audio chapters need the time at which pages are read (--timestamps)"),
+            Self::ChapterError(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+/// Return `nav` written in `format`. The pages of the document, if known, are used to resolve
+/// links to pages by name and to know where the last node ends, and `timestamps` to place the
+/// nodes in an audio recording of the document.
+pub fn export(
+    nav: &Nav,
+    format: ExportFormat,
+    pages: Option<&[PageInfo]>,
+    timestamps: Option<&Timestamps>,
+) -> Result<String, ExportError> {
+    match format {
+        ExportFormat::Json => serde_json::to_string_pretty(nav)
+            .map(|mut json| {
+                json.push('\n');
+                json
+            })
+            .map_err(ExportError::SerializationError),
+        ExportFormat::Csv => Ok(statistics_csv(nav, pages)),
+        ExportFormat::Pdfmark => {
+            let mut pdfmark = String::new();
+            push_pdfmarks(&mut pdfmark, &nav.nodes, pages);
+            Ok(pdfmark)
+        },
+        ExportFormat::Pdftk => Ok(pdftk_bookmarks(nav, pages)),
+        ExportFormat::Text => Ok(to_text(nav)),
+        ExportFormat::Markdown => Ok(markdown_list(nav, pages)),
+        ExportFormat::FfMetadata | ExportFormat::Cue => {
+            let timestamps = timestamps.ok_or(ExportError::MissingTimestamps)?;
+            let chapters = chapters(nav, pages, timestamps).map_err(ExportError::ChapterError)?;
+            match format {
+                ExportFormat::FfMetadata => ffmetadata(&chapters, timestamps),
+                _ => cue_sheet(&chapters, timestamps),
+            }
+            .map_err(ExportError::ChapterError)
+        },
+    }
+}
+
+/// Return `template` with each `{name}` replaced by the value of the field `name` of `fields`.
+/// Placeholders of unknown fields are left as they are, and values are not searched for
+/// placeholders.
+fn fill(template: &str, fields: &[(&str, &dyn Display)]) -> String {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}')
+            .and_then(|end| fields.iter().find(|(name, _)| *name == &rest[1..end]).map(|(_, value)| (end, value)));
+        match value {
+            Some((end, value)) => {
+                filled.push_str(&value.to_string());
+                rest = &rest[end + 1..];
+            },
+            None => {
+                filled.push('{');
+                rest = &rest[1..];
+            },
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// The bookmark of an entry in the format of `pdftk dump_data_utf8`.
+const PDFTK_BOOKMARK: &str = "BookmarkBegin\nBookmarkTitle: {title}\nBookmarkLevel: {level}\nBookmarkPageNumber: {page}\n";
+/// A chapter of an FFmpeg metadata file, with times in milliseconds.
+const FFMETADATA_CHAPTER: &str = "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={start}\nEND={end}\ntitle={title}\n";
+/// A track of a cue sheet, starting at minutes, seconds and frames of 1/75 second.
+const CUE_TRACK: &str = "  TRACK {track} AUDIO\n    TITLE {title}\n    INDEX 01 {minutes}:{seconds}:{frames}\n";
+
+/// Append the `pdfmark` of each node of `nodes`, followed by those of its descendants. Closed
+/// nodes have a negative count of children. Nodes pointing to a URL open it, and nodes pointing
+/// neither to a page nor to a URL have no destination.
+fn push_pdfmarks(pdfmark: &mut String, nodes: &[NavNode], pages: Option<&[PageInfo]>) {
+    for node in nodes {
+        pdfmark.push('[');
+        if !node.children.is_empty() {
+            pdfmark.push_str(&format!("/Count -{} ", node.children.len()));
+        }
+        pdfmark.push_str(&format!("/Title {}", postscript_string(&node.string)));
+        match (page_number(&node.link, pages), UrlResolver.resolve(&node.link, &[])) {
+            (Some(page), _) => pdfmark.push_str(&format!(" /Page {}", page)),
+            (None, Some(LinkTarget::Url(url))) => {
+                pdfmark.push_str(&format!(" /Action << /Subtype /URI /URI {} >>", postscript_string(&url)));
+            },
+            _ => (),
+        }
+        pdfmark.push_str(" /OUT pdfmark\n");
+        push_pdfmarks(pdfmark, &node.children, pages);
+    }
+}
+
+/// Write `s` as a PostScript string: in parentheses if it is ASCII, or else in hexadecimal
+/// UTF-16 with a byte order mark, which is how PDF expects text outside of its own encoding.
+fn postscript_string(s: &str) -> String {
+    if s.is_ascii() {
+        let escaped = s.replace('\\', "\\\\").replace('(', "\\(").replace(')', "\\)");
+        // Line breaks would end up in the title.
+        format!("({})", escaped.replace('\n', "\\n").replace('\r', "\\r"))
+    } else {
+        let hex: String = s.encode_utf16().map(|unit| format!("{:04X}", unit)).collect();
+        format!("<FEFF{}>", hex)
+    }
+}
+
+/// Write the outline as the bookmarks of `pdftk dump_data_utf8`. pdftk needs a page for every
+/// bookmark, so nodes not pointing to a page point to the page of the node before them.
+fn pdftk_bookmarks(nav: &Nav, pages: Option<&[PageInfo]>) -> String {
+    let mut bookmarks = String::new();
+    let mut previous_page = 1;
+    for (id, node) in nav.all_nodes() {
+        let page = page_number(&node.link, pages).unwrap_or(previous_page);
+        previous_page = page;
+        // pdftk decodes entities in titles, and would misread a line break.
+        let title = node.string
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace(['\n', '\r'], " ");
+        bookmarks.push_str(&fill(PDFTK_BOOKMARK, &[("title", &title), ("level", &id.len()), ("page", &page)]));
+    }
+    bookmarks
+}
+
+fn statistics_csv(nav: &Nav, pages: Option<&[PageInfo]>) -> String {
+    let nodes = nav.all_nodes();
+    let page_numbers: Vec<_> = nodes.iter()
+        .map(|(_, node)| page_number(&node.link, pages))
+        .collect();
+    // The last node goes on until the end of the document.
+    let end = pages.map(|pages| pages.len() as u32 + 1);
+
+    let mut csv = String::from("id,depth,title,page,span,children\n");
+    for (i, (id, node)) in nodes.iter().enumerate() {
+        let page = page_numbers[i];
+        let next = page_numbers[i + 1..].iter().find_map(|n| *n).or(end);
+        let span = page.zip(next).and_then(|(page, next)| next.checked_sub(page));
+        let optional = |n: Option<u32>| n.map(|n| n.to_string()).unwrap_or_default();
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            format_identifier(id),
+            id.len(),
+            csv_field(&node.string),
+            optional(page),
+            optional(span),
+            node.children.len(),
+        ));
+    }
+    csv
+}
+
+/// Write the outline as a nested Markdown list, each item linking to its page with the `#page=`
+/// fragment most viewers understand, or to its URL. Items pointing to neither are not links.
+fn markdown_list(nav: &Nav, pages: Option<&[PageInfo]>) -> String {
+    let mut markdown = String::new();
+    for (id, node) in nav.all_nodes() {
+        let mut title = String::new();
+        for c in node.string.chars() {
+            match c {
+                '\\' | '[' | ']' | '*' | '_' | '`' | '<' => {
+                    title.push('\\');
+                    title.push(c);
+                },
+                '\n' | '\r' => title.push(' '),
+                c => title.push(c),
+            }
+        }
+        let target = match (page_number(&node.link, pages), UrlResolver.resolve(&node.link, &[])) {
+            (Some(page), _) => Some(format!("#page={}", page)),
+            (None, Some(LinkTarget::Url(url))) => Some(url.replace(' ', "%20").replace(')', "%29")),
+            _ => None,
+        };
+        let item = match target {
+            Some(target) => format!("[{}]({})", title, target),
+            None => title,
+        };
+        markdown.push_str(&format!("{}- {}\n", "  ".repeat(id.len() - 1), item));
+    }
+    markdown
+}
+
+/// Escape the characters with a meaning in FFmpeg metadata files.
+fn ffmetadata_value(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Write `chapters` as an FFmpeg metadata file, to add them to the recording with
+/// `ffmpeg -i book.mp3 -i chapters.txt -map_metadata 1 -codec copy book.m4b`. Each chapter ends
+/// where the next one starts, and the last one at the end of the recording.
+fn ffmetadata(chapters: &[Chapter], timestamps: &Timestamps) -> Result<String, ChapterError> {
+    let end = timestamps.end.ok_or(ChapterError::MissingEnd)?;
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for (i, chapter) in chapters.iter().enumerate() {
+        let chapter_end = chapters.get(i + 1).map_or(end, |next| next.start);
+        let title = ffmetadata_value(chapter.title);
+        metadata.push_str(&fill(FFMETADATA_CHAPTER, &[("start", &chapter.start), ("end", &chapter_end), ("title", &title)]));
+    }
+    Ok(metadata)
+}
+
+/// Write `chapters` as a cue sheet with one track per chapter.
+fn cue_sheet(chapters: &[Chapter], timestamps: &Timestamps) -> Result<String, ChapterError> {
+    let file = timestamps.file.as_deref().ok_or(ChapterError::MissingFile)?;
+    let lowercase = file.to_lowercase();
+    let file_type = if lowercase.ends_with(".wav") {
+        "WAVE"
+    } else if lowercase.ends_with(".aif") || lowercase.ends_with(".aiff") {
+        "AIFF"
+    } else {
+        "MP3"
+    };
+    // Quotes cannot be escaped in cue sheets.
+    let quoted = |s: &str| format!("\"{}\"", s.replace('"', "'").replace(['\n', '\r'], " "));
+    let mut cue = format!("FILE {} {}\n", quoted(file), file_type);
+    for (i, chapter) in chapters.iter().enumerate() {
+        let frames = chapter.start * 75 / 1000;
+        let two_digits = |n: u64| format!("{:02}", n);
+        cue.push_str(&fill(CUE_TRACK, &[
+            ("track", &two_digits(i as u64 + 1)),
+            ("title", &quoted(chapter.title)),
+            ("minutes", &two_digits(frames / 75 / 60)),
+            ("seconds", &two_digits(frames / 75 % 60)),
+            ("frames", &two_digits(frames % 75)),
+        ]));
+    }
+    Ok(cue)
+}
+
+/// Quote `field` if it contains a character with a meaning in CSV.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::{node, BookmarkLink, NavNode};
+
+    #[test]
+    fn json_export() {
+        let nav = Nav {
+            nodes: vec![NavNode {
+                string: String::from("Chapter 1"),
+                link: BookmarkLink::PageNumber(3),
+                children: vec![NavNode {
+                    string: String::from("Plate"),
+                    link: BookmarkLink::PageLink(String::from("p0012.djvu")),
+                    children: vec![],
+                }],
+            }],
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&export(&nav, ExportFormat::Json, None, None).unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!({
+            "nodes": [{
+                "title": "Chapter 1",
+                "page": 3,
+                "children": [{"title": "Plate", "link": "p0012.djvu"}],
+            }]
+        }));
+    }
+
+    #[test]
+    fn pdf_outlines() {
+        let nav = Nav {
+            nodes: vec![
+                NavNode {
+                    string: String::from("Part (I)"),
+                    link: BookmarkLink::PageNumber(3),
+                    children: vec![NavNode {
+                        string: String::from("Préface & notes"),
+                        link: BookmarkLink::PageNumber(4),
+                        children: vec![],
+                    }],
+                },
+                NavNode {
+                    string: String::from("Errata"),
+                    link: BookmarkLink::PageLink(String::from("https://example.org/errata")),
+                    children: vec![],
+                },
+            ],
+        };
+
+        assert_eq!(export(&nav, ExportFormat::Pdfmark, None, None).unwrap(), concat!(
+            "[/Count -1 /Title (Part \\(I\\)) /Page 3 /OUT pdfmark\n",
+            "[/Title <FEFF0050007200E90066006100630065002000260020006E006F007400650073> /Page 4 /OUT pdfmark\n",
+            "[/Title (Errata) /Action << /Subtype /URI /URI (https://example.org/errata) >> /OUT pdfmark\n",
+        ));
+        assert_eq!(export(&nav, ExportFormat::Pdftk, None, None).unwrap(), concat!(
+            "BookmarkBegin\nBookmarkTitle: Part (I)\nBookmarkLevel: 1\nBookmarkPageNumber: 3\n",
+            "BookmarkBegin\nBookmarkTitle: Préface &amp; notes\nBookmarkLevel: 2\nBookmarkPageNumber: 4\n",
+            "BookmarkBegin\nBookmarkTitle: Errata\nBookmarkLevel: 1\nBookmarkPageNumber: 4\n",
+        ));
+    }
+
+    #[test]
+    fn markdown_export() {
+        let nav = Nav {
+            nodes: vec![NavNode {
+                string: String::from("Part [I]"),
+                link: BookmarkLink::PageNumber(3),
+                children: vec![
+                    NavNode {
+                        string: String::from("Errata"),
+                        link: BookmarkLink::PageLink(String::from("https://example.org/errata")),
+                        children: vec![],
+                    },
+                    NavNode {
+                        string: String::from("Plate"),
+                        link: BookmarkLink::PageLink(String::from("p0012.djvu")),
+                        children: vec![],
+                    },
+                ],
+            }],
+        };
+
+        assert_eq!(export(&nav, ExportFormat::Markdown, None, None).unwrap(), concat!(
+            "- [Part \\[I\\]](#page=3)\n",
+            "  - [Errata](https://example.org/errata)\n",
+            "  - Plate\n",
+        ));
+    }
+
+    #[test]
+    fn csv_statistics() {
+        let pages: Vec<_> = (1..=20)
+            .map(|number| PageInfo { number, id: format!("p{:04}.djvu", number), title: None })
+            .collect();
+        let nav = Nav {
+            nodes: vec![
+                NavNode {
+                    string: String::from("Part 1, \"Origins\""),
+                    link: BookmarkLink::PageNumber(3),
+                    children: vec![NavNode {
+                        string: String::from("Plate"),
+                        link: BookmarkLink::PageLink(String::from("p0008.djvu")),
+                        children: vec![],
+                    }],
+                },
+                NavNode {
+                    string: String::from("Part 2"),
+                    link: BookmarkLink::PageNumber(12),
+                    children: vec![],
+                },
+            ],
+        };
+
+        assert_eq!(export(&nav, ExportFormat::Csv, Some(&pages), None).unwrap(), concat!(
+            "id,depth,title,page,span,children\n",
+            "1,1,\"Part 1, \"\"Origins\"\"\",3,5,1\n",
+            "1.1,2,Plate,8,4,0\n",
+            "2,1,Part 2,12,9,0\n",
+        ));
+    }
+
+    #[test]
+    fn audio_chapters() {
+        let timestamps = Timestamps::parse("1 0\n11 10:00\n21 1:00:00.5\nend 1:10:00\nfile \"book.mp3\"\n").unwrap();
+        let nav = Nav {
+            nodes: vec![
+                node("Part One", 1, vec![node("Chapter 1 = Start", 6, vec![])]),
+                node("Part \"Two\"", 21, vec![]),
+            ],
+        };
+        assert_eq!(export(&nav, ExportFormat::FfMetadata, None, Some(&timestamps)).unwrap(), concat!(
+            ";FFMETADATA1\n",
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=300000\ntitle=Part One\n",
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=300000\nEND=3600500\ntitle=Chapter 1 \\= Start\n",
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=3600500\nEND=4200000\ntitle=Part \"Two\"\n",
+        ));
+        assert_eq!(export(&nav, ExportFormat::Cue, None, Some(&timestamps)).unwrap(), concat!(
+            "FILE \"book.mp3\" MP3\n",
+            "  TRACK 01 AUDIO\n    TITLE \"Part One\"\n    INDEX 01 00:00:00\n",
+            "  TRACK 02 AUDIO\n    TITLE \"Chapter 1 = Start\"\n    INDEX 01 05:00:00\n",
+            "  TRACK 03 AUDIO\n    TITLE \"Part 'Two'\"\n    INDEX 01 60:00:37\n",
+        ));
+        assert!(matches!(
+            export(&nav, ExportFormat::FfMetadata, None, Some(&Timestamps::default())),
+            Err(ExportError::ChapterError(ChapterError::PageNotCovered(1))),
+        ));
+        let no_end = Timestamps { end: None, ..timestamps };
+        assert!(matches!(
+            export(&nav, ExportFormat::FfMetadata, None, Some(&no_end)),
+            Err(ExportError::ChapterError(ChapterError::MissingEnd)),
+        ));
+        assert_eq!(fill("{a}{b} {unknown} {", &[("a", &"{b}"), ("b", &1)]), "{b}1 {unknown} {");
+    }
+}
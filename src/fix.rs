@@ -0,0 +1,309 @@
+use crate::{
+    casing::recase,
+    config::PolicyConfig,
+    djvu::PageInfo,
+    heading::guess_heading,
+    history::format_identifier,
+    link::{LinkResolvers, LinkTarget},
+    lint::Finding,
+    nav::{BookmarkLink, Nav, NavNode},
+    tree_widget::{TreeIdentifier, TreeIdentifierVec},
+};
+
+/// An automatic correction for a problem reported by the linter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuickFix {
+    /// Bring a page number past either end of the document back to the first or last page.
+    ClampPage,
+    /// Replace a link to a page by name with a link to its page number.
+    ConvertToPageNumber,
+    /// Replace a link to a page number with a link to its page by name.
+    ConvertToPageName,
+    /// Remove control characters and the characters forbidden by the policy from the title.
+    StripCharacters,
+    /// Cut the title down to the maximum length allowed by the policy.
+    TruncateTitle,
+    /// Take the heading of the target page as the title of an entry without one.
+    FillTitle,
+    /// Remove an entry that has neither a title nor children.
+    DeleteEmptyLeaf,
+    /// Change the case of the title to follow the policy.
+    FixCasing,
+}
+
+pub const ALL_FIXES: [QuickFix; 8] = [
+    QuickFix::ClampPage,
+    QuickFix::ConvertToPageNumber,
+    QuickFix::ConvertToPageName,
+    QuickFix::StripCharacters,
+    QuickFix::TruncateTitle,
+    QuickFix::FillTitle,
+    QuickFix::DeleteEmptyLeaf,
+    QuickFix::FixCasing,
+];
+
+/// What the fixes need to know about the document besides its outline.
+pub struct FixContext<'a> {
+    pub pages: Option<&'a [PageInfo]>,
+    pub policy: &'a PolicyConfig,
+    pub resolvers: &'a LinkResolvers,
+    /// Return the text of a page by number, if it can be read.
+    pub page_text: &'a dyn Fn(u32) -> Option<String>,
+}
+
+impl QuickFix {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::ClampPage => "Clamp page to the document",
+            Self::ConvertToPageNumber => "Convert link to a page number",
+            Self::ConvertToPageName => "Convert link to a page name",
+            Self::StripCharacters => "Strip forbidden characters from the title",
+            Self::TruncateTitle => "Truncate title",
+            Self::FillTitle => "Fill the empty title from the page text",
+            Self::DeleteEmptyLeaf => "Delete empty entry",
+            Self::FixCasing => "Fix the case of the title",
+        }
+    }
+
+    /// Return the fixes that would change the node `id` of `nav`.
+    pub fn applicable(
+        nav: &Nav,
+        id: TreeIdentifier,
+        context: &FixContext,
+    ) -> Vec<Self> {
+        ALL_FIXES.into_iter()
+            .filter(|fix| fix.preview(nav, id, context).is_some())
+            .collect()
+    }
+
+    /// Return the nodes `self` would change among those with `findings`, in document order.
+    pub fn targets(
+        self,
+        nav: &Nav,
+        findings: &[Finding],
+        context: &FixContext,
+    ) -> Vec<TreeIdentifierVec> {
+        let mut ids: Vec<_> = findings.iter().map(|finding| finding.id.clone()).collect();
+        ids.dedup();
+        ids.into_iter()
+            .filter(|id| self.preview(nav, id, context).is_some())
+            .collect()
+    }
+
+    /// Describe the change `self` would make to the node `id` of `nav`, or return `None` if it
+    /// would not change it.
+    pub fn preview(
+        self,
+        nav: &Nav,
+        id: TreeIdentifier,
+        context: &FixContext,
+    ) -> Option<String> {
+        let node = nav.get(id)?;
+        let before = format!("{}: \"{}\" #{}", format_identifier(id), node.string, node.link);
+        if self == Self::DeleteEmptyLeaf {
+            return is_empty_leaf(node).then(|| format!("{} -> deleted", before));
+        }
+        let mut fixed = node.clone();
+        self.fix_node(&mut fixed, context)
+            .then(|| format!("{} -> \"{}\" #{}", before, fixed.string, fixed.link))
+    }
+
+    /// Apply `self` to the node `id` of `nav`, returning whether the outline was changed.
+    pub fn apply(
+        self,
+        nav: &mut Nav,
+        id: TreeIdentifier,
+        context: &FixContext,
+    ) -> bool {
+        if nav.get(id).is_none() {
+            return false;
+        }
+        if self == Self::DeleteEmptyLeaf {
+            if !is_empty_leaf(&nav[id]) {
+                return false;
+            }
+            nav.delete_entry(id);
+            return true;
+        }
+        self.fix_node(&mut nav[id], context)
+    }
+
+    /// Apply `self` to all the nodes `ids` of `nav`, given in document order. Return the number
+    /// of nodes changed.
+    pub fn apply_all(
+        self,
+        nav: &mut Nav,
+        ids: &[TreeIdentifierVec],
+        context: &FixContext,
+    ) -> usize {
+        // Going backwards, so that deleting a node does not shift the identifiers still to visit.
+        ids.iter()
+            .rev()
+            .filter(|id| self.apply(nav, id, context))
+            .count()
+    }
+
+    /// Apply `self` to `node` alone, returning whether it was changed.
+    fn fix_node(self, node: &mut NavNode, context: &FixContext) -> bool {
+        let policy = context.policy;
+        let new_link = match (self, context.resolvers.resolve(&node.link, context.pages)) {
+            (Self::ClampPage, LinkTarget::PageOutOfRange(n, count)) if count > 0 => {
+                Some(BookmarkLink::PageNumber(n.clamp(1, count as u32)))
+            },
+            (Self::ConvertToPageNumber, LinkTarget::Page(page))
+                if matches!(node.link, BookmarkLink::PageLink(_)) =>
+            {
+                Some(BookmarkLink::PageNumber(page.number))
+            },
+            (Self::ConvertToPageName, LinkTarget::Page(page))
+                if matches!(node.link, BookmarkLink::PageNumber(_)) =>
+            {
+                Some(BookmarkLink::PageLink(page.id.clone()))
+            },
+            _ => None,
+        };
+        if let Some(link) = new_link {
+            node.link = link;
+            return true;
+        }
+
+        let new_title = match self {
+            Self::StripCharacters => {
+                let forbidden = policy.forbidden_characters.as_deref().unwrap_or_default();
+                node.string.chars()
+                    .filter(|c| !c.is_control() && !forbidden.contains(*c))
+                    .collect()
+            },
+            Self::TruncateTitle => match policy.max_title_length {
+                Some(max) => node.string.chars().take(max).collect::<String>().trim_end().to_owned(),
+                None => node.string.clone(),
+            },
+            Self::FixCasing => match policy.casing {
+                Some(style) => recase(
+                    &node.string,
+                    style,
+                    policy.casing_language.unwrap_or_default(),
+                    policy.casing_minor_words.as_deref().unwrap_or_default(),
+                ),
+                None => node.string.clone(),
+            },
+            Self::FillTitle if node.string.trim().is_empty() => {
+                title_from_page(node, context).unwrap_or_else(|| node.string.clone())
+            },
+            _ => node.string.clone(),
+        };
+        if new_title != node.string {
+            node.string = new_title;
+            return true;
+        }
+        false
+    }
+}
+
+/// Return the heading of the page `node` points to, to give it as a title.
+pub fn title_from_page(node: &NavNode, context: &FixContext) -> Option<String> {
+    let page = match context.resolvers.resolve(&node.link, context.pages) {
+        LinkTarget::Page(page) => page.number,
+        LinkTarget::Unresolved(BookmarkLink::PageNumber(n)) => *n,
+        _ => return None,
+    };
+    guess_heading(&(context.page_text)(page)?)
+}
+
+fn is_empty_leaf(node: &NavNode) -> bool {
+    node.string.trim().is_empty() && node.children.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+    use crate::link::LinkResolver;
+
+    #[test]
+    fn fixes_offered_for_out_of_range_page() {
+        let pages = vec![PageInfo { number: 1, id: String::from("p1.djvu"), title: None }];
+        let mut nav = Nav {
+            nodes: vec![NavNode {
+                string: String::from("Index\u{7}"),
+                link: BookmarkLink::PageNumber(7),
+                children: vec![],
+            }],
+        };
+        let context = FixContext { pages: Some(&pages), policy: &PolicyConfig::default(), resolvers: &LinkResolvers::default(), page_text: &|_| None };
+
+        assert_eq!(
+            QuickFix::applicable(&nav, &[0], &context),
+            vec![QuickFix::ClampPage, QuickFix::StripCharacters]
+        );
+        assert!(QuickFix::ClampPage.apply(&mut nav, &[0], &context));
+        assert_eq!(nav.nodes[0].link, BookmarkLink::PageNumber(1));
+        assert!(QuickFix::StripCharacters.apply(&mut nav, &[0], &context));
+        assert_eq!(nav.nodes[0].string, "Index");
+    }
+
+    #[test]
+    fn delete_all_empty_leaves() {
+        let empty = || NavNode::default();
+        let mut nav = Nav {
+            nodes: vec![
+                empty(),
+                NavNode { string: String::from("Part"), children: vec![empty(), empty()], ..empty() },
+                NavNode { children: vec![NavNode { string: String::from("a"), ..empty() }], ..empty() },
+            ],
+        };
+        let ids = vec![vec![0], vec![1, 0], vec![1, 1], vec![2]];
+
+        let context = FixContext { pages: None, policy: &PolicyConfig::default(), resolvers: &LinkResolvers::default(), page_text: &|_| None };
+        let changed = QuickFix::DeleteEmptyLeaf.apply_all(&mut nav, &ids, &context);
+        assert_eq!(changed, 3);
+        assert_eq!(nav.nodes.len(), 2);
+        assert!(nav.nodes[0].children.is_empty());
+    }
+
+    #[test]
+    fn empty_titles_filled_from_page_text() {
+        let mut nav = Nav { nodes: vec![node("", 3, vec![]), node("Preface", 1, vec![]), node(" ", 9, vec![])] };
+        let page_text = |page| (page == 3).then(|| String::from("12\nChapter 1\nThe story begins here."));
+        let context = FixContext { pages: None, policy: &PolicyConfig::default(), resolvers: &LinkResolvers::default(), page_text: &page_text };
+
+        assert_eq!(QuickFix::applicable(&nav, &[0], &context), vec![QuickFix::FillTitle, QuickFix::DeleteEmptyLeaf]);
+        assert_eq!(QuickFix::FillTitle.preview(&nav, &[1], &context), None);
+        // The page of the last entry has no text to take a title from.
+        let ids = vec![vec![0], vec![1], vec![2]];
+        assert_eq!(QuickFix::FillTitle.apply_all(&mut nav, &ids, &context), 1);
+        assert_eq!(nav.nodes[0].string, "Chapter 1");
+    }
+
+    /// Resolves links to pages by their title.
+    struct TitleResolver;
+
+    impl LinkResolver for TitleResolver {
+        fn resolve<'a>(&self, link: &'a BookmarkLink, pages: &'a [PageInfo]) -> Option<LinkTarget<'a>> {
+            let BookmarkLink::PageLink(s) = link else {
+                return None;
+            };
+            pages.iter().find(|page| page.title.as_deref() == Some(s)).map(LinkTarget::Page)
+        }
+    }
+
+    #[test]
+    fn links_resolved_by_the_registered_resolvers() {
+        let pages = vec![
+            PageInfo { number: 1, id: String::from("p1.djvu"), title: None },
+            PageInfo { number: 2, id: String::from("p2.djvu"), title: Some(String::from("Intro")) },
+        ];
+        let mut nav = Nav {
+            nodes: vec![NavNode { string: String::from("Introduction"), link: BookmarkLink::PageLink(String::from("Intro")), children: vec![] }],
+        };
+        let builtin = LinkResolvers::default();
+        let mut context = FixContext { pages: Some(&pages), policy: &PolicyConfig::default(), resolvers: &builtin, page_text: &|_| None };
+        assert_eq!(QuickFix::ConvertToPageNumber.preview(&nav, &[0], &context), None);
+
+        let mut resolvers = LinkResolvers::default();
+        resolvers.register(Box::new(TitleResolver));
+        context.resolvers = &resolvers;
+        assert!(QuickFix::ConvertToPageNumber.apply(&mut nav, &[0], &context));
+        assert_eq!(nav.nodes[0].link, BookmarkLink::PageNumber(2));
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt::Display;
+
+use chrono::{Days, Months, NaiveDate};
+
+use crate::{
+    djvu::PageInfo,
+    nav::{BookmarkLink, NavNode},
+};
+
+/// How the entries of a generated skeleton outline are spread across the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spacing {
+    /// One entry every given number of pages.
+    Every(u32),
+    /// A given number of entries in total.
+    Count(u32),
+}
+
+/// Time between two issues of a periodical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Days(u32),
+    Months(u32),
+}
+
+impl Frequency {
+    /// Parse `daily`, `weekly`, `biweekly`, `monthly`, `quarterly`, or a number of days or months
+    /// such as `3d` or `2m`.
+    pub fn from_string(input: &str) -> Option<Self> {
+        match input {
+            "daily" => Some(Self::Days(1)),
+            "weekly" => Some(Self::Days(7)),
+            "biweekly" | "fortnightly" => Some(Self::Days(14)),
+            "monthly" => Some(Self::Months(1)),
+            "quarterly" => Some(Self::Months(3)),
+            _ => {
+                let (n, unit) = input.split_at(input.len().checked_sub(1)?);
+                let n = n.parse().ok().filter(|n| *n > 0)?;
+                match unit {
+                    "d" => Some(Self::Days(n)),
+                    "m" => Some(Self::Months(n)),
+                    _ => None,
+                }
+            },
+        }
+    }
+
+    /// Date of the issue `index` issues after the one published on `start`.
+    fn nth_date(self, start: NaiveDate, index: u32) -> Option<NaiveDate> {
+        match self {
+            Self::Days(n) => start.checked_add_days(Days::new(u64::from(n) * u64::from(index))),
+            Self::Months(n) => start.checked_add_months(Months::new(n.checked_mul(index)?)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum IssueListError {
+    /// The line with the given number (starting at 1) is not of the form `<title>,<page>`.
+    InvalidLine(usize),
+}
+
+impl Display for IssueListError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLine(n) => write!(f, "line {} is not of the form <title>,<page>", n),
+        }
+    }
+}
+
+impl std::error::Error for IssueListError {}
+
+/// Generate bookmarks spread evenly across `pages` according to `spacing`, as waypoints for
+/// documents without a table of contents. Each bookmark is titled after its page label, or its
+/// page number if the page has no label.
+pub fn evenly_spaced(pages: &[PageInfo], spacing: Spacing) -> Vec<NavNode> {
+    let page_count = pages.len();
+    let indices: Vec<usize> = match spacing {
+        Spacing::Every(step) => (0..page_count).step_by(step as usize).collect(),
+        Spacing::Count(count) => {
+            let count = (count as usize).min(page_count);
+            (0..count).map(|i| i * page_count / count).collect()
+        },
+    };
+    indices.into_iter()
+        .map(|i| {
+            let page = &pages[i];
+            NavNode {
+                string: format!("Page {}", page.title.as_deref().unwrap_or(&page.number.to_string())),
+                link: BookmarkLink::PageNumber(page.number),
+                children: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+/// Generate one bookmark per issue of a periodical volume with `page_count` pages, the first
+/// issue being published on `start`, and every issue being `pages_per_issue` pages long. Each
+/// bookmark is titled after the date of its issue, e.g. `1923-03-14`.
+pub fn periodical_issues(
+    page_count: usize,
+    start: NaiveDate,
+    frequency: Frequency,
+    pages_per_issue: u32,
+) -> Vec<NavNode> {
+    (0..)
+        .map(|index: u32| (index, 1 + index as usize * pages_per_issue as usize))
+        .take_while(|(_, page)| *page <= page_count)
+        .map_while(|(index, page)| {
+            let date = frequency.nth_date(start, index)?;
+            Some(NavNode {
+                string: date.format("%Y-%m-%d").to_string(),
+                link: BookmarkLink::PageNumber(page as u32),
+                children: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// Generate one bookmark per line of `content`, a CSV list of issue boundaries of the form
+/// `<title>,<first page>`. Empty lines, lines starting with `#`, and a header line are ignored.
+pub fn issues_from_csv(content: &str) -> Result<Vec<NavNode>, IssueListError> {
+    let mut nodes = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = line.rsplit_once(',')
+            .and_then(|(title, page)| Some((title.trim().trim_matches('"'), page.trim().parse().ok()?)));
+        match parsed {
+            Some((title, page)) => nodes.push(NavNode {
+                string: String::from(title),
+                link: BookmarkLink::PageNumber(page),
+                children: Vec::new(),
+            }),
+            None if index == 0 => continue,
+            None => return Err(IssueListError::InvalidLine(index + 1)),
+        }
+    }
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages(count: u32) -> Vec<PageInfo> {
+        (1..=count)
+            .map(|number| PageInfo { number, id: format!("p{}.djvu", number), title: None })
+            .collect()
+    }
+
+    fn targets(nodes: &[NavNode]) -> Vec<BookmarkLink> {
+        nodes.iter().map(|node| node.link.clone()).collect()
+    }
+
+    #[test]
+    fn every_n_pages() {
+        let nodes = evenly_spaced(&pages(60), Spacing::Every(25));
+        assert_eq!(targets(&nodes), [1, 26, 51].map(BookmarkLink::PageNumber));
+        assert_eq!(nodes[1].string, "Page 26");
+    }
+
+    #[test]
+    fn fixed_count() {
+        let nodes = evenly_spaced(&pages(10), Spacing::Count(4));
+        assert_eq!(targets(&nodes), [1, 3, 6, 8].map(BookmarkLink::PageNumber));
+    }
+
+    #[test]
+    fn monthly_issues() {
+        let start = NaiveDate::from_ymd_opt(1923, 1, 31).unwrap();
+        let nodes = periodical_issues(30, start, Frequency::Months(1), 12);
+        let titles: Vec<_> = nodes.iter().map(|node| node.string.as_str()).collect();
+        assert_eq!(titles, ["1923-01-31", "1923-02-28", "1923-03-31"]);
+        assert_eq!(nodes[2].link, BookmarkLink::PageNumber(25));
+    }
+
+    #[test]
+    fn issues_csv() {
+        let csv = "date,page\n1923-03-14,1\n\n1923-03-21, 17\n";
+        let nodes = issues_from_csv(csv).unwrap();
+        assert_eq!(targets(&nodes), [1, 17].map(BookmarkLink::PageNumber));
+        assert_eq!(nodes[1].string, "1923-03-21");
+        assert!(issues_from_csv("1923-03-14,1\n1923-03-21").is_err());
+    }
+
+    #[test]
+    fn frequencies() {
+        assert_eq!(Frequency::from_string("weekly"), Some(Frequency::Days(7)));
+        assert_eq!(Frequency::from_string("2m"), Some(Frequency::Months(2)));
+        assert_eq!(Frequency::from_string("0d"), None);
+        assert_eq!(Frequency::from_string("often"), None);
+    }
+}
@@ -0,0 +1,207 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use regex_lite::Regex;
+use serde::Deserialize;
+
+use crate::{
+    dedup::normalize_title,
+    nav::{BookmarkLink, NavNode},
+};
+
+/// Number of lines from the top of a page considered when looking for its heading.
+const LINES_CONSIDERED: usize = 15;
+/// Lines longer than this are body text, not headings.
+const MAX_HEADING_LENGTH: usize = 100;
+
+/// Lines found on more pages than this are running headers, not headings.
+const MAX_REPEATS: usize = 2;
+
+/// Patterns used when none are configured, with the level of the headings they find: parts,
+/// chapters and appendices, sections numbered such as `2.1 Title`, and lines in capitals.
+const DEFAULT_HEADING_PATTERNS: [(&str, usize); 4] = [
+    (r"(?i)^part\s+([0-9]+|[ivxlc]+|one|two|three|four|five|six|seven|eight|nine|ten)\b", 1),
+    (r"(?i)^(chapter|appendix)\s+\S+", 2),
+    (r"^[0-9]+\.[0-9]+\.?\s+[A-Z]", 3),
+    (r"^[A-Z][A-Z0-9 ,'&:.-]{3,}$", 2),
+];
+
+/// Lines of the text of a page matching `pattern` are headings of `level`, 1 being the top level.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeadingPattern {
+    pub pattern: String,
+    pub level: usize,
+}
+
+pub fn default_heading_patterns() -> Vec<HeadingPattern> {
+    DEFAULT_HEADING_PATTERNS.iter()
+        .map(|(pattern, level)| HeadingPattern { pattern: String::from(*pattern), level: *level })
+        .collect()
+}
+
+#[derive(Debug)]
+pub struct InvalidPatternError(String, regex_lite::Error);
+
+impl Display for InvalidPatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid heading pattern \"{}\": {}", self.0, self.1)
+    }
+}
+
+impl std::error::Error for InvalidPatternError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.1)
+    }
+}
+
+/// Propose an outline of a document whose pages have the text `texts`, the first page first: one
+/// entry per line matching one of `patterns`, the first that matches giving its level, nested
+/// under the closest entry above it of a lower level. Lines repeated on many pages are left out.
+pub fn outline_from_text(texts: &[String], patterns: &[HeadingPattern]) -> Result<Vec<NavNode>, InvalidPatternError> {
+    let regexes = patterns.iter()
+        .map(|heading| Regex::new(&heading.pattern)
+            .map(|regex| (regex, heading.level))
+            .map_err(|e| InvalidPatternError(heading.pattern.clone(), e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut headings = Vec::new();
+    for (page, text) in (1..).zip(texts) {
+        for line in text.lines() {
+            let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+            let letters = line.chars().filter(|c| c.is_alphabetic()).count();
+            if line.chars().count() > MAX_HEADING_LENGTH || letters < 2 {
+                continue;
+            }
+            if let Some((_, level)) = regexes.iter().find(|(regex, _)| regex.is_match(&line)) {
+                headings.push((page, *level, line));
+            }
+        }
+    }
+
+    let mut pages: HashMap<String, HashSet<u32>> = HashMap::new();
+    for (page, _, title) in &headings {
+        pages.entry(normalize_title(title)).or_default().insert(*page);
+    }
+    let mut nodes: Vec<NavNode> = Vec::new();
+    // Levels of the last heading at each depth, down to the last heading added.
+    let mut levels: Vec<usize> = Vec::new();
+    for (page, level, title) in headings {
+        if pages[&normalize_title(&title)].len() > MAX_REPEATS {
+            continue;
+        }
+        while levels.last().is_some_and(|last| *last >= level) {
+            levels.pop();
+        }
+        let mut siblings = &mut nodes;
+        for _ in 0..levels.len() {
+            siblings = &mut siblings.last_mut().unwrap().children;
+        }
+        siblings.push(NavNode { string: title, link: BookmarkLink::PageNumber(page), children: vec![] });
+        levels.push(level);
+    }
+    Ok(nodes)
+}
+
+/// Words that commonly introduce a heading.
+const HEADING_WORDS: [&str; 7] = ["chapter", "part", "section", "book", "appendix", "lecture", "lesson"];
+
+/// Return the line of `text` that looks the most like a heading, among the first lines of the
+/// page.
+pub fn guess_heading(text: &str) -> Option<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .take(LINES_CONSIDERED)
+        .enumerate()
+        .filter_map(|(position, line)| heading_score(line).map(|score| (score - position as i32, line)))
+        // Keep the first line in case of a tie, which `max_by_key` would not.
+        .fold(None, |best, candidate| match best {
+            Some((best_score, _)) if best_score >= candidate.0 => best,
+            _ => Some(candidate),
+        })
+        .map(|(_, line)| line.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// How much `line` looks like a heading, or `None` if it cannot be one.
+fn heading_score(line: &str) -> Option<i32> {
+    let length = line.chars().count();
+    let letters: Vec<char> = line.chars().filter(|c| c.is_alphabetic()).collect();
+    if length > MAX_HEADING_LENGTH || letters.len() < 2 {
+        return None;
+    }
+
+    let mut score = 0;
+    let first_word = line.split_whitespace().next().unwrap_or_default().to_lowercase();
+    if HEADING_WORDS.contains(&first_word.as_str()) {
+        score += 6;
+    }
+    if starts_with_numbering(line) {
+        score += 4;
+    }
+    if letters.len() >= 3 && letters.iter().all(|c| !c.is_lowercase()) {
+        score += 4;
+    }
+    if length <= 60 {
+        score += 2;
+    }
+    if line.ends_with(['.', ',', ';', '-']) {
+        score -= 4;
+    }
+    Some(score)
+}
+
+/// Whether `line` starts with a section number such as `3`, `2.1` or `IV.`.
+fn starts_with_numbering(line: &str) -> bool {
+    let first_word = line.split_whitespace().next().unwrap_or_default();
+    let number = first_word.trim_end_matches(['.', ')']);
+    !number.is_empty()
+        && line.split_whitespace().nth(1).is_some()
+        && (number.chars().all(|c| c.is_ascii_digit() || c == '.')
+            || number.chars().all(|c| "IVXLC".contains(c)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::Nav;
+
+    #[test]
+    fn chapter_heading_beats_running_header() {
+        let text = "12\nA HISTORY OF ALGEBRA\n\nChapter 3  The  Cubic\nIn the year 1545, Cardano published.\n";
+        assert_eq!(guess_heading(text), Some(String::from("Chapter 3 The Cubic")));
+    }
+
+    #[test]
+    fn no_heading_in_blank_page() {
+        assert_eq!(guess_heading("\n  \n 42 \n"), None);
+    }
+
+    #[test]
+    fn outline_from_headings() {
+        let texts: Vec<String> = [
+            "A HISTORY OF ALGEBRA\n",
+            "A HISTORY OF ALGEBRA\nPart One\nChapter 1 Beginnings\nIn the year 1545.\n",
+            "A HISTORY OF ALGEBRA\n1.1 Babylon\nSome text.\n1.2  Egypt\n",
+            "A HISTORY OF ALGEBRA\nChapter 2 The Cubic\n",
+            "INDEX\n",
+        ].iter().map(|text| String::from(*text)).collect();
+        let nodes = outline_from_text(&texts, &default_heading_patterns()).unwrap();
+        let nav = Nav { nodes };
+        let titles: Vec<_> = nav.all_nodes().into_iter()
+            .map(|(id, node)| (id, node.string.as_str(), node.link.to_string()))
+            .collect();
+        assert_eq!(titles, [
+            (vec![0], "Part One", String::from("2")),
+            (vec![0, 0], "Chapter 1 Beginnings", String::from("2")),
+            (vec![0, 0, 0], "1.1 Babylon", String::from("3")),
+            (vec![0, 0, 1], "1.2 Egypt", String::from("3")),
+            (vec![0, 1], "Chapter 2 The Cubic", String::from("4")),
+            (vec![0, 2], "INDEX", String::from("5")),
+        ]);
+
+        assert!(outline_from_text(&texts, &[HeadingPattern { pattern: String::from("("), level: 1 }]).is_err());
+    }
+}
@@ -0,0 +1,568 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    mem,
+    rc::{Rc, Weak},
+    time::SystemTime,
+};
+#[cfg(feature = "tui")]
+use std::time::Duration;
+
+#[cfg(feature = "tui")]
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::{
+    metadata::Metadata,
+    nav::{BookmarkLink, Nav, NavNode},
+    report::{Report, ReportRow},
+    tree_widget::{TreeIdentifier, TreeIdentifierVec},
+};
+
+/// Number of steps kept in the history when the configuration does not say.
+pub const DEFAULT_HISTORY_LIMIT: usize = 1000;
+
+/// A node of a snapshot of the outline, stored once for all the snapshots it appears in.
+#[derive(Debug)]
+struct SharedNode {
+    string: String,
+    link: BookmarkLink,
+    children: Vec<Rc<SharedNode>>,
+}
+
+impl SharedNode {
+    fn to_node(&self) -> NavNode {
+        NavNode {
+            string: self.string.clone(),
+            link: self.link.clone(),
+            children: self.children.iter().map(|child| child.to_node()).collect(),
+        }
+    }
+
+    /// Return the number of bytes taken by `self`, without its children.
+    fn size(&self) -> usize {
+        let link = match &self.link {
+            BookmarkLink::PageLink(link) => link.capacity(),
+            BookmarkLink::PageNumber(_) => 0,
+        };
+        // An `Rc` holds two counters besides its value.
+        2 * mem::size_of::<usize>()
+            + mem::size_of::<Self>()
+            + self.string.capacity()
+            + link
+            + self.children.capacity() * mem::size_of::<Rc<Self>>()
+    }
+}
+
+/// The nodes of the snapshots by hash, so that a subtree found in several snapshots is stored
+/// once. Nodes are dropped along with the last snapshot holding them.
+#[derive(Debug, Default)]
+struct Interner {
+    nodes: HashMap<u64, Vec<Weak<SharedNode>>>,
+}
+
+impl Interner {
+    fn snapshot(&mut self, nav: &Nav) -> Snapshot {
+        Snapshot { nodes: nav.nodes.iter().map(|node| self.intern(node).0).collect() }
+    }
+
+    /// Return the shared copy of `node`, and its hash.
+    fn intern(&mut self, node: &NavNode) -> (Rc<SharedNode>, u64) {
+        let mut hasher = DefaultHasher::new();
+        node.string.hash(&mut hasher);
+        node.link.hash(&mut hasher);
+        let children: Vec<_> = node.children.iter()
+            .map(|child| {
+                let (child, hash) = self.intern(child);
+                hash.hash(&mut hasher);
+                child
+            })
+            .collect();
+        let hash = hasher.finish();
+
+        let candidates = self.nodes.entry(hash).or_default();
+        candidates.retain(|candidate| candidate.strong_count() > 0);
+        // The children are interned already, so comparing them takes comparing pointers.
+        let existing = candidates.iter()
+            .filter_map(Weak::upgrade)
+            .find(|candidate| {
+                candidate.string == node.string
+                    && candidate.link == node.link
+                    && candidate.children.len() == children.len()
+                    && candidate.children.iter().zip(&children).all(|(a, b)| Rc::ptr_eq(a, b))
+            });
+        let shared = existing.unwrap_or_else(|| {
+            let shared = Rc::new(SharedNode { string: node.string.clone(), link: node.link.clone(), children });
+            candidates.push(Rc::downgrade(&shared));
+            shared
+        });
+        (shared, hash)
+    }
+
+    /// Forget the nodes no snapshot holds anymore.
+    fn prune(&mut self) {
+        self.nodes.retain(|_, candidates| {
+            candidates.retain(|candidate| candidate.strong_count() > 0);
+            !candidates.is_empty()
+        });
+    }
+
+    /// Return the number of bytes taken by the nodes of the snapshots.
+    fn size(&self) -> usize {
+        self.nodes.values()
+            .flatten()
+            .filter_map(Weak::upgrade)
+            .map(|node| node.size())
+            .sum()
+    }
+}
+
+/// A state of the outline, sharing its unchanged subtrees with the other states of the history.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    nodes: Vec<Rc<SharedNode>>,
+}
+
+impl Snapshot {
+    fn to_nav(&self) -> Nav {
+        Nav { nodes: self.nodes.iter().map(|node| node.to_node()).collect() }
+    }
+
+    #[cfg(feature = "tui")]
+    fn get(&self, id: TreeIdentifier) -> Option<&SharedNode> {
+        let (first, rest) = id.split_first()?;
+        let mut node = self.nodes.get(*first)?;
+        for index in rest {
+            node = node.children.get(*index)?;
+        }
+        Some(node)
+    }
+}
+
+/// What steps change besides the outline, kept whole in each step since it is small.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Extras {
+    /// The metadata of the document, `None` until they are read.
+    pub metadata: Option<Metadata>,
+    /// The titles of the pages of the document in order, empty if the pages could not be listed.
+    pub page_titles: Vec<Option<String>>,
+}
+
+/// A single undoable step: the state of the outline right before the step was applied.
+///
+/// A step may be made of several elementary edits (a bulk rename, an import), in which case all
+/// of them are undone at once.
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub label: String,
+    pub timestamp: SystemTime,
+    /// Nodes touched by the step, as identifiers in the state right after it.
+    pub affected: Vec<TreeIdentifierVec>,
+    nav: Snapshot,
+    extras: Extras,
+    selected: TreeIdentifierVec,
+}
+
+impl HistoryEntry {
+    fn new(label: &str, nav: Snapshot, extras: &Extras, selected: TreeIdentifier) -> Self {
+        Self {
+            label: String::from(label),
+            timestamp: SystemTime::now(),
+            affected: Vec::new(),
+            nav,
+            extras: extras.clone(),
+            selected: selected.to_owned(),
+        }
+    }
+
+    pub fn nav(&self) -> Nav {
+        self.nav.to_nav()
+    }
+
+    pub fn extras(&self) -> &Extras {
+        &self.extras
+    }
+
+    pub fn selected(&self) -> TreeIdentifier<'_> {
+        &self.selected
+    }
+}
+
+/// Undo log of the edits made to a `Nav` and its `Extras`.
+///
+/// Edits are recorded as transactions: `begin` snapshots the outline, `commit` closes the
+/// transaction and pushes it to the log if the outline or its extras actually changed. Transactions can be
+/// nested, in which case only the outermost one is recorded, so that a compound command is
+/// undone in one go no matter how many smaller commands it is built from.
+///
+/// Undone steps can be redone until a new step is recorded. Past `limit` steps, the oldest ones
+/// are forgotten.
+///
+/// The states of the outline share their unchanged subtrees, so that a step costs the memory of
+/// the entries it changed rather than of the whole outline.
+#[derive(Debug)]
+pub struct History {
+    undo_stack: Vec<HistoryEntry>,
+    /// Undone steps, the last undone one on top. Each entry holds the state right after the step.
+    redo_stack: Vec<HistoryEntry>,
+    /// Label, state and selection before the transaction being recorded.
+    pending: Option<(String, Nav, Extras, TreeIdentifierVec)>,
+    depth: usize,
+    limit: usize,
+    interner: Interner,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::with_limit(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl History {
+    /// Return an empty history keeping at most `limit` steps.
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            pending: None,
+            depth: 0,
+            limit,
+            interner: Interner::default(),
+        }
+    }
+
+    pub fn begin(&mut self, label: &str, nav: &Nav, extras: &Extras, selected: TreeIdentifier) {
+        if self.depth == 0 {
+            self.pending = Some((String::from(label), nav.clone(), extras.clone(), selected.to_owned()));
+        }
+        self.depth += 1;
+    }
+
+    pub fn commit(&mut self, nav: &Nav, extras: &Extras) {
+        if self.depth == 0 {
+            return;
+        }
+        self.depth -= 1;
+        if self.depth > 0 {
+            return;
+        }
+        if let Some((label, before, extras_before, selected)) = self.pending.take() {
+            if before != *nav || extras_before != *extras {
+                let mut entry = HistoryEntry::new(&label, self.interner.snapshot(&before), &extras_before, &selected);
+                entry.affected = before.changed_nodes(nav);
+                self.undo_stack.push(entry);
+                self.redo_stack.clear();
+                if self.undo_stack.len() > self.limit {
+                    let excess = self.undo_stack.len() - self.limit;
+                    self.undo_stack.drain(..excess);
+                }
+                self.interner.prune();
+            }
+        }
+    }
+
+    /// Return the number of bytes taken by the states of the outline kept in the history, roughly.
+    pub fn memory_usage(&self) -> usize {
+        let top_levels: usize = self.undo_stack.iter()
+            .chain(&self.redo_stack)
+            .map(|entry| entry.nav.nodes.capacity() * mem::size_of::<Rc<SharedNode>>())
+            .sum();
+        self.interner.size() + top_levels
+    }
+
+    /// Recorded steps, oldest first.
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.undo_stack
+    }
+
+    /// State of the outline right after step `index`, `current` being the state after the last
+    /// one.
+    pub fn state_after(&self, index: usize, current: &Nav) -> Nav {
+        self.undo_stack.get(index + 1).map_or_else(|| current.clone(), |entry| entry.nav())
+    }
+
+    /// Render the list of recorded steps to `area`, newest first, along with the changes made by
+    /// the one selected in `state`.
+    #[cfg(feature = "tui")]
+    pub fn ui(&self, f: &mut Frame, area: Rect, state: &mut ListState, current: &Nav) {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+
+        let now = SystemTime::now();
+        let items: Vec<_> = self.undo_stack.iter()
+            .enumerate()
+            .rev()
+            .map(|(i, entry)| {
+                let age = now.duration_since(entry.timestamp).unwrap_or_default();
+                ListItem::new(format!("{}. {} ({} ago)", i + 1, entry.label, format_duration(age)))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(format!(
+                "History ({} steps, {})",
+                self.undo_stack.len(),
+                format_size(self.memory_usage()),
+            )))
+            .highlight_style(Style::default().fg(Color::Black).bg(Color::LightGreen))
+            .highlight_symbol("> ");
+        f.render_stateful_widget(list, chunks[0], state);
+
+        let details = state.selected()
+            .and_then(|row| self.undo_stack.len().checked_sub(row + 1))
+            .map(|index| self.describe_step(index, current))
+            .unwrap_or_default();
+        let paragraph = Paragraph::new(details)
+            .block(Block::default().borders(Borders::ALL).title("Changes"))
+            .wrap(Wrap { trim: false });
+        f.render_widget(paragraph, chunks[1]);
+    }
+
+    /// One line per node affected by step `index`, showing its state before and after the step.
+    #[cfg(feature = "tui")]
+    fn describe_step<'a>(&self, index: usize, current: &Nav) -> Vec<Line<'a>> {
+        let entry = &self.undo_stack[index];
+        let describe = |node: Option<(&String, &BookmarkLink)>| match node {
+            Some((string, link)) => format!("\"{}\" -> {}", string, link),
+            None => String::from("(none)"),
+        };
+        let before = |id| describe(entry.nav.get(id).map(|node| (&node.string, &node.link)));
+        let after = |id| match self.undo_stack.get(index + 1) {
+            Some(next) => describe(next.nav.get(id).map(|node| (&node.string, &node.link))),
+            None => describe(current.get(id).map(|node| (&node.string, &node.link))),
+        };
+        entry.affected.iter()
+            .map(|id| {
+                let line = if id.is_empty() {
+                    String::from("top level entries added or removed")
+                } else {
+                    format!("{}: {}  =>  {}", format_identifier(id), before(id), after(id))
+                };
+                Line::from(line)
+            })
+            .collect()
+    }
+
+    /// Pop the last recorded step, `current` and `selected` being the state right after it, so
+    /// that it can be redone. The returned entry holds the state to go back to.
+    pub fn undo(&mut self, current: &Nav, extras: &Extras, selected: TreeIdentifier) -> Option<HistoryEntry> {
+        if self.depth > 0 {
+            return None;
+        }
+        let entry = self.undo_stack.pop()?;
+        let mut redo = HistoryEntry::new(&entry.label, self.interner.snapshot(current), extras, selected);
+        redo.affected = entry.affected.clone();
+        self.redo_stack.push(redo);
+        Some(entry)
+    }
+
+    /// Record again the last undone step, `current` and `selected` being the state right before
+    /// it. The returned entry holds the state to go to.
+    pub fn redo(&mut self, current: &Nav, extras: &Extras, selected: TreeIdentifier) -> Option<HistoryEntry> {
+        if self.depth > 0 {
+            return None;
+        }
+        let entry = self.redo_stack.pop()?;
+        let mut undo = HistoryEntry::new(&entry.label, self.interner.snapshot(current), extras, selected);
+        undo.affected = entry.affected.clone();
+        self.undo_stack.push(undo);
+        Some(entry)
+    }
+}
+
+/// Format a node identifier as a dotted, 1-based section number, e.g. `2.1.3`.
+pub fn format_identifier(id: TreeIdentifier) -> String {
+    id.iter()
+        .map(|i| (i + 1).to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// List the entries of `nav` that differ from `loaded`, the outline as it was opened.
+pub fn changes_report(loaded: &Nav, nav: &Nav) -> Report {
+    let rows = loaded.changed_nodes(nav).into_iter()
+        .map(|id| match (loaded.get(&id), nav.get(&id)) {
+            (Some(old), Some(new)) if old.string != new.string || old.link != new.link => {
+                let text = format!(
+                    "{} \"{}\" -> {} was \"{}\" -> {}",
+                    format_identifier(&id), new.string, new.link, old.string, old.link,
+                );
+                ReportRow::node(text, id)
+            },
+            (None, Some(new)) => {
+                let text = format!("{} \"{}\" -> {} added", format_identifier(&id), new.string, new.link);
+                ReportRow::node(text, id)
+            },
+            (_, Some(new)) => {
+                let text = format!("{} \"{}\": entries added or removed below", format_identifier(&id), new.string);
+                ReportRow::node(text, id)
+            },
+            _ => ReportRow::header(String::from("Top level entries added or removed")),
+        })
+        .collect();
+    Report { title: String::from("Entries changed since the file was opened"), rows }
+}
+
+#[cfg(feature = "tui")]
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{} KiB", bytes / 1024),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
+
+#[cfg(feature = "tui")]
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}h", secs / 3600)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::NavNode;
+
+    fn nav_with(titles: &[&str]) -> Nav {
+        Nav {
+            nodes: titles.iter()
+                .map(|t| NavNode { string: t.to_string(), ..NavNode::default() })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn nested_transactions_undo_as_one() {
+        let mut history = History::default();
+        let mut nav = nav_with(&["a"]);
+
+        history.begin("bulk", &nav, &Extras::default(), &[0]);
+        for title in ["b", "c", "d"] {
+            history.begin("single", &nav, &Extras::default(), &[0]);
+            nav.nodes.push(NavNode { string: title.to_string(), ..NavNode::default() });
+            history.commit(&nav, &Extras::default());
+        }
+        history.commit(&nav, &Extras::default());
+
+        let entry = history.undo(&nav, &Extras::default(), &[0]).unwrap();
+        assert_eq!(entry.label, "bulk");
+        assert_eq!(entry.nav(), nav_with(&["a"]));
+        assert!(history.undo(&entry.nav(), &Extras::default(), &[0]).is_none());
+    }
+
+    #[test]
+    fn unchanged_transaction_is_not_recorded() {
+        let mut history = History::default();
+        let nav = nav_with(&["a"]);
+
+        history.begin("noop", &nav, &Extras::default(), &[0]);
+        history.commit(&nav, &Extras::default());
+
+        assert!(history.undo(&nav, &Extras::default(), &[0]).is_none());
+    }
+
+    #[test]
+    fn undone_steps_can_be_redone_until_a_new_step() {
+        let mut history = History::default();
+        let mut nav = nav_with(&["a"]);
+
+        history.begin("add", &nav, &Extras::default(), &[0]);
+        nav.nodes.push(NavNode::default());
+        history.commit(&nav, &Extras::default());
+
+        let undone = history.undo(&nav, &Extras::default(), &[1]).unwrap();
+        let redone = history.redo(&undone.nav(), &Extras::default(), undone.selected()).unwrap();
+        assert_eq!(redone.nav(), nav);
+        assert_eq!(redone.selected(), &[1]);
+        assert_eq!(history.entries().len(), 1);
+
+        let undone = history.undo(&nav, &Extras::default(), &[1]).unwrap();
+        nav = undone.nav();
+        history.begin("rename", &nav, &Extras::default(), &[0]);
+        nav.nodes[0].string = String::from("b");
+        history.commit(&nav, &Extras::default());
+        assert!(history.redo(&nav, &Extras::default(), &[0]).is_none());
+    }
+
+    #[test]
+    fn extras_are_undone_with_the_outline() {
+        let mut history = History::default();
+        let nav = nav_with(&["a"]);
+        let mut metadata = Metadata::default();
+        metadata.set("title", "Le Temps");
+        let before = Extras { metadata: Some(metadata.clone()), ..Extras::default() };
+        metadata.set("year", "1923");
+        let after = Extras { metadata: Some(metadata), page_titles: vec![Some(String::from("i"))] };
+
+        history.begin("edit metadata", &nav, &before, &[0]);
+        history.commit(&nav, &after);
+
+        let undone = history.undo(&nav, &after, &[0]).unwrap();
+        assert_eq!(undone.extras(), &before);
+        assert_eq!(history.redo(&nav, &before, &[0]).unwrap().extras(), &after);
+    }
+
+    #[test]
+    fn affected_nodes_are_recorded() {
+        let mut history = History::default();
+        let mut nav = nav_with(&["a", "b"]);
+
+        history.begin("rename", &nav, &Extras::default(), &[1]);
+        nav.nodes[1].string = String::from("c");
+        history.commit(&nav, &Extras::default());
+
+        assert_eq!(history.entries()[0].affected, vec![vec![1]]);
+    }
+
+    #[test]
+    fn oldest_steps_are_forgotten_and_subtrees_shared() {
+        let mut history = History::with_limit(2);
+        let mut nav = nav_with(&["a", "b"]);
+        nav.nodes[1].children = (0..100).map(|i| NavNode { string: i.to_string(), ..NavNode::default() }).collect();
+
+        for title in ["c", "d", "e"] {
+            history.begin("rename", &nav, &Extras::default(), &[0]);
+            nav.nodes[0].string = String::from(title);
+            history.commit(&nav, &Extras::default());
+        }
+        assert_eq!(history.entries().len(), 2);
+        assert_eq!(history.entries()[0].nav().nodes[0].string, "c");
+        assert!(Rc::ptr_eq(&history.entries()[0].nav.nodes[1], &history.entries()[1].nav.nodes[1]));
+
+        let single = {
+            let mut history = History::default();
+            history.begin("rename", &nav, &Extras::default(), &[0]);
+            nav.nodes[0].string = String::from("f");
+            history.commit(&nav, &Extras::default());
+            history.memory_usage()
+        };
+        assert!(history.memory_usage() < 2 * single);
+    }
+
+    #[test]
+    fn changes_since_loading() {
+        let loaded = nav_with(&["a", "b"]);
+        let mut nav = loaded.clone();
+        nav.nodes[1].string = String::from("c");
+        nav.nodes[0].children.push(NavNode::default());
+
+        let report = changes_report(&loaded, &nav);
+        assert_eq!(report.rows, vec![
+            ReportRow::node(String::from("1 \"a\": entries added or removed below"), vec![0]),
+            ReportRow::node(String::from("1.1 \"\" -> 0 added"), vec![0, 0]),
+            ReportRow::node(String::from("2 \"c\" -> 0 was \"b\" -> 0"), vec![1]),
+        ]);
+    }
+}
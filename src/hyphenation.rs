@@ -0,0 +1,99 @@
+//! Words broken at the end of a line of the page and hyphenated, which titles read from the text
+//! layer keep, as in "Introduc- tion", and nearly every imported outline has.
+
+use std::collections::HashSet;
+
+use crate::{nav::Nav, tree_widget::TreeIdentifierVec};
+
+/// The characters OCR reads at the end of hyphenated lines. Only the first two are also hyphens
+/// of words.
+const HYPHENS: [char; 4] = ['-', '\u{2010}', '\u{ad}', '¬'];
+
+/// Words after which a hyphen and a space are written on purpose, as in "pre- and post-war".
+const CONJUNCTIONS: [&str; 11] = ["and", "or", "nor", "to", "und", "oder", "bis", "et", "ou", "à", "y"];
+
+/// Return the words of the titles of `nav` in lower case, those written with a hyphen whole, to
+/// tell whether a broken word has a hyphen of its own.
+pub fn vocabulary(nav: &Nav) -> HashSet<String> {
+    nav.all_nodes().into_iter()
+        .flat_map(|(_, node)| {
+            node.string
+                .split(|c: char| !c.is_alphabetic() && !HYPHENS[..2].contains(&c))
+                .map(|word| word.trim_matches(&HYPHENS[..]).to_lowercase())
+                .collect::<Vec<_>>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Return `title` with the words broken by hyphenation joined back, or `None` if it has none. A
+/// word is broken where a hyphen follows a letter, and spaces then a lower case letter follow it.
+/// The hyphen is kept if the word is in `vocabulary` with it, and not without it.
+pub fn dehyphenate(title: &str, vocabulary: &HashSet<String>) -> Option<String> {
+    let mut joined = String::new();
+    let mut rest = title;
+    let mut changed = false;
+    while let Some(i) = rest.find(HYPHENS) {
+        let (before, after) = rest.split_at(i);
+        let hyphen = after.chars().next()?;
+        let after = &after[hyphen.len_utf8()..];
+        let next = after.trim_start();
+        let start = before.rsplit(|c: char| !c.is_alphabetic()).next().unwrap_or_default();
+        let end = &next[..next.find(|c: char| !c.is_alphabetic()).unwrap_or(next.len())];
+        joined.push_str(before);
+        let broken = !start.is_empty()
+            && next.len() < after.len()
+            && end.starts_with(char::is_lowercase)
+            && !CONJUNCTIONS.contains(&end);
+        if broken {
+            let whole = format!("{}{}", start, end).to_lowercase();
+            let hyphenated = format!("{}-{}", start, end).to_lowercase();
+            if HYPHENS[..2].contains(&hyphen) && vocabulary.contains(&hyphenated) && !vocabulary.contains(&whole) {
+                joined.push(hyphen);
+            }
+            changed = true;
+            rest = next;
+        } else {
+            joined.push(hyphen);
+            rest = after;
+        }
+    }
+    joined.push_str(rest);
+    changed.then_some(joined)
+}
+
+/// Return the entries of `nav` whose titles have words broken by hyphenation, with their titles
+/// joined back, in document order.
+pub fn broken_titles(nav: &Nav) -> Vec<(TreeIdentifierVec, String)> {
+    let vocabulary = vocabulary(nav);
+    nav.all_nodes().into_iter()
+        .filter_map(|(id, node)| Some((id, dehyphenate(&node.string, &vocabulary)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+
+    #[test]
+    fn broken_words_are_joined() {
+        let nav = Nav {
+            nodes: vec![
+                node("Introduc- tion", 1, vec![]),
+                node("A self- made man", 1, vec![]),
+                node("The self-made myth", 1, vec![]),
+                node("Pre- and post-war Europe", 1, vec![]),
+                node("Über- blick und Ausblick", 1, vec![]),
+                node("Chapter 3 - the end", 1, vec![]),
+                node("Soft\u{ad} ware, hard-\u{a0} ware", 1, vec![]),
+            ],
+        };
+        assert_eq!(broken_titles(&nav), [
+            (vec![0], String::from("Introduction")),
+            (vec![1], String::from("A self-made man")),
+            (vec![4], String::from("Überblick und Ausblick")),
+            (vec![6], String::from("Software, hardware")),
+        ]);
+    }
+}
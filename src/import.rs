@@ -0,0 +1,98 @@
+use std::{fmt::Display, path::Path};
+
+use crate::{
+    nav::Nav,
+    text_outline::{from_text, TextOutlineError},
+};
+
+/// Formats an outline can be imported from, with the same structure as the JSON export, or as
+/// indented text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    Json,
+    Yaml,
+    Text,
+}
+
+pub const FORMAT_NAMES: [&str; 3] = ["json", "yaml", "txt"];
+
+impl ImportFormat {
+    pub fn from_string(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "txt" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    /// Guess the format of the file `path` from its extension.
+    pub fn from_path(path: &str) -> Option<Self> {
+        Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| Self::from_string(&extension.to_lowercase()))
+    }
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    JsonError(serde_json::Error),
+    YamlError(serde_yaml::Error),
+    TextError(TextOutlineError),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::JsonError(e) => write!(f, "invalid JSON outline: {}", e),
+            Self::YamlError(e) => write!(f, "invalid YAML outline: {}", e),
+            Self::TextError(e) => write!(f, "invalid text outline: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// Parse `input`, an outline written in `format`.
+pub fn import(input: &str, format: ImportFormat) -> Result<Nav, ImportError> {
+    match format {
+        ImportFormat::Json => serde_json::from_str(input).map_err(ImportError::JsonError),
+        ImportFormat::Yaml => serde_yaml::from_str(input).map_err(ImportError::YamlError),
+        ImportFormat::Text => from_text(input).map_err(ImportError::TextError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::{BookmarkLink, NavNode};
+
+    #[test]
+    fn yaml_import() {
+        let yaml = r#"
+nodes:
+  - title: Chapter 1
+    page: 3
+    children:
+      - title: Plate
+        link: p0012.djvu
+"#;
+        assert_eq!(import(yaml, ImportFormat::Yaml).unwrap(), Nav {
+            nodes: vec![NavNode {
+                string: String::from("Chapter 1"),
+                link: BookmarkLink::PageNumber(3),
+                children: vec![NavNode {
+                    string: String::from("Plate"),
+                    link: BookmarkLink::PageLink(String::from("p0012.djvu")),
+                    children: vec![],
+                }],
+            }],
+        });
+    }
+
+    #[test]
+    fn missing_link_is_an_error() {
+        assert!(import(r#"{"nodes": [{"title": "Preface"}]}"#, ImportFormat::Json).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::nav::{BookmarkLink, NavNode};
+
+/// An entry of a back-of-book index: a term and the pages it is found on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub term: String,
+    pub pages: Vec<u32>,
+}
+
+/// Parse the text of index pages into index entries. Lines that do not end with page references
+/// (letter headings, "see also" references, running heads) are skipped.
+pub fn parse_index(text: &str) -> Vec<IndexEntry> {
+    text.lines().filter_map(parse_index_line).collect()
+}
+
+/// Parse a line of the form `term, 12, 45–47`. The term may itself contain commas. The comma
+/// after it may be missing when there is no other, as in `term 12`, since with more references
+/// the number would be part of the term, as in `World War 2, 45`.
+fn parse_index_line(line: &str) -> Option<IndexEntry> {
+    let mut fields: Vec<&str> = line.split(',').map(str::trim).collect();
+    if let [field] = fields[..] {
+        if let Some((term, reference)) = field.rsplit_once(' ') {
+            if parse_page_reference(reference).is_some() {
+                fields = vec![term.trim_end(), reference];
+            }
+        }
+    }
+    let first_reference = fields.iter()
+        .rposition(|field| parse_page_reference(field).is_none())
+        .map_or(0, |i| i + 1);
+    if first_reference == 0 || first_reference == fields.len() {
+        return None;
+    }
+    let term = fields[..first_reference].join(", ");
+    if term.is_empty() {
+        return None;
+    }
+    let pages = fields[first_reference..].iter()
+        .filter_map(|field| parse_page_reference(field))
+        .collect();
+    Some(IndexEntry { term, pages })
+}
+
+/// Parse a page reference such as `12`, `45–47` or `80f.`, returning the first page referred to.
+fn parse_page_reference(field: &str) -> Option<u32> {
+    let first = field.split(['-', '–', '—']).next()?;
+    let first = first.trim_end_matches(|c: char| c == '.' || c.is_alphabetic()).trim();
+    first.parse().ok()
+}
+
+/// Build a bookmark tree out of `entries`: one node per initial letter, in alphabetical order,
+/// holding one node per term linking to the first page the term is found on. If
+/// `expand_multiple_targets` is set, terms found on several pages get one child per page.
+pub fn index_to_nodes(mut entries: Vec<IndexEntry>, expand_multiple_targets: bool) -> Vec<NavNode> {
+    entries.sort_by_key(|entry| entry.term.to_lowercase());
+
+    let mut groups: Vec<NavNode> = Vec::new();
+    for entry in entries {
+        let initial: String = entry.term.chars().next().unwrap_or(' ').to_uppercase().collect();
+        let children = if expand_multiple_targets && entry.pages.len() > 1 {
+            entry.pages.iter()
+                .map(|page| NavNode {
+                    string: format!("p. {}", page),
+                    link: BookmarkLink::PageNumber(*page),
+                    children: Vec::new(),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let node = NavNode {
+            string: entry.term,
+            link: BookmarkLink::PageNumber(entry.pages[0]),
+            children,
+        };
+        match groups.last_mut() {
+            Some(group) if group.string == initial => group.children.push(node),
+            _ => groups.push(NavNode {
+                string: initial,
+                link: node.link.clone(),
+                children: vec![node],
+            }),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_lines() {
+        let text = "INDEX\nA\nAbel, Niels Henrik, 12, 45–47\nalgebra, 3f.\n  see also equations\n";
+        assert_eq!(parse_index(text), vec![
+            IndexEntry { term: String::from("Abel, Niels Henrik"), pages: vec![12, 45] },
+            IndexEntry { term: String::from("algebra"), pages: vec![3] },
+        ]);
+        assert_eq!(parse_index("World War 2, 45
+Wages 12
+"), vec![
+            IndexEntry { term: String::from("World War 2"), pages: vec![45] },
+            IndexEntry { term: String::from("Wages"), pages: vec![12] },
+        ]);
+    }
+
+    #[test]
+    fn grouped_by_initial() {
+        let entries = parse_index("Wages, 87\nabacus, 4\nAbel, 12\n");
+        let nodes = index_to_nodes(entries, false);
+
+        let groups: Vec<_> = nodes.iter()
+            .map(|group| (group.string.as_str(), group.children.len()))
+            .collect();
+        assert_eq!(groups, [("A", 2), ("W", 1)]);
+        assert_eq!(nodes[0].children[0].string, "abacus");
+        assert_eq!(nodes[0].link, BookmarkLink::PageNumber(4));
+    }
+
+    #[test]
+    fn multiple_targets_expanded() {
+        let nodes = index_to_nodes(parse_index("Wages, 12, 87, 203\n"), true);
+
+        let wages = &nodes[0].children[0];
+        let children: Vec<_> = wages.children.iter().map(|child| child.link.clone()).collect();
+        assert_eq!(wages.string, "Wages");
+        assert_eq!(children, [12, 87, 203].map(BookmarkLink::PageNumber));
+        assert_eq!(wages.children[1].string, "p. 87");
+    }
+}
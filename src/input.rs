@@ -0,0 +1,92 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use unicode_width::UnicodeWidthStr;
+
+/// A single line of editable text, with a cursor.
+#[derive(Debug, Default, Clone)]
+pub struct LineInput {
+    text: String,
+    /// Position of the cursor, in characters.
+    cursor: usize,
+}
+
+impl LineInput {
+    /// Return an input holding `text`, with the cursor at its end.
+    pub fn new(text: &str) -> Self {
+        Self { text: String::from(text), cursor: text.chars().count() }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Display width of the text before the cursor, to place the terminal cursor.
+    pub fn cursor_width(&self) -> usize {
+        self.text[..self.byte_index(self.cursor)].width()
+    }
+
+    /// Apply the editing key `key`. Return `false` if `key` is not an editing key.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let len = self.text.chars().count();
+        match key.code {
+            KeyCode::Char(c) => {
+                let index = self.byte_index(self.cursor);
+                self.text.insert(index, c);
+                self.cursor += 1;
+            },
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let index = self.byte_index(self.cursor);
+                self.text.remove(index);
+            },
+            KeyCode::Delete if self.cursor < len => {
+                let index = self.byte_index(self.cursor);
+                self.text.remove(index);
+            },
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(len),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = len,
+            KeyCode::Backspace | KeyCode::Delete => (),
+            _ => return false,
+        }
+        true
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.text.char_indices()
+            .nth(char_index)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn type_keys(input: &mut LineInput, keys: &[KeyCode]) {
+        for key in keys {
+            input.handle_key(KeyEvent::new(*key, KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn editing_in_the_middle_of_unicode_text() {
+        let mut input = LineInput::default();
+        let typed: Vec<_> = "Théorie".chars().map(KeyCode::Char).collect();
+        type_keys(&mut input, &typed);
+        type_keys(&mut input, &[KeyCode::Home, KeyCode::Right, KeyCode::Right, KeyCode::Delete]);
+        type_keys(&mut input, &[KeyCode::Char('e'), KeyCode::End, KeyCode::Backspace]);
+
+        assert_eq!(input.text(), "Theori");
+        assert_eq!(input.cursor_width(), 6);
+    }
+
+    #[test]
+    fn new_input_starts_at_the_end() {
+        let mut input = LineInput::new("Théorie");
+        assert_eq!(input.cursor_width(), 7);
+        type_keys(&mut input, &[KeyCode::Char('s')]);
+        assert_eq!(input.text(), "Théories");
+    }
+}
@@ -0,0 +1,320 @@
+#[cfg(feature = "tui")]
+use std::{collections::HashMap, fmt::Display};
+
+#[cfg(feature = "tui")]
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+/// Something the user can do while navigating the outline, bound to keys by the `KeyMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    WriteQuit,
+    Write,
+    MoveLeft,
+    MoveDown,
+    MoveUp,
+    MoveRight,
+    /// Select the first entry of the outline.
+    FirstEntry,
+    /// Select the last entry shown in the tree.
+    LastEntry,
+    /// Move the selection and the tree half the height of the tree down.
+    HalfPageDown,
+    HalfPageUp,
+    /// Move the selection and the tree the height of the tree down.
+    PageDown,
+    PageUp,
+    /// Edit the title and link of the selected entry in a popup.
+    EditEntry,
+    /// Edit the title and link of the selected entry in the external editor.
+    EditInEditor,
+    /// Edit the whole outline as a plan in the external editor.
+    EditPlan,
+    ToggleLock,
+    /// Select a range of siblings, for the next actions to apply to all of them.
+    VisualMode,
+    Focus,
+    AddEntry,
+    Cut,
+    Yank,
+    PasteBelow,
+    PasteAbove,
+    MoveEntryUp,
+    MoveEntryDown,
+    Demote,
+    Promote,
+    Undo,
+    Redo,
+    History,
+    QuickFix,
+    BulkFix,
+    ProposeTitles,
+    CommandLine,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    /// Show a preview of the page the selected entry points to in place of its details.
+    TogglePreview,
+    /// Open the document at the page the selected entry points to, in an external viewer.
+    OpenInViewer,
+    /// Switch to or from the display for reviewing the outline with others.
+    TogglePresentation,
+    /// Show the metadata of the document, such as its title and author, to edit them.
+    ShowMetadata,
+    /// Show the pages of the document with their titles, which viewers show as page labels, to
+    /// edit them.
+    ShowPageTitles,
+    /// Remove the default binding of a key.
+    #[serde(rename = "none")]
+    Unbound,
+}
+
+/// Bindings available without configuration: vim-like keys, and arrow keys.
+#[cfg(feature = "tui")]
+const DEFAULT_BINDINGS: [(&str, Action); 55] = [
+    ("q", Action::Quit),
+    ("Z Z", Action::WriteQuit),
+    ("w", Action::Write),
+    ("h", Action::MoveLeft),
+    ("j", Action::MoveDown),
+    ("k", Action::MoveUp),
+    ("l", Action::MoveRight),
+    ("Left", Action::MoveLeft),
+    ("Down", Action::MoveDown),
+    ("Up", Action::MoveUp),
+    ("Right", Action::MoveRight),
+    ("g g", Action::FirstEntry),
+    ("Home", Action::FirstEntry),
+    ("G", Action::LastEntry),
+    ("End", Action::LastEntry),
+    ("Ctrl-d", Action::HalfPageDown),
+    ("Ctrl-u", Action::HalfPageUp),
+    ("PageDown", Action::PageDown),
+    ("PageUp", Action::PageUp),
+    ("i", Action::EditEntry),
+    ("F2", Action::EditEntry),
+    ("e", Action::EditInEditor),
+    ("E", Action::EditPlan),
+    ("L", Action::ToggleLock),
+    ("V", Action::VisualMode),
+    ("z", Action::Focus),
+    ("o", Action::AddEntry),
+    ("d", Action::Cut),
+    ("Delete", Action::Cut),
+    ("y", Action::Yank),
+    ("p", Action::PasteBelow),
+    ("P", Action::PasteAbove),
+    ("K", Action::MoveEntryUp),
+    ("J", Action::MoveEntryDown),
+    ("Shift-Up", Action::MoveEntryUp),
+    ("Shift-Down", Action::MoveEntryDown),
+    (">", Action::Demote),
+    ("<", Action::Promote),
+    ("Tab", Action::Demote),
+    ("BackTab", Action::Promote),
+    ("u", Action::Undo),
+    ("Ctrl-r", Action::Redo),
+    ("H", Action::History),
+    ("f", Action::QuickFix),
+    ("F", Action::BulkFix),
+    ("T", Action::ProposeTitles),
+    (":", Action::CommandLine),
+    ("/", Action::Search),
+    ("n", Action::NextMatch),
+    ("N", Action::PreviousMatch),
+    ("v", Action::OpenInViewer),
+    ("F3", Action::TogglePreview),
+    ("F4", Action::ShowMetadata),
+    ("F5", Action::TogglePresentation),
+    ("F6", Action::ShowPageTitles),
+];
+
+impl Action {
+    /// Whether `self` is available in presentation mode, where the outline can be browsed but not
+    /// edited.
+    pub fn is_available_when_presenting(self) -> bool {
+        matches!(
+            self,
+            Self::Quit
+                | Self::MoveLeft
+                | Self::MoveDown
+                | Self::MoveUp
+                | Self::MoveRight
+                | Self::FirstEntry
+                | Self::LastEntry
+                | Self::Focus
+                | Self::NextMatch
+                | Self::PreviousMatch
+                | Self::OpenInViewer
+                | Self::TogglePreview
+                | Self::TogglePresentation
+        )
+    }
+}
+
+/// A key along with the modifiers held, such as `Ctrl-r`.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+#[cfg(feature = "tui")]
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+
+#[cfg(feature = "tui")]
+impl Key {
+    /// Shift is already part of characters and of `BackTab`, so it is ignored for them: `J` is
+    /// reported with or without it depending on the terminal.
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let modifiers = modifiers.intersection(KeyModifiers::SHIFT | KeyModifiers::CONTROL | KeyModifiers::ALT);
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers.difference(KeyModifiers::SHIFT),
+            _ => modifiers,
+        };
+        Self { code, modifiers }
+    }
+
+    /// Parse a key such as `j`, `Ctrl-r`, `Shift-Up` or `F2`.
+    fn from_string(input: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = input;
+        // A lone `-` is a key, not a separator.
+        while let Some((modifier, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = key;
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "space" => KeyCode::Char(' '),
+                name => KeyCode::F(name.strip_prefix('f')?.parse().ok().filter(|n| (1..=12).contains(n))?),
+            },
+        };
+        Some(Self::new(code, modifiers))
+    }
+}
+
+/// One key, or two keys pressed one after the other such as `Z Z`.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeySequence(Vec<Key>);
+
+#[cfg(feature = "tui")]
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidKeyError(String);
+
+#[cfg(feature = "tui")]
+impl Display for InvalidKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid key: \"{}\" (expected e.g. \"j\", \"Ctrl-r\", \"Shift-Up\" or \"g g\")", self.0)
+    }
+}
+
+#[cfg(feature = "tui")]
+impl std::error::Error for InvalidKeyError {}
+
+#[cfg(feature = "tui")]
+impl TryFrom<String> for KeySequence {
+    type Error = InvalidKeyError;
+
+    fn try_from(input: String) -> Result<Self, Self::Error> {
+        let keys: Option<Vec<_>> = input.split_whitespace().map(Key::from_string).collect();
+        match keys {
+            Some(keys) if (1..=2).contains(&keys.len()) => Ok(Self(keys)),
+            _ => Err(InvalidKeyError(input)),
+        }
+    }
+}
+
+/// The action bound to each key while navigating.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: HashMap<KeySequence, Action>,
+}
+
+#[cfg(feature = "tui")]
+impl KeyMap {
+    /// Return the default bindings, changed by `overrides`.
+    pub fn new(overrides: &HashMap<KeySequence, Action>) -> Self {
+        let mut bindings: HashMap<_, _> = DEFAULT_BINDINGS.into_iter()
+            .map(|(keys, action)| (KeySequence::try_from(String::from(keys)).unwrap(), action))
+            .collect();
+        for (keys, action) in overrides {
+            match action {
+                Action::Unbound => bindings.remove(keys),
+                _ => bindings.insert(keys.clone(), *action),
+            };
+        }
+        Self { bindings }
+    }
+
+    /// Return the action bound to `keys`.
+    pub fn get(&self, keys: &[Key]) -> Option<Action> {
+        self.bindings.get(&KeySequence(keys.to_vec())).copied()
+    }
+}
+
+#[cfg(all(test, feature = "tui"))]
+mod tests {
+    use super::*;
+
+    fn key(input: &str) -> Key {
+        Key::from_string(input).unwrap()
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(key("Ctrl-r"), Key::new(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        assert_eq!(key("Shift-Up"), Key::new(KeyCode::Up, KeyModifiers::SHIFT));
+        assert_eq!(key("-"), Key::new(KeyCode::Char('-'), KeyModifiers::NONE));
+        assert_eq!(key("F12"), Key::new(KeyCode::F(12), KeyModifiers::NONE));
+        assert_eq!(key("J"), Key::new(KeyCode::Char('J'), KeyModifiers::SHIFT));
+        assert_eq!(Key::from_string("Hyper-x"), None);
+        assert_eq!(Key::from_string("F13"), None);
+        assert!(KeySequence::try_from(String::from("g g g")).is_err());
+    }
+
+    #[test]
+    fn overrides_replace_defaults() {
+        let overrides: HashMap<KeySequence, Action> = toml::from_str(
+            "\"Ctrl-s\" = \"write\"\nw = \"none\"\n\"g g\" = \"move-up\""
+        ).unwrap();
+        let keymap = KeyMap::new(&overrides);
+
+        assert_eq!(keymap.get(&[key("Ctrl-s")]), Some(Action::Write));
+        assert_eq!(keymap.get(&[key("w")]), None);
+        assert_eq!(keymap.get(&[key("g"), key("g")]), Some(Action::MoveUp));
+        assert_eq!(keymap.get(&[key("Up")]), Some(Action::MoveUp));
+    }
+}
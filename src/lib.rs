@@ -0,0 +1,93 @@
+//! Reading, checking and editing the outline (the `NAV` section) of DjVu files.
+//!
+//! The outline is read and written in the file directly, falling back to `djvused` with the
+//! `djvused` feature:
+//!
+//! ```no_run
+//! use djvu_nav::{get_nav_from_djvu, embed_nav_in_djvu_file, BookmarkLink, NavNode};
+//!
+//! let mut nav = get_nav_from_djvu("book.djvu")?;
+//! nav.nodes.push(NavNode {
+//!     string: String::from("Index"),
+//!     link: BookmarkLink::PageNumber(412),
+//!     children: vec![],
+//! });
+//! embed_nav_in_djvu_file("book.djvu", &nav)?;
+//! # Ok::<(), djvu_nav::djvu::NavReadingError>(())
+//! ```
+//!
+//! The `nav_edit` binary is the interactive editor built on top of this library.
+
+pub mod nav;
+pub mod djvu;
+#[cfg(feature = "tui")]
+pub mod app;
+pub mod tree_widget;
+pub mod history;
+pub mod config;
+pub mod lint;
+pub mod link;
+#[cfg(feature = "tui")]
+mod detail;
+pub mod fix;
+#[cfg(feature = "tui")]
+mod popup;
+pub mod heading;
+#[cfg(feature = "tui")]
+mod input;
+#[cfg(feature = "tui")]
+mod command;
+pub mod generate;
+pub mod index_import;
+pub mod export;
+pub mod import;
+pub mod cli;
+pub mod report;
+pub mod dedup;
+pub mod offset;
+pub mod query;
+pub mod plan;
+pub mod casing;
+pub mod targets;
+pub mod check;
+#[cfg(feature = "tui")]
+mod status_bar;
+#[cfg(feature = "tui")]
+mod presentation;
+#[cfg(feature = "tui")]
+mod preview;
+pub mod verify;
+pub mod keymap;
+pub mod selftest;
+pub mod viewer;
+pub mod archive;
+pub mod scandata;
+pub mod text_outline;
+pub mod ocr;
+pub mod series;
+pub mod columns;
+pub mod tempfile;
+pub mod autosave;
+#[cfg(feature = "tui")]
+pub mod recording;
+pub mod validate;
+pub mod diagnostics;
+pub mod merge;
+pub mod strict;
+pub mod page_expr;
+pub mod metadata;
+pub mod page_titles;
+pub mod anchors;
+pub mod chapters;
+pub mod tasks;
+pub mod hyphenation;
+pub mod session;
+
+pub use djvu::{
+    embed_nav_in_djvu_file, get_nav_from_djvu, get_page_text, get_pages_from_djvu,
+    NavReadingError, PageInfo,
+};
+pub use nav::{BookmarkLink, Nav, NavNode};
+
+/// Name of the directories holding the configuration and the temporary files.
+pub const APP_NAME: &str = "nav_edit";
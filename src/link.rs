@@ -0,0 +1,311 @@
+use std::{borrow::Cow, collections::BTreeMap, fmt::Display};
+
+use crate::{
+    djvu::PageInfo,
+    nav::{BookmarkLink, Nav},
+    tree_widget::TreeIdentifier,
+};
+
+/// What a `BookmarkLink` points to in a given document.
+#[derive(Debug, PartialEq, Eq)]
+pub enum LinkTarget<'a> {
+    Page(&'a PageInfo),
+    /// A page number outside of the document, along with the number of pages of the document.
+    PageOutOfRange(u32, usize),
+    UnknownComponent(&'a str),
+    /// A URL, possibly rewritten from the link by a `UrlRewrite`.
+    Url(Cow<'a, str>),
+    /// The pages of the document could not be listed, so the link cannot be checked.
+    Unresolved(&'a BookmarkLink),
+}
+
+impl<'a> LinkTarget<'a> {
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, Self::PageOutOfRange(..) | Self::UnknownComponent(_))
+    }
+}
+
+/// A way of interpreting some of the links, such as links to pages by number.
+pub trait LinkResolver {
+    /// Return what `link` points to in the document with pages `pages`, or `None` if `self` does
+    /// not handle this kind of link.
+    fn resolve<'a>(&self, link: &'a BookmarkLink, pages: &'a [PageInfo]) -> Option<LinkTarget<'a>>;
+}
+
+/// Resolves links to pages by physical page number.
+pub struct PageNumberResolver;
+
+impl LinkResolver for PageNumberResolver {
+    fn resolve<'a>(&self, link: &'a BookmarkLink, pages: &'a [PageInfo]) -> Option<LinkTarget<'a>> {
+        let BookmarkLink::PageNumber(n) = link else {
+            return None;
+        };
+        let target = pages.iter()
+            .find(|page| page.number == *n)
+            .map_or(LinkTarget::PageOutOfRange(*n, pages.len()), LinkTarget::Page);
+        Some(target)
+    }
+}
+
+/// Resolves links containing `://` to URLs.
+pub struct UrlResolver;
+
+impl LinkResolver for UrlResolver {
+    fn resolve<'a>(&self, link: &'a BookmarkLink, _pages: &'a [PageInfo]) -> Option<LinkTarget<'a>> {
+        match link {
+            BookmarkLink::PageLink(s) if s.contains("://") => Some(LinkTarget::Url(Cow::Borrowed(s))),
+            _ => None,
+        }
+    }
+}
+
+/// Resolves links to pages by the name of their component file.
+pub struct ComponentResolver;
+
+impl LinkResolver for ComponentResolver {
+    fn resolve<'a>(&self, link: &'a BookmarkLink, pages: &'a [PageInfo]) -> Option<LinkTarget<'a>> {
+        let BookmarkLink::PageLink(s) = link else {
+            return None;
+        };
+        let target = pages.iter()
+            .find(|page| page.id == *s)
+            .map_or(LinkTarget::UnknownComponent(s), LinkTarget::Page);
+        Some(target)
+    }
+}
+
+/// Turns links starting with a prefix into URLs, such as `doi:10.1000/182` into
+/// `https://doi.org/10.1000/182`.
+pub struct UrlRewrite {
+    pub prefix: String,
+    pub replacement: String,
+}
+
+impl LinkResolver for UrlRewrite {
+    fn resolve<'a>(&self, link: &'a BookmarkLink, _pages: &'a [PageInfo]) -> Option<LinkTarget<'a>> {
+        let BookmarkLink::PageLink(s) = link else {
+            return None;
+        };
+        let rest = s.strip_prefix(&self.prefix)?;
+        Some(LinkTarget::Url(Cow::Owned(format!("{}{}", self.replacement, rest))))
+    }
+}
+
+/// Resolvers of the kinds of links every viewer understands, tried in order.
+const BUILTIN_RESOLVERS: [&dyn LinkResolver; 3] = [&PageNumberResolver, &UrlResolver, &ComponentResolver];
+
+/// The resolvers to use for a document: the ones from the configuration, then the built-in ones.
+#[derive(Default)]
+pub struct LinkResolvers {
+    resolvers: Vec<Box<dyn LinkResolver>>,
+}
+
+impl LinkResolvers {
+    /// Return the built-in resolvers preceded by one for each of `rewrites`, which map prefixes to
+    /// their replacements. The longest prefixes are tried first.
+    pub fn with_rewrites(rewrites: &BTreeMap<String, String>) -> Self {
+        let mut rewrites: Vec<_> = rewrites.iter()
+            .map(|(prefix, replacement)| UrlRewrite { prefix: prefix.clone(), replacement: replacement.clone() })
+            .collect();
+        rewrites.sort_by_key(|rewrite| std::cmp::Reverse(rewrite.prefix.len()));
+        let mut resolvers = Self::default();
+        for rewrite in rewrites {
+            resolvers.register(Box::new(rewrite));
+        }
+        resolvers
+    }
+
+    /// Add `resolver`, to be tried after the ones already registered and before the built-in ones.
+    pub fn register(&mut self, resolver: Box<dyn LinkResolver>) {
+        self.resolvers.push(resolver);
+    }
+
+    /// Interpret `link` against the pages of the document, if they are known.
+    pub fn resolve<'a>(&self, link: &'a BookmarkLink, pages: Option<&'a [PageInfo]>) -> LinkTarget<'a> {
+        let Some(pages) = pages else {
+            return LinkTarget::Unresolved(link);
+        };
+        self.resolvers.iter()
+            .map(Box::as_ref)
+            .chain(BUILTIN_RESOLVERS)
+            .find_map(|resolver| resolver.resolve(link, pages))
+            .unwrap_or(LinkTarget::Unresolved(link))
+    }
+}
+
+/// Interpret `link` against the pages of the document, if they are known, with the built-in
+/// resolvers only.
+pub fn resolve<'a>(link: &'a BookmarkLink, pages: Option<&'a [PageInfo]>) -> LinkTarget<'a> {
+    let Some(pages) = pages else {
+        return LinkTarget::Unresolved(link);
+    };
+    BUILTIN_RESOLVERS.into_iter()
+        .find_map(|resolver| resolver.resolve(link, pages))
+        .unwrap_or(LinkTarget::Unresolved(link))
+}
+
+/// Return the physical page number `link` points to, if it points to a page, even outside of the
+/// document.
+pub fn page_number(link: &BookmarkLink, pages: Option<&[PageInfo]>) -> Option<u32> {
+    match resolve(link, pages) {
+        LinkTarget::Page(page) => Some(page.number),
+        LinkTarget::PageOutOfRange(n, _) => Some(n),
+        LinkTarget::Unresolved(BookmarkLink::PageNumber(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+/// The two ways links point to pages of the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageLinkKind {
+    /// By physical page number.
+    Number,
+    /// By name of the component file, which stays right when pages are inserted or removed.
+    Id,
+}
+
+/// Return `link` made to point to the same page of `pages` in the way `kind`, or `None` if it
+/// does not point to a page of the document or already points to it that way.
+pub fn convert_link(link: &BookmarkLink, kind: PageLinkKind, pages: &[PageInfo]) -> Option<BookmarkLink> {
+    let LinkTarget::Page(page) = resolve(link, Some(pages)) else {
+        return None;
+    };
+    match (link, kind) {
+        (BookmarkLink::PageLink(_), PageLinkKind::Number) => Some(BookmarkLink::PageNumber(page.number)),
+        (BookmarkLink::PageNumber(_), PageLinkKind::Id) => Some(BookmarkLink::PageLink(page.id.clone())),
+        _ => None,
+    }
+}
+
+/// Make the links of the node `within` and its descendants, or of the whole outline if `within`
+/// is empty, point to their pages in the way `kind`. Links outside of the document are left alone.
+/// Return the number of entries changed.
+pub fn convert_links(nav: &mut Nav, within: TreeIdentifier, kind: PageLinkKind, pages: &[PageInfo]) -> usize {
+    let ids: Vec<_> = nav.all_nodes().into_iter()
+        .map(|(id, _)| id)
+        .filter(|id| id.starts_with(within))
+        .collect();
+    let mut changed = 0;
+    for id in ids {
+        let node = &mut nav[&id];
+        if let Some(link) = convert_link(&node.link, kind, pages) {
+            node.link = link;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Return the pages whose name could complete `typed`: those whose name starts with it, then those
+/// whose name contains it, ignoring case, each in page order.
+pub fn page_id_candidates<'a>(typed: &str, pages: &'a [PageInfo]) -> Vec<&'a PageInfo> {
+    let typed = typed.to_lowercase();
+    let (mut starting, containing): (Vec<_>, Vec<_>) = pages.iter()
+        .filter(|page| page.id.to_lowercase().contains(&typed))
+        .partition(|page| page.id.to_lowercase().starts_with(&typed));
+    starting.extend(containing);
+    starting
+}
+
+impl<'a> Display for LinkTarget<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Page(page) => {
+                write!(f, "physical page {}, component {}", page.number, page.id)?;
+                if let Some(title) = &page.title {
+                    write!(f, ", label \"{}\"", title)?;
+                }
+                Ok(())
+            },
+            Self::PageOutOfRange(n, count) => {
+                write!(f, "page {} does not exist (the document has {} pages)", n, count)
+            },
+            Self::UnknownComponent(id) => write!(f, "no page is named {}", id),
+            Self::Url(url) => write!(f, "URL {}, outside of the document", url),
+            Self::Unresolved(BookmarkLink::PageNumber(n)) => write!(f, "physical page {} (unchecked)", n),
+            Self::Unresolved(BookmarkLink::PageLink(s)) => write!(f, "page named {} (unchecked)", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::NavNode;
+
+    fn pages() -> Vec<PageInfo> {
+        (1..=3)
+            .map(|number| PageInfo { number, id: format!("p{:04}.djvu", number), title: None })
+            .collect()
+    }
+
+    #[test]
+    fn number_and_name_resolve_to_the_same_page() {
+        let pages = pages();
+        let by_number = BookmarkLink::PageNumber(2);
+        let by_name = BookmarkLink::PageLink(String::from("p0002.djvu"));
+
+        assert_eq!(resolve(&by_number, Some(&pages)), LinkTarget::Page(&pages[1]));
+        assert_eq!(resolve(&by_name, Some(&pages)), LinkTarget::Page(&pages[1]));
+    }
+
+    #[test]
+    fn links_converted_between_numbers_and_names() {
+        let mut pages = pages();
+        pages[2].id = String::from("index.djvu");
+        let nav = Nav {
+            nodes: vec![
+                NavNode { string: String::from("Preface"), link: BookmarkLink::PageNumber(1), children: vec![] },
+                NavNode { string: String::from("Index"), link: BookmarkLink::PageLink(String::from("index.djvu")), children: vec![] },
+                NavNode { string: String::from("Errata"), link: BookmarkLink::PageNumber(9), children: vec![] },
+            ],
+        };
+
+        let mut by_name = nav.clone();
+        assert_eq!(convert_links(&mut by_name, &[], PageLinkKind::Id, &pages), 1);
+        assert_eq!(by_name.nodes[0].link, BookmarkLink::PageLink(String::from("p0001.djvu")));
+        assert_eq!(by_name.nodes[2].link, BookmarkLink::PageNumber(9));
+
+        let mut by_number = nav.clone();
+        assert_eq!(convert_links(&mut by_number, &[1], PageLinkKind::Number, &pages), 1);
+        assert_eq!(by_number.nodes[1].link, BookmarkLink::PageNumber(3));
+
+        pages[0].id = String::from("contents-index.djvu");
+        let candidates: Vec<_> = page_id_candidates("IN", &pages).iter().map(|page| page.id.as_str()).collect();
+        assert_eq!(candidates, ["index.djvu", "contents-index.djvu"]);
+    }
+
+    #[test]
+    fn invalid_targets() {
+        let pages = pages();
+        let past_end = BookmarkLink::PageNumber(4);
+        let unknown = BookmarkLink::PageLink(String::from("p0012.djvu"));
+
+        assert!(!resolve(&past_end, Some(&pages)).is_valid());
+        assert!(!resolve(&unknown, Some(&pages)).is_valid());
+        assert!(resolve(&unknown, None).is_valid());
+    }
+
+    #[test]
+    fn rewritten_prefixes_become_urls() {
+        let pages = pages();
+        let rewrites = BTreeMap::from([
+            (String::from("doi:"), String::from("https://doi.org/")),
+            (String::from("doi:local/"), String::from("file:///srv/papers/")),
+        ]);
+        let resolvers = LinkResolvers::with_rewrites(&rewrites);
+        let doi = BookmarkLink::PageLink(String::from("doi:10.1000/182"));
+        let local = BookmarkLink::PageLink(String::from("doi:local/182.pdf"));
+
+        assert_eq!(
+            resolvers.resolve(&doi, Some(&pages)),
+            LinkTarget::Url(Cow::Borrowed("https://doi.org/10.1000/182"))
+        );
+        assert_eq!(
+            resolvers.resolve(&local, Some(&pages)),
+            LinkTarget::Url(Cow::Borrowed("file:///srv/papers/182.pdf"))
+        );
+        assert!(!resolve(&doi, Some(&pages)).is_valid());
+        assert_eq!(resolvers.resolve(&BookmarkLink::PageNumber(2), Some(&pages)), LinkTarget::Page(&pages[1]));
+    }
+}
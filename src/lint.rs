@@ -0,0 +1,244 @@
+use serde::Deserialize;
+
+use crate::{
+    casing::recase,
+    config::PolicyConfig,
+    djvu::PageInfo,
+    link::LinkResolvers,
+    nav::{Nav, NavNode, BookmarkLink},
+    tree_widget::{TreeIdentifier, TreeIdentifierVec},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    TitleTooLong,
+    TooDeep,
+    ForbiddenCharacter,
+    ViewerIncompatibility,
+    InvalidTarget,
+    Casing,
+    /// The entry points before the entry above it, see `validate`.
+    OutOfOrder,
+    /// The entry has the title and page of an earlier one.
+    Duplicate,
+    /// Many pages follow the entry without another entry.
+    Gap,
+}
+
+/// A viewer with known limitations in the way it displays outlines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ViewerProfile {
+    #[serde(rename = "djview4")]
+    Djview4,
+    #[serde(rename = "okular")]
+    Okular,
+    /// The Internet Archive BookReader.
+    #[serde(rename = "bookreader")]
+    BookReader,
+    #[serde(rename = "djvu.js")]
+    DjvuJs,
+}
+
+/// Something a viewer does not handle well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quirk {
+    /// Links to page 0 (pages are numbered from 1) are ignored or go to an arbitrary page.
+    ZeroPageIgnored,
+    /// Only page numbers are understood, not links to a component page by name.
+    NoComponentLinks,
+    /// Links to a URL rather than a page of the document are dropped.
+    NoUrlLinks,
+    /// Entries without a title are not shown, and neither are their children.
+    EmptyTitleHidden,
+}
+
+impl ViewerProfile {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Djview4 => "djview4",
+            Self::Okular => "okular",
+            Self::BookReader => "bookreader",
+            Self::DjvuJs => "djvu.js",
+        }
+    }
+
+    fn quirks(self) -> &'static [Quirk] {
+        match self {
+            Self::Djview4 => &[Quirk::ZeroPageIgnored],
+            Self::Okular => &[Quirk::ZeroPageIgnored, Quirk::NoUrlLinks],
+            Self::BookReader => &[
+                Quirk::ZeroPageIgnored,
+                Quirk::NoComponentLinks,
+                Quirk::NoUrlLinks,
+                Quirk::EmptyTitleHidden,
+            ],
+            Self::DjvuJs => &[Quirk::ZeroPageIgnored, Quirk::NoUrlLinks],
+        }
+    }
+}
+
+impl Quirk {
+    /// Return a description of the problem if `node` is affected by `self`.
+    fn check(self, node: &NavNode) -> Option<&'static str> {
+        let is_url = |s: &str| s.contains("://");
+        match (self, &node.link) {
+            (Self::ZeroPageIgnored, BookmarkLink::PageNumber(0)) => Some("links to page 0 are ignored"),
+            (Self::NoComponentLinks, BookmarkLink::PageLink(s)) if !is_url(s) => {
+                Some("links to pages by name are not supported")
+            },
+            (Self::NoUrlLinks, BookmarkLink::PageLink(s)) if is_url(s) => Some("URL links are dropped"),
+            (Self::EmptyTitleHidden, _) if node.string.trim().is_empty() => {
+                Some("entries without a title are hidden")
+            },
+            _ => None,
+        }
+    }
+}
+
+/// A problem found on a node of the outline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub id: TreeIdentifierVec,
+    pub kind: FindingKind,
+    pub message: String,
+}
+
+/// Check every node of `nav` against `policy` and, if they are known, the pages of the document.
+/// Return the violations in document order.
+pub fn lint(
+    nav: &Nav,
+    policy: &PolicyConfig,
+    pages: Option<&[PageInfo]>,
+    resolvers: &LinkResolvers,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for (id, node) in nav.all_nodes() {
+        let mut push = |kind, message| findings.push(Finding { id: id.clone(), kind, message });
+
+        let title_length = node.string.chars().count();
+        if let Some(max) = policy.max_title_length {
+            if title_length > max {
+                push(
+                    FindingKind::TitleTooLong,
+                    format!("title is {} characters long (max {})", title_length, max),
+                );
+            }
+        }
+        if let Some(max) = policy.max_depth {
+            if id.len() > max {
+                push(
+                    FindingKind::TooDeep,
+                    format!("entry is at depth {} (max {})", id.len(), max),
+                );
+            }
+        }
+        if let Some(forbidden) = &policy.forbidden_characters {
+            if let Some(c) = node.string.chars().find(|c| forbidden.contains(*c)) {
+                push(
+                    FindingKind::ForbiddenCharacter,
+                    format!("title contains forbidden character {:?}", c),
+                );
+            }
+        }
+        if let Some(style) = policy.casing {
+            let recased = recase(
+                &node.string,
+                style,
+                policy.casing_language.unwrap_or_default(),
+                policy.casing_minor_words.as_deref().unwrap_or_default(),
+            );
+            if recased != node.string {
+                push(FindingKind::Casing, format!("title should be \"{}\"", recased));
+            }
+        }
+        let target = resolvers.resolve(&node.link, pages);
+        if !target.is_valid() {
+            push(FindingKind::InvalidTarget, format!("invalid link: {}", target));
+        }
+        for viewer in policy.viewers.iter().flatten() {
+            for quirk in viewer.quirks() {
+                if let Some(problem) = quirk.check(node) {
+                    push(FindingKind::ViewerIncompatibility, format!("{}: {}", viewer.name(), problem));
+                }
+            }
+        }
+    }
+    findings
+}
+
+/// Return the findings concerning the node `id`.
+pub fn findings_for<'a>(findings: &'a [Finding], id: TreeIdentifier<'a>) -> impl Iterator<Item = &'a Finding> {
+    findings.iter().filter(move |finding| finding.id == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{casing::CaseStyle, nav::NavNode};
+
+    #[test]
+    fn policy_violations_are_reported() {
+        let nav = Nav {
+            nodes: vec![NavNode {
+                string: String::from("A rather long title"),
+                children: vec![NavNode {
+                    string: String::from("a|b"),
+                    ..NavNode::default()
+                }],
+                ..NavNode::default()
+            }],
+        };
+        let policy = PolicyConfig {
+            max_title_length: Some(10),
+            max_depth: Some(1),
+            forbidden_characters: Some(String::from("|")),
+            ..PolicyConfig::default()
+        };
+
+        let kinds: Vec<_> = lint(&nav, &policy, None, &LinkResolvers::default()).into_iter().map(|f| (f.id, f.kind)).collect();
+        assert_eq!(kinds, vec![
+            (vec![0], FindingKind::TitleTooLong),
+            (vec![0, 0], FindingKind::TooDeep),
+            (vec![0, 0], FindingKind::ForbiddenCharacter),
+        ]);
+    }
+
+    #[test]
+    fn viewer_quirks_are_reported() {
+        let nav = Nav {
+            nodes: vec![
+                NavNode { string: String::from("Cover"), ..NavNode::default() },
+                NavNode {
+                    string: String::from("Plate"),
+                    link: BookmarkLink::PageLink(String::from("p012.djvu")),
+                    children: vec![],
+                },
+            ],
+        };
+        let policy = PolicyConfig {
+            viewers: Some(vec![ViewerProfile::Djview4, ViewerProfile::BookReader]),
+            ..PolicyConfig::default()
+        };
+
+        let messages: Vec<_> = lint(&nav, &policy, None, &LinkResolvers::default()).into_iter().map(|f| f.message).collect();
+        assert_eq!(messages, vec![
+            "djview4: links to page 0 are ignored",
+            "bookreader: links to page 0 are ignored",
+            "bookreader: links to pages by name are not supported",
+        ]);
+    }
+
+    #[test]
+    fn casing_deviations_are_reported() {
+        let nav = Nav {
+            nodes: vec![
+                NavNode { string: String::from("The history of Rome"), ..NavNode::default() },
+                NavNode { string: String::from("The Fall of the Republic"), ..NavNode::default() },
+            ],
+        };
+        let policy = PolicyConfig { casing: Some(CaseStyle::Title), ..PolicyConfig::default() };
+
+        let messages: Vec<_> = lint(&nav, &policy, None, &LinkResolvers::default()).into_iter().map(|f| (f.id, f.message)).collect();
+        assert_eq!(messages, vec![(vec![0], String::from("title should be \"The History of Rome\""))]);
+    }
+}
@@ -1,36 +1,365 @@
-mod nav;
-mod djvu;
-mod app;
-mod tree_widget;
+#[cfg(feature = "tui")]
+use djvu_nav::app::App;
+use djvu_nav::{
+    cli,
+    diagnostics::{self, Diagnostics},
+    djvu::remote::is_url,
+    export::{self, ExportFormat},
+    import::{self, ImportFormat},
+    tempfile,
+    validate::validation_report,
+};
 
-use crate::app::App;
+use std::{io, path::Path, process::ExitCode};
+#[cfg(feature = "tui")]
+use std::time::Duration;
 
-use std::{io, time::Duration};
+use clap::{value_parser, Command, Arg, ArgAction, ArgMatches};
 
-use clap::{Command, Arg};
-
-fn main() -> Result<(), io::Error> {
+fn main() -> Result<ExitCode, io::Error> {
     let cmd = Command::new("nav_edit")
         .version("1.0.0")
         .about("Edit NAV section of djvu files.")
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
         .arg(
             Arg::new("filename")
                 .required(true)
+                .help("Path of the file, or its URL with --read-only")
+        )
+        .arg(
+            Arg::new("read-only")
+                .long("read-only")
+                .action(ArgAction::SetTrue)
+                .help("Browse the outline without being able to write it")
+        )
+        .arg(strict_arg())
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .value_name("CAST")
+                .help("Record the session as an asciinema cast, to replay with `asciinema play`")
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Print the outline of a djvu file.")
+                .arg(Arg::new("filename").required(true))
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(export::FORMAT_NAMES)
+                        .default_value("json")
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Write to this file instead of the standard output")
+                )
+                .arg(
+                    Arg::new("timestamps")
+                        .long("timestamps")
+                        .value_name("FILE")
+                        .help("Time at which the reading of pages starts in an audio recording, for ffmetadata and cue")
+                )
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Replace the outline of a djvu file with one read from a JSON or YAML file.")
+                .arg(Arg::new("filename").required(true))
+                .arg(Arg::new("outline").required(true))
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(import::FORMAT_NAMES)
+                        .help("Format of the outline file, guessed from its extension by default")
+                )
+                .arg(strict_arg())
+        )
+        .subcommand(
+            Command::new("export-txt")
+                .about("Print the outline of a djvu file as indented text, one `title<TAB>page` line per entry.")
+                .arg(Arg::new("filename").required(true))
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Write to this file instead of the standard output")
+                )
+        )
+        .subcommand(
+            Command::new("import-txt")
+                .about("Replace the outline of a djvu file with one read from an indented text file.")
+                .arg(Arg::new("filename").required(true))
+                .arg(Arg::new("outline").required(true))
+                .arg(strict_arg())
+        )
+        .subcommand(
+            Command::new("generate")
+                .about("Propose an outline of a djvu file made of the headings found in its text layer, as indented text to review and pass to import-txt.")
+                .arg(Arg::new("filename").required(true))
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .short('o')
+                        .help("Write to this file instead of the standard output")
+                )
+        )
+        .subcommand(
+            Command::new("offset")
+                .about("Add a number of pages, possibly negative, to the page numbers of the outlines of djvu files.")
+                .arg(
+                    Arg::new("pages")
+                        .required(true)
+                        .allow_negative_numbers(true)
+                        .value_parser(value_parser!(i32))
+                )
+                .arg(filenames_arg())
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .value_parser(value_parser!(u32))
+                        .help("Only change the entries pointing to this page or later")
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .action(ArgAction::SetTrue)
+                        .help("Print what would change without writing the files")
+                )
+                .arg(strict_arg())
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Check the outlines of djvu files against the policy of the configuration and their pages.")
+                .arg(filenames_arg())
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("List the entries of djvu files out of page order, the duplicate entries and the large gaps between entries.")
+                .arg(filenames_arg())
+        )
+        .subcommand(
+            Command::new("labels")
+                .about("Set the page titles of a djvu file to the page numbers listed in a scandata.xml file.")
+                .arg(Arg::new("filename").required(true))
+                .arg(Arg::new("scandata").required(true))
+        )
+        .subcommand(
+            Command::new("selftest")
+                .about("Check that djvulibre can read and write outlines, on a generated document.")
+        )
+        .subcommand(
+            Command::new("archive")
+                .about("Work with the DjVu file of an item of the Internet Archive.")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("pages")
+                        .about("Print the printed page numbers of the pages, and the offset they suggest.")
+                        .arg(Arg::new("identifier").required(true))
+                )
+                .subcommand(
+                    Command::new("download")
+                        .about("Download the DjVu file of the item.")
+                        .arg(Arg::new("identifier").required(true))
+                        .arg(
+                            Arg::new("output")
+                                .long("output")
+                                .short('o')
+                                .help("Write to this file instead of the one named after the item")
+                        )
+                )
+                .subcommand(
+                    Command::new("upload")
+                        .about("Replace the DjVu file of the item, with the keys set in the configuration.")
+                        .arg(Arg::new("identifier").required(true))
+                        .arg(Arg::new("filename").required(true))
+                )
+        )
+        .subcommand(
+            Command::new("query")
+                .about("Run a jq filter on the outline of a djvu file, as exported in JSON.")
+                .arg(Arg::new("filename").required(true))
+                .arg(Arg::new("expression").required(true))
+                .arg(
+                    Arg::new("raw")
+                        .long("raw-output")
+                        .short('r')
+                        .action(ArgAction::SetTrue)
+                        .help("Print strings without quotes")
+                )
         );
 
     let args = cmd.get_matches();
+    let _ = tempfile::remove_stale();
+    if let Some((name, sub_args)) = args.subcommand() {
+        return Ok(run_subcommand(name, sub_args));
+    }
+
     let filename = args.get_one::<String>("filename").unwrap();
+    let read_only = args.get_flag("read-only");
+    let strict = args.get_flag("strict");
+    let record = args.get_one::<String>("record").map(Path::new);
+    if is_url(filename) && !read_only {
+        eprintln!("nav_edit: remote files can only be opened with --read-only");
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(run_editor(filename, read_only, strict, record))
+}
+
+/// Edit `filename` in the terminal until the user quits.
+#[cfg(feature = "tui")]
+fn run_editor(filename: &str, read_only: bool, strict: bool, record: Option<&Path>) -> ExitCode {
     let tick_rate = Duration::from_millis(250);
-    match App::new(filename) {
-        Ok(mut application) => {
-            let res = application.run(tick_rate);
-            if let Err(err) = res {
-                println!("{err:?}");
+    // The terminal is given back by the time an error is returned.
+    match App::new(filename, read_only, strict, record).and_then(|mut application| application.run(tick_rate)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("nav_edit: {err}");
+            if err.is_unexpected() {
+                diagnostics::report(&Diagnostics::of(&err));
             }
+            ExitCode::FAILURE
         },
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_editor(_filename: &str, _read_only: bool, _strict: bool, _record: Option<&Path>) -> ExitCode {
+    eprintln!("nav_edit: this build has no editor (the `tui` feature), only the subcommands");
+    ExitCode::FAILURE
+}
+
+/// The files a batch subcommand works on, as paths or patterns such as `*.djvu`.
+fn filenames_arg() -> Arg {
+    Arg::new("filenames")
+        .required(true)
+        .num_args(1..)
+        .help("Paths of the files, or patterns such as '*.djvu'")
+}
+
+/// The flag asking to write outlines in strict mode.
+fn strict_arg() -> Arg {
+    Arg::new("strict")
+        .long("strict")
+        .action(ArgAction::SetTrue)
+        .help("Only write outlines that pass validation, after a backup, and read them back to compare")
+}
+
+/// Run the non-interactive subcommand `name`, reporting errors on the standard error.
+fn run_subcommand(name: &str, args: &ArgMatches) -> ExitCode {
+    let result = match name {
+        "selftest" => cli::selftest(),
+        "archive" => run_archive_subcommand(args),
+        "offset" | "check" | "lint" => run_batch_subcommand(name, args),
+        _ => run_file_subcommand(name, args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
         Err(err) => {
-            println!("{err:?}");
-        }
+            eprintln!("nav_edit: {err}");
+            if let cli::CliError::NavReadingError(e) = &err {
+                if e.is_unexpected() {
+                    diagnostics::report(&Diagnostics::of(e));
+                }
+            }
+            ExitCode::FAILURE
+        },
+    }
+}
+
+/// Run the subcommand of `nav_edit archive` given in `args`.
+fn run_archive_subcommand(args: &ArgMatches) -> Result<(), cli::CliError> {
+    let (name, args) = args.subcommand().unwrap();
+    let identifier = args.get_one::<String>("identifier").unwrap();
+    match name {
+        "pages" => cli::archive_pages(identifier),
+        "download" => {
+            let output = args.get_one::<String>("output").map(String::as_str);
+            cli::archive_download(identifier, output).map(|path| eprintln!("Downloaded to {}", path))
+        },
+        "upload" => {
+            let filename = args.get_one::<String>("filename").unwrap();
+            cli::archive_upload(identifier, filename).map(|()| eprintln!("Uploaded {} to {}", filename, identifier))
+        },
+        _ => unreachable!("unknown subcommand {}", name),
+    }
+}
+
+/// Run the subcommand `name` on each of the files given as its `filenames` argument, printing a
+/// line per file.
+fn run_batch_subcommand(name: &str, args: &ArgMatches) -> Result<(), cli::CliError> {
+    let patterns: Vec<String> = args.get_many::<String>("filenames").unwrap().cloned().collect();
+    let filenames = cli::expand_globs(&patterns)?;
+    match name {
+        "offset" => {
+            let delta = *args.get_one::<i32>("pages").unwrap();
+            let from = args.get_one::<u32>("from").copied().unwrap_or(0);
+            let dry_run = args.get_flag("dry-run");
+            let strict = args.get_flag("strict");
+            cli::batch(&filenames, |filename| {
+                let changed = cli::offset_outline(filename, delta, from, dry_run, strict)?;
+                let verb = if dry_run { "would shift" } else { "shifted" };
+                Ok(format!("{} {} entries by {} pages", verb, changed, delta))
+            })
+        },
+        "check" => cli::batch(&filenames, |filename| {
+            cli::check_outline(filename).map(|entries| format!("{} entries, no warnings", entries))
+        }),
+        "lint" => cli::batch(&filenames, |filename| {
+            let (nav, findings) = cli::lint_outline(filename)?;
+            for row in validation_report(&nav, &findings).rows {
+                println!("{}: {}", filename, row.text);
+            }
+            match findings.len() {
+                0 => Ok(format!("{} entries in page order, no duplicates or large gaps", nav.all_nodes().len())),
+                count => Err(cli::CliError::PolicyViolations(count)),
+            }
+        }),
+        _ => unreachable!("unknown subcommand {}", name),
+    }
+}
+
+/// Run the subcommand `name`, which works on the file given as its `filename` argument.
+fn run_file_subcommand(name: &str, args: &ArgMatches) -> Result<(), cli::CliError> {
+    let filename = args.get_one::<String>("filename").unwrap();
+    match name {
+        "export" => {
+            let format = ExportFormat::from_string(args.get_one::<String>("format").unwrap()).unwrap();
+            let output = args.get_one::<String>("output").map(String::as_str);
+            let timestamps = args.get_one::<String>("timestamps").map(String::as_str);
+            cli::export_outline(filename, format, output, timestamps)
+        },
+        "import" => {
+            let outline = args.get_one::<String>("outline").unwrap();
+            let format = args.get_one::<String>("format").and_then(|f| ImportFormat::from_string(f));
+            cli::import_outline(filename, outline, format, args.get_flag("strict"))
+        },
+        "export-txt" => {
+            let output = args.get_one::<String>("output").map(String::as_str);
+            cli::export_outline(filename, ExportFormat::Text, output, None)
+        },
+        "import-txt" => {
+            let outline = args.get_one::<String>("outline").unwrap();
+            cli::import_outline(filename, outline, Some(ImportFormat::Text), args.get_flag("strict"))
+        },
+        "generate" => {
+            let output = args.get_one::<String>("output").map(String::as_str);
+            cli::generate_outline(filename, output).map(|count| eprintln!("Found {} headings", count))
+        },
+        "labels" => {
+            let scandata = args.get_one::<String>("scandata").unwrap();
+            cli::import_page_labels(filename, scandata).map(|(labelled, offset)| {
+                eprintln!("Labelled {} pages", labelled);
+                if let Some(offset) = offset {
+                    eprintln!("Printed page numbers are offset by {}: `nav_edit offset {} {}`", offset, offset, filename);
+                }
+            })
+        },
+        "query" => {
+            let expression = args.get_one::<String>("expression").unwrap();
+            cli::query_outline(filename, expression, args.get_flag("raw"))
+        },
+        _ => unreachable!("unknown subcommand {}", name),
     }
-    Ok(())
 }
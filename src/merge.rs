@@ -0,0 +1,126 @@
+//! Reviewing an imported outline entry by entry before it is added to the outline or replaces it.
+
+use std::collections::HashSet;
+
+use crate::{
+    dedup::normalize_title,
+    nav::{Nav, NavNode},
+};
+
+/// An entry of the imported outline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incoming {
+    /// Depth in the imported outline, 1 for the top level.
+    pub depth: usize,
+    /// The entry, without its children.
+    pub node: NavNode,
+    pub accepted: bool,
+    /// Whether the outline has an entry with the same title and link already.
+    pub known: bool,
+}
+
+/// An imported outline being reviewed, its entries in document order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merge {
+    /// What was imported, such as `generate`.
+    pub source: String,
+    pub rows: Vec<Incoming>,
+    /// Whether the accepted entries replace the outline, rather than being added at its end.
+    pub replace: bool,
+}
+
+impl Merge {
+    /// Start reviewing `nodes`, imported from `source`, against `nav`. Unless they replace the
+    /// outline, the entries already in `nav` are rejected, and the others accepted.
+    pub fn new(source: &str, nodes: Vec<NavNode>, nav: &Nav, replace: bool) -> Self {
+        let existing: HashSet<_> = nav.all_nodes().into_iter()
+            .map(|(_, node)| (normalize_title(&node.string), node.link.to_string()))
+            .collect();
+        let rows = Nav { nodes }.all_nodes().into_iter()
+            .map(|(id, node)| {
+                let known = existing.contains(&(normalize_title(&node.string), node.link.to_string()));
+                let node = NavNode { children: vec![], ..node.clone() };
+                Incoming { depth: id.len(), node, accepted: replace || !known, known }
+            })
+            .collect();
+        Self { source: String::from(source), rows, replace }
+    }
+
+    pub fn accepted(&self) -> usize {
+        self.rows.iter().filter(|row| row.accepted).count()
+    }
+
+    /// Return the accepted entries as a tree. The children of a rejected entry take its place
+    /// under its closest accepted ancestor.
+    pub fn nodes(&self) -> Vec<NavNode> {
+        let mut nodes: Vec<NavNode> = Vec::new();
+        // Depths in the imported outline of the accepted ancestors of the next entry.
+        let mut depths: Vec<usize> = Vec::new();
+        for row in self.rows.iter().filter(|row| row.accepted) {
+            while depths.last().is_some_and(|depth| *depth >= row.depth) {
+                depths.pop();
+            }
+            let mut siblings = &mut nodes;
+            for _ in 0..depths.len() {
+                siblings = &mut siblings.last_mut().unwrap().children;
+            }
+            siblings.push(row.node.clone());
+            depths.push(row.depth);
+        }
+        nodes
+    }
+
+    /// Add the accepted entries at the end of `nav`, or replace its entries with them.
+    pub fn apply(&self, nav: &mut Nav) {
+        if self.replace {
+            nav.nodes.clear();
+        }
+        nav.nodes.extend(self.nodes());
+    }
+
+    /// Return one line per entry: whether it is accepted, its title indented by depth, its link,
+    /// and whether the outline has it already.
+    pub fn labels(&self) -> Vec<String> {
+        self.rows.iter()
+            .map(|row| format!(
+                "[{}] {}{} ({}){}",
+                if row.accepted { 'x' } else { ' ' },
+                "  ".repeat(row.depth - 1),
+                row.node.string,
+                row.node.link,
+                if row.known { ", in the outline" } else { "" },
+            ))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+
+    #[test]
+    fn rejected_entries_give_way_to_their_children() {
+        let nav = Nav { nodes: vec![node("Preface", 1, vec![])] };
+        let imported = vec![
+            node("Preface", 1, vec![]),
+            node("Part One", 3, vec![node("Chapter 1", 3, vec![node("1.1", 4, vec![])]), node("Chapter 2", 9, vec![])]),
+        ];
+        let mut merge = Merge::new("import", imported, &nav, false);
+        assert_eq!(merge.rows.iter().map(|row| row.accepted).collect::<Vec<_>>(), [false, true, true, true, true]);
+        assert_eq!(merge.labels()[0], "[ ] Preface (1), in the outline");
+        assert_eq!(merge.labels()[3], "[x]     1.1 (4)");
+
+        merge.rows[1].accepted = false;
+        merge.rows[2].node.string = String::from("Chapter One");
+        assert_eq!(merge.nodes(), [node("Chapter One", 3, vec![node("1.1", 4, vec![])]), node("Chapter 2", 9, vec![])]);
+        assert_eq!(merge.accepted(), 3);
+
+        let mut merged = nav.clone();
+        merge.apply(&mut merged);
+        assert_eq!(merged.nodes.len(), 3);
+        merge.replace = true;
+        merge.apply(&mut merged);
+        assert_eq!(merged.nodes.len(), 2);
+    }
+}
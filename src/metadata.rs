@@ -0,0 +1,99 @@
+//! The metadata of the document, such as its title and author, which viewers and catalogs show.
+
+#[cfg(feature = "tui")]
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Frame,
+};
+
+/// Keys listed even when they are not set, to fill them in.
+pub const STANDARD_KEYS: [&str; 3] = ["title", "author", "year"];
+
+/// Keys and values of the metadata, in the order of the file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub entries: Vec<(String, String)>,
+}
+
+/// Whether `key` can be a key of the metadata, which `djvused` reads as a bare word.
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+impl Metadata {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter().find(|(other, _)| other == key).map(|(_, value)| value.as_str())
+    }
+
+    /// Set `key` to `value`, or remove it if `value` is empty.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let position = self.entries.iter().position(|(other, _)| other == key);
+        match (position, value.is_empty()) {
+            (Some(position), true) => {
+                self.entries.remove(position);
+            },
+            (Some(position), false) => self.entries[position].1 = String::from(value),
+            (None, true) => (),
+            (None, false) => self.entries.push((String::from(key), String::from(value))),
+        }
+    }
+
+    /// Return the keys to list, the standard ones first whether they are set or not, then the
+    /// others in the order of the file.
+    pub fn keys(&self) -> Vec<&str> {
+        let others = self.entries.iter()
+            .map(|(key, _)| key.as_str())
+            .filter(|key| !STANDARD_KEYS.contains(key));
+        STANDARD_KEYS.into_iter().chain(others).collect()
+    }
+
+    /// Write `self` in the format `set-meta` of `djvused` reads, one `key "value"` line per entry.
+    pub fn to_djvused(&self) -> String {
+        self.entries.iter()
+            .map(|(key, value)| {
+                let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+                format!("{}\t\"{}\"\n", key, value)
+            })
+            .collect()
+    }
+
+    /// Render the keys and values to `area`, with the key selected in `state` highlighted.
+    #[cfg(feature = "tui")]
+    pub fn ui(&self, f: &mut Frame, area: Rect, state: &mut ListState) {
+        let keys = self.keys();
+        let width = keys.iter().map(|key| key.chars().count()).max().unwrap_or(0);
+        let items: Vec<_> = keys.iter()
+            .map(|key| match self.get(key) {
+                Some(value) => ListItem::new(format!("{:width$}  {}", key, value)),
+                None => ListItem::new(format!("{:width$}  (not set)", key))
+                    .style(Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC)),
+            })
+            .collect();
+        let title = "Metadata (Enter to edit, a to add a key, d to remove, w to write, Esc to go back)";
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().fg(Color::Black).bg(Color::LightGreen))
+            .highlight_symbol("> ");
+        f.render_stateful_widget(list, area, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_keys_come_first() {
+        let mut metadata = Metadata {
+            entries: vec![(String::from("publisher"), String::from("Hachette")), (String::from("title"), String::from("Les \"Misérables\""))],
+        };
+        assert_eq!(metadata.keys(), ["title", "author", "year", "publisher"]);
+
+        metadata.set("year", "1862");
+        metadata.set("publisher", "");
+        assert_eq!(metadata.to_djvused(), "title\t\"Les \\\"Misérables\\\"\"\nyear\t\"1862\"\n");
+        assert!(is_valid_key("ISBN") && !is_valid_key("two words"));
+    }
+}
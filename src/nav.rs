@@ -1,15 +1,24 @@
-use std::{fmt::Display, ops::{Index, IndexMut}};
+#[cfg(feature = "tui")]
+use std::borrow::Cow;
+use std::{fmt::Display, ops::{Index, IndexMut, RangeInclusive}};
 
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "tui")]
 use ratatui::{
+    layout::Rect,
     style::{Style, Color},
     Frame,
 };
 
-use crate::tree_widget::{TreeState, TreeItem, Tree, TreeIdentifier, TreeView};
+use crate::tree_widget::{TreeIdentifier, TreeIdentifierVec, TreeView};
+#[cfg(feature = "tui")]
+use crate::tree_widget::{Tree, TreeItem, TreeState};
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub enum BookmarkLink {
+    #[serde(rename = "page")]
     PageNumber(u32),
+    #[serde(rename = "link")]
     PageLink(String),
 }
 
@@ -33,20 +42,30 @@ impl Display for BookmarkLink {
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct NavNode {
+    #[serde(rename = "title")]
     pub string: String,
+    #[serde(flatten)]
     pub link: BookmarkLink,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub children: Vec<NavNode>,
 }
 
+/// Escape `input` to write it between quotes for `djvused`, which reads C escapes.
 fn escape_characters(input: String) -> String {
     let mut res = String::new();
     for c in input.chars() {
-        if c == '"' {
-            res.push('\\');
+        match c {
+            '"' | '\\' => {
+                res.push('\\');
+                res.push(c);
+            },
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            _ => res.push(c),
         }
-        res.push(c);
     }
     res
 }
@@ -95,6 +114,92 @@ impl NavNode {
     }
 }
 
+/// Push to `out` the identifiers of the nodes of `new` that differ from their counterpart in
+/// `old`. Children are matched by their title and link, so that an entry added or removed among
+/// them does not hide the changes of its siblings. When the number of children of a node changed,
+/// that node is reported too.
+fn changed_nodes(
+    old: &[NavNode],
+    new: &[NavNode],
+    path: &mut TreeIdentifierVec,
+    out: &mut Vec<TreeIdentifierVec>,
+) {
+    if old.len() != new.len() {
+        out.push(path.clone());
+    }
+    let mut pairs = matching_nodes(old, new).into_iter().peekable();
+    let (mut old_index, mut new_index) = (0, 0);
+    while new_index < new.len() {
+        let (old_end, new_end) = pairs.peek().copied().unwrap_or((old.len(), new.len()));
+        // Entries between two matches were edited in place when there are as many on both sides,
+        // and added or removed otherwise.
+        if old_end - old_index == new_end - new_index {
+            for (old_node, new_index) in old[old_index..old_end].iter().zip(new_index..new_end) {
+                path.push(new_index);
+                if !same_entry(old_node, &new[new_index]) {
+                    out.push(path.clone());
+                }
+                changed_nodes(&old_node.children, &new[new_index].children, path, out);
+                path.pop();
+            }
+        } else {
+            out.extend((new_index..new_end).map(|index| [&path[..], &[index]].concat()));
+        }
+        if new_end < new.len() {
+            path.push(new_end);
+            changed_nodes(&old[old_end].children, &new[new_end].children, path, out);
+            path.pop();
+            pairs.next();
+        }
+        (old_index, new_index) = (old_end + 1, new_end + 1);
+    }
+}
+
+/// Return whether `old` and `new` have the same title and link, whatever their children.
+fn same_entry(old: &NavNode, new: &NavNode) -> bool {
+    old.string == new.string && old.link == new.link
+}
+
+/// Return the indices of the pairs of nodes of `old` and `new` with the same title and link, in a
+/// longest common subsequence of both lists.
+fn matching_nodes(old: &[NavNode], new: &[NavNode]) -> Vec<(usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(old, new)| same_entry(old, new)).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| same_entry(old, new))
+        .count();
+    let (middle_old, middle_new) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    let mut pairs: Vec<_> = (0..prefix).map(|index| (index, index)).collect();
+    // Lists this long are rewritten rather than edited, and not worth a quadratic search.
+    if middle_old.len().saturating_mul(middle_new.len()) <= 1 << 20 {
+        // `lengths[i][j]` is the length of a longest common subsequence of the nodes of the middle
+        // from `i` in `old` and from `j` in `new`.
+        let mut lengths = vec![vec![0; middle_new.len() + 1]; middle_old.len() + 1];
+        for i in (0..middle_old.len()).rev() {
+            for j in (0..middle_new.len()).rev() {
+                lengths[i][j] = if same_entry(&middle_old[i], &middle_new[j]) {
+                    lengths[i + 1][j + 1] + 1
+                } else {
+                    lengths[i + 1][j].max(lengths[i][j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < middle_old.len() && j < middle_new.len() {
+            if same_entry(&middle_old[i], &middle_new[j]) {
+                pairs.push((prefix + i, prefix + j));
+                (i, j) = (i + 1, j + 1);
+            } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+    pairs.extend((0..suffix).map(|index| (old.len() - suffix + index, new.len() - suffix + index)));
+    pairs
+}
+
 impl Default for NavNode {
     fn default() -> Self {
         Self { 
@@ -105,7 +210,7 @@ impl Default for NavNode {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Nav {
     pub nodes: Vec<NavNode>,
 }
@@ -121,34 +226,261 @@ impl Nav {
         s
     }
 
-    /// Render `self` to the `Frame` `f`, as a tree. Use `state` for persistence of open and
-    /// selected nodes.
-    pub fn ui(&self, f: &mut Frame, state: &mut TreeState) {
-        let tree = Tree::new(self)
+    /// Render `self` to the `area` of the `Frame` `f`, as a tree. Use `state` for persistence of
+    /// open and selected nodes, and `style` to give each node its style.
+    #[cfg(feature = "tui")]
+    pub fn ui<S>(&self, f: &mut Frame, area: Rect, state: &mut TreeState, style: S)
+    where
+        S: Fn(TreeIdentifier) -> Style
+    {
+        self.ui_with_text(f, area, state, |_, node| Cow::Borrowed(node.string.as_str()), |_, _| None, style);
+    }
+
+    /// Same as `ui`, showing for each node the text returned by `text` instead of its title, and
+    /// the text returned by `right`, if any, aligned to the right after a leader of dots.
+    #[cfg(feature = "tui")]
+    pub fn ui_with_text<T, R, S>(&self, f: &mut Frame, area: Rect, state: &mut TreeState, text: T, right: R, style: S)
+    where
+        T: for<'a> Fn(TreeIdentifier, &'a NavNode) -> Cow<'a, str>,
+        R: Fn(TreeIdentifier, &NavNode) -> Option<String>,
+        S: Fn(TreeIdentifier) -> Style
+    {
+        fn items<'a, T, R, S>(nodes: &'a [NavNode], path: &mut TreeIdentifierVec, text: &T, right: &R, style: &S) -> Vec<TreeItem<'a>>
+        where
+            T: Fn(TreeIdentifier, &'a NavNode) -> Cow<'a, str>,
+            R: Fn(TreeIdentifier, &NavNode) -> Option<String>,
+            S: Fn(TreeIdentifier) -> Style
+        {
+            nodes.iter()
+                .enumerate()
+                .map(|(index, node)| {
+                    path.push(index);
+                    let mut item = TreeItem::new(text(path, node), items(&node.children, path, text, right, style))
+                        .style(style(path));
+                    if let Some(right) = right(path, node) {
+                        item = item.right_text(right);
+                    }
+                    path.pop();
+                    item
+                })
+                .collect()
+        }
+        let tree = Tree::new(items(&self.nodes, &mut Vec::new(), &text, &right, &style))
+            .leader('.')
             .highlight_style(
                 Style::default()
                     .fg(Color::Black)
                     .bg(Color::LightGreen)
             )
             .highlight_symbol("> ");
-        f.render_stateful_widget(tree, f.size(), state);
+        f.render_stateful_widget(tree, area, state);
     }
 
-    pub fn new_first_child(&mut self, index: TreeIdentifier) {
-        if index.is_empty() {
-            self.nodes.insert(0,NavNode::default());
-        } else {
-            self[index].children.insert(0, NavNode::default());
+    /// Return the node with identifier `index`, if it exists.
+    pub fn get(&self, index: TreeIdentifier) -> Option<&NavNode> {
+        let (first, rest) = index.split_first()?;
+        let mut node = self.nodes.get(*first)?;
+        for i in rest {
+            node = node.children.get(*i)?;
+        }
+        Some(node)
+    }
+
+    /// Return the titles of the node `index` and of its ancestors, outermost first.
+    pub fn breadcrumb(&self, index: TreeIdentifier) -> Vec<&str> {
+        (1..=index.len())
+            .map_while(|depth| self.get(&index[..depth]))
+            .map(|node| node.string.as_str())
+            .collect()
+    }
+
+    /// Return every node along with its identifier, in document order.
+    pub fn all_nodes(&self) -> Vec<(TreeIdentifierVec, &NavNode)> {
+        fn visit<'a>(
+            nodes: &'a [NavNode],
+            path: &mut TreeIdentifierVec,
+            out: &mut Vec<(TreeIdentifierVec, &'a NavNode)>,
+        ) {
+            for (index, node) in nodes.iter().enumerate() {
+                path.push(index);
+                out.push((path.clone(), node));
+                visit(&node.children, path, out);
+                path.pop();
+            }
         }
+        let mut out = Vec::new();
+        visit(&self.nodes, &mut Vec::new(), &mut out);
+        out
+    }
+
+    /// Return the identifiers of the nodes with children down to depth `depth`, the top level being
+    /// at depth 1, to open them so that the nodes down to `depth + 1` are shown.
+    pub fn down_to_depth(&self, depth: usize) -> Vec<TreeIdentifierVec> {
+        self.all_nodes().into_iter()
+            .filter(|(id, node)| id.len() <= depth && !node.children.is_empty())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Return the identifiers of the nodes whose title contains `query`, ignoring case, in
+    /// document order.
+    pub fn find(&self, query: &str) -> Vec<TreeIdentifierVec> {
+        let query = query.to_lowercase();
+        self.all_nodes().into_iter()
+            .filter(|(_, node)| node.string.to_lowercase().contains(&query))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Return the identifiers of the nodes that differ between `self` and `other`. An empty
+    /// identifier means the top level entries were added or removed.
+    pub fn changed_nodes(&self, other: &Nav) -> Vec<TreeIdentifierVec> {
+        let mut out = Vec::new();
+        changed_nodes(&self.nodes, &other.nodes, &mut Vec::new(), &mut out);
+        out
+    }
+
+    pub fn new_first_child(&mut self, index: TreeIdentifier) {
+        self.insert_first_child(index, NavNode::default());
     }
 
     pub fn new_sibling_below(&mut self, index: TreeIdentifier) {
-        let father = &index[..index.len() - 1];
+        self.insert_sibling_below(index, NavNode::default());
+    }
+
+    /// Insert `node` as the first child of the node `index`, or as the first top level entry if
+    /// `index` is empty. Return the identifier of the inserted node.
+    pub fn insert_first_child(&mut self, index: TreeIdentifier, node: NavNode) -> TreeIdentifierVec {
+        self.children_mut(index).insert(0, node);
+        [index, &[0]].concat()
+    }
+
+    /// Insert `node` right after the node `index`, or as the first top level entry if `index` is
+    /// empty. Return the identifier of the inserted node.
+    pub fn insert_sibling_below(&mut self, index: TreeIdentifier, node: NavNode) -> TreeIdentifierVec {
+        let Some((&last, father)) = index.split_last() else {
+            return self.insert_first_child(index, node);
+        };
+        self.children_mut(father).insert(last + 1, node);
+        [father, &[last + 1]].concat()
+    }
+
+    /// Return the list of children of the node `father`, the top level entries if it is empty.
+    fn children_mut(&mut self, father: TreeIdentifier) -> &mut Vec<NavNode> {
         if father.is_empty() {
-            self.nodes.insert(index[0] + 1, NavNode::default());
+            &mut self.nodes
         } else {
-            self[father].children.insert(index.last().unwrap() + 1, NavNode::default());
+            &mut self[father].children
+        }
+    }
+
+    /// Swap the node `index` with its previous sibling. Return its new identifier, or `None` if it
+    /// is already the first child.
+    pub fn move_up(&mut self, index: TreeIdentifier) -> Option<TreeIdentifierVec> {
+        let (&last, father) = index.split_last()?;
+        if last == 0 {
+            return None;
         }
+        self.children_mut(father).swap(last - 1, last);
+        Some([father, &[last - 1]].concat())
+    }
+
+    /// Swap the node `index` with its next sibling. Return its new identifier, or `None` if it is
+    /// already the last child.
+    pub fn move_down(&mut self, index: TreeIdentifier) -> Option<TreeIdentifierVec> {
+        let (&last, father) = index.split_last()?;
+        let siblings = self.children_mut(father);
+        if last + 1 >= siblings.len() {
+            return None;
+        }
+        siblings.swap(last, last + 1);
+        Some([father, &[last + 1]].concat())
+    }
+
+    /// Make the node `index` the last child of its previous sibling. Return its new identifier, or
+    /// `None` if it is the first child.
+    pub fn indent(&mut self, index: TreeIdentifier) -> Option<TreeIdentifierVec> {
+        let (&last, father) = index.split_last()?;
+        if last == 0 {
+            return None;
+        }
+        let siblings = self.children_mut(father);
+        let node = siblings.remove(last);
+        let new_father = &mut siblings[last - 1].children;
+        new_father.push(node);
+        Some([father, &[last - 1, new_father.len() - 1]].concat())
+    }
+
+    /// Make the node `index` the sibling right after its father. Return its new identifier, or
+    /// `None` if it is a top level entry.
+    pub fn outdent(&mut self, index: TreeIdentifier) -> Option<TreeIdentifierVec> {
+        let (&last, father) = index.split_last()?;
+        let (&father_last, grandfather) = father.split_last()?;
+        let node = self.children_mut(father).remove(last);
+        self.children_mut(grandfather).insert(father_last + 1, node);
+        Some([grandfather, &[father_last + 1]].concat())
+    }
+
+    /// Move the siblings `range` of the node `father` before their previous sibling. Return their
+    /// father and new positions, or `None` if they are already the first children.
+    pub fn move_range_up(&mut self, father: TreeIdentifier, range: RangeInclusive<usize>) -> Option<(TreeIdentifierVec, RangeInclusive<usize>)> {
+        let (start, end) = range.into_inner();
+        if start == 0 {
+            return None;
+        }
+        self.children_mut(father)[start - 1..=end].rotate_left(1);
+        Some((father.to_owned(), start - 1..=end - 1))
+    }
+
+    /// Move the siblings `range` of the node `father` after their next sibling. Return their
+    /// father and new positions, or `None` if they are already the last children.
+    pub fn move_range_down(&mut self, father: TreeIdentifier, range: RangeInclusive<usize>) -> Option<(TreeIdentifierVec, RangeInclusive<usize>)> {
+        let (start, end) = range.into_inner();
+        let siblings = self.children_mut(father);
+        if end + 1 >= siblings.len() {
+            return None;
+        }
+        siblings[start..=end + 1].rotate_right(1);
+        Some((father.to_owned(), start + 1..=end + 1))
+    }
+
+    /// Make the siblings `range` of the node `father` the last children of their previous sibling.
+    /// Return their new father and positions, or `None` if they are the first children.
+    pub fn indent_range(&mut self, father: TreeIdentifier, range: RangeInclusive<usize>) -> Option<(TreeIdentifierVec, RangeInclusive<usize>)> {
+        let start = *range.start();
+        if start == 0 {
+            return None;
+        }
+        let siblings = self.children_mut(father);
+        let nodes: Vec<_> = siblings.drain(range).collect();
+        let new_father = &mut siblings[start - 1].children;
+        let first = new_father.len();
+        new_father.extend(nodes);
+        Some(([father, &[start - 1]].concat(), first..=new_father.len() - 1))
+    }
+
+    /// Make the siblings `range` of the node `father` the siblings right after it. Return their new
+    /// father and positions, or `None` if they are top level entries.
+    pub fn outdent_range(&mut self, father: TreeIdentifier, range: RangeInclusive<usize>) -> Option<(TreeIdentifierVec, RangeInclusive<usize>)> {
+        let (&father_last, grandfather) = father.split_last()?;
+        let nodes: Vec<_> = self.children_mut(father).drain(range).collect();
+        let count = nodes.len();
+        let siblings = self.children_mut(grandfather);
+        siblings.splice(father_last + 1..father_last + 1, nodes);
+        Some((grandfather.to_owned(), father_last + 1..=father_last + count))
+    }
+
+    /// Remove the siblings `range` of the node `father`, and return them.
+    pub fn remove_range(&mut self, father: TreeIdentifier, range: RangeInclusive<usize>) -> Vec<NavNode> {
+        self.children_mut(father).drain(range).collect()
+    }
+
+    /// Insert `nodes` among the children of the node `father`, from position `at`. Return their
+    /// positions.
+    pub fn insert_range(&mut self, father: TreeIdentifier, at: usize, nodes: Vec<NavNode>) -> RangeInclusive<usize> {
+        let count = nodes.len();
+        self.children_mut(father).splice(at..at, nodes);
+        at..=at + count - 1
     }
 
     pub fn delete_entry(&mut self, index: TreeIdentifier) {
@@ -168,6 +500,7 @@ impl Nav {
     }
 }
 
+#[cfg(feature = "tui")]
 impl<'a> Into<TreeItem<'a>> for &'a NavNode {
     fn into(self) -> TreeItem<'a> {
         let children: Vec<_> = self.children
@@ -191,6 +524,7 @@ impl TreeView for Nav {
     }
 }
 
+#[cfg(feature = "tui")]
 impl<'a> Into<Vec<TreeItem<'a>>> for &'a Nav {
     fn into(self) -> Vec<TreeItem<'a>> {
         self.nodes.iter()
@@ -223,3 +557,192 @@ impl<'a> IndexMut<TreeIdentifier<'a>> for Nav {
         self.nodes[index[0]].get_node_from_id_mut(&index[1..])
     }
 }
+
+/// Return an entry titled `title` pointing to page `page`, with `children`, to write outlines in
+/// tests.
+#[cfg(test)]
+pub(crate) fn node(title: &str, page: u32, children: Vec<NavNode>) -> NavNode {
+    NavNode { string: String::from(title), link: BookmarkLink::PageNumber(page), children }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn titles(nodes: &[NavNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.string.as_str()).collect()
+    }
+
+    #[test]
+    fn escaped_titles_read_back() {
+        let nav = Nav {
+            nodes: vec![NavNode { string: String::from("C:\\Users \"me\"\nand\tyou"), ..NavNode::default() }],
+        };
+        let written = nav.to_djvu();
+        assert!(written.contains(r#"("C:\\Users \"me\"\nand\tyou""#));
+        assert_eq!(crate::djvu::parser::parse_djvu_nav(&written).unwrap().1, nav);
+    }
+
+    #[test]
+    #[cfg(feature = "tui")]
+    fn page_references_aligned_to_the_right() {
+        use ratatui::{backend::TestBackend, Terminal};
+
+        let nav = Nav { nodes: vec![node("Preface", 0, vec![])] };
+        let row = |width: u16| {
+            let mut terminal = Terminal::new(TestBackend::new(width, 1)).unwrap();
+            terminal.draw(|f| {
+                let reference = |_: TreeIdentifier, _: &NavNode| Some(String::from("p. 5 (v)"));
+                nav.ui_with_text(f, f.size(), &mut TreeState::default(), |_, node| Cow::Borrowed(node.string.as_str()), reference, |_| Style::default());
+            }).unwrap();
+            terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect::<String>()
+        };
+        assert_eq!(row(24), "  Preface ..... p. 5 (v)");
+        // Too narrow for the reference and some of the title.
+        assert_eq!(row(16), "  Preface       ");
+    }
+
+    #[test]
+    #[cfg(feature = "tui")]
+    fn rows_under_the_mouse() {
+        use ratatui::{backend::TestBackend, Terminal};
+        use crate::tree_widget::TreeHit;
+
+        let nav = Nav {
+            nodes: vec![node("a", 0, vec![node("a.1", 0, vec![]), node("a.2", 0, vec![])]), node("b", 0, vec![]), node("c", 0, vec![]), node("d", 0, vec![])],
+        };
+        let mut state = TreeState::default();
+        state.open(&[0]);
+        state.select(vec![0]);
+        let mut terminal = Terminal::new(TestBackend::new(12, 3)).unwrap();
+        let mut draw = |state: &mut TreeState| {
+            terminal.draw(|f| nav.ui(f, f.size(), state, |_| Style::default())).unwrap();
+        };
+        draw(&mut state);
+        assert_eq!(state.hit(2, 0), Some(TreeHit::Toggle(vec![0])));
+        assert_eq!(state.hit(8, 0), Some(TreeHit::Item(vec![0])));
+        assert_eq!(state.hit(2, 1), Some(TreeHit::Item(vec![0, 0])));
+        assert_eq!(state.hit(2, 3), None);
+
+        // The selection follows the top of the view, which stops at the last node.
+        state.scroll_down(&nav, 2);
+        assert_eq!((state.get_offset(), state.selected()), (2, &[0, 1][..]));
+        draw(&mut state);
+        state.scroll_down(&nav, 5);
+        assert_eq!((state.get_offset(), state.selected()), (3, &[1][..]));
+        draw(&mut state);
+        state.select(vec![3]);
+        state.scroll_up(2);
+        assert_eq!((state.get_offset(), state.selected()), (1, &[1][..]));
+    }
+
+    #[test]
+    fn move_among_siblings() {
+        let mut nav = Nav { nodes: vec![node("a", 0, vec![]), node("b", 0, vec![]), node("c", 0, vec![])] };
+
+        assert_eq!(nav.move_up(&[0]), None);
+        assert_eq!(nav.move_down(&[0]), Some(vec![1]));
+        assert_eq!(titles(&nav.nodes), ["b", "a", "c"]);
+        assert_eq!(nav.move_down(&[2]), None);
+        assert_eq!(nav.move_up(&[2]), Some(vec![1]));
+        assert_eq!(titles(&nav.nodes), ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn indent_and_outdent() {
+        let mut nav = Nav {
+            nodes: vec![node("a", 0, vec![node("a.1", 0, vec![])]), node("b", 0, vec![node("b.1", 0, vec![])])],
+        };
+
+        assert_eq!(nav.indent(&[0]), None);
+        assert_eq!(nav.indent(&[1]), Some(vec![0, 1]));
+        assert_eq!(titles(&nav.nodes), ["a"]);
+        assert_eq!(titles(&nav.nodes[0].children), ["a.1", "b"]);
+        assert_eq!(titles(&nav.nodes[0].children[1].children), ["b.1"]);
+
+        assert_eq!(nav.outdent(&[0, 0]), Some(vec![1]));
+        assert_eq!(titles(&nav.nodes), ["a", "a.1"]);
+        assert_eq!(nav.outdent(&[1]), None);
+    }
+
+    #[test]
+    fn ranges_of_siblings() {
+        let mut nav = Nav {
+            nodes: vec![node("a", 0, vec![]), node("b", 0, vec![]), node("c", 0, vec![]), node("d", 0, vec![])],
+        };
+
+        assert_eq!(nav.move_range_up(&[], 0..=1), None);
+        assert_eq!(nav.move_range_down(&[], 1..=2), Some((vec![], 2..=3)));
+        assert_eq!(titles(&nav.nodes), ["a", "d", "b", "c"]);
+        assert_eq!(nav.move_range_down(&[], 2..=3), None);
+
+        assert_eq!(nav.indent_range(&[], 2..=3), Some((vec![1], 0..=1)));
+        assert_eq!(titles(&nav.nodes), ["a", "d"]);
+        assert_eq!(titles(&nav.nodes[1].children), ["b", "c"]);
+        assert_eq!(nav.outdent_range(&[1], 0..=1), Some((vec![], 2..=3)));
+        assert_eq!(titles(&nav.nodes), ["a", "d", "b", "c"]);
+        assert_eq!(nav.outdent_range(&[], 0..=1), None);
+
+        let removed = nav.remove_range(&[], 0..=1);
+        assert_eq!(titles(&removed), ["a", "d"]);
+        assert_eq!(titles(&nav.nodes), ["b", "c"]);
+        assert_eq!(nav.insert_range(&[], 1, removed), 1..=2);
+        assert_eq!(titles(&nav.nodes), ["b", "a", "d", "c"]);
+    }
+
+    #[test]
+    fn find_ignores_case() {
+        let nav = Nav {
+            nodes: vec![node("Preface", 0, vec![]), node("Part I", 0, vec![node("The preface revisited", 0, vec![])])],
+        };
+
+        assert_eq!(nav.find("PREFACE"), vec![vec![0], vec![1, 0]]);
+        assert!(nav.find("index").is_empty());
+    }
+
+    #[test]
+    fn insert_subtrees() {
+        let mut nav = Nav { nodes: vec![] };
+
+        assert_eq!(nav.insert_sibling_below(&[], node("b", 0, vec![])), vec![0]);
+        assert_eq!(nav.insert_first_child(&[0], node("b.1", 0, vec![])), vec![0, 0]);
+        assert_eq!(nav.insert_sibling_below(&[0], nav.nodes[0].clone()), vec![1]);
+        assert_eq!(titles(&nav.nodes), ["b", "b"]);
+        assert_eq!(titles(&nav.nodes[1].children), ["b.1"]);
+    }
+
+    #[test]
+    fn changes_next_to_added_entries() {
+        let old = Nav {
+            nodes: vec![
+                node("Preface", 0, vec![]),
+                node("Part I", 0, vec![node("Chapter 1", 0, vec![]), node("Chapter 2", 0, vec![])]),
+                node("Part II", 0, vec![]),
+                node("Index", 0, vec![]),
+            ],
+        };
+        let mut new = old.clone();
+        new.nodes.insert(1, node("Introduction", 0, vec![]));
+        new.nodes[2].children[1].string = String::from("Chapter Two");
+        new.nodes[3].string = String::from("Part 2");
+
+        assert_eq!(old.changed_nodes(&new), vec![vec![], vec![1], vec![2, 1], vec![3]]);
+
+        // Replacing the preface with the introduction edits it in place.
+        new.nodes.remove(0);
+        assert_eq!(old.changed_nodes(&new), vec![vec![0], vec![1, 1], vec![2]]);
+    }
+
+    #[test]
+    fn nodes_down_to_depth() {
+        let nav = Nav {
+            nodes: vec![
+                node("Part One", 1, vec![node("Chapter 1", 2, vec![node("Section", 3, vec![])])]),
+                node("Part Two", 4, vec![]),
+            ],
+        };
+        assert_eq!(nav.down_to_depth(0), Vec::<TreeIdentifierVec>::new());
+        assert_eq!(nav.down_to_depth(1), vec![vec![0]]);
+        assert_eq!(nav.down_to_depth(5), vec![vec![0], vec![0, 0]]);
+    }
+}
@@ -0,0 +1,226 @@
+//! Reading the text of scanned pages from hOCR or ALTO files, for documents whose own text layer
+//! is poor.
+
+use std::{collections::HashMap, fmt::Display};
+
+use crate::scandata::Leaf;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum OcrError {
+    /// The file is neither hOCR nor ALTO.
+    UnknownFormat,
+    NoPages,
+}
+
+impl Display for OcrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownFormat => write!(f, "expected an hOCR or an ALTO file"),
+            Self::NoPages => write!(f, "the OCR output has no page"),
+        }
+    }
+}
+
+impl std::error::Error for OcrError {}
+
+/// The text of a scanned image, one line of text per line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OcrPage {
+    /// Number of the image in the scan, starting at 0.
+    pub image: u32,
+    pub text: String,
+}
+
+/// Return the value of the attribute `name` in `tag`, quoted with either kind of quotes.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    ['"', '\''].into_iter().find_map(|quote| {
+        let start = tag.find(&format!("{}={}", name, quote))? + name.len() + 2;
+        let length = tag[start..].find(quote)?;
+        Some(&tag[start..start + length])
+    })
+}
+
+/// Replace the entities of `text` by the characters they stand for.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity.strip_prefix('#')?.parse().ok().and_then(char::from_u32),
+            },
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            },
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Return the text of `html` without its tags, with its spacing collapsed.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                text.push(' ');
+            },
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => (),
+        }
+    }
+    decode_entities(&text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Split `document` before each occurrence of `marker`, dropping what comes before the first one.
+fn split_before<'a>(document: &'a str, marker: &str) -> Vec<&'a str> {
+    let starts: Vec<_> = document.match_indices(marker).map(|(start, _)| start).collect();
+    starts.iter()
+        .enumerate()
+        .map(|(i, start)| &document[*start..starts.get(i + 1).copied().unwrap_or(document.len())])
+        .collect()
+}
+
+/// Read the pages of an hOCR file, whose lines are the elements of class `ocr_line` and the
+/// like, and whose images are numbered by the `ppageno` property of the pages.
+fn parse_hocr(hocr: &str) -> Vec<OcrPage> {
+    let is_line_class = |class: &str| {
+        class.split_whitespace().any(|class| ["ocr_line", "ocr_header", "ocr_caption", "ocr_textfloat"].contains(&class))
+    };
+    split_before(hocr, "ocr_page").into_iter()
+        .enumerate()
+        .map(|(i, page)| {
+            let image = attribute(page, "title")
+                .and_then(|title| title.split(';').find_map(|property| property.trim().strip_prefix("ppageno ")))
+                .and_then(|number| number.trim().parse().ok())
+                .unwrap_or(i as u32);
+            let line_starts: Vec<_> = page.match_indices('<')
+                .map(|(start, _)| start)
+                .filter(|start| {
+                    let tag = &page[*start..];
+                    attribute(&tag[..tag.find('>').unwrap_or(tag.len())], "class").is_some_and(is_line_class)
+                })
+                .collect();
+            // The text of a line goes on until the next line, or the end of the page.
+            let lines: Vec<_> = line_starts.iter()
+                .enumerate()
+                .map(|(i, start)| strip_tags(&page[*start..line_starts.get(i + 1).copied().unwrap_or(page.len())]))
+                .filter(|line| !line.is_empty())
+                .collect();
+            OcrPage { image, text: lines.join("\n") }
+        })
+        .collect()
+}
+
+/// Read the pages of an ALTO file, whose lines are `TextLine` elements holding the words in the
+/// `CONTENT` of their `String` elements, and whose images are numbered by `PHYSICAL_IMG_NR`.
+fn parse_alto(alto: &str) -> Vec<OcrPage> {
+    split_before(alto, "<Page ").into_iter()
+        .enumerate()
+        .map(|(i, page)| {
+            let page_tag = &page[..page.find('>').unwrap_or(page.len())];
+            let image = attribute(page_tag, "PHYSICAL_IMG_NR")
+                .and_then(|number| number.parse::<u32>().ok())
+                .map_or(i as u32, |number| number.saturating_sub(1));
+            let lines: Vec<_> = split_before(page, "<TextLine").into_iter()
+                .map(|line| {
+                    let words: Vec<_> = split_before(line, "<String ").into_iter()
+                        .filter_map(|word| attribute(word, "CONTENT").map(decode_entities))
+                        .collect();
+                    words.join(" ")
+                })
+                .filter(|line| !line.is_empty())
+                .collect();
+            OcrPage { image, text: lines.join("\n") }
+        })
+        .collect()
+}
+
+/// Read the pages of `document`, an hOCR or an ALTO file.
+pub fn parse_ocr(document: &str) -> Result<Vec<OcrPage>, OcrError> {
+    let pages = if document.contains("<alto") {
+        parse_alto(document)
+    } else if document.contains("ocr_page") {
+        parse_hocr(document)
+    } else {
+        return Err(OcrError::UnknownFormat);
+    };
+    if pages.is_empty() {
+        return Err(OcrError::NoPages);
+    }
+    Ok(pages)
+}
+
+/// Return the text of each page of the document, by physical page number. Images are mapped to
+/// pages with `leaves`, the scan data of the document, if known, and are taken to be the pages in
+/// order otherwise.
+pub fn text_by_page(pages: Vec<OcrPage>, leaves: Option<&[Leaf]>) -> HashMap<u32, String> {
+    pages.into_iter()
+        .filter_map(|page| {
+            let number = match leaves {
+                Some(leaves) => leaves.iter().find(|leaf| leaf.number == page.image)?.page?,
+                None => page.image + 1,
+            };
+            Some((number, page.text))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hocr_lines() {
+        let hocr = "<html><body>\
+            <div class='ocr_page' title='image \"p1.png\"; bbox 0 0 2000 3000; ppageno 4'>\
+            <span class='ocr_header' title='bbox 100 100 900 150'><span class='ocrx_word'>CHAPTER</span> <span class='ocrx_word'>IV.</span></span>\
+            <span class='ocr_line' title='bbox 100 200 900 250'><span class='ocrx_word'>Rabbit</span> <span class='ocrx_word'>&amp;</span> <span class='ocrx_word'>Bill</span></span>\
+            </div></body></html>";
+        assert_eq!(parse_ocr(hocr).unwrap(), [OcrPage { image: 4, text: String::from("CHAPTER IV.\nRabbit & Bill") }]);
+    }
+
+    #[test]
+    fn alto_lines_mapped_through_scandata() {
+        let alto = "<alto><Layout>\
+            <Page ID=\"P1\" PHYSICAL_IMG_NR=\"1\"><PrintSpace><TextBlock>\
+            <TextLine><String CONTENT=\"Color\"/><SP/><String CONTENT=\"card\"/></TextLine>\
+            </TextBlock></PrintSpace></Page>\
+            <Page ID=\"P2\" PHYSICAL_IMG_NR=\"2\"><PrintSpace><TextBlock>\
+            <TextLine><String CONTENT=\"Part\"/><SP/><String CONTENT=\"&quot;One&quot;\"/></TextLine>\
+            <TextLine><String CONTENT=\"The\"/><SP/><String CONTENT=\"Beginning\"/></TextLine>\
+            </TextBlock></PrintSpace></Page>\
+            </Layout></alto>";
+        let pages = parse_ocr(alto).unwrap();
+        assert_eq!(pages[1], OcrPage { image: 1, text: String::from("Part \"One\"\nThe Beginning") });
+
+        let leaves = [
+            Leaf { number: 0, page: None, label: None },
+            Leaf { number: 1, page: Some(1), label: None },
+        ];
+        let texts = text_by_page(pages, Some(&leaves));
+        assert_eq!(texts.len(), 1);
+        assert_eq!(texts[&1], "Part \"One\"\nThe Beginning");
+        assert_eq!(parse_ocr("plain text"), Err(OcrError::UnknownFormat));
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{
+    djvu::PageInfo,
+    link::page_number,
+    nav::{BookmarkLink, Nav},
+    tree_widget::{TreeIdentifier, TreeIdentifierVec},
+};
+
+/// An entry pointing to an earlier page than the entry before it in document order, which usually
+/// means that the pages from `page` onwards were offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderBreak {
+    pub id: TreeIdentifierVec,
+    pub page: u32,
+    pub previous_page: u32,
+}
+
+/// Add `delta` to the page number of every entry pointing to page `from` or later, among the node
+/// `within` and its descendants, or the whole outline if `within` is empty. Links to pages by name
+/// are left alone, since inserting or removing pages does not change them, and so are the entries
+/// that would end up before the first page. Return the number of entries changed.
+pub fn shift_pages(nav: &mut Nav, within: TreeIdentifier, delta: i32, from: u32) -> usize {
+    let ids: Vec<_> = nav.all_nodes().into_iter()
+        .map(|(id, _)| id)
+        .filter(|id| id.starts_with(within))
+        .collect();
+    let mut changed = 0;
+    for id in ids {
+        let node = &mut nav[&id];
+        let BookmarkLink::PageNumber(n) = node.link else {
+            continue;
+        };
+        match n.checked_add_signed(delta) {
+            Some(shifted) if n >= from && shifted > 0 && shifted != n => {
+                node.link = BookmarkLink::PageNumber(shifted);
+                changed += 1;
+            },
+            _ => (),
+        }
+    }
+    changed
+}
+
+/// Give the children of `parent`, or the top level entries if it is empty, that point to no page
+/// yet, as new entries do, pages spread evenly between the pages around each run of them: the last
+/// page pointed to before the run in document order, and the first one after it or else
+/// `last_page`. Return the number of entries changed.
+pub fn interpolate_pages(nav: &mut Nav, parent: TreeIdentifier, pages: Option<&[PageInfo]>, last_page: Option<u32>) -> usize {
+    let targets: Vec<_> = nav.all_nodes().into_iter()
+        .map(|(id, node)| {
+            let unset = node.link == BookmarkLink::PageNumber(0);
+            (id, unset, page_number(&node.link, pages).filter(|page| *page > 0))
+        })
+        .collect();
+
+    // Positions in `targets` of the runs of siblings without a page.
+    let mut runs: Vec<Vec<usize>> = Vec::new();
+    let mut run = Vec::new();
+    for (position, (id, unset, _)) in targets.iter().enumerate() {
+        if id.len() != parent.len() + 1 || !id.starts_with(parent) {
+            continue;
+        }
+        if *unset {
+            run.push(position);
+        } else if !run.is_empty() {
+            runs.push(std::mem::take(&mut run));
+        }
+    }
+    if !run.is_empty() {
+        runs.push(run);
+    }
+
+    let mut changed = 0;
+    for run in runs {
+        let (first, last) = (run[0], run[run.len() - 1]);
+        let before = targets[..first].iter().rev().find_map(|(_, _, page)| *page).unwrap_or(1);
+        let after = targets[last + 1..].iter().find_map(|(_, _, page)| *page).or(last_page);
+        let Some(after) = after.filter(|after| *after >= before) else {
+            continue;
+        };
+        let steps = run.len() as u32 + 1;
+        for (i, position) in (1..).zip(&run) {
+            nav[&targets[*position].0].link = BookmarkLink::PageNumber(before + (after - before) * i / steps);
+            changed += 1;
+        }
+    }
+    changed
+}
+
+/// Return the places where the target pages of the entries stop increasing, in document order.
+pub fn order_breaks(nav: &Nav, pages: Option<&[PageInfo]>) -> Vec<OrderBreak> {
+    let mut breaks = Vec::new();
+    let mut previous_page = None;
+    for (id, node) in nav.all_nodes() {
+        let Some(page) = page_number(&node.link, pages) else {
+            continue;
+        };
+        if let Some(previous_page) = previous_page.filter(|previous| page < *previous) {
+            breaks.push(OrderBreak { id, page, previous_page });
+        }
+        previous_page = Some(page);
+    }
+    breaks
+}
+
+/// Return the number of pages to add to printed page numbers to get the pages of the document,
+/// agreed on by the most pages of `labels`, or `None` if no page is numbered in arabic numerals.
+pub fn label_offset(labels: &[Option<String>]) -> Option<i32> {
+    let offsets: Vec<i32> = labels.iter()
+        .enumerate()
+        .filter_map(|(i, label)| {
+            let printed: i32 = label.as_deref()?.parse().ok()?;
+            Some(i as i32 + 1 - printed)
+        })
+        .collect();
+    let count = |offset: &i32| offsets.iter().filter(|other| *other == offset).count();
+    // The first of the most common offsets, when front matter and body tie.
+    offsets.iter().fold(None, |best: Option<i32>, offset| match best {
+        Some(best) if count(&best) >= count(offset) => Some(best),
+        _ => Some(*offset),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::NavNode;
+
+    fn nav_with(pages: &[u32]) -> Nav {
+        Nav {
+            nodes: pages.iter()
+                .map(|n| NavNode { link: BookmarkLink::PageNumber(*n), ..NavNode::default() })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn only_later_pages_are_shifted() {
+        let mut nav = nav_with(&[5, 180, 200, 240]);
+
+        assert_eq!(shift_pages(&mut nav, &[], 8, 200), 2);
+        assert_eq!(nav, nav_with(&[5, 180, 208, 248]));
+        assert_eq!(shift_pages(&mut nav, &[], -10, 0), 3);
+        assert_eq!(nav, nav_with(&[5, 170, 198, 238]));
+    }
+
+    #[test]
+    fn only_the_subtree_is_shifted() {
+        let mut nav = nav_with(&[1, 10, 20]);
+        nav.nodes[1].children = nav_with(&[11, 15]).nodes;
+
+        assert_eq!(shift_pages(&mut nav, &[1], 12, 0), 3);
+        assert_eq!(nav.nodes[0].link, BookmarkLink::PageNumber(1));
+        assert_eq!(nav.nodes[1].children[1].link, BookmarkLink::PageNumber(27));
+        assert_eq!(nav.nodes[2].link, BookmarkLink::PageNumber(20));
+    }
+
+    #[test]
+    fn unset_pages_are_spread_between_known_ones() {
+        let mut nav = nav_with(&[10, 0, 0, 0, 30, 0]);
+        nav.nodes[0].children = nav_with(&[12]).nodes;
+
+        assert_eq!(interpolate_pages(&mut nav, &[], None, None), 3);
+        let pages: Vec<_> = nav.nodes.iter().map(|node| node.link.clone()).collect();
+        assert_eq!(pages, nav_with(&[10, 16, 21, 25, 30, 0]).nodes.into_iter().map(|node| node.link).collect::<Vec<_>>());
+
+        assert_eq!(interpolate_pages(&mut nav, &[], None, Some(40)), 1);
+        assert_eq!(nav.nodes[5].link, BookmarkLink::PageNumber(35));
+    }
+
+    #[test]
+    fn page_order_breaks_are_found() {
+        let nav = nav_with(&[5, 180, 212, 196, 230]);
+
+        assert_eq!(order_breaks(&nav, None), vec![OrderBreak { id: vec![3], page: 196, previous_page: 212 }]);
+    }
+
+    #[test]
+    fn offset_agreed_on_by_most_labels() {
+        let labels: Vec<_> = [None, Some("i"), Some("1"), Some("2"), Some("7")].into_iter()
+            .map(|label| label.map(String::from))
+            .collect();
+        assert_eq!(label_offset(&labels), Some(2));
+        assert_eq!(label_offset(&labels[..2]), None);
+    }
+}
@@ -0,0 +1,182 @@
+//! Pages typed as simple arithmetic on the pages of the entries around the one being edited, such
+//! as `last+1` or `next-1`, to transcribe a printed table of contents without counting.
+
+use std::fmt::Display;
+
+use crate::{anchors::Anchors, nav::BookmarkLink};
+
+/// What a page expression starts from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageBase {
+    Page(u32),
+    /// The page of the entry above, in document order (`last` or `prev`).
+    Previous,
+    /// The page of the entry below, in document order.
+    Next,
+    /// The page of the parent entry.
+    Parent,
+    /// The last page of the document.
+    End,
+}
+
+/// A page, possibly relative to the entries around the one being edited: a page number or one of
+/// `last`, `prev`, `next`, `parent` and `end`, followed by numbers to add or subtract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageExpr {
+    pub base: PageBase,
+    pub delta: i64,
+}
+
+/// The pages a page expression can refer to, those not known being `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PageContext {
+    pub previous: Option<u32>,
+    pub next: Option<u32>,
+    pub parent: Option<u32>,
+    pub end: Option<u32>,
+}
+
+/// Zeros of the sets of decimal digits tables of contents are pasted with besides ASCII ones:
+/// full-width, Arabic-Indic, Eastern Arabic-Indic (Persian and Urdu), Devanagari, Bengali and Thai.
+const ZEROS: [char; 6] = ['\u{ff10}', '\u{660}', '\u{6f0}', '\u{966}', '\u{9e6}', '\u{e50}'];
+
+/// Return `input` with the digits of other numeral systems replaced by ASCII digits, and the
+/// full-width and typographic plus and minus signs by ASCII ones.
+pub fn ascii_digits(input: &str) -> String {
+    input.chars()
+        .map(|c| match c {
+            '\u{ff0b}' => '+',
+            '\u{ff0d}' | '\u{2212}' => '-',
+            c => ZEROS.iter()
+                .find_map(|zero| char::from_digit(u32::from(c).checked_sub(u32::from(*zero))?, 10))
+                .unwrap_or(c),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PageExprError {
+    /// The expression starts from a page that is not known.
+    Unknown(PageBase),
+    /// The expression gives this number, which is not a page.
+    OutOfRange(i64),
+}
+
+impl Display for PageExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unknown(PageBase::Previous) => write!(f, "no entry above has a page"),
+            Self::Unknown(PageBase::Next) => write!(f, "no entry below has a page"),
+            Self::Unknown(PageBase::Parent) => write!(f, "the entry has no parent with a page"),
+            Self::Unknown(_) => write!(f, "the number of pages is not known"),
+            Self::OutOfRange(value) => write!(f, "{} is not a page", value),
+        }
+    }
+}
+
+impl std::error::Error for PageExprError {}
+
+impl PageExpr {
+    pub fn page(page: u32) -> Self {
+        Self { base: PageBase::Page(page), delta: 0 }
+    }
+
+    /// Parse `input`, such as `12`, `last+1` or `next - 1`, or return `None` if it is not a page
+    /// expression. Numbers can be written with the digits of other numeral systems, such as `１２`
+    /// or `١٢`.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input: String = ascii_digits(input).chars().filter(|c| !c.is_whitespace()).collect();
+        let end = input.find(['+', '-']).unwrap_or(input.len());
+        let (base, mut rest) = input.split_at(end);
+        let base = match base {
+            "last" | "prev" => PageBase::Previous,
+            "next" => PageBase::Next,
+            "parent" => PageBase::Parent,
+            "end" => PageBase::End,
+            page => PageBase::Page(page.parse().ok()?),
+        };
+        let mut delta: i64 = 0;
+        while let Some(sign) = rest.chars().next() {
+            let term = &rest[1..];
+            let end = term.find(['+', '-']).unwrap_or(term.len());
+            let number: i64 = term[..end].parse::<u32>().ok()?.into();
+            delta = if sign == '+' { delta.checked_add(number)? } else { delta.checked_sub(number)? };
+            rest = &term[end..];
+        }
+        Some(Self { base, delta })
+    }
+
+    /// Return the page `self` gives in `context`.
+    pub fn evaluate(&self, context: &PageContext) -> Result<u32, PageExprError> {
+        let base = match self.base {
+            PageBase::Page(page) if self.delta == 0 => return Ok(page),
+            PageBase::Page(page) => Some(page),
+            PageBase::Previous => context.previous,
+            PageBase::Next => context.next,
+            PageBase::Parent => context.parent,
+            PageBase::End => context.end,
+        };
+        let base = base.ok_or(PageExprError::Unknown(self.base))?;
+        let value = i64::from(base) + self.delta;
+        u32::try_from(value).ok().filter(|page| *page > 0).ok_or(PageExprError::OutOfRange(value))
+    }
+}
+
+impl PageContext {
+    /// Return the context of entry `index` of `entries`, given in document order as their depth
+    /// and page, for a document of `end` pages if known.
+    pub fn around(entries: &[(usize, Option<u32>)], index: usize, end: Option<u32>) -> Self {
+        let Some((depth, _)) = entries.get(index) else {
+            return Self { end, ..Self::default() };
+        };
+        let before = &entries[..index];
+        Self {
+            previous: before.iter().rev().find_map(|(_, page)| *page),
+            next: entries[index + 1..].iter().find_map(|(_, page)| *page),
+            parent: before.iter().rev().find(|(other, _)| other < depth).and_then(|(_, page)| *page),
+            end,
+        }
+    }
+}
+
+/// Return the link typed as `input`: the page of a page expression evaluated in `context`, the
+/// page named so in `anchors`, or else the link as it is.
+pub fn parse_link(input: &str, context: &PageContext, anchors: &Anchors) -> Result<BookmarkLink, PageExprError> {
+    match PageExpr::parse(input) {
+        Some(expr) => expr.evaluate(context).map(BookmarkLink::PageNumber),
+        None => Ok(anchors.page(input.trim()).map_or_else(|| BookmarkLink::from_string(input), BookmarkLink::PageNumber)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions_around_an_entry() {
+        assert_eq!(PageExpr::parse("last + 1"), Some(PageExpr { base: PageBase::Previous, delta: 1 }));
+        assert_eq!(PageExpr::parse("next-1+3"), Some(PageExpr { base: PageBase::Next, delta: 2 }));
+        assert_eq!(PageExpr::parse("12"), Some(PageExpr::page(12)));
+        assert_eq!(PageExpr::parse("p0012.djvu"), None);
+        assert_eq!(PageExpr::parse("last+"), None);
+        assert_eq!(PageExpr::parse("１２"), Some(PageExpr::page(12)));
+        assert_eq!(PageExpr::parse("٣٤٥"), Some(PageExpr::page(345)));
+        assert_eq!(PageExpr::parse("next－۲"), Some(PageExpr { base: PageBase::Next, delta: -2 }));
+
+        // Part One (3), Chapter 1 (5), the edited entry, Chapter 3 (20).
+        let entries = [(1, Some(3)), (2, Some(5)), (2, None), (2, Some(20))];
+        let context = PageContext::around(&entries, 2, Some(300));
+        let anchors = Anchors { pages: [(String::from("frontispiece"), 2)].into() };
+        assert_eq!(context, PageContext { previous: Some(5), next: Some(20), parent: Some(3), end: Some(300) });
+        assert_eq!(parse_link("last+1", &context, &anchors), Ok(BookmarkLink::PageNumber(6)));
+        assert_eq!(parse_link("next-1", &context, &anchors), Ok(BookmarkLink::PageNumber(19)));
+        assert_eq!(parse_link("parent", &context, &anchors), Ok(BookmarkLink::PageNumber(3)));
+        assert_eq!(parse_link("#intro", &context, &anchors), Ok(BookmarkLink::PageLink(String::from("#intro"))));
+        assert_eq!(parse_link("0", &context, &anchors), Ok(BookmarkLink::PageNumber(0)));
+        assert_eq!(parse_link("parent-3", &context, &anchors), Err(PageExprError::OutOfRange(0)));
+        assert_eq!(parse_link("frontispiece", &context, &anchors), Ok(BookmarkLink::PageNumber(2)));
+
+        let first = PageContext::around(&entries, 0, None);
+        assert_eq!(parse_link("last+1", &first, &anchors).unwrap_err().to_string(), "no entry above has a page");
+    }
+}
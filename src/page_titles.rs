@@ -0,0 +1,114 @@
+//! The titles of the pages of the document, which viewers show as page labels such as `iii` or
+//! `12`, listed to be edited one by one or numbered in sequence.
+
+#[cfg(feature = "tui")]
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Frame,
+};
+
+use crate::djvu::PageInfo;
+
+const ROMAN_NUMERALS: [(u32, &str); 13] = [
+    (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+    (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+];
+
+/// Return `n` as a lowercase roman numeral.
+fn to_roman(mut n: u32) -> String {
+    let mut roman = String::new();
+    for (value, numeral) in ROMAN_NUMERALS {
+        while n >= value {
+            roman.push_str(numeral);
+            n -= value;
+        }
+    }
+    roman
+}
+
+/// Return the value of the lowercase roman numeral `roman`, if it is one written the usual way.
+fn from_roman(roman: &str) -> Option<u32> {
+    let mut rest = roman;
+    let mut n = 0;
+    for (value, numeral) in ROMAN_NUMERALS {
+        while let Some(after) = rest.strip_prefix(numeral) {
+            n += value;
+            rest = after;
+        }
+    }
+    (rest.is_empty() && n > 0 && to_roman(n) == roman).then_some(n)
+}
+
+/// Return the label following `label` in its numbering: `13` after `12`, `iv` after `iii`, `X`
+/// after `IX`, or `None` if it is not numbered.
+pub fn next_label(label: &str) -> Option<String> {
+    if let Ok(n) = label.parse::<u32>() {
+        return Some(n.checked_add(1)?.to_string());
+    }
+    let lowercase = label.to_lowercase();
+    let next = to_roman(from_roman(&lowercase)?.checked_add(1)?);
+    Some(if lowercase == label { next } else { next.to_uppercase() })
+}
+
+/// Return the titles to write for the pages `pages` to go from `saved`: the title of the pages
+/// that changed, empty to remove it, and `None` for the others.
+pub fn changed_titles(pages: &[PageInfo], saved: &[Option<String>]) -> Vec<Option<String>> {
+    pages.iter()
+        .zip(saved)
+        .map(|(page, saved)| match (&page.title, saved) {
+            (title, saved) if title == saved => None,
+            (Some(title), _) => Some(title.clone()),
+            (None, _) => Some(String::new()),
+        })
+        .collect()
+}
+
+/// Render the pages `pages`, with their number, the name of their file and their title, to
+/// `area`, with the page selected in `state` highlighted.
+#[cfg(feature = "tui")]
+pub fn ui(f: &mut Frame, area: Rect, pages: &[PageInfo], state: &mut ListState) {
+    let number_width = pages.len().to_string().len();
+    let id_width = pages.iter().map(|page| page.id.chars().count()).max().unwrap_or(0);
+    let items: Vec<_> = pages.iter()
+        .map(|page| {
+            let line = format!("{:>number_width$}  {:id_width$}  ", page.number, page.id);
+            match &page.title {
+                Some(title) => ListItem::new(line + title),
+                None => ListItem::new(line + "(no title)")
+                    .style(Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC)),
+            }
+        })
+        .collect();
+    let title = "Page titles (Enter to edit, n to number the next page, d to remove, w to write, Esc to go back)";
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::LightGreen))
+        .highlight_symbol("> ");
+    f.render_stateful_widget(list, area, state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_in_sequence() {
+        assert_eq!(next_label("12").as_deref(), Some("13"));
+        assert_eq!(next_label("iii").as_deref(), Some("iv"));
+        assert_eq!(next_label("XXXIX").as_deref(), Some("XL"));
+        assert_eq!(next_label("iiii"), None);
+        assert_eq!(next_label("Plate"), None);
+        assert_eq!(next_label("4294967295"), None);
+
+        let page = |number: u32, title: Option<&str>| PageInfo {
+            number,
+            id: format!("p{:04}.djvu", number),
+            title: title.map(String::from),
+        };
+        let pages = [page(1, Some("i")), page(2, None), page(3, Some("1"))];
+        let saved = [Some(String::from("i")), Some(String::from("ii")), None];
+        assert_eq!(changed_titles(&pages, &saved), [None, Some(String::new()), Some(String::from("1"))]);
+    }
+}
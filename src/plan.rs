@@ -0,0 +1,313 @@
+use std::fmt::Display;
+
+use crate::{
+    history::format_identifier,
+    nav::{BookmarkLink, Nav, NavNode},
+    tree_widget::TreeIdentifierVec,
+};
+
+/// Explanation put at the top of a plan.
+const PLAN_HEADER: &str = "\
+# Edit the action at the start of each line, then save and quit to apply the plan.
+#
+# keep (k)    leave the entry as it is
+# rename (r)  use the page and title written on the line
+# delete (d)  remove the entry and its children
+# move (m)    put the entry, with its children, right after the entry on the line above
+#
+# Entries missing from the plan are kept. Lines starting with # are ignored. Links with spaces
+# are written in double quotes, as are links to pages by name that look like page numbers.
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Keep,
+    Rename,
+    Delete,
+    Move,
+}
+
+impl Action {
+    fn from_string(name: &str) -> Option<Self> {
+        match name {
+            "keep" | "k" => Some(Self::Keep),
+            "rename" | "r" => Some(Self::Rename),
+            "delete" | "d" => Some(Self::Delete),
+            "move" | "m" => Some(Self::Move),
+            _ => None,
+        }
+    }
+}
+
+/// Errors in an edited plan, with the number of the offending line, starting from 1.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PlanError {
+    InvalidLine(usize),
+    UnknownAction(usize, String),
+    UnknownEntry(usize, String),
+    /// An entry is moved after one of its own children.
+    MoveIntoItself(usize),
+}
+
+impl Display for PlanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLine(line) => write!(f, "line {}: expected <action> <entry> <page> <title>", line),
+            Self::UnknownAction(line, action) => write!(f, "line {}: unknown action {}", line, action),
+            Self::UnknownEntry(line, id) => write!(f, "line {}: no entry {}", line, id),
+            Self::MoveIntoItself(line) => write!(f, "line {}: cannot move an entry into itself", line),
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+/// A line of a plan, about the entry with identifier `id` in the outline the plan was made from.
+#[derive(Debug)]
+struct Step {
+    line: usize,
+    action: Action,
+    id: TreeIdentifierVec,
+    link: BookmarkLink,
+    title: String,
+}
+
+/// A node of the outline tagged with its identifier in the outline the plan was made from, so that
+/// it can be found whatever the steps applied before.
+struct Tagged {
+    id: TreeIdentifierVec,
+    node: NavNode,
+    children: Vec<Tagged>,
+}
+
+impl Tagged {
+    fn from_nodes(nodes: &[NavNode], parent: &[usize]) -> Vec<Self> {
+        nodes.iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let id = [parent, &[i]].concat();
+                let children = Self::from_nodes(&node.children, &id);
+                let node = NavNode { children: Vec::new(), ..node.clone() };
+                Self { id, node, children }
+            })
+            .collect()
+    }
+
+    fn into_nodes(tagged: Vec<Self>) -> Vec<NavNode> {
+        tagged.into_iter()
+            .map(|t| NavNode { children: Self::into_nodes(t.children), ..t.node })
+            .collect()
+    }
+
+    fn contains(&self, id: &[usize]) -> bool {
+        self.id == id || self.children.iter().any(|child| child.contains(id))
+    }
+}
+
+/// Remove the node tagged `id` from `nodes`, along with its children, and return it.
+fn take(nodes: &mut Vec<Tagged>, id: &[usize]) -> Option<Tagged> {
+    if let Some(i) = nodes.iter().position(|t| t.id == id) {
+        return Some(nodes.remove(i));
+    }
+    nodes.iter_mut().find_map(|t| take(&mut t.children, id))
+}
+
+/// Insert `node` right after the node tagged `anchor` in `nodes`, or give it back if `anchor` is
+/// not there.
+fn insert_after(nodes: &mut Vec<Tagged>, anchor: &[usize], mut node: Tagged) -> Option<Tagged> {
+    if let Some(i) = nodes.iter().position(|t| t.id == anchor) {
+        nodes.insert(i + 1, node);
+        return None;
+    }
+    for t in nodes {
+        node = insert_after(&mut t.children, anchor, node)?;
+    }
+    Some(node)
+}
+
+/// Write a plan listing every entry of `nav`, all of them kept.
+pub fn make_plan(nav: &Nav) -> String {
+    let mut plan = String::from(PLAN_HEADER);
+    plan.push('\n');
+    for (id, node) in nav.all_nodes() {
+        let indent = "  ".repeat(id.len() - 1);
+        plan.push_str(&format!("keep {}{} {} {}\n", indent, format_identifier(&id), format_link(&node.link), node.string));
+    }
+    plan
+}
+
+/// Write `link` as a single word, in double quotes with JSON escapes if it would not read back
+/// as it is otherwise.
+fn format_link(link: &BookmarkLink) -> String {
+    match link {
+        BookmarkLink::PageLink(s)
+            if s.is_empty() || s.starts_with('"') || s.contains(char::is_whitespace) || s.trim().parse::<u32>().is_ok() =>
+        {
+            serde_json::Value::from(s.as_str()).to_string()
+        },
+        _ => link.to_string(),
+    }
+}
+
+/// Split the link written by `format_link` off `input`, ignoring leading whitespace.
+fn next_link(input: &str) -> Option<(BookmarkLink, &str)> {
+    let input = input.trim_start();
+    if !input.starts_with('"') {
+        let (link, rest) = next_word(input)?;
+        return Some((BookmarkLink::from_string(link), rest));
+    }
+    let mut quoted = serde_json::Deserializer::from_str(input).into_iter::<String>();
+    let link = quoted.next()?.ok()?;
+    Some((BookmarkLink::PageLink(link), &input[quoted.byte_offset()..]))
+}
+
+fn parse_identifier(input: &str) -> Option<TreeIdentifierVec> {
+    input.split('.')
+        .map(|i| i.parse::<usize>().ok()?.checked_sub(1))
+        .collect()
+}
+
+/// Split the first word off `input`, ignoring leading whitespace.
+fn next_word(input: &str) -> Option<(&str, &str)> {
+    let input = input.trim_start();
+    let end = input.find(char::is_whitespace).unwrap_or(input.len());
+    (end > 0).then(|| input.split_at(end))
+}
+
+fn parse_plan(plan: &str, nav: &Nav) -> Result<Vec<Step>, PlanError> {
+    let mut steps = Vec::new();
+    for (i, line) in plan.lines().enumerate() {
+        let line_number = i + 1;
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let invalid = || PlanError::InvalidLine(line_number);
+        let (action, rest) = next_word(line).ok_or_else(invalid)?;
+        let (id, rest) = next_word(rest).ok_or_else(invalid)?;
+        let (link, rest) = next_link(rest).ok_or_else(invalid)?;
+        let title = rest.strip_prefix(' ').unwrap_or(rest);
+
+        let action = Action::from_string(action)
+            .ok_or_else(|| PlanError::UnknownAction(line_number, String::from(action)))?;
+        let id = parse_identifier(id)
+            .filter(|id| nav.get(id).is_some())
+            .ok_or_else(|| PlanError::UnknownEntry(line_number, String::from(id)))?;
+        steps.push(Step {
+            line: line_number,
+            action,
+            id,
+            link,
+            title: String::from(title),
+        });
+    }
+    Ok(steps)
+}
+
+/// Apply the edited `plan`, made by `make_plan` from `nav`, and return the resulting outline.
+pub fn apply_plan(plan: &str, nav: &Nav) -> Result<Nav, PlanError> {
+    let steps = parse_plan(plan, nav)?;
+    let mut tagged = Tagged::from_nodes(&nav.nodes, &[]);
+
+    for step in steps.iter().filter(|step| step.action == Action::Rename) {
+        let node = find_mut(&mut tagged, &step.id).expect("the entry exists");
+        node.node.string = step.title.clone();
+        node.node.link = step.link.clone();
+    }
+
+    let mut anchor: Option<&[usize]> = None;
+    for step in &steps {
+        if step.action == Action::Move {
+            let node = take(&mut tagged, &step.id).expect("the entry exists");
+            match anchor {
+                Some(anchor) if node.contains(anchor) => return Err(PlanError::MoveIntoItself(step.line)),
+                Some(anchor) => {
+                    let not_inserted = insert_after(&mut tagged, anchor, node);
+                    debug_assert!(not_inserted.is_none(), "the anchor is in the outline");
+                },
+                None => tagged.insert(0, node),
+            }
+        }
+        if step.action != Action::Delete {
+            anchor = Some(&step.id);
+        }
+    }
+
+    for step in steps.iter().filter(|step| step.action == Action::Delete) {
+        take(&mut tagged, &step.id);
+    }
+    Ok(Nav { nodes: Tagged::into_nodes(tagged) })
+}
+
+fn find_mut<'a>(nodes: &'a mut [Tagged], id: &[usize]) -> Option<&'a mut Tagged> {
+    for t in nodes {
+        if t.id == id {
+            return Some(t);
+        }
+        if let Some(found) = find_mut(&mut t.children, id) {
+            return Some(found);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+
+    fn nav() -> Nav {
+        Nav {
+            nodes: vec![
+                node("Preface", 5, vec![]),
+                node("Part I", 10, vec![node("Chapter 1", 12, vec![]), node("Chapter 2", 30, vec![])]),
+                node("Index", 300, vec![]),
+            ],
+        }
+    }
+
+    #[test]
+    fn unchanged_plan_keeps_the_outline() {
+        let nav = nav();
+        assert_eq!(apply_plan(&make_plan(&nav), &nav), Ok(nav));
+    }
+
+    #[test]
+    fn edited_plan() {
+        let plan = "\
+            keep 1 5 Preface
+            m 3 300 Index
+            rename 2 10 Part One
+              move 2.2 30 Chapter 2
+              delete 2.1 12 Chapter 1
+        ";
+
+        assert_eq!(apply_plan(plan, &nav()), Ok(Nav {
+            nodes: vec![
+                node("Preface", 5, vec![]),
+                node("Index", 300, vec![]),
+                node("Part One", 10, vec![]),
+                node("Chapter 2", 30, vec![]),
+            ],
+        }));
+    }
+
+    #[test]
+    fn links_read_back() {
+        let link = |s: &str| NavNode { link: BookmarkLink::PageLink(String::from(s)), ..node("Notes", 1, vec![]) };
+        let nav = Nav { nodes: vec![link("#p 12.djvu"), link("12"), link("\"quoted\""), link(""), link("#p12.djvu")] };
+        let plan = make_plan(&nav);
+        assert!(plan.contains("keep 1 \"#p 12.djvu\" Notes\n"));
+        assert!(plan.contains("keep 5 #p12.djvu Notes\n"));
+        assert_eq!(apply_plan(&plan, &nav), Ok(nav));
+    }
+
+    #[test]
+    fn invalid_plans() {
+        let nav = nav();
+        assert_eq!(apply_plan("keep 1", &nav), Err(PlanError::InvalidLine(1)));
+        assert_eq!(apply_plan("\nswap 1 5 Preface", &nav), Err(PlanError::UnknownAction(2, String::from("swap"))));
+        assert_eq!(apply_plan("keep 4 1 Nothing", &nav), Err(PlanError::UnknownEntry(1, String::from("4"))));
+        assert_eq!(apply_plan("keep 2.1 12 a\nmove 2 10 b", &nav), Err(PlanError::MoveIntoItself(2)));
+        assert_eq!(apply_plan("keep 1 \"unterminated Preface", &nav), Err(PlanError::InvalidLine(1)));
+    }
+}
@@ -0,0 +1,88 @@
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::input::LineInput;
+
+/// Return a rectangle of `width` by `height` cells centered in `area`, shrunk to fit if needed.
+pub fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// Render a menu of `items` in a popup centered in `area`, with the entry selected in `state`
+/// highlighted.
+pub fn menu<S: AsRef<str>>(f: &mut Frame, area: Rect, title: &str, items: &[S], state: &mut ListState) {
+    let width = items.iter()
+        .map(|item| item.as_ref().chars().count())
+        .chain(std::iter::once(title.chars().count()))
+        .max()
+        .unwrap_or(0) as u16 + 6;
+    let popup = centered_rect(width, items.len() as u16 + 2, area);
+
+    let items: Vec<_> = items.iter().map(|item| ListItem::new(item.as_ref())).collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(Style::default().fg(Color::Black).bg(Color::LightGreen))
+        .highlight_symbol("> ");
+    f.render_widget(Clear, popup);
+    f.render_stateful_widget(list, popup, state);
+}
+
+/// Render `text` wrapped in a popup centered in `area`, as wide as the screen allows up to 80
+/// columns.
+pub fn message(f: &mut Frame, area: Rect, title: &str, text: &str) {
+    let width = area.width.saturating_sub(4).min(80);
+    let inner_width = usize::from(width.saturating_sub(2)).max(1);
+    let height = text.lines()
+        .map(|line| line.chars().count().max(1).div_ceil(inner_width))
+        .sum::<usize>() as u16 + 2;
+    let popup = centered_rect(width, height, area);
+
+    let paragraph = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(Wrap { trim: false });
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Render a form made of one labelled input per line in a popup centered in `area`, with the
+/// terminal cursor in the input at index `focused`.
+pub fn form(f: &mut Frame, area: Rect, title: &str, fields: &[(&str, &LineInput)], focused: usize) {
+    let label_width = fields.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0) + 2;
+    let popup = centered_rect(area.width.saturating_sub(4).min(80), fields.len() as u16 + 2, area);
+
+    let lines: Vec<_> = fields.iter()
+        .enumerate()
+        .map(|(i, (label, input))| {
+            let style = if i == focused {
+                Style::default().add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            Line::from(vec![
+                Span::styled(format!("{:width$}", format!("{}:", label), width = label_width), style),
+                Span::raw(input.text()),
+            ])
+        })
+        .collect();
+    let paragraph = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+
+    if let Some((_, input)) = fields.get(focused) {
+        let x = popup.x + 1 + (label_width + input.cursor_width()) as u16;
+        f.set_cursor(x.min(popup.right().saturating_sub(2)), popup.y + 1 + focused as u16);
+    }
+}
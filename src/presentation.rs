@@ -0,0 +1,91 @@
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Padding, Paragraph},
+    Frame,
+};
+
+use crate::{
+    nav::{Nav, NavNode},
+    tree_widget::{TreeIdentifierVec, TreeState},
+};
+
+/// Columns of indentation per level, wider than in the tree to make the structure obvious from
+/// afar.
+const INDENT: usize = 4;
+
+/// Return the lines showing the entries of `nodes` and of their opened descendants, each followed
+/// by a blank line, along with the index of the line of the selected entry if it is visible.
+fn push_lines<'a>(
+    nodes: &'a [NavNode],
+    state: &TreeState,
+    path: &mut TreeIdentifierVec,
+    lines: &mut Vec<Line<'a>>,
+) -> Option<usize> {
+    let mut selected_line = None;
+    for (index, node) in nodes.iter().enumerate() {
+        path.push(index);
+        let is_selected = state.selected() == path.as_slice();
+        let indent = " ".repeat(INDENT * (path.len() - 1));
+        let (marker, style) = if is_selected {
+            selected_line = Some(lines.len());
+            ("▶ ", Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED))
+        } else {
+            ("  ", Style::default())
+        };
+        lines.push(Line::from(vec![
+            Span::raw(indent),
+            Span::styled(format!("{}{}", marker, node.string), style),
+            Span::styled(format!("   p. {}", node.link), Style::default().add_modifier(Modifier::DIM)),
+        ]));
+        lines.push(Line::from(""));
+        if !node.children.is_empty() && state.is_open(path) {
+            selected_line = selected_line.or(push_lines(&node.children, state, path, lines));
+        }
+        path.pop();
+    }
+    selected_line
+}
+
+/// Render the visible entries of `nav` to `area` for reviewing the outline with others: spaced
+/// out, without the editing panes, and with the selected entry emphasized and kept in the middle
+/// of the screen.
+pub fn ui(f: &mut Frame, area: Rect, nav: &Nav, state: &TreeState, title: &str) {
+    let mut lines = Vec::new();
+    let selected_line = push_lines(&nav.nodes, state, &mut Vec::new(), &mut lines).unwrap_or(0);
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .title_alignment(Alignment::Center)
+        .padding(Padding::new(2, 2, 1, 1));
+    let height = block.inner(area).height;
+    let scroll = selected_line.saturating_sub(usize::from(height / 2)) as u16;
+    f.render_widget(Paragraph::new(lines).block(block).scroll((scroll, 0)), area);
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::*;
+    use crate::nav::node;
+
+    #[test]
+    fn selected_entry_is_kept_on_screen() {
+        let nav = Nav { nodes: (1..=30).map(|n| node(&format!("Chapter {}", n), n, vec![])).collect() };
+        let mut state = TreeState::default();
+        state.select(vec![24]);
+
+        let mut terminal = Terminal::new(TestBackend::new(40, 12)).unwrap();
+        terminal.draw(|f| ui(f, f.size(), &nav, &state, "book.djvu")).unwrap();
+        let rows: Vec<String> = terminal.backend().buffer().content()
+            .chunks(40)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect())
+            .collect();
+
+        let selected = rows.iter().position(|row| row.contains("▶ Chapter 25   p. 25")).unwrap();
+        assert!((4..=7).contains(&selected));
+        assert!(rows[selected + 1].trim_matches(|c| c == '│' || c == ' ').is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    io,
+    process::ExitStatus,
+};
+
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    style::{Color, Style},
+    widgets::{Block, Borders, Paragraph, Widget},
+    Frame,
+};
+
+use crate::{
+    djvu::{CancellableTools, ToolRunner},
+    tasks::Tasks,
+};
+
+#[derive(Debug)]
+pub enum PreviewError {
+    IOError(io::Error),
+    DdjvuError(ExitStatus, String),
+    InvalidImage,
+}
+
+impl Display for PreviewError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(e) => write!(f, "cannot run ddjvu: {}", e),
+            Self::DdjvuError(status, stderr) => write!(f, "ddjvu failed ({}): {}", status, stderr.trim_end()),
+            Self::InvalidImage => write!(f, "ddjvu wrote an invalid image"),
+        }
+    }
+}
+
+impl Error for PreviewError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IOError(e) => Some(e),
+            Self::DdjvuError(..) | Self::InvalidImage => None,
+        }
+    }
+}
+
+/// A grayscale image, one byte per pixel, row after row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl PageImage {
+    fn pixel(&self, x: usize, y: usize) -> Option<u8> {
+        (x < self.width && y < self.height).then(|| self.pixels[y * self.width + x])
+    }
+}
+
+/// Parse a binary PGM image, as written by `ddjvu -format=pgm`.
+pub fn parse_pgm(data: &[u8]) -> Option<PageImage> {
+    let mut rest = data.strip_prefix(b"P5")?;
+    let mut fields = [0; 3];
+    for field in &mut fields {
+        loop {
+            rest = rest.trim_ascii_start();
+            match rest.strip_prefix(b"#") {
+                // Comments run to the end of the line.
+                Some(comment) => rest = &comment[comment.iter().position(|b| *b == b'\n')?..],
+                None => break,
+            }
+        }
+        let digits = rest.iter().take_while(|b| b.is_ascii_digit()).count();
+        *field = std::str::from_utf8(&rest[..digits]).ok()?.parse().ok()?;
+        rest = &rest[digits..];
+    }
+    let [width, height, max] = fields;
+    // A single whitespace character separates the header from the pixels.
+    let pixels = rest.get(1..)?.get(..width * height)?;
+    if max == 0 || max > 255 {
+        return None;
+    }
+    let pixels = pixels.iter().map(|p| (usize::from(*p) * 255 / max) as u8).collect();
+    Some(PageImage { width, height, pixels })
+}
+
+/// Render page `page` of the file `filename` with `ddjvu`, scaled to fit in `width` by `height`
+/// pixels.
+pub fn render_page(
+    tools: &impl ToolRunner,
+    filename: &str,
+    page: u32,
+    width: u16,
+    height: u16,
+) -> Result<PageImage, PreviewError> {
+    let args = [
+        "-format=pgm".to_string(),
+        format!("-page={}", page),
+        format!("-size={}x{}", width, height),
+        filename.to_string(),
+        "-".to_string(),
+    ];
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    let output = tools.output("ddjvu", &args).map_err(PreviewError::IOError)?;
+    if !output.status.success() {
+        return Err(PreviewError::DdjvuError(
+            output.status,
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+    parse_pgm(&output.stdout).ok_or(PreviewError::InvalidImage)
+}
+
+/// Draws an image with half blocks, so that each cell shows two pixels on top of each other.
+struct HalfBlocks<'a>(&'a PageImage);
+
+impl<'a> Widget for HalfBlocks<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let image = self.0;
+        // Centered, since the page keeps its aspect ratio.
+        let x_offset = (usize::from(area.width).saturating_sub(image.width) / 2) as u16;
+        for row in 0..area.height {
+            for column in 0..area.width.min(image.width as u16) {
+                let x = usize::from(column);
+                let y = usize::from(row) * 2;
+                let (Some(top), bottom) = (image.pixel(x, y), image.pixel(x, y + 1)) else {
+                    continue;
+                };
+                let gray = |value: u8| Color::Rgb(value, value, value);
+                let mut style = Style::default().fg(gray(top));
+                if let Some(bottom) = bottom {
+                    style = style.bg(gray(bottom));
+                }
+                buf.get_mut(area.x + x_offset + column, area.y + row).set_symbol("▀").set_style(style);
+            }
+        }
+    }
+}
+
+/// A page and the size in pixels it is rendered to fit in.
+type RenderKey = (u32, u16, u16);
+/// A rendered page, or why it could not be rendered.
+type Rendering = Result<PageImage, String>;
+
+/// Pages rendered for the preview pane, by page and size, rendered in the background one at a
+/// time so that moving around the outline stays responsive.
+pub struct Previews {
+    filename: String,
+    renderings: Tasks<RenderKey, Rendering>,
+}
+
+impl Previews {
+    pub fn new(filename: &str) -> Self {
+        Self { filename: String::from(filename), renderings: Tasks::new() }
+    }
+
+    /// Return page `page` rendered to fit in `width` by `height` pixels, or `None` if it is still
+    /// being rendered. Rendering is started if needed, after the pages requested before it.
+    fn get(&mut self, page: u32, width: u16, height: u16) -> Option<&Rendering> {
+        let key = (page, width, height);
+        self.renderings.poll();
+        let filename = self.filename.clone();
+        self.renderings.request(key, move |cancel| {
+            render_page(&CancellableTools::new(cancel), &filename, page, width, height)
+                .map_err(|e| e.to_string())
+        });
+        self.renderings.get(&key)
+    }
+
+    /// Stop rendering the pages other than `page`, no longer shown.
+    pub fn keep(&mut self, page: Option<u32>) {
+        self.renderings.keep(|(other, _, _)| Some(*other) == page);
+    }
+
+    /// Forget the rendered pages and stop rendering, to render them again when shown.
+    pub fn release(&mut self) {
+        self.renderings.clear();
+    }
+
+    /// Render a preview of page `page`, if any, to the `area` of `f`.
+    pub fn ui(&mut self, f: &mut Frame, area: Rect, page: Option<u32>) {
+        let title = page.map_or(String::from("Preview"), |page| format!("Preview of page {}", page));
+        let block = Block::default().borders(Borders::ALL).title(title);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        if inner.width == 0 || inner.height == 0 {
+            return;
+        }
+        let message = match page.map(|page| self.get(page, inner.width, inner.height * 2)) {
+            None => String::from("The entry does not point to a page"),
+            Some(None) => String::from("Rendering..."),
+            Some(Some(Err(e))) => format!("Cannot render the page: {}", e),
+            Some(Some(Ok(image))) => {
+                f.render_widget(HalfBlocks(image), inner);
+                return;
+            },
+        };
+        f.render_widget(Paragraph::new(message).alignment(Alignment::Center), inner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::*;
+
+    #[test]
+    fn pgm_images_are_parsed() {
+        let image = parse_pgm(b"P5\n# written by ddjvu\n3 2\n255\n\x00\x80\xff\x10\x20\x30").unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixels, [0x00, 0x80, 0xff, 0x10, 0x20, 0x30]);
+        assert_eq!(parse_pgm(b"P5 3 2 15 \x00\x0f\x00\x0f\x00\x0f").unwrap().pixels[1], 255);
+        assert_eq!(parse_pgm(b"P5\n3 2\n255\n\x00"), None);
+        assert_eq!(parse_pgm(b"P6\n1 1\n255\n\x00\x00\x00"), None);
+    }
+
+    #[test]
+    fn two_pixels_per_cell() {
+        let image = PageImage { width: 2, height: 3, pixels: vec![0, 10, 20, 30, 40, 50] };
+        let mut terminal = Terminal::new(TestBackend::new(4, 2)).unwrap();
+        terminal.draw(|f| f.render_widget(HalfBlocks(&image), f.size())).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let cell = buffer.get(1, 0);
+        assert_eq!(cell.symbol(), "▀");
+        assert_eq!((cell.fg, cell.bg), (Color::Rgb(0, 0, 0), Color::Rgb(20, 20, 20)));
+        // The last row has no pixel below.
+        assert_eq!(buffer.get(2, 1).bg, Color::Reset);
+        assert_eq!(buffer.get(0, 0).symbol(), " ");
+    }
+}
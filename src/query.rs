@@ -0,0 +1,78 @@
+use std::fmt::Display;
+
+use jaq_core::{
+    load::{Arena, File, Loader},
+    Compiler, Ctx, RcIter,
+};
+use jaq_json::Val;
+use serde_json::Value;
+
+use crate::nav::Nav;
+
+#[derive(Debug)]
+pub enum QueryError {
+    SerializationError(serde_json::Error),
+    /// The query is not a valid jq filter.
+    ParsingError,
+    /// The query uses a filter or variable that does not exist.
+    UndefinedError(String),
+    /// The query failed on the outline, with this message.
+    EvaluationError(String),
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SerializationError(e) => write!(f, "cannot serialize the outline: {}", e),
+            Self::ParsingError => write!(f, "cannot parse the query"),
+            Self::UndefinedError(name) => write!(f, "undefined in the query: {}", name),
+            Self::EvaluationError(message) => write!(f, "query failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Run the jq filter `query` on the JSON form of `nav` (as exported by the `json` format), and
+/// return the values it outputs.
+pub fn query(nav: &Nav, query: &str) -> Result<Vec<Value>, QueryError> {
+    let input = serde_json::to_value(nav).map_err(QueryError::SerializationError)?;
+
+    let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+    let arena = Arena::default();
+    let modules = loader.load(&arena, File { code: query, path: () })
+        .map_err(|_| QueryError::ParsingError)?;
+    let filter = Compiler::default()
+        .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+        .compile(modules)
+        .map_err(|errors| {
+            let names: Vec<_> = errors.into_iter()
+                .flat_map(|(_, undefined)| undefined)
+                .map(|(name, _)| name)
+                .collect();
+            QueryError::UndefinedError(names.join(", "))
+        })?;
+
+    let inputs = RcIter::new(core::iter::empty());
+    filter.run((Ctx::new([], &inputs), Val::from(input)))
+        .map(|output| output.map(Value::from).map_err(|e| QueryError::EvaluationError(e.to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+
+    #[test]
+    fn select_titles_by_page() {
+        let nav = Nav { nodes: vec![node("Preface", 5, vec![]), node("Chapter 1", 120, vec![]), node("Index", 300, vec![])] };
+
+        assert_eq!(
+            query(&nav, ".nodes[] | select(.page > 100) | .title").unwrap(),
+            vec![Value::from("Chapter 1"), Value::from("Index")]
+        );
+        assert!(matches!(query(&nav, ".nodes[] |"), Err(QueryError::ParsingError)));
+        assert!(matches!(query(&nav, "nonexistent"), Err(QueryError::UndefinedError(_))));
+    }
+}
@@ -0,0 +1,137 @@
+//! Recording what the editor shows as asciinema casts, which `asciinema play` replays at any
+//! speed, to make training material or to show how to reproduce a bug.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Stdout, Write},
+    path::Path,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Writes a cast in the version 2 format of asciinema to `W`: a header, then one line per flush
+/// with the time since the start and what was written to the terminal.
+pub struct Cast<W: Write> {
+    writer: W,
+    start: Instant,
+    /// Output not written to the cast yet, which may end in the middle of a character.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Cast<W> {
+    /// Start a cast of a terminal of `width` columns and `height` lines.
+    pub fn new(mut writer: W, width: u16, height: u16) -> io::Result<Self> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let header = serde_json::json!({ "version": 2, "width": width, "height": height, "timestamp": timestamp });
+        writeln!(writer, "{}", header)?;
+        Ok(Self { writer, start: Instant::now(), pending: Vec::new() })
+    }
+}
+
+impl<W: Write> Write for Cast<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Invalid bytes are replaced, but a character cut at the end waits for the next write.
+        let mut text = String::new();
+        let mut consumed = 0;
+        loop {
+            let rest = &self.pending[consumed..];
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    consumed = self.pending.len();
+                    break;
+                },
+                Err(e) => {
+                    text.push_str(&String::from_utf8_lossy(&rest[..e.valid_up_to()]));
+                    consumed += e.valid_up_to();
+                    let Some(invalid) = e.error_len() else {
+                        break;
+                    };
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    consumed += invalid;
+                },
+            }
+        }
+        if !text.is_empty() {
+            let event = serde_json::json!([self.start.elapsed().as_secs_f64(), "o", text]);
+            writeln!(self.writer, "{}", event)?;
+        }
+        self.pending.drain(..consumed);
+        self.writer.flush()
+    }
+}
+
+/// The standard output, copied to a cast while the session is recorded.
+pub struct Screen {
+    stdout: Stdout,
+    cast: Option<Cast<BufWriter<File>>>,
+}
+
+impl Screen {
+    pub fn new() -> Self {
+        Self { stdout: io::stdout(), cast: None }
+    }
+
+    /// Return the standard output, recorded to a cast written to `path`.
+    pub fn recorded(path: &Path) -> io::Result<Self> {
+        let (width, height) = crossterm::terminal::size()?;
+        let cast = Cast::new(BufWriter::new(File::create(path)?), width, height)?;
+        Ok(Self { stdout: io::stdout(), cast: Some(cast) })
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Write for Screen {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.stdout.write(buf)?;
+        if let Some(cast) = &mut self.cast {
+            cast.write_all(&buf[..written])?;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()?;
+        match &mut self.cast {
+            Some(cast) => cast.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cast_events() {
+        let mut cast = Cast::new(Vec::new(), 80, 24).unwrap();
+        cast.write_all("\u{1b}[1;1H> Chapter".as_bytes()).unwrap();
+        // The first byte of `é` is kept until the rest of the character is written.
+        cast.write_all(&[b' ', 0xc3]).unwrap();
+        cast.flush().unwrap();
+        cast.write_all(&[0xa9]).unwrap();
+        cast.flush().unwrap();
+        // Bytes that cannot start a character are replaced rather than held back forever.
+        cast.write_all(&[0xff, b'a', 0xc3]).unwrap();
+        cast.flush().unwrap();
+
+        let text = String::from_utf8(cast.writer).unwrap();
+        let lines: Vec<serde_json::Value> = text.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!((&lines[0]["version"], &lines[0]["width"], &lines[0]["height"]), (&2.into(), &80.into(), &24.into()));
+        assert_eq!((&lines[1][1], &lines[1][2]), (&"o".into(), &"\u{1b}[1;1H> Chapter ".into()));
+        assert_eq!(lines[2][2], "é");
+        assert_eq!(lines[3][2], "\u{fffd}a");
+        assert_eq!(cast.pending, [0xc3]);
+        assert_eq!(lines.len(), 4);
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(feature = "tui")]
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Frame,
+};
+
+use crate::tree_widget::TreeIdentifierVec;
+
+/// A line of a report, possibly about a node of the outline the user can jump to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportRow {
+    pub text: String,
+    pub target: Option<TreeIdentifierVec>,
+}
+
+impl ReportRow {
+    /// A row that is not about a specific node, such as a group header.
+    pub fn header(text: String) -> Self {
+        Self { text, target: None }
+    }
+
+    pub fn node(text: String, target: TreeIdentifierVec) -> Self {
+        Self { text, target: Some(target) }
+    }
+}
+
+/// A read-only listing computed from the outline.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub title: String,
+    pub rows: Vec<ReportRow>,
+}
+
+impl Report {
+    /// Render `self` to `area`, with the row selected in `state` highlighted. Headers are shown in
+    /// bold.
+    #[cfg(feature = "tui")]
+    pub fn ui(&self, f: &mut Frame, area: Rect, state: &mut ListState) {
+        let items: Vec<_> = self.rows.iter()
+            .map(|row| {
+                let style = if row.target.is_none() {
+                    Style::default().add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                ListItem::new(row.text.as_str()).style(style)
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(self.title.as_str()))
+            .highlight_style(Style::default().fg(Color::Black).bg(Color::LightGreen))
+            .highlight_symbol("> ");
+        f.render_stateful_widget(list, area, state);
+    }
+}
@@ -0,0 +1,95 @@
+//! Reading the `scandata.xml` files written by scanning pipelines such as the one of the Internet
+//! Archive, which describe each leaf that was scanned and the page number printed on it.
+
+use std::fmt::Display;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScandataError {
+    /// No leaf of the scan ended up in the document.
+    NoPages,
+}
+
+impl Display for ScandataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoPages => write!(f, "the scan data lists no page"),
+        }
+    }
+}
+
+impl std::error::Error for ScandataError {}
+
+/// A scanned leaf, as described in scan data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leaf {
+    /// Number of the leaf in the scan, starting at 0, and so of its image among the images of
+    /// the scan.
+    pub number: u32,
+    /// Physical page number of the leaf in the document made from the scan, starting at 1, or
+    /// `None` if the leaf was left out, as color cards are.
+    pub page: Option<u32>,
+    /// Page number printed on the leaf.
+    pub label: Option<String>,
+}
+
+/// Return the leaves of the scan described by `xml`, in order.
+pub fn parse_leaves(xml: &str) -> Result<Vec<Leaf>, ScandataError> {
+    let element = |xml: &str, name: &str| -> Option<String> {
+        let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+        let length = xml[start..].find(&format!("</{}>", name))?;
+        Some(String::from(xml[start..start + length].trim()))
+    };
+    let mut leaves = Vec::new();
+    let mut pages = 0;
+    // `<page ` and `<page>`, but not `<pageData>` nor `<pageNumber>`.
+    let starts = xml.match_indices("<page")
+        .map(|(start, _)| start)
+        .filter(|start| xml[start + 5..].starts_with([' ', '>']));
+    for start in starts {
+        let leaf = &xml[start..];
+        let leaf = &leaf[..leaf.find("</page>").unwrap_or(leaf.len())];
+        let number = leaf.split_once("leafNum=\"")
+            .and_then(|(_, rest)| rest.split('"').next()?.parse().ok())
+            .unwrap_or(leaves.len() as u32);
+        // Leaves left out of the access formats are not in the document.
+        let page = (element(leaf, "addToAccessFormats").as_deref() != Some("false")).then(|| {
+            pages += 1;
+            pages
+        });
+        let label = element(leaf, "pageNumber").filter(|label| !label.is_empty());
+        leaves.push(Leaf { number, page, label });
+    }
+    if pages == 0 {
+        return Err(ScandataError::NoPages);
+    }
+    Ok(leaves)
+}
+
+/// Return the printed page number of each page of the document made from the scan described by
+/// `xml`, where `None` stands for unnumbered pages such as covers and plates.
+pub fn parse_scandata(xml: &str) -> Result<Vec<Option<String>>, ScandataError> {
+    let leaves = parse_leaves(xml)?;
+    Ok(leaves.into_iter().filter(|leaf| leaf.page.is_some()).map(|leaf| leaf.label).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaves_left_out_are_skipped() {
+        let scandata = "<book><pageData>\
+            <page leafNum=\"0\"><pageType>Color Card</pageType><addToAccessFormats>false</addToAccessFormats></page>\
+            <page leafNum=\"1\"><pageType>Cover</pageType><addToAccessFormats>true</addToAccessFormats></page>\
+            <page leafNum=\"2\"><pageType>Normal</pageType><pageNumber>i</pageNumber></page>\
+            <page leafNum=\"3\"><pageType>Normal</pageType><pageNumber>1</pageNumber></page>\
+        </pageData></book>";
+
+        assert_eq!(
+            parse_scandata(scandata).unwrap(),
+            [None, Some(String::from("i")), Some(String::from("1"))],
+        );
+        assert_eq!(parse_leaves(scandata).unwrap()[2], Leaf { number: 2, page: Some(2), label: Some(String::from("i")) });
+        assert_eq!(parse_scandata("<book></book>"), Err(ScandataError::NoPages));
+    }
+}
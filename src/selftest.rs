@@ -0,0 +1,168 @@
+use std::{fmt::Display, fs, path::{Path, PathBuf}};
+
+use crate::{
+    djvu::{
+        embed_nav_in_djvu_file_with, get_nav_from_djvu_with, get_page_text_with,
+        get_pages_from_djvu_with, ToolRunner,
+    },
+    nav::{BookmarkLink, Nav, NavNode},
+    APP_NAME,
+};
+
+/// Number of pages of the generated fixture.
+const FIXTURE_PAGES: u32 = 3;
+
+/// Return a DjVu file holding a single blank page of `width` by `height` pixels at `dpi`.
+pub fn blank_page(width: u16, height: u16, dpi: u16) -> Vec<u8> {
+    let mut info = Vec::with_capacity(10);
+    info.extend(width.to_be_bytes());
+    info.extend(height.to_be_bytes());
+    // Minor and major version of the format, as written by djvulibre.
+    info.extend([26, 0]);
+    info.extend(dpi.to_le_bytes());
+    // Gamma 2.2, and no rotation.
+    info.extend([22, 1]);
+
+    let mut form = Vec::from(*b"DJVU");
+    form.extend(b"INFO");
+    form.extend((info.len() as u32).to_be_bytes());
+    form.extend(info);
+
+    let mut file = Vec::from(*b"AT&TFORM");
+    file.extend((form.len() as u32).to_be_bytes());
+    file.extend(form);
+    file
+}
+
+/// The outline written to the fixture and read back, with the characters and structures that are
+/// the most likely to be mangled.
+fn sample_nav() -> Nav {
+    let entry = |title: &str, link, children| NavNode { string: String::from(title), link, children };
+    Nav {
+        nodes: vec![
+            entry("Préface « naïve »", BookmarkLink::PageNumber(1), vec![]),
+            entry("Part \"One\"", BookmarkLink::PageNumber(2), vec![
+                entry("Chapter 1 (draft)", BookmarkLink::PageNumber(2), vec![]),
+                entry("Chapter 2 ; 日本語", BookmarkLink::PageNumber(3), vec![]),
+            ]),
+            entry("Home page", BookmarkLink::PageLink(String::from("https://example.org/?a=1&b=2")), vec![]),
+        ],
+    }
+}
+
+/// Outcome of one step of the self-test.
+pub struct Check {
+    pub name: &'static str,
+    pub result: Result<(), String>,
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.result {
+            Ok(()) => write!(f, "ok      {}", self.name),
+            Err(e) => write!(f, "FAILED  {}: {}", self.name, e),
+        }
+    }
+}
+
+/// Return the directory the fixture is generated in, creating it if needed.
+fn fixture_dir() -> Result<PathBuf, String> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_NAME).map_err(|e| e.to_string())?;
+    xdg_dirs.create_cache_directory("selftest").map_err(|e| e.to_string())
+}
+
+/// Write blank pages to `dir` and bundle them into a document with `djvm`, falling back to a
+/// single page document if `djvm` cannot be run. Return the path of the document.
+fn generate_fixture(tools: &impl ToolRunner, dir: &Path) -> Result<PathBuf, String> {
+    let mut pages = Vec::new();
+    for n in 1..=FIXTURE_PAGES {
+        let path = dir.join(format!("page{}.djvu", n));
+        fs::write(&path, blank_page(850, 1100, 100)).map_err(|e| e.to_string())?;
+        pages.push(path.display().to_string());
+    }
+    let document = dir.join("fixture.djvu");
+    let document_name = document.display().to_string();
+    let mut args = vec!["-c", document_name.as_str()];
+    args.extend(pages.iter().map(String::as_str));
+    match tools.output("djvm", &args) {
+        Ok(output) if output.status.success() => Ok(document),
+        _ => {
+            fs::copy(&pages[0], &document).map_err(|e| e.to_string())?;
+            Ok(document)
+        },
+    }
+}
+
+/// Exercise the `djvused` wrappers with `tools` on a generated document: list its pages, read its
+/// text and outline, and write an outline and read it back. Return the outcome of each step,
+/// stopping at the first step the others depend on that fails.
+pub fn run(tools: &impl ToolRunner) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut check = |name, result: Result<(), String>| {
+        let passed = result.is_ok();
+        checks.push(Check { name, result });
+        passed
+    };
+
+    let fixture = fixture_dir().and_then(|dir| generate_fixture(tools, &dir));
+    let Ok(fixture) = fixture else {
+        check("generate the fixture", fixture.map(|_| ()));
+        return checks;
+    };
+    check("generate the fixture", Ok(()));
+    let filename = fixture.display().to_string();
+
+    let pages = get_pages_from_djvu_with(tools, &filename);
+    let page_count = pages.as_ref().map_or(0, Vec::len);
+    if !check("list the pages", pages.map(|_| ()).map_err(|e| e.to_string())) {
+        return checks;
+    }
+    check(
+        "read the text layer",
+        get_page_text_with(tools, &filename, 1).map(|_| ()).map_err(|e| e.to_string()),
+    );
+    check(
+        "read an empty outline",
+        match get_nav_from_djvu_with(tools, &filename) {
+            Ok(nav) if nav.nodes.is_empty() => Ok(()),
+            Ok(nav) => Err(format!("found {} entries", nav.nodes.len())),
+            Err(e) => Err(e.to_string()),
+        },
+    );
+
+    let mut sample = sample_nav();
+    if page_count < FIXTURE_PAGES as usize {
+        // Without djvm, the fixture has a single page.
+        sample.nodes.iter_mut().flat_map(|node| node.children.iter_mut()).for_each(|node| {
+            node.link = BookmarkLink::PageNumber(1);
+        });
+    }
+    let written = embed_nav_in_djvu_file_with(tools, &filename, &sample);
+    if !check("write an outline", written.map_err(|e| e.to_string())) {
+        return checks;
+    }
+    check(
+        "read the outline back unchanged",
+        match get_nav_from_djvu_with(tools, &filename) {
+            Ok(nav) if nav == sample => Ok(()),
+            Ok(nav) => Err(format!("read back {}", nav.to_djvu().replace('\n', " "))),
+            Err(e) => Err(e.to_string()),
+        },
+    );
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_page_chunks() {
+        let page = blank_page(850, 1100, 100);
+        assert_eq!(&page[..8], b"AT&TFORM");
+        assert_eq!(u32::from_be_bytes(page[8..12].try_into().unwrap()) as usize, page.len() - 12);
+        assert_eq!(&page[12..20], b"DJVUINFO");
+        assert_eq!(u32::from_be_bytes(page[20..24].try_into().unwrap()), 10);
+        assert_eq!(&page[24..28], [3, 82, 4, 76]);
+    }
+}
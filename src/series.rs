@@ -0,0 +1,182 @@
+//! Proposing the outline of a volume of a series from the finished outline of another volume with
+//! the same structure.
+
+use crate::nav::{BookmarkLink, Nav, NavNode};
+
+/// Numerals of the roman numbering, with their value, from the largest.
+const ROMAN_NUMERALS: [(&str, u32); 13] = [
+    ("M", 1000), ("CM", 900), ("D", 500), ("CD", 400), ("C", 100), ("XC", 90), ("L", 50),
+    ("XL", 40), ("X", 10), ("IX", 9), ("V", 5), ("IV", 4), ("I", 1),
+];
+/// Words before the number in a numbered title, at most.
+const MAX_PREFIX_WORDS: usize = 2;
+
+/// How the numbers of the titles of the new volume follow those of the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Numbering {
+    /// Each volume numbers its chapters from 1 again.
+    Restart,
+    /// The chapters of the new volume are numbered after those of the model.
+    Continue,
+}
+
+/// Return the value of `numeral` if it is a roman number written in the usual way.
+fn roman_value(numeral: &str) -> Option<u32> {
+    if numeral.is_empty() || !numeral.chars().all(|c| "IVXLCDM".contains(c)) {
+        return None;
+    }
+    let mut rest = numeral;
+    let mut value = 0;
+    for (symbol, symbol_value) in ROMAN_NUMERALS {
+        while let Some(after) = rest.strip_prefix(symbol) {
+            value += symbol_value;
+            rest = after;
+        }
+    }
+    // Reject numerals such as `IIII` or `VX` by writing the value back.
+    (rest.is_empty() && to_roman(value) == numeral).then_some(value)
+}
+
+fn to_roman(mut value: u32) -> String {
+    let mut numeral = String::new();
+    for (symbol, symbol_value) in ROMAN_NUMERALS {
+        while value >= symbol_value {
+            numeral.push_str(symbol);
+            value -= symbol_value;
+        }
+    }
+    numeral
+}
+
+/// A title made of a few words, a number, and the rest, such as `Chapter XII. The Sea`.
+#[derive(Debug, PartialEq, Eq)]
+struct NumberedTitle<'a> {
+    prefix: &'a str,
+    number: u32,
+    roman: bool,
+    lowercase: bool,
+    /// Punctuation right after the number, such as `.` or `:`.
+    punctuation: &'a str,
+}
+
+impl<'a> NumberedTitle<'a> {
+    fn parse(title: &'a str) -> Option<Self> {
+        let mut start = 0;
+        for (i, word) in title.split(' ').enumerate().take(MAX_PREFIX_WORDS + 1) {
+            let number = word.trim_end_matches(['.', ':', ',', ')', '—', '-']);
+            let punctuation = &word[number.len()..];
+            let parsed = match number.parse() {
+                Ok(n) => Some((n, false, false)),
+                Err(_) => roman_value(&number.to_uppercase())
+                    // A lone `I` or `i` is a word rather than a number, unless it follows a word
+                    // such as `Chapter`.
+                    .filter(|_| i > 0 || number.len() > 1)
+                    .filter(|_| number.chars().all(char::is_uppercase) || number.chars().all(char::is_lowercase))
+                    .map(|n| (n, true, number.chars().all(char::is_lowercase))),
+            };
+            if let Some((number, roman, lowercase)) = parsed {
+                return Some(Self { prefix: &title[..start], number, roman, lowercase, punctuation });
+            }
+            start += word.len() + 1;
+        }
+        None
+    }
+
+    /// Return the title numbered `number`, without the words after the number, which differ from
+    /// a volume to the next.
+    fn renumbered(&self, number: u32) -> String {
+        let number = match (self.roman, self.lowercase) {
+            (false, _) => number.to_string(),
+            (true, false) => to_roman(number),
+            (true, true) => to_roman(number).to_lowercase(),
+        };
+        format!("{}{}{}", self.prefix, number, self.punctuation)
+    }
+}
+
+/// Return the outline proposed for a volume with `page_count` pages, made from `model`, the
+/// outline of a volume with `model_page_count` pages of the same series. The structure is kept,
+/// and so are the titles without a number, such as `Preface` or `Index`; numbered titles keep
+/// their numbering only, continued after the model with `Numbering::Continue`. Pages are placed
+/// at the same position relative to the length of the volume, to be confirmed.
+pub fn propose_volume(model: &Nav, model_page_count: u32, page_count: u32, numbering: Numbering) -> Nav {
+    // Number of the last title of each kind, such as `Chapter ` in upper case roman numbers.
+    let mut last_numbers: Vec<(String, u32)> = Vec::new();
+    if numbering == Numbering::Continue {
+        for (_, node) in model.all_nodes() {
+            if let Some(title) = NumberedTitle::parse(&node.string) {
+                let kind = title.renumbered(0);
+                match last_numbers.iter_mut().find(|(other, _)| *other == kind) {
+                    Some((_, last)) => *last = (*last).max(title.number),
+                    None => last_numbers.push((kind, title.number)),
+                }
+            }
+        }
+    }
+
+    let propose_node = |node: &NavNode| -> NavNode {
+        let string = match NumberedTitle::parse(&node.string) {
+            Some(title) => {
+                let kind = title.renumbered(0);
+                let shift = last_numbers.iter().find(|(other, _)| *other == kind).map_or(0, |(_, last)| *last);
+                title.renumbered(title.number + shift)
+            },
+            None => node.string.clone(),
+        };
+        let link = match node.link {
+            BookmarkLink::PageNumber(page) if model_page_count > 0 => {
+                let scaled = (u64::from(page) * u64::from(page_count) + u64::from(model_page_count) / 2)
+                    / u64::from(model_page_count);
+                BookmarkLink::PageNumber((scaled as u32).clamp(1, page_count.max(1)))
+            },
+            ref link => link.clone(),
+        };
+        NavNode { string, link, children: Vec::new() }
+    };
+    fn propose(nodes: &[NavNode], propose_node: &impl Fn(&NavNode) -> NavNode) -> Vec<NavNode> {
+        nodes.iter()
+            .map(|node| NavNode { children: propose(&node.children, propose_node), ..propose_node(node) })
+            .collect()
+    }
+    Nav { nodes: propose(&model.nodes, &propose_node) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+
+    #[test]
+    fn numbered_titles() {
+        let title = NumberedTitle::parse("Chapter XII. The Sea").unwrap();
+        assert_eq!((title.prefix, title.number, title.punctuation), ("Chapter ", 12, "."));
+        assert_eq!(title.renumbered(14), "Chapter XIV.");
+        assert_eq!(NumberedTitle::parse("3 Results").unwrap().renumbered(4), "4");
+        assert_eq!(NumberedTitle::parse("I Remember"), None);
+        assert_eq!(NumberedTitle::parse("A Voyage to the Moon"), None);
+        assert_eq!(roman_value("IIII"), None);
+    }
+
+    #[test]
+    fn volume_proposed_from_model() {
+        let model = Nav {
+            nodes: vec![
+                node("Preface", 5, vec![]),
+                NavNode { children: vec![node("Chapter 1. Arrival", 20, vec![]), node("Chapter 2. Winter", 110, vec![])], ..node("Book I", 19, vec![]) },
+                node("Index", 200, vec![]),
+            ],
+        };
+
+        let restarted = propose_volume(&model, 200, 300, Numbering::Restart);
+        assert_eq!(restarted, Nav {
+            nodes: vec![
+                node("Preface", 8, vec![]),
+                NavNode { children: vec![node("Chapter 1.", 30, vec![]), node("Chapter 2.", 165, vec![])], ..node("Book I", 29, vec![]) },
+                node("Index", 300, vec![]),
+            ],
+        });
+        let continued = propose_volume(&model, 200, 200, Numbering::Continue);
+        assert_eq!(continued.nodes[1].string, "Book II");
+        assert_eq!(continued.nodes[1].children[1], node("Chapter 4.", 110, vec![]));
+    }
+}
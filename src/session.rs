@@ -0,0 +1,177 @@
+//! Where the tree of each file was left when the editor was quit, with what was selected and
+//! opened, kept in the state directory so that reopening a file goes back there.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{djvu::write_through_copy, nav::Nav, APP_NAME};
+
+/// File of the state directory holding the trees of the files.
+const SESSIONS_FILE: &str = "sessions.json";
+/// Number of files whose tree is kept, the ones closed last.
+const MAX_SESSIONS: usize = 500;
+
+#[derive(Debug)]
+pub enum SessionError {
+    IOError(io::Error),
+    XDGSpecificError(xdg::BaseDirectoriesError),
+    FormatError(serde_json::Error),
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IOError(e) => write!(f, "cannot save where the tree was left: {}", e),
+            Self::XDGSpecificError(e) => write!(f, "cannot find the state directory: {}", e),
+            Self::FormatError(e) => write!(f, "invalid saved tree: {}", e),
+        }
+    }
+}
+
+impl Error for SessionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IOError(e) => Some(e),
+            Self::XDGSpecificError(e) => Some(e),
+            Self::FormatError(e) => Some(e),
+        }
+    }
+}
+
+/// What was selected and opened in the tree of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Session {
+    /// Absolute path of the file.
+    pub filename: PathBuf,
+    pub selected: Vec<usize>,
+    pub opened: Vec<Vec<usize>>,
+}
+
+impl Session {
+    /// Leave out the nodes missing from `nav`, such as those of an outline changed by another
+    /// program since, and the opened ones without children.
+    pub fn valid_in(mut self, nav: &Nav) -> Self {
+        self.opened.retain(|id| nav.get(id).is_some_and(|node| !node.children.is_empty()));
+        if nav.get(&self.selected).is_none() {
+            self.selected.clear();
+        }
+        self
+    }
+}
+
+/// The trees of the files, the one closed last first.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sessions(Vec<Session>);
+
+impl Sessions {
+    pub fn read_from(path: &Path) -> Result<Self, SessionError> {
+        match fs::read_to_string(path) {
+            Ok(json) => serde_json::from_str(&json).map_err(SessionError::FormatError),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(SessionError::IOError(e)),
+        }
+    }
+
+    /// Write the sessions to `path`, through a copy renamed over it so that a crash while writing
+    /// leaves the previous ones.
+    pub fn write_to(&self, path: &Path) -> Result<(), SessionError> {
+        let json = serde_json::to_string(self).map_err(SessionError::FormatError)?;
+        write_through_copy(path, |copy| fs::write(copy, json), |e| e).map_err(SessionError::IOError)
+    }
+
+    pub fn get(&self, filename: &Path) -> Option<&Session> {
+        self.0.iter().find(|session| session.filename == filename)
+    }
+
+    /// Keep `session` in place of the earlier one of its file, forgetting the files closed longest
+    /// ago past `MAX_SESSIONS`.
+    pub fn remember(&mut self, session: Session) {
+        self.0.retain(|other| other.filename != session.filename);
+        self.0.insert(0, session);
+        self.0.truncate(MAX_SESSIONS);
+    }
+}
+
+fn sessions_path() -> Result<PathBuf, SessionError> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_NAME)
+        .map_err(SessionError::XDGSpecificError)?;
+    xdg_dirs.place_state_file(SESSIONS_FILE).map_err(SessionError::IOError)
+}
+
+/// Return where the tree of the file at the absolute path `filename` was left, if it was opened
+/// before.
+pub fn recall(filename: &Path) -> Result<Option<Session>, SessionError> {
+    Ok(Sessions::read_from(&sessions_path()?)?.get(filename).cloned())
+}
+
+/// Save where the tree of the file of `session` is left, along with those of the other files.
+pub fn save(session: Session) -> Result<(), SessionError> {
+    save_to(&sessions_path()?, session)
+}
+
+/// Merge `session` into the sessions at `path`. Editors quitting at the same time take turns on
+/// a lock file next to it, so that none writes over the session another one just saved.
+fn save_to(path: &Path, session: Session) -> Result<(), SessionError> {
+    let lock = fs::File::create(path.with_extension("lock")).map_err(SessionError::IOError)?;
+    // Released when `lock` is dropped.
+    lock.lock().map_err(SessionError::IOError)?;
+    let mut sessions = Sessions::read_from(path)?;
+    sessions.remember(session);
+    sessions.write_to(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{nav::NavNode, tempfile::TempFile};
+
+    #[test]
+    fn sessions_read_back() {
+        let session = |filename: &str, selected: Vec<usize>| Session {
+            filename: PathBuf::from(filename),
+            selected,
+            opened: vec![vec![0], vec![1]],
+        };
+        let mut sessions = Sessions::default();
+        sessions.remember(session("/books/alice.djvu", vec![0]));
+        sessions.remember(session("/books/emma.djvu", vec![1]));
+        sessions.remember(session("/books/alice.djvu", vec![0, 0]));
+        let file = TempFile::new(".json").unwrap();
+        sessions.write_to(file.path()).unwrap();
+        let sessions = Sessions::read_from(file.path()).unwrap();
+        assert_eq!(sessions.0.len(), 2);
+        assert_eq!(sessions.get(Path::new("/books/alice.djvu")), Some(&session("/books/alice.djvu", vec![0, 0])));
+
+        // The outline lost its second entry and the children of the first since.
+        let nav = Nav { nodes: vec![NavNode::default()] };
+        let restored = sessions.get(Path::new("/books/emma.djvu")).unwrap().clone().valid_in(&nav);
+        assert_eq!((restored.selected, restored.opened), (vec![], vec![]));
+    }
+
+    #[test]
+    fn concurrent_saves_are_merged() {
+        let file = TempFile::new(".json").unwrap();
+        Sessions::default().write_to(file.path()).unwrap();
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let path = file.path();
+                scope.spawn(move || {
+                    let session = Session {
+                        filename: PathBuf::from(format!("/books/{}.djvu", i)),
+                        selected: vec![i],
+                        opened: vec![],
+                    };
+                    save_to(path, session).unwrap();
+                });
+            }
+        });
+        let _ = fs::remove_file(file.path().with_extension("lock"));
+        assert_eq!(Sessions::read_from(file.path()).unwrap().0.len(), 8);
+    }
+}
@@ -0,0 +1,63 @@
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+
+/// Summary of the edited file and of the selection, shown at the bottom of the screen.
+#[derive(Debug, Clone, Default)]
+pub struct StatusBar {
+    pub filename: String,
+    /// Whether there are unsaved changes.
+    pub dirty: bool,
+    pub read_only: bool,
+    /// Titles of the selected node and of its ancestors, outermost first.
+    pub breadcrumb: Vec<String>,
+    /// Description of what the selected node points to.
+    pub target: Option<String>,
+    /// Progress of the task running in the background, if any.
+    pub task: Option<String>,
+}
+
+impl StatusBar {
+    /// Render `self` to `area`, which should be one line high, the target being aligned right.
+    /// If `compact`, only the title of the selected node is shown, without its ancestors and its
+    /// target.
+    pub fn ui(&self, f: &mut Frame, area: Rect, compact: bool) {
+        let style = Style::default().add_modifier(Modifier::REVERSED);
+        let dirty = match (self.dirty, self.read_only) {
+            (_, true) => " [read-only]",
+            (true, false) => " [+]",
+            (false, false) => "",
+        };
+        let breadcrumb = if compact {
+            self.breadcrumb.last().cloned().unwrap_or_default()
+        } else {
+            self.breadcrumb.join(" > ")
+        };
+        let left = Line::from(vec![
+            Span::styled(format!(" {}{} ", self.filename, dirty), style.add_modifier(Modifier::BOLD)),
+            Span::styled(format!(" {}", breadcrumb), style),
+        ]);
+        let left = match &self.task {
+            Some(task) => {
+                let mut spans = left.spans;
+                spans.insert(1, Span::styled(format!(" {} |", task), style));
+                Line::from(spans)
+            },
+            None => left,
+        };
+        f.render_widget(Paragraph::new(left).style(style), area);
+
+        if let Some(target) = self.target.as_ref().filter(|_| !compact) {
+            let width = (target.chars().count() as u16 + 2).min(area.width / 2);
+            let right = Rect { x: area.right() - width, width, ..area };
+            let target = Paragraph::new(format!(" {} ", target))
+                .style(style)
+                .alignment(Alignment::Right);
+            f.render_widget(target, right);
+        }
+    }
+}
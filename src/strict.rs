@@ -0,0 +1,291 @@
+//! Strict mode, for archives whose procedures require every write to be checked and recorded:
+//! the outline must pass validation and have titles that are written as they are, it is always
+//! written through `djvused` and read back to compare, and the file is backed up beforehand, with
+//! the checksums of the file before and after written to a log.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use sha2::{Digest, Sha256};
+use unicode_normalization::is_nfc;
+
+use crate::{
+    autosave::absolute_path,
+    config::Config,
+    djvu::{
+        edit_copy, embed_nav_in_djvu_file_with, get_nav_from_djvu_with, NavReadingError, PageInfo, SystemTools,
+        ToolRunner,
+    },
+    history::format_identifier,
+    link::LinkResolvers,
+    lint::lint,
+    nav::Nav,
+    tree_widget::TreeIdentifierVec,
+    validate::validate,
+    APP_NAME,
+};
+
+/// Directory of the state directory holding the copies of the files made before writing them.
+const BACKUP_DIRECTORY: &str = "backups";
+/// Number of backups kept, the ones made last.
+const MAX_BACKUPS: usize = 100;
+/// File of the state directory listing the writes, one per line.
+const LOG_FILE: &str = "strict.log";
+
+#[derive(Debug)]
+pub enum StrictError {
+    /// These entries have titles that would not be written as they are, for the given reasons.
+    LossyTitles(Vec<(TreeIdentifierVec, &'static str)>),
+    /// The outline breaks the policy or the order of the pages this number of times.
+    Findings(usize),
+    /// The outline read back from the file differs from the one written. The file was restored.
+    RoundTripMismatch,
+    NavReadingError(NavReadingError),
+    IOError(io::Error),
+    XDGSpecificError(xdg::BaseDirectoriesError),
+}
+
+impl Display for StrictError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LossyTitles(titles) => {
+                let (id, reason) = &titles[0];
+                write!(f, "the title of {} {}", format_identifier(id), reason)?;
+                match titles.len() {
+                    1 => Ok(()),
+                    n => write!(f, ", and {} more titles would not be written as they are", n - 1),
+                }
+            },
+            Self::Findings(count) => write!(f, "the outline has {} problems (:check and :lint list them)", count),
+            Self::RoundTripMismatch => {
+                write!(f, "the outline read back differs from the one written, the file was restored")
+            },
+            Self::NavReadingError(e) => write!(f, "{}", e),
+            Self::IOError(e) => write!(f, "cannot back up the file: {}", e),
+            Self::XDGSpecificError(e) => write!(f, "cannot find the state directory: {}", e),
+        }
+    }
+}
+
+impl Error for StrictError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::NavReadingError(e) => Some(e),
+            Self::IOError(e) => Some(e),
+            Self::XDGSpecificError(e) => Some(e),
+            Self::LossyTitles(_) | Self::Findings(_) | Self::RoundTripMismatch => None,
+        }
+    }
+}
+
+/// Return the entries of `nav` whose titles would not be written as they are, and why: control
+/// characters are shown differently by viewers, and the same text may be encoded in several
+/// Unicode forms.
+pub fn lossy_titles(nav: &Nav) -> Vec<(TreeIdentifierVec, &'static str)> {
+    nav.all_nodes().into_iter()
+        .filter_map(|(id, node)| {
+            let reason = if node.string.chars().any(char::is_control) {
+                "has control characters"
+            } else if !is_nfc(&node.string) {
+                "is not in Unicode normalization form C"
+            } else {
+                return None;
+            };
+            Some((id, reason))
+        })
+        .collect()
+}
+
+/// Check that `nav` can be written in strict mode: its titles are written as they are, and it
+/// follows the policy of `config` and the order of `pages`.
+pub fn check(nav: &Nav, pages: Option<&[PageInfo]>, config: &Config, resolvers: &LinkResolvers) -> Result<(), StrictError> {
+    let lossy = lossy_titles(nav);
+    if !lossy.is_empty() {
+        return Err(StrictError::LossyTitles(lossy));
+    }
+    let findings = lint(nav, &config.policy, pages, resolvers).len()
+        + validate(nav, pages, config.policy.max_gap).len();
+    match findings {
+        0 => Ok(()),
+        count => Err(StrictError::Findings(count)),
+    }
+}
+
+/// Return the SHA-256 checksum of `data` in hexadecimal.
+fn checksum(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Write `nav` to the file `filename` through `djvused`, after copying the file to the backup
+/// directory and making the other changes of the write with `write_others`, and read it back to
+/// compare. Log the write, and return the path of the backup.
+pub fn write(
+    filename: &str,
+    nav: &Nav,
+    write_others: impl FnOnce() -> Result<(), NavReadingError>,
+) -> Result<PathBuf, StrictError> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_NAME)
+        .map_err(StrictError::XDGSpecificError)?;
+    write_with(&SystemTools, &xdg_dirs.get_state_home(), filename, nav, write_others)
+}
+
+/// Same as `write`, running `djvused` with `tools`, and keeping the backups and the log in the
+/// directory `state`.
+pub fn write_with(
+    tools: &impl ToolRunner,
+    state: &Path,
+    filename: &str,
+    nav: &Nav,
+    write_others: impl FnOnce() -> Result<(), NavReadingError>,
+) -> Result<PathBuf, StrictError> {
+    let before = fs::read(filename).map_err(StrictError::IOError)?;
+    let before_checksum = checksum(&before);
+    // Backups are named after their checksum, so that writing the same file twice keeps one.
+    let backup = state.join(BACKUP_DIRECTORY).join(format!("{}.djvu", before_checksum));
+    fs::create_dir_all(state.join(BACKUP_DIRECTORY))
+        .and_then(|()| fs::write(&backup, &before))
+        .map_err(StrictError::IOError)?;
+    // Failing to remove old backups is no reason not to write.
+    if let Some(directory) = backup.parent() {
+        let _ = prune_backups(directory);
+    }
+
+    // The checksum after the write covers the other changes too, and the backup comes before them.
+    write_others()
+        .and_then(|()| edit_copy(filename, |copy| embed_nav_in_djvu_file_with(tools, copy, nav)))
+        .map_err(StrictError::NavReadingError)?;
+    let read_back = get_nav_from_djvu_with(tools, filename).map_err(StrictError::NavReadingError)?;
+    // The outlines are compared in the form they are written in, which is the same whatever way
+    // `djvused` lays them out.
+    if read_back.to_djvu() != nav.to_djvu() {
+        edit_copy(filename, |copy| fs::write(copy, &before).map_err(NavReadingError::ReplaceError))
+            .map_err(StrictError::NavReadingError)?;
+        return Err(StrictError::RoundTripMismatch);
+    }
+
+    let after = fs::read(filename).map_err(StrictError::IOError)?;
+    let log = state.join(LOG_FILE);
+    let line = log_line(&absolute_path(filename), &before_checksum, &checksum(&after), &backup);
+    OpenOptions::new().create(true).append(true).open(log)
+        .and_then(|mut log| log.write_all(line.as_bytes()))
+        .map_err(StrictError::IOError)?;
+    Ok(backup)
+}
+
+/// Remove the backups of `directory` past the `MAX_BACKUPS` made last.
+fn prune_backups(directory: &Path) -> io::Result<()> {
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if let Ok(modified) = fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+            backups.push((modified, path));
+        }
+    }
+    for path in backups_past_limit(backups, MAX_BACKUPS) {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Return the paths of `backups`, given with the time they were made, past the `keep` made last.
+fn backups_past_limit(mut backups: Vec<(SystemTime, PathBuf)>, keep: usize) -> Vec<PathBuf> {
+    backups.sort_by_key(|(made, _)| std::cmp::Reverse(*made));
+    backups.into_iter().skip(keep).map(|(_, path)| path).collect()
+}
+
+/// Return the line of the log recording a write of the file `path`: the time in seconds since the
+/// Unix epoch, the path, the checksums before and after, and the path of the backup, separated by
+/// tabs.
+fn log_line(path: &Path, before: &str, after: &str, backup: &Path) -> String {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    format!("{}\t{}\t{}\t{}\t{}\n", seconds, path.display(), before, after, backup.display())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    use std::{os::unix::process::ExitStatusExt, process::{ExitStatus, Output}};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::nav::node;
+
+    /// Stands for `djvused` on files made of their outline alone: `set-outline` writes the outline
+    /// given to the file, and `print-outline` prints it, changed by `alter` to stand for a
+    /// `djvused` not reading back what it wrote.
+    #[cfg(unix)]
+    struct MockTools {
+        alter: fn(String) -> String,
+    }
+
+    #[cfg(unix)]
+    impl ToolRunner for MockTools {
+        fn output(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+            assert_eq!(program, "djvused");
+            let stdout = match args.iter().find_map(|arg| arg.strip_prefix("set-outline ")) {
+                Some(outline) => fs::copy(outline, args[0]).map(|_| Vec::new())?,
+                None if args.contains(&"print-outline") => (self.alter)(fs::read_to_string(args[0])?).into_bytes(),
+                None => Vec::new(),
+            };
+            Ok(Output { status: ExitStatus::from_raw(0), stdout, stderr: Vec::new() })
+        }
+    }
+
+    #[test]
+    fn titles_written_as_they_are() {
+        let nav = Nav {
+            nodes: vec![node("Café", 1, vec![]), node("Tab\there", 1, vec![]), node("Line\nbreak", 1, vec![]), node("Cafe\u{301}", 1, vec![]), node("C:\\Users", 1, vec![])],
+        };
+        let lossy = lossy_titles(&nav);
+        assert_eq!(lossy.iter().map(|(id, _)| id.clone()).collect::<Vec<_>>(), [vec![1], vec![2], vec![3]]);
+        assert_eq!(
+            StrictError::LossyTitles(lossy).to_string(),
+            "the title of 2 has control characters, and 2 more titles would not be written as they are",
+        );
+
+        assert_eq!(checksum(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn oldest_backups_pruned() {
+        let backup = |seconds, name| (UNIX_EPOCH + Duration::from_secs(seconds), PathBuf::from(name));
+        let backups = vec![backup(20, "b.djvu"), backup(30, "c.djvu"), backup(10, "a.djvu"), backup(40, "d.djvu")];
+        assert_eq!(backups_past_limit(backups.clone(), 2), [PathBuf::from("b.djvu"), PathBuf::from("a.djvu")]);
+        assert!(backups_past_limit(backups, 4).is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn writes_are_backed_up_read_back_and_logged() {
+        let state = std::env::temp_dir().join(format!("nav_edit_strict_{}", std::process::id()));
+        let filename = state.join("book.djvu");
+        fs::create_dir_all(&state).unwrap();
+        fs::write(&filename, "(bookmarks)").unwrap();
+        let filename = filename.to_str().unwrap();
+        let nav = Nav { nodes: vec![node("Preface", 3, vec![]), node("Index", 90, vec![])] };
+        let faithful = MockTools { alter: |outline| outline };
+
+        let backup = write_with(&faithful, &state, filename, &nav, || Ok(())).unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "(bookmarks)");
+        assert_eq!(fs::read_to_string(filename).unwrap(), nav.to_djvu());
+        let log = fs::read_to_string(state.join(LOG_FILE)).unwrap();
+        let fields: Vec<_> = log.trim_end().split('\t').collect();
+        assert_eq!(fields[2..4], [checksum(b"(bookmarks)"), checksum(nav.to_djvu().as_bytes())]);
+
+        let lossy = MockTools { alter: |outline| outline.replacen("Index", "Inde", 1) };
+        let mut changed = nav.clone();
+        changed.nodes[1].string = String::from("Index of names");
+        let written = write_with(&lossy, &state, filename, &changed, || Ok(()));
+        assert!(matches!(written, Err(StrictError::RoundTripMismatch)));
+        assert_eq!(fs::read_to_string(filename).unwrap(), nav.to_djvu());
+        assert_eq!(fs::read_to_string(state.join(LOG_FILE)).unwrap(), log);
+
+        fs::remove_dir_all(&state).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    djvu::PageInfo,
+    history::format_identifier,
+    link::page_number,
+    nav::Nav,
+    report::{Report, ReportRow},
+    tree_widget::TreeIdentifierVec,
+};
+
+/// Return the entry of `nav` covering page `page`: the one pointing to the greatest page up to
+/// it, the last in the outline among those pointing to the same page, so that a section is
+/// preferred to the chapter starting on the same page.
+pub fn entry_at_page(nav: &Nav, pages: Option<&[PageInfo]>, page: u32) -> Option<TreeIdentifierVec> {
+    nav.all_nodes().into_iter()
+        .filter_map(|(id, node)| Some((page_number(&node.link, pages).filter(|target| *target <= page)?, id)))
+        .fold(None, |best: Option<(u32, TreeIdentifierVec)>, (target, id)| match best {
+            Some((best_target, _)) if best_target > target => best,
+            _ => Some((target, id)),
+        })
+        .map(|(_, id)| id)
+}
+
+/// List the pages several entries point to, the most crowded first, then the entries whose
+/// children all point to their own page, which usually comes from a careless import.
+pub fn target_groups(nav: &Nav, pages: Option<&[PageInfo]>) -> Report {
+    let nodes = nav.all_nodes();
+    let mut by_page: BTreeMap<u32, Vec<_>> = BTreeMap::new();
+    for (id, node) in &nodes {
+        if let Some(page) = page_number(&node.link, pages) {
+            by_page.entry(page).or_default().push((id, node));
+        }
+    }
+    let mut crowded: Vec<_> = by_page.into_iter()
+        .filter(|(_, entries)| entries.len() > 1)
+        .collect();
+    // Stable, so pages with as many entries stay in page order.
+    crowded.sort_by_key(|(_, entries)| std::cmp::Reverse(entries.len()));
+
+    let mut rows = Vec::new();
+    for (page, entries) in crowded {
+        rows.push(ReportRow::header(format!("Page {}: {} entries", page, entries.len())));
+        for (id, node) in entries {
+            rows.push(ReportRow::node(format!("  {} \"{}\"", format_identifier(id), node.string), id.clone()));
+        }
+    }
+
+    let flat: Vec<_> = nodes.iter()
+        .filter(|(_, node)| !node.children.is_empty())
+        .filter(|(_, node)| {
+            let page = page_number(&node.link, pages);
+            page.is_some() && node.children.iter().all(|child| page_number(&child.link, pages) == page)
+        })
+        .collect();
+    if !flat.is_empty() {
+        rows.push(ReportRow::header(String::from("Children all on the page of their parent")));
+        for (id, node) in flat {
+            let text = format!("  {} \"{}\" ({} children)", format_identifier(id), node.string, node.children.len());
+            rows.push(ReportRow::node(text, id.clone()));
+        }
+    }
+    Report {
+        title: String::from("Entries grouped by target page"),
+        rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+
+    #[test]
+    fn entry_covering_a_page() {
+        let nav = Nav {
+            nodes: vec![
+                node("Preface", 5, vec![]),
+                node("Part I", 9, vec![node("Chapter 1", 9, vec![]), node("Chapter 2", 30, vec![])]),
+                node("Notes", 12, vec![]),
+            ],
+        };
+
+        assert_eq!(entry_at_page(&nav, None, 4), None);
+        assert_eq!(entry_at_page(&nav, None, 10), Some(vec![1, 0]));
+        // Out of page order, the notes come before the second chapter in the book.
+        assert_eq!(entry_at_page(&nav, None, 29), Some(vec![2]));
+        assert_eq!(entry_at_page(&nav, None, 153), Some(vec![1, 1]));
+    }
+
+    #[test]
+    fn crowded_pages_and_flat_chapters() {
+        let nav = Nav {
+            nodes: vec![
+                node("Preface", 5, vec![]),
+                node("Part I", 9, vec![node("Chapter 1", 9, vec![]), node("Chapter 2", 9, vec![])]),
+                node("Notes", 5, vec![]),
+            ],
+        };
+
+        let headers: Vec<_> = target_groups(&nav, None).rows.into_iter()
+            .filter(|row| row.target.is_none())
+            .map(|row| row.text)
+            .collect();
+        assert_eq!(headers, [
+            "Page 9: 3 entries",
+            "Page 5: 2 entries",
+            "Children all on the page of their parent",
+        ]);
+    }
+}
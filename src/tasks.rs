@@ -0,0 +1,261 @@
+//! Queries about the document made for the panes showing the selected entry, such as rendering its
+//! page or reading its text, run in the background one at a time. Moving through the outline
+//! leaves queries behind, so those no longer wanted are cancelled: the queued ones never start,
+//! and the running one is told to stop.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+/// Tells a job that its result is no longer wanted, for it to stop early.
+#[derive(Debug, Clone, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `self` and `other` are the flag of the same job.
+    fn is(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// A job of `Tasks`, run with the flag telling it to stop.
+struct Job<K, T> {
+    key: K,
+    cancel: CancelFlag,
+    run: Box<dyn FnOnce(&CancelFlag) -> T + Send>,
+}
+
+/// Jobs computing a value of type `T` for a key of type `K`, run in order in a background thread,
+/// and their results by key.
+pub struct Tasks<K, T> {
+    jobs: Sender<Job<K, T>>,
+    /// The results with the flag of their job, to tell those no longer wanted.
+    results: Receiver<(K, CancelFlag, T)>,
+    /// The flags of the jobs queued or running.
+    pending: HashMap<K, CancelFlag>,
+    done: HashMap<K, T>,
+    /// The keys of `done`, the one requested longest ago first.
+    recent: VecDeque<K>,
+    /// Number of results kept, if bounded.
+    limit: Option<usize>,
+}
+
+impl<K, T> Tasks<K, T>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    T: Send + 'static,
+{
+    /// Start the thread running the jobs, which ends along with `self`.
+    pub fn new() -> Self {
+        let (jobs, queue) = mpsc::channel::<Job<K, T>>();
+        let (sender, results) = mpsc::channel();
+        thread::spawn(move || {
+            for job in queue {
+                if job.cancel.is_cancelled() {
+                    continue;
+                }
+                let result = (job.run)(&job.cancel);
+                if !job.cancel.is_cancelled() && sender.send((job.key, job.cancel, result)).is_err() {
+                    return;
+                }
+            }
+        });
+        Self { jobs, results, pending: HashMap::new(), done: HashMap::new(), recent: VecDeque::new(), limit: None }
+    }
+
+    /// Start the thread running the jobs like `new`, keeping only the results of the `limit` keys
+    /// requested last.
+    pub fn with_limit(limit: usize) -> Self {
+        let mut tasks = Self::new();
+        tasks.limit = Some(limit);
+        tasks
+    }
+
+    /// Collect the results of the jobs that finished since the last call, leaving out those of the
+    /// jobs cancelled after they finished, or replaced by another job for the same key.
+    pub fn poll(&mut self) {
+        loop {
+            match self.results.try_recv() {
+                Ok((key, cancel, result)) => {
+                    if cancel.is_cancelled() || !self.pending.get(&key).is_some_and(|pending| pending.is(&cancel)) {
+                        continue;
+                    }
+                    self.pending.remove(&key);
+                    self.recent.push_back(key.clone());
+                    self.done.insert(key, result);
+                    self.forget_oldest();
+                },
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return,
+            }
+        }
+    }
+
+    /// Return the result for `key`, if its job finished by the last `poll`.
+    pub fn get(&self, key: &K) -> Option<&T> {
+        self.done.get(key)
+    }
+
+    /// Queue `run` to compute the result for `key`, unless it is known or being computed already.
+    pub fn request<F>(&mut self, key: K, run: F)
+    where
+        F: FnOnce(&CancelFlag) -> T + Send + 'static,
+    {
+        if self.done.contains_key(&key) {
+            // Requested again, so kept longer.
+            self.recent.retain(|other| *other != key);
+            self.recent.push_back(key);
+            return;
+        }
+        if self.pending.contains_key(&key) {
+            return;
+        }
+        let cancel = CancelFlag::default();
+        let job = Job { key: key.clone(), cancel: cancel.clone(), run: Box::new(run) };
+        if self.jobs.send(job).is_ok() {
+            self.pending.insert(key, cancel);
+        }
+    }
+
+    /// Cancel the jobs queued or running for the keys not `wanted`. The results already known are
+    /// kept.
+    pub fn keep(&mut self, wanted: impl Fn(&K) -> bool) {
+        self.pending.retain(|key, cancel| {
+            if !wanted(key) {
+                cancel.cancel();
+            }
+            wanted(key)
+        });
+    }
+
+    /// Cancel every job and forget the results, to compute them again when requested.
+    pub fn clear(&mut self) {
+        self.keep(|_| false);
+        self.done.clear();
+        self.recent.clear();
+    }
+
+    /// Forget the results requested longest ago past the limit.
+    fn forget_oldest(&mut self) {
+        while self.limit.is_some_and(|limit| self.done.len() > limit) {
+            let Some(key) = self.recent.pop_front() else {
+                return;
+            };
+            self.done.remove(&key);
+        }
+    }
+}
+
+impl<K, T> Default for Tasks<K, T>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    T: Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, T> Drop for Tasks<K, T> {
+    fn drop(&mut self) {
+        for cancel in self.pending.values() {
+            cancel.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Poll `tasks` until there is a result for `key`, for a second at most.
+    fn wait_for<'a>(tasks: &'a mut Tasks<u32, &'static str>, key: u32) -> Option<&'a &'static str> {
+        let start = Instant::now();
+        while tasks.get(&key).is_none() && start.elapsed() < Duration::from_secs(1) {
+            thread::sleep(Duration::from_millis(5));
+            tasks.poll();
+        }
+        tasks.get(&key)
+    }
+
+    #[test]
+    fn jobs_no_longer_wanted_are_cancelled() {
+        let mut tasks = Tasks::new();
+        let (start, started) = mpsc::channel();
+        // The first job runs until it is cancelled, while the others wait behind it.
+        tasks.request(1, move |cancel: &CancelFlag| {
+            start.send(()).unwrap();
+            while !cancel.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            "stopped"
+        });
+        tasks.request(2, |_| "two");
+        tasks.request(3, |_| "three");
+        started.recv().unwrap();
+        tasks.keep(|key| *key == 3);
+
+        assert_eq!(wait_for(&mut tasks, 3), Some(&"three"));
+        assert_eq!((tasks.get(&1), tasks.get(&2)), (None, None));
+        // A result is kept until cleared, and not computed again.
+        tasks.request(3, |_| "again");
+        assert_eq!(wait_for(&mut tasks, 3), Some(&"three"));
+        tasks.clear();
+        tasks.request(3, |_| "again");
+        assert_eq!(wait_for(&mut tasks, 3), Some(&"again"));
+    }
+
+    #[test]
+    fn results_of_cancelled_jobs_are_dropped() {
+        let mut tasks = Tasks::new();
+        let (start, started) = mpsc::channel();
+        tasks.request(1, |_| "old");
+        // The second job starts once the result of the first was sent.
+        tasks.request(2, move |_| {
+            start.send(()).unwrap();
+            "two"
+        });
+        started.recv().unwrap();
+        tasks.clear();
+        let (finish, finished) = mpsc::channel::<()>();
+        tasks.request(1, move |_| {
+            finished.recv().unwrap();
+            "new"
+        });
+
+        tasks.poll();
+        assert_eq!(tasks.get(&1), None);
+        finish.send(()).unwrap();
+        assert_eq!(wait_for(&mut tasks, 1), Some(&"new"));
+    }
+
+    #[test]
+    fn results_past_the_limit_are_forgotten() {
+        let mut tasks = Tasks::with_limit(2);
+        tasks.request(1, |_| "one");
+        assert_eq!(wait_for(&mut tasks, 1), Some(&"one"));
+        tasks.request(2, |_| "two");
+        assert_eq!(wait_for(&mut tasks, 2), Some(&"two"));
+        // Asking for the first result again keeps it over the second.
+        tasks.request(1, |_| "again");
+        tasks.request(3, |_| "three");
+        assert_eq!(wait_for(&mut tasks, 3), Some(&"three"));
+        assert_eq!((tasks.get(&1), tasks.get(&2)), (Some(&"one"), None));
+    }
+}
@@ -0,0 +1,144 @@
+//! Temporary files, used to pass outlines to `djvused` and text to the editor.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::APP_NAME;
+
+/// Directory of the cache holding the temporary files.
+const TEMP_DIRECTORY: &str = "tmp";
+
+/// Temporary files older than this were left by a process that crashed or was killed.
+const STALE_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Number of temporary files created so far by this process, to name the next one.
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug)]
+pub enum TempFileError {
+    SystemIOError(io::Error),
+    XDGSpecificError(xdg::BaseDirectoriesError),
+}
+
+impl Display for TempFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SystemIOError(e) => write!(f, "cannot create a temporary file: {}", e),
+            Self::XDGSpecificError(e) => write!(f, "cannot find the cache directory: {}", e),
+        }
+    }
+}
+
+impl Error for TempFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::SystemIOError(e) => Some(e),
+            Self::XDGSpecificError(e) => Some(e),
+        }
+    }
+}
+
+/// An empty file of the cache directory, named after the process so that several instances do
+/// not share it, and removed when dropped, which also happens when unwinding from a panic.
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// Create a temporary file whose name ends with `suffix`, such as `.txt`.
+    pub fn new(suffix: &str) -> Result<Self, TempFileError> {
+        Self::create(suffix, File::options().write(true).create_new(true))
+    }
+
+    /// Create a temporary file like `new`, only readable and writable by its owner, for secrets
+    /// such as keys.
+    pub fn private(suffix: &str) -> Result<Self, TempFileError> {
+        let mut options = File::options();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        Self::create(suffix, &options)
+    }
+
+    fn create(suffix: &str, options: &OpenOptions) -> Result<Self, TempFileError> {
+        let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_NAME)
+            .map_err(TempFileError::XDGSpecificError)?;
+        loop {
+            let name = format!("{}/{}-{}{}", TEMP_DIRECTORY, process::id(), CREATED.fetch_add(1, Ordering::Relaxed), suffix);
+            // This creates the directories, but not the file.
+            let path = xdg_dirs.place_cache_file(name).map_err(TempFileError::SystemIOError)?;
+            // A file left by an earlier process with the same identifier is not reused.
+            match options.open(&path) {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(TempFileError::SystemIOError(e)),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Remove the temporary files left by processes that could not remove theirs, because they
+/// crashed or were killed. Return the number of files removed.
+pub fn remove_stale() -> Result<usize, TempFileError> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix(APP_NAME)
+        .map_err(TempFileError::XDGSpecificError)?;
+    let Some(directory) = xdg_dirs.find_cache_file(TEMP_DIRECTORY) else {
+        return Ok(0);
+    };
+    let mut removed = 0;
+    for entry in fs::read_dir(directory).map_err(TempFileError::SystemIOError)? {
+        let path = entry.map_err(TempFileError::SystemIOError)?.path();
+        let stale = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > STALE_AGE));
+        if stale && fs::remove_file(&path).is_ok() {
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_files_are_distinct_and_removed() {
+        let (first, second) = (TempFile::new(".txt").unwrap(), TempFile::new(".txt").unwrap());
+        assert_ne!(first.path(), second.path());
+        assert!(first.path().to_string_lossy().ends_with(".txt"));
+
+        let path = first.path().to_owned();
+        assert!(path.exists());
+        drop(first);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_files_are_only_for_their_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let file = TempFile::private(".headers").unwrap();
+        assert_eq!(fs::metadata(file.path()).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt::Display;
+
+use crate::nav::{BookmarkLink, Nav, NavNode};
+
+/// Spaces added to the indentation of each level when writing an outline.
+const INDENT: &str = "  ";
+/// Width of a tab in the indentation, in spaces.
+const TAB_WIDTH: usize = 4;
+
+/// Errors in an outline written as text, with the number of the offending line, starting from 1.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TextOutlineError {
+    MissingPage(usize),
+    /// The line is less indented than the line before it, but more than the parent of that line.
+    InvalidIndentation(usize),
+}
+
+impl Display for TextOutlineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingPage(line) => write!(f, "line {}: expected <title><tab><page>", line),
+            Self::InvalidIndentation(line) => {
+                write!(f, "line {}: the indentation matches none of the lines above", line)
+            },
+        }
+    }
+}
+
+impl std::error::Error for TextOutlineError {}
+
+/// Write `nav` as one `<indent><title><tab><page>` line per node, in document order, children
+/// being indented by two more spaces than their parent.
+pub fn to_text(nav: &Nav) -> String {
+    let mut text = String::new();
+    for (id, node) in nav.all_nodes() {
+        // Tabs and line breaks in titles would be read back as separators.
+        let title = node.string.replace(['\t', '\n', '\r'], " ");
+        text.push_str(&format!("{}{}\t{}\n", INDENT.repeat(id.len() - 1), title, node.link));
+    }
+    text
+}
+
+/// Split `line` into its indented title and its link, separated by the last tab or, for editors
+/// turning tabs into spaces, by the last run of at least two spaces after the indentation. An
+/// untitled entry, which `to_text` writes as its indentation then a tab, has the tab ending its
+/// indentation as separator.
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    let indentation = line.len() - line.trim_start().len();
+    let rest = &line[indentation..];
+    let (title, link) = match rest.rfind('\t') {
+        Some(tab) => (&line[..indentation + tab], &rest[tab + 1..]),
+        None => match rest.rfind("  ") {
+            Some(end) if !rest[..end].trim().is_empty() => {
+                (line[..indentation + end].trim_end_matches(' '), &rest[end + 2..])
+            },
+            _ => line.rsplit_once('\t')?,
+        },
+    };
+    let link = link.trim();
+    (!link.is_empty()).then_some((title, link))
+}
+
+/// Parse `text`, an outline written as `to_text` does. Any consistent indentation is accepted, and
+/// blank lines are skipped.
+pub fn from_text(text: &str) -> Result<Nav, TextOutlineError> {
+    let mut nav = Nav { nodes: vec![] };
+    // Indentation of the last node of each level, from the top level down to the last line.
+    let mut levels: Vec<usize> = Vec::new();
+    for (i, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        // The indentation is counted before the title only, since an untitled entry is followed
+        // by the tab before its page.
+        let (title, link) = split_line(line).ok_or(TextOutlineError::MissingPage(i + 1))?;
+        let indentation = title.chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+            .sum();
+        let title = title.trim_start();
+
+        // A line less indented than the one before it closes the levels it is less indented
+        // than, and has to line up with the level it goes back to.
+        let mut closed = false;
+        while levels.last().is_some_and(|last| *last > indentation) {
+            levels.pop();
+            closed = true;
+        }
+        if levels.last() == Some(&indentation) {
+            levels.pop();
+        } else if closed {
+            return Err(TextOutlineError::InvalidIndentation(i + 1));
+        }
+
+        let node = NavNode {
+            string: String::from(title),
+            link: BookmarkLink::from_string(link),
+            children: vec![],
+        };
+        let mut siblings = &mut nav.nodes;
+        for _ in 0..levels.len() {
+            siblings = &mut siblings.last_mut().unwrap().children;
+        }
+        siblings.push(node);
+        levels.push(indentation);
+    }
+    Ok(nav)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+
+    #[test]
+    fn text_reads_back() {
+        let nav = Nav {
+            nodes: vec![
+                node("Preface", 3, vec![]),
+                NavNode {
+                    children: vec![
+                        NavNode { children: vec![node("1.1 Origins", 14, vec![])], ..node("Chapter 1", 12, vec![]) },
+                        node("Chapter 2", 30, vec![]),
+                    ],
+                    ..node("Part I", 11, vec![])
+                },
+                NavNode { link: BookmarkLink::PageLink(String::from("p0400.djvu")), ..node("Index", 0, vec![]) },
+            ],
+        };
+        let text = to_text(&nav);
+
+        assert_eq!(text, "Preface\t3\nPart I\t11\n  Chapter 1\t12\n    1.1 Origins\t14\n  Chapter 2\t30\nIndex\tp0400.djvu\n");
+        assert_eq!(from_text(&text).unwrap(), nav);
+    }
+
+    #[test]
+    fn untitled_entries_read_back() {
+        let nav = Nav {
+            nodes: vec![
+                node("", 1, vec![]),
+                NavNode { children: vec![node("", 5, vec![]), node("Chapter 2", 9, vec![])], ..node("Part I", 4, vec![]) },
+            ],
+        };
+        let text = to_text(&nav);
+
+        assert_eq!(text, "\t1\nPart I\t4\n  \t5\n  Chapter 2\t9\n");
+        assert_eq!(from_text(&text).unwrap(), nav);
+    }
+
+    #[test]
+    fn typed_text() {
+        let text = "Part I    11\n\n\tChapter 1  12\n\tChapter 2\t30\nPart II\t40\n";
+        let nav = from_text(text).unwrap();
+        assert_eq!(nav.nodes[0].children, vec![node("Chapter 1", 12, vec![]), node("Chapter 2", 30, vec![])]);
+        assert_eq!(nav.nodes[1], node("Part II", 40, vec![]));
+
+        assert_eq!(from_text("Part I\t1\n    Chapter 1\t2\n  Chapter 2\t3"), Err(TextOutlineError::InvalidIndentation(3)));
+        assert_eq!(from_text("Part I\t1\nChapter 1 2"), Err(TextOutlineError::MissingPage(2)));
+    }
+}
@@ -1,45 +1,74 @@
-use crate::tree_widget::identifier::{TreeIdentifier, TreeIdentifierVec};
+use crate::tree_widget::identifier::{TreeIdentifier, TreeIdentifierSet, TreeIdentifierVec};
 use crate::tree_widget::TreeItem;
 
-pub struct Flattened<'a> {
-    pub identifier: Vec<usize>,
+/// A visible item of a [`FlatTree`], along with its identifier.
+pub struct Flattened<'t, 'a> {
+    pub identifier: TreeIdentifier<'t>,
     pub item: &'a TreeItem<'a>,
 }
 
-impl<'a> Flattened<'a> {
+impl<'t, 'a> Flattened<'t, 'a> {
     #[must_use]
     pub fn depth(&self) -> usize {
         self.identifier.len() - 1
     }
 }
 
+/// The visible items of a tree, in display order. The identifiers are stored one after the other
+/// in a single buffer, so that flattening a large tree does not allocate for every item.
+pub struct FlatTree<'a> {
+    identifiers: Vec<usize>,
+    /// Start of the identifier in `identifiers`, its length, and the item.
+    entries: Vec<(usize, usize, &'a TreeItem<'a>)>,
+}
+
+impl<'a> FlatTree<'a> {
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<Flattened<'_, 'a>> {
+        let (start, len, item) = *self.entries.get(index)?;
+        Some(Flattened { identifier: &self.identifiers[start..start + len], item })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Flattened<'_, 'a>> {
+        self.entries.iter().map(|(start, len, item)| Flattened {
+            identifier: &self.identifiers[*start..*start + *len],
+            item,
+        })
+    }
+}
+
 /// Get a flat list of all visible [`TreeItem`s](TreeItem)
 #[must_use]
-pub fn flatten<'a>(opened: &[TreeIdentifierVec], items: &'a [TreeItem<'a>]) -> Vec<Flattened<'a>> {
-    internal(opened, items, &[])
+pub fn flatten<'a>(opened: &TreeIdentifierSet, items: &'a [TreeItem<'a>]) -> FlatTree<'a> {
+    let mut result = FlatTree { identifiers: Vec::new(), entries: Vec::new() };
+    internal(opened, items, &mut Vec::new(), &mut result);
+    result
 }
 
-#[must_use]
+/// Push the visible items among `items`, whose parent is `current`, to `result`.
 fn internal<'a>(
-    opened: &[TreeIdentifierVec],
+    opened: &TreeIdentifierSet,
     items: &'a [TreeItem<'a>],
-    current: TreeIdentifier,
-) -> Vec<Flattened<'a>> {
-    let mut result = Vec::new();
-
+    current: &mut TreeIdentifierVec,
+    result: &mut FlatTree<'a>,
+) {
     for (index, item) in items.iter().enumerate() {
-        let mut child_identifier = current.to_vec();
-        child_identifier.push(index);
-
-        result.push(Flattened {
-            item,
-            identifier: child_identifier.clone(),
-        });
-
-        if opened.contains(&child_identifier) {
-            let mut child_result = internal(opened, &item.children, &child_identifier);
-            result.append(&mut child_result);
+        current.push(index);
+        result.entries.push((result.identifiers.len(), current.len(), item));
+        result.identifiers.extend_from_slice(current);
+        if !item.children.is_empty() && opened.contains(current.as_slice()) {
+            internal(opened, &item.children, current, result);
         }
+        current.pop();
     }
-    result
 }
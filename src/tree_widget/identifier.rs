@@ -1,6 +1,41 @@
 #![allow(clippy::module_name_repetitions)]
 
+use std::collections::HashSet;
+use std::hash::{BuildHasherDefault, Hasher};
+
 /// Reference to a [`TreeItem`](crate::TreeItem) in a [`Tree`](crate::Tree)
 pub type TreeIdentifier<'a> = &'a [usize];
 /// Reference to a [`TreeItem`](crate::TreeItem) in a [`Tree`](crate::Tree)
 pub type TreeIdentifierVec = Vec<usize>;
+
+/// Set of identifiers, such as the opened items of a tree.
+pub type TreeIdentifierSet = HashSet<TreeIdentifierVec, BuildHasherDefault<IdentifierHasher>>;
+
+/// A hasher for identifiers, which are short and not chosen by an attacker, much faster than the
+/// default one. This is the multiply-rotate hash of Firefox and rustc.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IdentifierHasher(u64);
+
+impl IdentifierHasher {
+    fn add(&mut self, word: u64) {
+        self.0 = (self.0.rotate_left(5) ^ word).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+impl Hasher for IdentifierHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.add(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.add(n as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -1,28 +1,66 @@
 #![forbid(unsafe_code)]
 
-use std::collections::HashSet;
+#[cfg(feature = "tui")]
+use std::{
+    borrow::Cow,
+    ops::{Range, RangeInclusive},
+};
 
-use ratatui::buffer::Buffer;
-use ratatui::layout::{Corner, Rect};
-use ratatui::style::Style;
-use ratatui::text::Text;
-use ratatui::widgets::{Block, StatefulWidget, Widget};
+#[cfg(feature = "tui")]
+use ratatui::{
+    buffer::Buffer,
+    layout::{Corner, Rect},
+    style::Style,
+    text::Text,
+    widgets::{Block, StatefulWidget, Widget},
+};
+#[cfg(feature = "tui")]
 use unicode_width::UnicodeWidthStr;
 
+// Only the identifiers and `TreeView` are needed without the editor.
+#[cfg(feature = "tui")]
 mod flatten;
 mod identifier;
+#[cfg(all(test, feature = "tui"))]
+mod scenario;
 
 pub trait TreeView {
     fn num_children(&self, index: TreeIdentifier) -> usize;
 }
 
+#[cfg(feature = "tui")]
 #[derive(Debug, Default, Clone)]
 pub struct TreeState {
     offset: usize,
-    opened: HashSet<TreeIdentifierVec>,
+    opened: TreeIdentifierSet,
     selected: TreeIdentifierVec,
+    /// Where the range of selected siblings starts, the selected node being where it ends.
+    anchor: Option<TreeIdentifierVec>,
+    /// The items drawn by the last render, from the top of the tree, to find them on the screen.
+    rendered: Vec<RenderedItem>,
+}
+
+/// Where an item of the tree was drawn.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone)]
+struct RenderedItem {
+    identifier: TreeIdentifierVec,
+    area: Rect,
+    /// Columns of the symbol opening or closing the item, if it has children.
+    toggle: Option<Range<u16>>,
+}
+
+/// What is at a position of the screen, according to the last render of the tree.
+#[cfg(feature = "tui")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeHit {
+    /// The symbol in front of a node with children, which opens or closes it.
+    Toggle(TreeIdentifierVec),
+    /// Anywhere else on the row of a node.
+    Item(TreeIdentifierVec),
 }
 
+#[cfg(feature = "tui")]
 impl TreeState {
     #[must_use]
     pub const fn get_offset(&self) -> usize {
@@ -58,6 +96,39 @@ impl TreeState {
         self.selected = identifier.into();
     }
 
+    /// Start a range of siblings at the selected node, which the selection then extends.
+    pub fn start_range(&mut self) {
+        self.anchor = Some(self.selected.clone());
+    }
+
+    pub fn clear_range(&mut self) {
+        self.anchor = None;
+    }
+
+    /// Select the siblings `range` of the node `father`, the selection ending at the last one.
+    pub fn select_range(&mut self, father: TreeIdentifier, range: RangeInclusive<usize>) {
+        self.anchor = Some([father, &[*range.start()]].concat());
+        self.selected = [father, &[*range.end()]].concat();
+    }
+
+    /// Return the father and the positions of the selected siblings, if a range was started and
+    /// the selection is still among the siblings it was started at.
+    #[must_use]
+    pub fn range(&self) -> Option<(TreeIdentifier<'_>, RangeInclusive<usize>)> {
+        let (&start, father) = self.anchor.as_ref()?.split_last()?;
+        let (&end, selected_father) = self.selected.split_last()?;
+        (father == selected_father).then_some((father, start.min(end)..=start.max(end)))
+    }
+
+    /// Whether `identifier` is one of the siblings of the range.
+    #[must_use]
+    pub fn is_in_range(&self, identifier: TreeIdentifier) -> bool {
+        match (self.range(), identifier.split_last()) {
+            (Some((father, range)), Some((last, item_father))) => father == item_father && range.contains(last),
+            _ => false,
+        }
+    }
+
     pub fn open(&mut self, identifier: TreeIdentifier) -> bool {
         if identifier.is_empty() {
             false
@@ -71,6 +142,22 @@ impl TreeState {
         self.opened.remove(identifier)
     }
 
+    /// Close `identifier` and all of its descendants.
+    pub fn close_recursive(&mut self, identifier: TreeIdentifier) -> bool {
+        let was_open = self.opened.contains(identifier);
+        self.opened.retain(|opened| !opened.starts_with(identifier));
+        was_open
+    }
+
+    /// Close all nodes except the ancestors and descendants of `identifier`, and open its
+    /// ancestors.
+    pub fn focus(&mut self, identifier: TreeIdentifier) {
+        self.opened.retain(|opened| opened.starts_with(identifier));
+        for depth in 1..identifier.len() {
+            self.open(&identifier[..depth]);
+        }
+    }
+
     pub fn toggle(&mut self, identifier: TreeIdentifier) {
         if !self.close(identifier) {
             self.open(identifier);
@@ -109,17 +196,25 @@ impl TreeState {
         if self.selected.is_empty() {
             return;
         }
-        if *self.selected.last().unwrap() == 0 {
-            self.selected.pop();
-        } else {
-            let mut index = self.selected.clone();
-            *index.last_mut().unwrap() -= 1;
-            while self.is_open(&index) && tree.num_children(&index) > 0 {
-                let next_value = tree.num_children(&index) - 1;
-                index.push(next_value);
-            }
-            self.selected = index;
+        // Above the first node, nothing is selected.
+        self.selected = self.previous_visible(tree, &self.selected).unwrap_or_default();
+    }
+
+    /// Return the node shown above the non-empty `identifier`, if it is not the first one.
+    fn previous_visible<T>(&self, tree: &T, identifier: TreeIdentifier) -> Option<TreeIdentifierVec>
+    where
+        T: TreeView
+    {
+        let (&last, father) = identifier.split_last()?;
+        if last == 0 {
+            return (!father.is_empty()).then(|| father.to_vec());
+        }
+        let mut index = [father, &[last - 1]].concat();
+        while self.is_open(&index) && tree.num_children(&index) > 0 {
+            let next_value = tree.num_children(&index) - 1;
+            index.push(next_value);
         }
+        Some(index)
     }
 
     pub fn key_down<T>(&mut self, tree: &T)
@@ -130,28 +225,119 @@ impl TreeState {
             self.select_first();
             return;
         }
+        if let Some(next) = self.next_visible(tree, &self.selected) {
+            self.selected = next;
+        }
+    }
 
-        if self.is_open(&self.selected) && tree.num_children(&self.selected) > 0 {
-            self.selected.push(0);
-        } else {
-            let selected_clone = self.selected.clone();
-            let mut father_index = &selected_clone[..self.selected.len() - 1];
-            let mut son_index = &selected_clone[..];
-
-            while !father_index.is_empty() {
-                if tree.num_children(father_index) - 1 > *son_index.last().unwrap() {
-                    self.selected = father_index.to_owned();
-                    self.selected.push(*son_index.last().unwrap() + 1);
-                    return;
-                }
-                son_index = father_index;
-                father_index = &father_index[..father_index.len() - 1];
+    /// Return the node shown below the non-empty `identifier`, if it is not the last one.
+    fn next_visible<T>(&self, tree: &T, identifier: TreeIdentifier) -> Option<TreeIdentifierVec>
+    where
+        T: TreeView
+    {
+        if self.is_open(identifier) && tree.num_children(identifier) > 0 {
+            return Some([identifier, &[0]].concat());
+        }
+        let mut father_index = &identifier[..identifier.len() - 1];
+        let mut son_index = identifier;
+
+        while !father_index.is_empty() {
+            if tree.num_children(father_index) - 1 > *son_index.last().unwrap() {
+                return Some([father_index, &[*son_index.last().unwrap() + 1]].concat());
+            }
+            son_index = father_index;
+            father_index = &father_index[..father_index.len() - 1];
+        }
+
+        (tree.num_children(&[]) - 1 > son_index[0]).then(|| vec![son_index[0] + 1])
+    }
+
+    /// Return the number of nodes drawn by the last render, which a page of the tree holds.
+    #[must_use]
+    pub fn page_size(&self) -> usize {
+        self.rendered.len()
+    }
+
+    /// Move the selection and the view `lines` nodes down, as far as the last node.
+    pub fn page_down<T>(&mut self, tree: &T, lines: usize)
+    where
+        T: TreeView
+    {
+        if self.selected.is_empty() {
+            self.select_first();
+        }
+        let mut selected = self.selected.clone();
+        for _ in 0..lines {
+            match self.next_visible(tree, &selected) {
+                Some(next) => selected = next,
+                None => break,
             }
+        }
+        self.scroll_down(tree, lines);
+        self.selected = selected;
+    }
 
-            if tree.num_children(&[]) - 1 > son_index[0] {
-                self.selected = vec![son_index[0] + 1];
+    /// Move the selection and the view `lines` nodes up, as far as the first node.
+    pub fn page_up<T>(&mut self, tree: &T, lines: usize)
+    where
+        T: TreeView
+    {
+        let mut selected = self.selected.clone();
+        for _ in 0..lines {
+            match self.previous_visible(tree, &selected) {
+                Some(previous) => selected = previous,
+                None => break,
             }
         }
+        self.scroll_up(lines);
+        self.selected = selected;
+    }
+
+    /// Return what is at `column` and `row` of the screen, if the last render drew a node there.
+    #[must_use]
+    pub fn hit(&self, column: u16, row: u16) -> Option<TreeHit> {
+        let item = self.rendered.iter().find(|item| {
+            (item.area.left()..item.area.right()).contains(&column)
+                && (item.area.top()..item.area.bottom()).contains(&row)
+        })?;
+        Some(match &item.toggle {
+            Some(toggle) if toggle.contains(&column) => TreeHit::Toggle(item.identifier.clone()),
+            _ => TreeHit::Item(item.identifier.clone()),
+        })
+    }
+
+    /// Scroll the view `lines` nodes down, as far as the last node, keeping the selection in view.
+    pub fn scroll_down<T>(&mut self, tree: &T, lines: usize)
+    where
+        T: TreeView
+    {
+        let (Some(first), Some(last)) = (self.rendered.first(), self.rendered.last()) else {
+            return;
+        };
+        let (mut first, mut last) = (first.identifier.clone(), last.identifier.clone());
+        let mut scrolled = 0;
+        while scrolled < lines {
+            let (Some(next_first), Some(next_last)) = (self.next_visible(tree, &first), self.next_visible(tree, &last)) else {
+                break;
+            };
+            if self.selected == first {
+                self.selected = next_first.clone();
+            }
+            (first, last) = (next_first, next_last);
+            scrolled += 1;
+        }
+        self.offset += scrolled;
+    }
+
+    /// Scroll the view `lines` nodes up, as far as the first node, keeping the selection in view.
+    pub fn scroll_up(&mut self, lines: usize) {
+        let scrolled = lines.min(self.offset);
+        self.offset -= scrolled;
+        let shown = self.rendered.len().saturating_sub(scrolled);
+        let selected_row = self.rendered.iter().position(|item| item.identifier == self.selected);
+        if selected_row.is_some_and(|row| row >= shown) && shown > 0 {
+            self.selected = self.rendered[shown - 1].identifier.clone();
+        }
     }
 
     pub fn key_left(&mut self) {
@@ -165,9 +351,10 @@ impl TreeState {
         self.open(&self.selected.clone());
     }
 }
-pub use flatten::{flatten, Flattened};
+#[cfg(feature = "tui")]
+pub use flatten::{flatten, FlatTree, Flattened};
 pub use identifier::{
-    TreeIdentifier, TreeIdentifierVec,
+    IdentifierHasher, TreeIdentifier, TreeIdentifierSet, TreeIdentifierVec,
 };
 
 /// One item inside a [`Tree`]
@@ -177,17 +364,21 @@ pub use identifier::{
 /// # Example
 ///
 /// ```
-/// # use tui_tree_widget::TreeItem;
+/// # use djvu_nav::tree_widget::TreeItem;
 /// let a = TreeItem::new_leaf("leaf");
 /// let b = TreeItem::new("root", vec![a]);
 /// ```
+#[cfg(feature = "tui")]
 #[derive(Debug, Clone)]
 pub struct TreeItem<'a> {
     text: Text<'a>,
+    /// Text aligned to the right of the first line, such as a page number.
+    right_text: Option<Cow<'a, str>>,
     style: Style,
     children: Vec<TreeItem<'a>>,
 }
 
+#[cfg(feature = "tui")]
 impl<'a> TreeItem<'a> {
     #[must_use]
     pub fn new_leaf<T>(text: T) -> Self
@@ -196,6 +387,7 @@ impl<'a> TreeItem<'a> {
     {
         Self {
             text: text.into(),
+            right_text: None,
             style: Style::default(),
             children: Vec::new(),
         }
@@ -209,6 +401,7 @@ impl<'a> TreeItem<'a> {
     {
         Self {
             text: text.into(),
+            right_text: None,
             style: Style::default(),
             children: children.into(),
         }
@@ -240,6 +433,16 @@ impl<'a> TreeItem<'a> {
         self
     }
 
+    /// Show `text` aligned to the right of the first line, where there is room for it.
+    #[must_use]
+    pub fn right_text<T>(mut self, text: T) -> Self
+    where
+        T: Into<Cow<'a, str>>,
+    {
+        self.right_text = Some(text.into());
+        self
+    }
+
     pub fn add_child(&mut self, child: TreeItem<'a>) {
         self.children.push(child);
     }
@@ -250,10 +453,10 @@ impl<'a> TreeItem<'a> {
 /// # Example
 ///
 /// ```
-/// # use tui_tree_widget::{Tree, TreeItem, TreeState};
-/// # use tui::backend::TestBackend;
-/// # use tui::Terminal;
-/// # use tui::widgets::{Block, Borders};
+/// # use djvu_nav::tree_widget::{Tree, TreeItem, TreeState};
+/// # use ratatui::backend::TestBackend;
+/// # use ratatui::Terminal;
+/// # use ratatui::widgets::{Block, Borders};
 /// # fn main() -> std::io::Result<()> {
 /// #     let mut terminal = Terminal::new(TestBackend::new(32, 32)).unwrap();
 /// let mut state = TreeState::default();
@@ -272,6 +475,7 @@ impl<'a> TreeItem<'a> {
 /// #     Ok(())
 /// # }
 /// ```
+#[cfg(feature = "tui")]
 #[derive(Debug, Clone)]
 pub struct Tree<'a> {
     items: Vec<TreeItem<'a>>,
@@ -292,8 +496,11 @@ pub struct Tree<'a> {
     node_open_symbol: &'a str,
     /// Symbol displayed in front of a node without children.
     node_no_children_symbol: &'a str,
+    /// Character filling the space between the text of an item and its right text.
+    leader: char,
 }
 
+#[cfg(feature = "tui")]
 impl<'a> Tree<'a> {
     #[must_use]
     pub fn new<T>(items: T) -> Self
@@ -310,6 +517,7 @@ impl<'a> Tree<'a> {
             node_closed_symbol: "\u{25b6} ", // Arrow to right
             node_open_symbol: "\u{25bc} ",   // Arrow down
             node_no_children_symbol: "  ",
+            leader: ' ',
         }
     }
 
@@ -361,8 +569,15 @@ impl<'a> Tree<'a> {
         self.node_no_children_symbol = symbol;
         self
     }
+
+    #[must_use]
+    pub const fn leader(mut self, leader: char) -> Self {
+        self.leader = leader;
+        self
+    }
 }
 
+#[cfg(feature = "tui")]
 impl<'a> StatefulWidget for Tree<'a> {
     type State = TreeState;
 
@@ -381,7 +596,8 @@ impl<'a> StatefulWidget for Tree<'a> {
             return;
         }
 
-        let visible = flatten(&state.get_all_opened(), &self.items);
+        state.rendered.clear();
+        let visible = flatten(&state.opened, &self.items);
         if visible.is_empty() {
             return;
         }
@@ -409,10 +625,10 @@ impl<'a> StatefulWidget for Tree<'a> {
         }
 
         while selected_index >= end {
-            height = height.saturating_add(visible[end].item.height());
+            height = height.saturating_add(visible.get(end).map_or(0, |item| item.item.height()));
             end += 1;
             while height > available_height {
-                height = height.saturating_sub(visible[start].item.height());
+                height = height.saturating_sub(visible.get(start).map_or(0, |item| item.item.height()));
                 start += 1;
             }
         }
@@ -454,45 +670,73 @@ impl<'a> StatefulWidget for Tree<'a> {
                 } else {
                     &blank_symbol
                 };
-                let (x, _) = buf.set_stringn(x, y, symbol, area.width as usize, item_style);
-                x
+                set_string_before(buf, x, y, symbol, area.right(), item_style)
             } else {
                 x
             };
 
-            let after_depth_x = {
-                let indent_width = item.depth() * 2;
-                let (after_indent_x, _) = buf.set_stringn(
-                    after_highlight_symbol_x,
-                    y,
-                    " ".repeat(indent_width),
-                    indent_width,
-                    item_style,
-                );
+            let (after_indent_x, after_depth_x) = {
+                let indent = " ".repeat(item.depth() * 2);
+                let after_indent_x =
+                    set_string_before(buf, after_highlight_symbol_x, y, &indent, area.right(), item_style);
                 let symbol = if item.item.children.is_empty() {
                     self.node_no_children_symbol
-                } else if state.opened.contains(&item.identifier) {
+                } else if state.opened.contains(item.identifier) {
                     self.node_open_symbol
                 } else {
                     self.node_closed_symbol
                 };
-                let max_width = area.width.saturating_sub(after_indent_x - x);
-                let (x, _) =
-                    buf.set_stringn(after_indent_x, y, symbol, max_width as usize, item_style);
-                x
+                (after_indent_x, set_string_before(buf, after_indent_x, y, symbol, area.right(), item_style))
             };
-
-            let max_element_width = area.width.saturating_sub(after_depth_x - x);
-            for (j, line) in item.item.text.lines.iter().enumerate() {
-                buf.set_line(after_depth_x, y + j as u16, line, max_element_width);
+            state.rendered.push(RenderedItem {
+                identifier: item.identifier.to_vec(),
+                area,
+                toggle: (!item.item.children.is_empty()).then_some(after_indent_x..after_depth_x),
+            });
+
+            if after_depth_x < area.right() {
+                // The right text is left out of rows too narrow for it and some of the text.
+                let right_text = item.item.right_text.as_deref()
+                    .filter(|right| right.width() + 1 + MIN_TEXT_WIDTH <= usize::from(area.right() - after_depth_x));
+                let right_x = right_text.map_or(area.right(), |right| area.right() - right.width() as u16);
+                let max_element_width = right_x - after_depth_x - u16::from(right_text.is_some());
+                let mut text_end = after_depth_x;
+                for (j, line) in item.item.text.lines.iter().enumerate() {
+                    let (end, _) = buf.set_line(after_depth_x, y + j as u16, line, max_element_width);
+                    if j == 0 {
+                        text_end = end;
+                    }
+                }
+                if let Some(right) = right_text {
+                    // A space on each side of the leader.
+                    for leader_x in (text_end + 1)..right_x.saturating_sub(1) {
+                        buf.get_mut(leader_x, y).set_char(self.leader);
+                    }
+                    buf.set_string(right_x, y, right, item_style);
+                }
             }
-            if is_selected {
+            if is_selected || state.is_in_range(item.identifier) {
                 buf.set_style(area, self.highlight_style);
             }
         }
     }
 }
 
+/// Width kept for the text of an item before its right text is left out.
+#[cfg(feature = "tui")]
+const MIN_TEXT_WIDTH: usize = 8;
+
+/// Write `string` to `buf` from column `x` of line `y`, stopping before column `right`, and return
+/// the column after the last one written.
+#[cfg(feature = "tui")]
+fn set_string_before(buf: &mut Buffer, x: u16, y: u16, string: &str, right: u16, style: Style) -> u16 {
+    if x >= right {
+        return x;
+    }
+    buf.set_stringn(x, y, string, usize::from(right - x), style).0
+}
+
+#[cfg(feature = "tui")]
 impl<'a> Widget for Tree<'a> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let mut state = TreeState::default();
@@ -0,0 +1,371 @@
+//! Navigation scenarios: a tree written as indented titles, the keys pressed, and the rows shown
+//! afterwards, to lock down how the selection moves and nodes open before changing `TreeState`.
+//!
+//! ```text
+//! scenario("
+//!     a
+//!       a.1
+//!     b
+//! ")
+//! .keys("l j")
+//! .shows("
+//!     |  ▼ a
+//!     |>     a.1
+//!     |      b
+//! ");
+//! ```
+//!
+//! The keys are those of the editor: `j`, `k`, `h` and `l` to move, `g` and `G` to go to the first
+//! and last nodes, `t` to open or close the selected node, `f` to focus on it, `c` to close all
+//! nodes, `ctrl-d`, `ctrl-u`, `page-down` and `page-up` to move by half a page or a page, and
+//! `wheel-up` and `wheel-down` to scroll by a node. Each expected row starts with `|`, and the
+//! selected one with `|>`.
+
+use ratatui::{backend::TestBackend, Terminal};
+
+use super::{Tree, TreeIdentifier, TreeItem, TreeState, TreeView};
+
+/// The items of a tree, to move in without a `Nav`.
+struct Items(Vec<TreeItem<'static>>);
+
+impl TreeView for Items {
+    fn num_children(&self, index: TreeIdentifier) -> usize {
+        match index.split_first() {
+            None => self.0.len(),
+            Some((first, rest)) => rest.iter()
+                .fold(&self.0[*first], |item, i| &item.children[*i])
+                .children
+                .len(),
+        }
+    }
+}
+
+struct Scenario {
+    items: Items,
+    state: TreeState,
+    terminal: Terminal<TestBackend>,
+}
+
+/// Start a scenario in `tree`, one title per line, children indented below their parent, with
+/// the first node selected and a screen high enough for the whole tree.
+fn scenario(tree: &str) -> Scenario {
+    let lines: Vec<&str> = tree.lines().filter(|line| !line.trim().is_empty()).collect();
+    let margin = lines.iter().map(|line| line.len() - line.trim_start().len()).min().unwrap_or(0);
+    let mut items: Vec<TreeItem> = Vec::new();
+    for line in &lines {
+        let depth = (line.len() - line.trim_start().len() - margin) / 2;
+        let mut siblings = &mut items;
+        for _ in 0..depth {
+            siblings = &mut siblings.last_mut().expect("indented below no node").children;
+        }
+        siblings.push(TreeItem::new_leaf(line.trim().to_owned()));
+    }
+    let mut state = TreeState::default();
+    state.select_first();
+    let height = u16::try_from(lines.len()).unwrap();
+    Scenario { items: Items(items), state, terminal: Terminal::new(TestBackend::new(24, height)).unwrap() }.render()
+}
+
+impl Scenario {
+    /// Show only `height` rows of the tree.
+    fn height(mut self, height: u16) -> Self {
+        self.terminal = Terminal::new(TestBackend::new(24, height)).unwrap();
+        self.render()
+    }
+
+    /// Press `keys`, separated by spaces, the tree being drawn after each one as in the editor.
+    fn keys(mut self, keys: &str) -> Self {
+        for key in keys.split_whitespace() {
+            let page = self.state.page_size();
+            match key {
+                "j" => self.state.key_down(&self.items),
+                "k" => self.state.key_up(&self.items),
+                "h" => self.state.key_left(),
+                "l" => self.state.key_right(),
+                "g" => self.state.select_first(),
+                "G" => self.state.select_last(&self.items),
+                "t" => self.state.toggle_selected(),
+                "f" => {
+                    let selected = self.state.selected().to_owned();
+                    self.state.focus(&selected);
+                },
+                "c" => self.state.close_all(),
+                "ctrl-d" => self.state.page_down(&self.items, (page / 2).max(1)),
+                "ctrl-u" => self.state.page_up(&self.items, (page / 2).max(1)),
+                "page-down" => self.state.page_down(&self.items, page),
+                "page-up" => self.state.page_up(&self.items, page),
+                "wheel-up" => self.state.scroll_up(1),
+                "wheel-down" => self.state.scroll_down(&self.items, 1),
+                _ => panic!("unknown key {:?}", key),
+            }
+            self = self.render();
+        }
+        self
+    }
+
+    fn render(mut self) -> Self {
+        let tree = Tree::new(self.items.0.clone()).highlight_symbol("> ");
+        self.terminal.draw(|f| f.render_stateful_widget(tree, f.size(), &mut self.state)).unwrap();
+        self
+    }
+
+    /// Check that the screen shows `rows`, each written after a `|`.
+    #[track_caller]
+    fn shows(self, rows: &str) -> Self {
+        let buffer = self.terminal.backend().buffer();
+        let width = usize::from(buffer.area.width);
+        let mut shown: Vec<String> = buffer.content
+            .chunks(width)
+            .map(|row| row.iter().map(|cell| cell.symbol()).collect::<String>().trim_end().to_owned())
+            .collect();
+        while shown.last().is_some_and(String::is_empty) {
+            shown.pop();
+        }
+        let expected: Vec<&str> = rows.lines()
+            .filter_map(|line| line.trim().strip_prefix('|'))
+            .map(str::trim_end)
+            .collect();
+        assert_eq!(shown, expected);
+        self
+    }
+}
+
+#[test]
+fn moving_down_enters_open_nodes_and_leaves_them_at_their_end() {
+    scenario("
+        a
+          a.1
+          a.2
+            a.2.1
+        b
+    ")
+    .keys("l j j l j j")
+    .shows("
+        |  ▼ a
+        |      a.1
+        |    ▼ a.2
+        |        a.2.1
+        |>   b
+    ");
+}
+
+#[test]
+fn moving_up_goes_to_the_last_visible_descendant() {
+    scenario("
+        a
+          a.1
+          a.2
+            a.2.1
+        b
+    ")
+    .keys("l j j l G k")
+    .shows("
+        |  ▼ a
+        |      a.1
+        |    ▼ a.2
+        |>       a.2.1
+        |    b
+    ");
+}
+
+#[test]
+fn moving_down_stays_on_the_last_node() {
+    scenario("
+        a
+        b
+          b.1
+    ")
+    .keys("G j j")
+    .shows("
+        |    a
+        |> ▶ b
+    ");
+}
+
+#[test]
+fn moving_left_closes_then_goes_to_the_parent_then_leaves_the_tree() {
+    scenario("
+        a
+          a.1
+            a.1.1
+        b
+    ")
+    .keys("l j l h")
+    .shows("
+        |  ▼ a
+        |>   ▶ a.1
+        |    b
+    ")
+    .keys("h")
+    .shows("
+        |> ▼ a
+        |    ▶ a.1
+        |    b
+    ")
+    // At the top level, the selection is cleared, which the editor prevents.
+    .keys("t h")
+    .shows("
+        |▶ a
+        |  b
+    ");
+}
+
+#[test]
+fn closing_all_nodes_hides_the_selection() {
+    scenario("
+        a
+          a.1
+        b
+    ")
+    .keys("l j c")
+    .shows("
+        |  ▶ a
+        |    b
+    ")
+    // The selection moves on from where it is hidden.
+    .keys("j")
+    .shows("
+        |  ▶ a
+        |>   b
+    ");
+}
+
+#[test]
+fn focusing_closes_the_other_nodes_and_opens_the_ancestors() {
+    scenario("
+        a
+          a.1
+        b
+          b.1
+            b.1.1
+    ")
+    .keys("l G l j l j f")
+    .shows("
+        |  ▶ a
+        |  ▼ b
+        |    ▼ b.1
+        |>       b.1.1
+    ");
+}
+
+#[test]
+fn the_view_follows_the_selection() {
+    scenario("
+        a
+        b
+        c
+        d
+    ")
+    .height(2)
+    .keys("j j")
+    .shows("
+        |    b
+        |>   c
+    ")
+    .keys("k k")
+    .shows("
+        |>   a
+        |    b
+    ");
+}
+
+#[test]
+fn the_wheel_scrolls_without_losing_the_selection() {
+    scenario("
+        a
+        b
+        c
+        d
+    ")
+    .height(2)
+    .keys("wheel-down")
+    .shows("
+        |>   b
+        |    c
+    ")
+    .keys("wheel-down wheel-down wheel-down")
+    .shows("
+        |>   c
+        |    d
+    ")
+    .keys("wheel-up wheel-up")
+    .shows("
+        |    a
+        |>   b
+    ");
+}
+
+#[test]
+fn pages_move_the_selection_and_the_view() {
+    scenario("
+        a
+        b
+        c
+        d
+        e
+        f
+        g
+        h
+    ")
+    .height(4)
+    .keys("ctrl-d")
+    .shows("
+        |>   c
+        |    d
+        |    e
+        |    f
+    ")
+    // The view stops at the last node, and the selection goes on to it.
+    .keys("page-down")
+    .shows("
+        |    e
+        |    f
+        |>   g
+        |    h
+    ")
+    .keys("page-down")
+    .shows("
+        |    e
+        |    f
+        |    g
+        |>   h
+    ")
+    .keys("ctrl-u")
+    .shows("
+        |    c
+        |    d
+        |    e
+        |>   f
+    ")
+    .keys("page-up")
+    .shows("
+        |    a
+        |>   b
+        |    c
+        |    d
+    ")
+    .keys("page-up")
+    .shows("
+        |>   a
+        |    b
+        |    c
+        |    d
+    ");
+}
+
+#[test]
+fn cleared_range_is_not_extended() {
+    let mut state = TreeState::default();
+    state.select(vec![0, 1]);
+    state.start_range();
+    assert_eq!(state.range(), Some((&[0][..], 1..=1)));
+    state.select(vec![0, 2]);
+    assert_eq!(state.range(), Some((&[0][..], 1..=2)));
+    assert!(state.is_in_range(&[0, 1]));
+
+    // After a cut, the selection moves to a sibling without extending a range.
+    state.clear_range();
+    state.select(vec![0, 1]);
+    assert_eq!(state.range(), None);
+    assert!(!state.is_in_range(&[0, 1]));
+}
@@ -0,0 +1,135 @@
+//! Checking that the targets of the outline follow the document: entries out of page order,
+//! duplicate entries, and long stretches of pages without an entry.
+
+use std::collections::HashMap;
+
+use crate::{
+    dedup::normalize_title,
+    djvu::PageInfo,
+    history::format_identifier,
+    lint::{Finding, FindingKind},
+    link::page_number,
+    nav::Nav,
+    offset::order_breaks,
+    report::{Report, ReportRow},
+    tree_widget::TreeIdentifierVec,
+};
+
+/// Gaps shorter than this many pages are never reported by default.
+const MIN_LARGE_GAP: u32 = 20;
+/// By default, gaps this many times longer than the usual gap between entries are reported.
+const LARGE_GAP_FACTOR: u32 = 4;
+
+/// Return the number of pages above which the gap between an entry and the next one is large,
+/// `max_gap` if set or else many times the median of `gaps`.
+fn gap_threshold(gaps: &[u32], max_gap: Option<u32>) -> u32 {
+    if let Some(max_gap) = max_gap {
+        return max_gap;
+    }
+    let mut sorted = gaps.to_vec();
+    sorted.sort_unstable();
+    let median = sorted.get(sorted.len() / 2).copied().unwrap_or(0);
+    MIN_LARGE_GAP.max(median * LARGE_GAP_FACTOR)
+}
+
+/// Check the targets of `nav` in document order: entries pointing before the entry above them,
+/// entries with the title and page of an earlier entry, and entries followed by more than
+/// `max_gap` pages without an entry. Return the findings in document order.
+pub fn validate(nav: &Nav, pages: Option<&[PageInfo]>, max_gap: Option<u32>) -> Vec<Finding> {
+    let mut findings: Vec<_> = order_breaks(nav, pages).into_iter()
+        .map(|order_break| Finding {
+            id: order_break.id,
+            kind: FindingKind::OutOfOrder,
+            message: format!(
+                "points to page {}, before page {} of the entry above",
+                order_break.page, order_break.previous_page,
+            ),
+        })
+        .collect();
+
+    let mut seen: HashMap<(String, u32), TreeIdentifierVec> = HashMap::new();
+    let mut targets = Vec::new();
+    for (id, node) in nav.all_nodes() {
+        let Some(page) = page_number(&node.link, pages) else {
+            continue;
+        };
+        let key = (normalize_title(&node.string), page);
+        match seen.get(&key) {
+            Some(first) => findings.push(Finding {
+                id: id.clone(),
+                kind: FindingKind::Duplicate,
+                message: format!("same title and page as {}", format_identifier(first)),
+            }),
+            None => {
+                seen.insert(key, id.clone());
+            },
+        }
+        targets.push((id, page));
+    }
+
+    let gaps: Vec<_> = targets.windows(2)
+        .filter_map(|pair| pair[1].1.checked_sub(pair[0].1))
+        .collect();
+    let threshold = gap_threshold(&gaps, max_gap);
+    for pair in targets.windows(2) {
+        let ((id, page), (_, next_page)) = (&pair[0], &pair[1]);
+        if next_page.saturating_sub(*page) > threshold {
+            findings.push(Finding {
+                id: id.clone(),
+                kind: FindingKind::Gap,
+                message: format!("{} pages until the next entry, on page {}", next_page - page, next_page),
+            });
+        }
+    }
+
+    let order: HashMap<_, _> = nav.all_nodes().into_iter().enumerate().map(|(i, (id, _))| (id, i)).collect();
+    findings.sort_by_key(|finding| order[&finding.id]);
+    findings
+}
+
+/// List `findings` about the entries of `nav`, to jump to them.
+pub fn validation_report(nav: &Nav, findings: &[Finding]) -> Report {
+    let rows = findings.iter()
+        .map(|finding| {
+            let title = nav.get(&finding.id).map_or("", |node| node.string.as_str());
+            let text = format!("{} \"{}\": {}", format_identifier(&finding.id), title, finding.message);
+            ReportRow::node(text, finding.id.clone())
+        })
+        .collect();
+    Report {
+        title: format!("{} problems with the order of the outline", findings.len()),
+        rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nav::node;
+
+    #[test]
+    fn order_duplicates_and_gaps() {
+        let nav = Nav {
+            nodes: vec![
+                node("Preface", 5, vec![]),
+                node("Chapter 1", 10, vec![node("Origins", 12, vec![]), node("Chapter 1", 10, vec![])]),
+                node("Chapter 2", 14, vec![]),
+                node("Index", 300, vec![]),
+            ],
+        };
+
+        let findings = validate(&nav, None, None);
+        let kinds: Vec<_> = findings.iter().map(|finding| (finding.id.clone(), finding.kind)).collect();
+        assert_eq!(kinds, [
+            (vec![1, 1], FindingKind::OutOfOrder),
+            (vec![1, 1], FindingKind::Duplicate),
+            (vec![2], FindingKind::Gap),
+        ]);
+        assert_eq!(findings[1].message, "same title and page as 2");
+        assert_eq!(findings[2].message, "286 pages until the next entry, on page 300");
+
+        assert!(validate(&nav, None, Some(300)).iter().all(|finding| finding.kind != FindingKind::Gap));
+        let report = validation_report(&nav, &findings);
+        assert_eq!(report.rows[0].text, "2.2 \"Chapter 1\": points to page 10, before page 12 of the entry above");
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use crate::{
+    djvu::{get_page_text, PageInfo},
+    link::page_number,
+    nav::{Nav, NavNode},
+    tree_widget::{TreeIdentifier, TreeIdentifierVec},
+};
+
+/// Outcome of looking for the title of an entry in the text layer of its target page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Found,
+    Missing,
+    /// The page has no text layer, or it could not be read.
+    NoText,
+}
+
+/// An entry to verify, as it was when the verification started.
+#[derive(Debug, Clone)]
+struct Check {
+    id: TreeIdentifierVec,
+    title: String,
+    page: u32,
+}
+
+/// Verification of the titles of an outline against the text of their pages, running in a
+/// background thread so that big documents do not block the interface.
+pub struct Verification {
+    receiver: Receiver<(Check, Verdict)>,
+    /// Verdicts received so far, along with the title and page they were given for.
+    results: HashMap<TreeIdentifierVec, (String, u32, Verdict)>,
+    total: usize,
+    finished: bool,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Verification {
+    /// Start verifying every entry of `nav` that has a title and points to a page of the file
+    /// `filename`.
+    pub fn start(filename: &str, nav: &Nav, pages: Option<&[PageInfo]>) -> Self {
+        let checks: Vec<_> = nav.all_nodes().into_iter()
+            .filter(|(_, node)| !node.string.trim().is_empty())
+            .filter_map(|(id, node)| {
+                let page = page_number(&node.link, pages)?;
+                Some(Check { id, title: node.string.clone(), page })
+            })
+            .collect();
+        let total = checks.len();
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let filename = String::from(filename);
+        let cancel_flag = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            let mut texts: HashMap<u32, Option<String>> = HashMap::new();
+            for check in checks {
+                if cancel_flag.load(Ordering::Relaxed) {
+                    return;
+                }
+                let text = texts.entry(check.page)
+                    .or_insert_with(|| get_page_text(&filename, check.page).ok());
+                let verdict = match text {
+                    Some(text) if !text.trim().is_empty() => if title_in_text(&check.title, text) {
+                        Verdict::Found
+                    } else {
+                        Verdict::Missing
+                    },
+                    _ => Verdict::NoText,
+                };
+                if sender.send((check, verdict)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self { receiver, results: HashMap::new(), total, finished: false, cancelled }
+    }
+
+    /// Collect the verdicts that arrived since the last call. Return whether the verification
+    /// finished during this call.
+    pub fn poll(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
+        loop {
+            match self.receiver.try_recv() {
+                Ok((check, verdict)) => {
+                    self.results.insert(check.id, (check.title, check.page, verdict));
+                },
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    return true;
+                },
+            }
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Return the number of entries verified so far, and the number of entries to verify.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.results.len(), self.total)
+    }
+
+    /// Return the verdict for the node `id`, which is now `node`, unless its title or target
+    /// changed since it was verified.
+    pub fn verdict(&self, id: TreeIdentifier, node: &NavNode, pages: Option<&[PageInfo]>) -> Option<Verdict> {
+        let (title, page, verdict) = self.results.get(id)?;
+        (*title == node.string && Some(*page) == page_number(&node.link, pages)).then_some(*verdict)
+    }
+
+    /// Return the number of titles not found on their page.
+    pub fn missing(&self) -> usize {
+        self.results.values().filter(|(_, _, verdict)| *verdict == Verdict::Missing).count()
+    }
+}
+
+impl Drop for Verification {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Whether `title` appears in `text`, ignoring case, spacing, punctuation and line breaks.
+pub fn title_in_text(title: &str, text: &str) -> bool {
+    let squash = |s: &str| -> String {
+        s.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    squash(text).contains(&squash(title))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_found_across_lines() {
+        let text = "CHAPTER IV.\nTHE RABBIT SENDS\nIN A LITTLE BILL\n\nIt was the White Rabbit...";
+        assert!(title_in_text("Chapter IV: The Rabbit sends in a little Bill", text));
+        assert!(title_in_text("The rabbit sends in a little bill", text));
+        assert!(!title_in_text("Chapter V", text));
+    }
+}
@@ -0,0 +1,40 @@
+/// Viewers tried, in order, when the configuration does not name one.
+pub const FALLBACK_VIEWERS: [&str; 3] = [
+    "djview4 -page={page} {file}",
+    "zathura --page={page} {file}",
+    "okular -p {page} {file}",
+];
+
+/// Return the program and the arguments to run to open the file `file` at page `page` (starting
+/// at 1) with the viewer command `template`, or `None` if `template` is empty.
+/// In each word of `template`, `{page}` is replaced with the page and `{file}` with the file, which
+/// is added as the last argument if `template` does not mention it.
+pub fn command_line(template: &str, file: &str, page: u32) -> Option<(String, Vec<String>)> {
+    let mut words: Vec<String> = template.split_whitespace()
+        .map(|word| word.replace("{page}", &page.to_string()).replace("{file}", file))
+        .collect();
+    if !template.contains("{file}") {
+        words.push(String::from(file));
+    }
+    let mut words = words.into_iter();
+    let program = words.next().filter(|_| !template.trim().is_empty())?;
+    Some((program, words.collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_are_replaced() {
+        assert_eq!(
+            command_line("djview4 -page={page} {file}", "my book.djvu", 12),
+            Some((String::from("djview4"), vec![String::from("-page=12"), String::from("my book.djvu")]))
+        );
+        assert_eq!(
+            command_line("okular -p {page}", "book.djvu", 3),
+            Some((String::from("okular"), vec![String::from("-p"), String::from("3"), String::from("book.djvu")]))
+        );
+        assert_eq!(command_line("  ", "book.djvu", 3), None);
+    }
+}
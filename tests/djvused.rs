@@ -0,0 +1,224 @@
+//! Reading and writing outlines with a mock in place of `djvused`.
+#![cfg(unix)]
+
+use std::{
+    cell::RefCell,
+    fs, io,
+    os::unix::process::ExitStatusExt,
+    process::{ExitStatus, Output},
+};
+
+use djvu_nav::{
+    djvu::{
+        check_djvused_with, embed_nav_in_djvu_file_with, get_document_text_with, get_metadata_with,
+        get_nav_from_djvu_with, get_page_count_with, get_pages_from_djvu_with, set_page_titles_with, ToolRunner,
+    },
+    BookmarkLink, Nav, NavNode, NavReadingError,
+};
+
+/// Answers every call with the same output, past the outputs of the first calls if given, and
+/// records the calls along with the outline passed to `set-outline`, if any.
+struct MockTools {
+    result: io::ErrorKind,
+    code: i32,
+    /// Outputs of the first calls, the next one last.
+    first_stdouts: RefCell<Vec<Vec<u8>>>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    calls: RefCell<Vec<Vec<String>>>,
+    embedded: RefCell<Option<String>>,
+}
+
+impl MockTools {
+    fn printing(stdout: impl Into<Vec<u8>>) -> Self {
+        Self {
+            result: io::ErrorKind::Other,
+            code: 0,
+            first_stdouts: RefCell::new(Vec::new()),
+            stdout: stdout.into(),
+            stderr: Vec::new(),
+            calls: RefCell::new(Vec::new()),
+            embedded: RefCell::new(None),
+        }
+    }
+
+    /// Print `first` for the first call, and `stdout` for the next ones.
+    fn printing_first(first: &str, stdout: impl Into<Vec<u8>>) -> Self {
+        Self { first_stdouts: RefCell::new(vec![first.into()]), ..Self::printing(stdout) }
+    }
+
+    fn failing(code: i32, stderr: &str) -> Self {
+        Self { code, stderr: stderr.into(), ..Self::printing("") }
+    }
+
+    fn missing() -> Self {
+        Self { result: io::ErrorKind::NotFound, ..Self::printing("") }
+    }
+}
+
+impl ToolRunner for MockTools {
+    fn output(&self, program: &str, args: &[&str]) -> io::Result<Output> {
+        assert_eq!(program, "djvused");
+        self.calls.borrow_mut().push(args.iter().map(|arg| arg.to_string()).collect());
+        if self.result == io::ErrorKind::NotFound {
+            return Err(io::Error::from(io::ErrorKind::NotFound));
+        }
+        let outline_file = args.iter().find_map(|arg| arg.strip_prefix("set-outline "));
+        if let Some(path) = outline_file {
+            *self.embedded.borrow_mut() = Some(fs::read_to_string(path)?);
+        }
+        Ok(Output {
+            status: ExitStatus::from_raw(self.code << 8),
+            stdout: self.first_stdouts.borrow_mut().pop().unwrap_or_else(|| self.stdout.clone()),
+            stderr: self.stderr.clone(),
+        })
+    }
+}
+
+fn leaf(title: &str, page: u32) -> NavNode {
+    NavNode { string: String::from(title), link: BookmarkLink::PageNumber(page), children: vec![] }
+}
+
+#[test]
+fn read_outline() {
+    let tools = MockTools::printing("(bookmarks\n (\"Preface\"\n  \"#3\" )\n (\"Part I\"\n  \"#p0012.djvu\"\n  (\"Chapter 1\"\n   \"#12\" ) ) )\n");
+    let nav = get_nav_from_djvu_with(&tools, "book.djvu").unwrap();
+
+    assert_eq!(nav.nodes.len(), 2);
+    assert_eq!(nav.nodes[1].link, BookmarkLink::PageLink(String::from("p0012.djvu")));
+    assert_eq!(nav.nodes[1].children, vec![leaf("Chapter 1", 12)]);
+    assert_eq!(tools.calls.borrow()[0], ["book.djvu", "-u", "-e", "print-outline"]);
+}
+
+#[test]
+fn read_huge_outline() {
+    let nav = Nav { nodes: (1..=20_000).map(|n| leaf(&format!("Entry {}", n), n)).collect() };
+    let tools = MockTools::printing(nav.to_djvu());
+
+    assert_eq!(get_nav_from_djvu_with(&tools, "book.djvu").unwrap(), nav);
+}
+
+#[test]
+fn djvused_failure_is_reported() {
+    let tools = MockTools::failing(10, "djvused: cannot open 'book.djvu'\n");
+    match get_nav_from_djvu_with(&tools, "book.djvu") {
+        Err(NavReadingError::DjvusedError(status, stderr)) => {
+            assert_eq!(status.code(), Some(10));
+            assert_eq!(stderr, "djvused: cannot open 'book.djvu'\n");
+        },
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert!(matches!(
+        get_pages_from_djvu_with(&tools, "book.djvu"),
+        Err(NavReadingError::DjvusedError(..))
+    ));
+}
+
+#[test]
+fn missing_djvused_is_reported() {
+    let tools = MockTools::missing();
+    assert!(matches!(get_nav_from_djvu_with(&tools, "book.djvu"), Err(NavReadingError::DjvusedNotFound)));
+    assert!(matches!(check_djvused_with(&tools), Err(NavReadingError::DjvusedNotFound)));
+}
+
+#[test]
+fn invalid_utf8_is_reported() {
+    let tools = MockTools::printing(b"(bookmarks\n (\"Pr\xe9face\"\n  \"#3\" ) )\n".to_vec());
+    assert!(matches!(
+        get_nav_from_djvu_with(&tools, "book.djvu"),
+        Err(NavReadingError::InvalidUtf8Error(_))
+    ));
+}
+
+#[test]
+fn malformed_outline_is_reported() {
+    let tools = MockTools::printing("(outline \"Preface\" )");
+    assert!(matches!(
+        get_nav_from_djvu_with(&tools, "book.djvu"),
+        Err(NavReadingError::NavParsingError(_))
+    ));
+}
+
+#[test]
+fn embedded_outline_reads_back() {
+    let nav = Nav { nodes: vec![NavNode { children: vec![leaf("Chapter \"One\"", 7)], ..leaf("Part I", 5) }] };
+    let tools = MockTools::printing("");
+    embed_nav_in_djvu_file_with(&tools, "book.djvu", &nav).unwrap();
+
+    let calls = tools.calls.borrow();
+    assert_eq!(calls[0][0], "book.djvu");
+    assert!(calls[0].contains(&String::from("-s")));
+    let embedded = tools.embedded.borrow().clone().unwrap();
+    let reader = MockTools::printing(embedded);
+    assert_eq!(get_nav_from_djvu_with(&reader, "book.djvu").unwrap(), nav);
+}
+
+#[test]
+fn empty_outline_is_removed() {
+    let tools = MockTools::printing("");
+    embed_nav_in_djvu_file_with(&tools, "book.djvu", &Nav { nodes: vec![] }).unwrap();
+    assert_eq!(tools.calls.borrow()[0], ["book.djvu", "-e", "remove-outline", "-s"]);
+    assert_eq!(*tools.embedded.borrow(), None);
+}
+
+#[test]
+fn failed_embedding_is_reported() {
+    let tools = MockTools::failing(1, "djvused: file is read-only\n");
+    let result = embed_nav_in_djvu_file_with(&tools, "book.djvu", &Nav { nodes: vec![leaf("Index", 9)] });
+    assert!(matches!(result, Err(NavReadingError::DjvusedError(..))));
+}
+
+#[test]
+fn djvused_version_is_read_from_its_usage() {
+    let tools = MockTools::failing(10, "DJVUSED --- DjVuLibre-3.5.28\n\nUsage: djvused [options] djvufile\n");
+    assert_eq!(check_djvused_with(&tools).unwrap(), "3.5.28");
+    assert_eq!(tools.calls.borrow()[0], ["--help"]);
+    assert_eq!(check_djvused_with(&MockTools::failing(1, "")).unwrap(), "unknown");
+}
+
+#[test]
+fn page_count_is_read() {
+    let tools = MockTools::printing("412\n");
+    assert_eq!(get_page_count_with(&tools, "book.djvu").unwrap(), 412);
+    assert_eq!(tools.calls.borrow()[0], ["book.djvu", "-u", "-e", "n"]);
+    assert!(matches!(
+        get_page_count_with(&MockTools::printing("none\n"), "book.djvu"),
+        Err(NavReadingError::NavParsingError(_))
+    ));
+}
+
+#[test]
+fn document_text_is_split_by_page() {
+    let tools = MockTools::printing("Chapter 1\nText\n\u{c}\u{c}Chapter 2\n\u{c}");
+    assert_eq!(get_document_text_with(&tools, "book.djvu").unwrap(), ["Chapter 1\nText\n", "", "Chapter 2\n"]);
+    assert_eq!(tools.calls.borrow()[0], ["book.djvu", "-u", "-e", "print-pure-txt"]);
+}
+
+#[test]
+fn page_titles_are_set() {
+    let tools = MockTools::printing("");
+    let titles = [None, Some(String::from("i")), Some(String::from("Plate \"A\""))];
+    set_page_titles_with(&tools, "book.djvu", &titles).unwrap();
+
+    assert_eq!(tools.calls.borrow()[0], [
+        "book.djvu",
+        "-e",
+        "select 2; set-page-title \"i\"\nselect 3; set-page-title \"Plate \\\"A\\\"\"\n",
+        "-s",
+    ]);
+    set_page_titles_with(&tools, "book.djvu", &[None]).unwrap();
+    assert_eq!(tools.calls.borrow().len(), 1);
+}
+
+#[test]
+fn metadata_is_read_from_the_shared_annotations() {
+    let tools = MockTools::printing_first("412\n", "title\t\"Le Temps\"\nyear\t\"1923\"\n");
+    let metadata = get_metadata_with(&tools, "book.djvu").unwrap();
+
+    assert_eq!(metadata.get("year"), Some("1923"));
+    assert_eq!(tools.calls.borrow()[1], ["book.djvu", "-u", "-e", "create-shared-ant; print-meta"]);
+
+    let tools = MockTools::printing_first("1\n", "");
+    get_metadata_with(&tools, "leaflet.djvu").unwrap();
+    assert_eq!(tools.calls.borrow()[1], ["leaflet.djvu", "-u", "-e", "select 1; print-meta"]);
+}